@@ -164,7 +164,10 @@ impl JsMemoryStore {
                 None
             };
 
-        if let Some(graph_format) = GraphFormat::from_media_type(mime_type) {
+        // JSON-LD is both a graph and a dataset format: it is used as a graph format only if a graph name is given
+        if let Some(graph_format) = GraphFormat::from_media_type(mime_type)
+            .filter(|f| *f != GraphFormat::JsonLd || to_graph_name.is_some())
+        {
             self.store
                 .load_graph(
                     Cursor::new(data),
@@ -196,7 +199,10 @@ impl JsMemoryStore {
             };
 
         let mut buffer = Vec::new();
-        if let Some(graph_format) = GraphFormat::from_media_type(mime_type) {
+        // JSON-LD is both a graph and a dataset format: it is used as a graph format only if a graph name is given
+        if let Some(graph_format) = GraphFormat::from_media_type(mime_type)
+            .filter(|f| *f != GraphFormat::JsonLd || from_graph_name.is_some())
+        {
             self.store
                 .dump_graph(
                     &mut buffer,
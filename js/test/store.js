@@ -135,6 +135,18 @@ describe('MemoryStore', function () {
       store.load('GRAPH <> { <http://example.com> <http://example.com> <> }', 'application/trig', 'http://example.com')
       assert(store.has(dataFactory.quad(ex, ex, ex, ex)))
     })
+
+    it('load JSON-LD', function () {
+      const store = new MemoryStore()
+      store.load('{"@id": "http://example.com", "@graph": {"@id": "http://example.com", "http://example.com": {"@id": "http://example.com"}}}', 'application/ld+json')
+      assert(store.has(dataFactory.quad(ex, ex, ex, ex)))
+    })
+
+    it('load JSON-LD in an other graph', function () {
+      const store = new MemoryStore()
+      store.load('{"@id": "http://example.com", "http://example.com": {"@id": "http://example.com"}}', 'application/ld+json', null, ex)
+      assert(store.has(dataFactory.quad(ex, ex, ex, ex)))
+    })
   })
 
   describe('#dump()', function () {
@@ -152,5 +164,10 @@ describe('MemoryStore', function () {
       const store = new MemoryStore([dataFactory.quad(ex, ex, ex, ex)])
       assert.strictEqual('', store.dump('application/n-triples'))
     })
+
+    it('dump named graph content as JSON-LD', function () {
+      const store = new MemoryStore([dataFactory.quad(ex, ex, ex, ex)])
+      assert.strictEqual('[{"@id":"http://example.com","http://example.com":[{"@id":"http://example.com"}]}]', store.dump('application/ld+json', ex))
+    })
   })
 })
@@ -1,6 +1,6 @@
 /// [RDF graph](https://www.w3.org/TR/rdf11-concepts/#dfn-graph) serialization formats.
 ///
-/// This enumeration is non exhaustive. New formats like JSON-LD will be added in the future.
+/// This enumeration is non exhaustive. New formats will be added in the future.
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
 #[non_exhaustive]
 pub enum GraphFormat {
@@ -10,6 +10,8 @@ pub enum GraphFormat {
     Turtle,
     /// [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/)
     RdfXml,
+    /// [JSON-LD](https://www.w3.org/TR/json-ld/) documents without named graphs
+    JsonLd,
 }
 
 impl GraphFormat {
@@ -26,6 +28,7 @@ impl GraphFormat {
             GraphFormat::NTriples => "http://www.w3.org/ns/formats/N-Triples",
             GraphFormat::Turtle => "http://www.w3.org/ns/formats/Turtle",
             GraphFormat::RdfXml => "http://www.w3.org/ns/formats/RDF_XML",
+            GraphFormat::JsonLd => "http://www.w3.org/ns/formats/JSON-LD",
         }
     }
 
@@ -42,6 +45,7 @@ impl GraphFormat {
             GraphFormat::NTriples => "application/n-triples",
             GraphFormat::Turtle => "text/turtle",
             GraphFormat::RdfXml => "application/rdf+xml",
+            GraphFormat::JsonLd => "application/ld+json",
         }
     }
 
@@ -58,6 +62,7 @@ impl GraphFormat {
             GraphFormat::NTriples => "nt",
             GraphFormat::Turtle => "ttl",
             GraphFormat::RdfXml => "rdf",
+            GraphFormat::JsonLd => "jsonld",
         }
     }
     /// Looks for a known format from a media type.
@@ -79,6 +84,7 @@ impl GraphFormat {
                     Some(GraphFormat::Turtle)
                 }
                 "application/rdf+xml" | "application/xml" | "text/xml" => Some(GraphFormat::RdfXml),
+                "application/ld+json" => Some(GraphFormat::JsonLd),
                 _ => None,
             }
         } else {
@@ -89,7 +95,7 @@ impl GraphFormat {
 
 /// [RDF dataset](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-dataset) serialization formats.
 ///
-/// This enumeration is non exhaustive. New formats like JSON-LD will be added in the future.
+/// This enumeration is non exhaustive. New formats will be added in the future.
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
 #[non_exhaustive]
 pub enum DatasetFormat {
@@ -97,6 +103,8 @@ pub enum DatasetFormat {
     NQuads,
     /// [TriG](https://www.w3.org/TR/trig/)
     TriG,
+    /// [JSON-LD](https://www.w3.org/TR/json-ld/)
+    JsonLd,
 }
 
 impl DatasetFormat {
@@ -112,6 +120,7 @@ impl DatasetFormat {
         match self {
             DatasetFormat::NQuads => "http://www.w3.org/ns/formats/N-Quads",
             DatasetFormat::TriG => "http://www.w3.org/ns/formats/TriG",
+            DatasetFormat::JsonLd => "http://www.w3.org/ns/formats/JSON-LD",
         }
     }
 
@@ -127,6 +136,7 @@ impl DatasetFormat {
         match self {
             DatasetFormat::NQuads => "application/n-quads",
             DatasetFormat::TriG => "application/trig",
+            DatasetFormat::JsonLd => "application/ld+json",
         }
     }
 
@@ -142,6 +152,7 @@ impl DatasetFormat {
         match self {
             DatasetFormat::NQuads => "nq",
             DatasetFormat::TriG => "trig",
+            DatasetFormat::JsonLd => "jsonld",
         }
     }
     /// Looks for a known format from a media type.
@@ -161,6 +172,7 @@ impl DatasetFormat {
                     Some(DatasetFormat::NQuads)
                 }
                 "application/trig" | "application/x-trig" => Some(DatasetFormat::TriG),
+                "application/ld+json" => Some(DatasetFormat::JsonLd),
                 _ => None,
             }
         } else {
@@ -0,0 +1,344 @@
+//! A subset of the [JSON-LD compaction algorithm](https://www.w3.org/TR/json-ld11-api/#compaction-algorithms)
+//!
+//! It only supports the shapes generated by the RDF to JSON-LD conversion and ignores `@language`, `@index`, `@id`, `@type` and `@graph` containers.
+
+use crate::io::jsonld::context::{JsonLdContext, TermDefinition};
+use crate::io::jsonld::json::{JsonObject, JsonValue};
+use std::collections::BTreeMap;
+
+/// Compacts expanded node objects against the given context
+pub fn compact(
+    expanded: &[JsonValue],
+    active_context: &JsonLdContext,
+    context_value: &JsonValue,
+) -> JsonValue {
+    let compactor = Compactor { active_context };
+    let nodes = expanded
+        .iter()
+        .filter_map(JsonValue::as_object)
+        .map(|node| compactor.compact_node(node))
+        .collect::<Vec<_>>();
+    let mut result = if nodes.len() == 1 {
+        match nodes.into_iter().next() {
+            Some(JsonValue::Object(node)) => node,
+            _ => JsonObject::new(),
+        }
+    } else {
+        let mut result = JsonObject::new();
+        if !nodes.is_empty() {
+            result.insert(compactor.compact_keyword("@graph"), JsonValue::Array(nodes));
+        }
+        result
+    };
+    result.insert("@context".into(), context_value.clone());
+    result.into()
+}
+
+struct Compactor<'a> {
+    active_context: &'a JsonLdContext,
+}
+
+impl<'a> Compactor<'a> {
+    fn compact_node(&self, node: &JsonObject) -> JsonValue {
+        let mut result = JsonObject::new();
+        for (property, values) in node {
+            match property.as_str() {
+                "@id" => {
+                    if let Some(id) = values.as_str() {
+                        result.insert(
+                            self.compact_keyword("@id"),
+                            self.compact_iri(id, false).into(),
+                        );
+                    }
+                }
+                "@type" => {
+                    let types = values
+                        .as_slice()
+                        .iter()
+                        .filter_map(JsonValue::as_str)
+                        .map(|t| JsonValue::from(self.compact_iri(t, true)))
+                        .collect::<Vec<_>>();
+                    result.insert(self.compact_keyword("@type"), unwrap_single(types));
+                }
+                "@graph" => {
+                    let nodes = values
+                        .as_slice()
+                        .iter()
+                        .filter_map(JsonValue::as_object)
+                        .map(|node| self.compact_node(node))
+                        .collect();
+                    result.insert(self.compact_keyword("@graph"), JsonValue::Array(nodes));
+                }
+                _ => {
+                    let mut by_term = BTreeMap::<_, (_, Vec<&JsonValue>)>::new();
+                    for value in values.as_slice() {
+                        let (term, definition) = self.select_term(property, value);
+                        by_term
+                            .entry(term)
+                            .or_insert_with(|| (definition, Vec::new()))
+                            .1
+                            .push(value);
+                    }
+                    for (term, (definition, values)) in by_term {
+                        let compacted = values
+                            .iter()
+                            .map(|value| self.compact_item(definition, value))
+                            .collect::<Vec<_>>();
+                        match definition {
+                            Some(definition) if definition.has_container("@list") => {
+                                if compacted.len() == 1 {
+                                    result.insert(term, compacted.into_iter().next().unwrap());
+                                } else {
+                                    // Multiple lists could not be written using a @list container
+                                    let lists = values
+                                        .iter()
+                                        .map(|value| self.compact_item(None, value))
+                                        .collect();
+                                    result.insert(
+                                        self.compact_iri(property, true),
+                                        JsonValue::Array(lists),
+                                    );
+                                }
+                            }
+                            Some(definition) if definition.has_container("@set") => {
+                                result.insert(term, JsonValue::Array(compacted));
+                            }
+                            _ => {
+                                result.insert(term, unwrap_single(compacted));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        result.into()
+    }
+
+    fn compact_item(&self, definition: Option<&TermDefinition>, value: &JsonValue) -> JsonValue {
+        let object = if let Some(object) = value.as_object() {
+            object
+        } else {
+            return value.clone();
+        };
+        if let Some(list) = object.get("@list") {
+            let items = list
+                .as_slice()
+                .iter()
+                .map(|item| {
+                    if matches!(item.as_object(), Some(o) if o.contains_key("@list")) {
+                        self.compact_item(None, item)
+                    } else {
+                        self.compact_item(definition, item)
+                    }
+                })
+                .collect::<Vec<_>>();
+            if matches!(definition, Some(d) if d.has_container("@list")) {
+                JsonValue::Array(items)
+            } else {
+                let mut result = JsonObject::new();
+                result.insert(self.compact_keyword("@list"), JsonValue::Array(items));
+                result.into()
+            }
+        } else if object.contains_key("@value") {
+            self.compact_value(definition, object)
+        } else if object.len() == 1 && object.contains_key("@id") {
+            let id = object["@id"].as_str().unwrap_or_default();
+            match definition.and_then(|d| d.type_mapping.as_deref()) {
+                Some("@id") => self.compact_iri(id, false).into(),
+                Some("@vocab") => self.compact_iri(id, true).into(),
+                _ => {
+                    let mut result = JsonObject::new();
+                    result.insert(
+                        self.compact_keyword("@id"),
+                        self.compact_iri(id, false).into(),
+                    );
+                    result.into()
+                }
+            }
+        } else {
+            self.compact_node(object)
+        }
+    }
+
+    fn compact_value(&self, definition: Option<&TermDefinition>, value: &JsonObject) -> JsonValue {
+        if self.is_scalar_compatible(definition, value) {
+            return value["@value"].clone();
+        }
+        let mut result = JsonObject::new();
+        for (key, v) in value {
+            result.insert(
+                self.compact_keyword(key),
+                match v.as_str() {
+                    Some("@json") if key == "@type" => self.compact_keyword("@json").into(),
+                    Some(t) if key == "@type" => self.compact_iri(t, true).into(),
+                    _ => v.clone(),
+                },
+            );
+        }
+        result.into()
+    }
+
+    /// Checks if the value object could be written as a plain JSON value for a term
+    fn is_scalar_compatible(
+        &self,
+        definition: Option<&TermDefinition>,
+        value: &JsonObject,
+    ) -> bool {
+        let type_mapping = definition.and_then(|d| d.type_mapping.as_deref());
+        if value.contains_key("@index") || value.contains_key("@direction") {
+            return false;
+        }
+        if let Some(datatype) = value.get("@type").and_then(JsonValue::as_str) {
+            return type_mapping == Some(datatype);
+        }
+        if type_mapping.is_some() || value.get("@value").and_then(JsonValue::as_str).is_none() {
+            return false;
+        }
+        let language = match definition.and_then(|d| d.language_mapping.as_ref()) {
+            Some(language) => language.as_deref(),
+            None => self.active_context.default_language.as_deref(),
+        };
+        let direction = match definition.and_then(|d| d.direction_mapping.as_ref()) {
+            Some(direction) => direction.as_deref(),
+            None => self.active_context.default_direction.as_deref(),
+        };
+        direction.is_none() && language == value.get("@language").and_then(JsonValue::as_str)
+    }
+
+    /// Selects the key used to write a property value and its term definition
+    fn select_term(
+        &self,
+        property: &str,
+        value: &JsonValue,
+    ) -> (String, Option<&'a TermDefinition>) {
+        let is_list = matches!(value.as_object(), Some(o) if o.contains_key("@list"));
+        let mut best: Option<(bool, &'a str, &'a TermDefinition)> = None;
+        for (term, definition) in &self.active_context.term_definitions {
+            if definition.iri_mapping.as_deref() != Some(property)
+                || definition.reverse_property
+                || definition.type_mapping.as_deref() == Some("@json")
+                || definition
+                    .container_mapping
+                    .iter()
+                    .any(|c| *c != "@set" && *c != "@list")
+                || definition.has_container("@list") != is_list
+            {
+                continue;
+            }
+            let is_scalar = match value.as_object() {
+                Some(object) if object.contains_key("@value") => {
+                    self.is_scalar_compatible(Some(definition), object)
+                }
+                Some(object) if object.len() == 1 && object.contains_key("@id") => matches!(
+                    definition.type_mapping.as_deref(),
+                    Some("@id") | Some("@vocab")
+                ),
+                _ => is_list,
+            };
+            let candidate = (is_scalar, term.as_str(), definition);
+            best = Some(match best {
+                Some(best) if !is_better_term(candidate, best) => best,
+                _ => candidate,
+            });
+        }
+        if let Some((_, term, definition)) = best {
+            (term.to_owned(), Some(definition))
+        } else {
+            (self.compact_iri(property, true), None)
+        }
+    }
+
+    /// [IRI compaction](https://www.w3.org/TR/json-ld11-api/#iri-compaction) without term selection
+    fn compact_iri(&self, iri: &str, vocab: bool) -> String {
+        if vocab {
+            let simple_term = self
+                .active_context
+                .term_definitions
+                .iter()
+                .filter(|(_, d)| {
+                    d.iri_mapping.as_deref() == Some(iri)
+                        && !d.reverse_property
+                        && d.type_mapping.is_none()
+                        && d.language_mapping.is_none()
+                        && d.direction_mapping.is_none()
+                        && d.container_mapping.is_empty()
+                })
+                .map(|(t, _)| t.as_str())
+                .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+            if let Some(term) = simple_term {
+                return term.to_owned();
+            }
+            if let Some(vocabulary_mapping) = &self.active_context.vocabulary_mapping {
+                if let Some(suffix) = iri.strip_prefix(vocabulary_mapping.as_str()) {
+                    if !suffix.is_empty()
+                        && !suffix.contains(':')
+                        && !self.active_context.term_definitions.contains_key(suffix)
+                    {
+                        return suffix.to_owned();
+                    }
+                }
+            }
+        }
+        let mut compact_iri: Option<String> = None;
+        for (term, definition) in &self.active_context.term_definitions {
+            if !definition.prefix {
+                continue;
+            }
+            let prefix_iri = if let Some(prefix_iri) = &definition.iri_mapping {
+                prefix_iri
+            } else {
+                continue;
+            };
+            if let Some(suffix) = iri.strip_prefix(prefix_iri.as_str()) {
+                if suffix.is_empty() || suffix.starts_with("//") {
+                    continue;
+                }
+                let candidate = format!("{}:{}", term, suffix);
+                if matches!(
+                    self.active_context.term_definitions.get(&candidate),
+                    Some(d) if d.iri_mapping.as_deref() != Some(iri)
+                ) {
+                    continue;
+                }
+                let is_better = match &compact_iri {
+                    Some(c) => {
+                        candidate.len() < c.len() || (candidate.len() == c.len() && candidate < *c)
+                    }
+                    None => true,
+                };
+                if is_better {
+                    compact_iri = Some(candidate);
+                }
+            }
+        }
+        compact_iri.unwrap_or_else(|| iri.to_owned())
+    }
+
+    /// Returns the shortest alias of a keyword
+    fn compact_keyword(&self, keyword: &str) -> String {
+        self.active_context
+            .term_definitions
+            .iter()
+            .filter(|(_, d)| d.iri_mapping.as_deref() == Some(keyword))
+            .map(|(t, _)| t.as_str())
+            .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+            .unwrap_or(keyword)
+            .to_owned()
+    }
+}
+
+/// Terms allowing to write scalar values are preferred, then the shortest ones
+fn is_better_term(
+    (is_scalar, term, _): (bool, &str, &TermDefinition),
+    (best_is_scalar, best_term, _): (bool, &str, &TermDefinition),
+) -> bool {
+    (!is_scalar, term.len(), term) < (!best_is_scalar, best_term.len(), best_term)
+}
+
+fn unwrap_single(mut values: Vec<JsonValue>) -> JsonValue {
+    if values.len() == 1 {
+        values.remove(0)
+    } else {
+        JsonValue::Array(values)
+    }
+}
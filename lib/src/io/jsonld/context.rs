@@ -0,0 +1,838 @@
+//! Implementation of the [JSON-LD context processing algorithms](https://www.w3.org/TR/json-ld11-api/#context-processing-algorithms)
+
+use crate::error::invalid_data_error;
+use crate::io::jsonld::json::{read_json, JsonObject, JsonValue};
+use crate::io::jsonld::JsonLdContextLoader;
+use oxiri::Iri;
+use std::collections::HashMap;
+use std::io;
+use std::io::Cursor;
+
+/// Maximal number of nested remote contexts
+const MAX_REMOTE_CONTEXTS: usize = 32;
+
+const CONTAINERS: [&str; 7] = [
+    "@graph",
+    "@id",
+    "@index",
+    "@language",
+    "@list",
+    "@set",
+    "@type",
+];
+
+/// A JSON-LD [active context](https://www.w3.org/TR/json-ld11-api/#dfn-active-context)
+#[derive(Clone, Default)]
+pub struct JsonLdContext {
+    pub base_iri: Option<Iri<String>>,
+    pub original_base_iri: Option<Iri<String>>,
+    pub vocabulary_mapping: Option<String>,
+    pub default_language: Option<String>,
+    pub default_direction: Option<String>,
+    pub term_definitions: HashMap<String, TermDefinition>,
+    pub previous_context: Option<Box<JsonLdContext>>,
+}
+
+/// A JSON-LD [term definition](https://www.w3.org/TR/json-ld11-api/#dfn-term-definition)
+#[derive(Clone, Default, PartialEq)]
+pub struct TermDefinition {
+    /// `None` if the term is explicitly mapped to `null`
+    pub iri_mapping: Option<String>,
+    pub prefix: bool,
+    pub protected: bool,
+    pub reverse_property: bool,
+    pub base_iri: Option<Iri<String>>,
+    pub context: Option<JsonValue>,
+    pub container_mapping: Vec<&'static str>,
+    /// `Some(None)` if the direction is explicitly set to `null`
+    pub direction_mapping: Option<Option<String>>,
+    pub index_mapping: Option<String>,
+    /// `Some(None)` if the language is explicitly set to `null`
+    pub language_mapping: Option<Option<String>>,
+    pub nest_value: Option<String>,
+    pub type_mapping: Option<String>,
+}
+
+impl TermDefinition {
+    pub fn has_container(&self, container: &str) -> bool {
+        self.container_mapping.contains(&container)
+    }
+}
+
+impl JsonLdContext {
+    pub fn new(base_iri: Option<Iri<String>>) -> Self {
+        Self {
+            original_base_iri: base_iri.clone(),
+            base_iri,
+            ..Self::default()
+        }
+    }
+
+    pub fn term_definition(&self, term: &str) -> Option<&TermDefinition> {
+        self.term_definitions.get(term)
+    }
+
+    /// [IRI expansion](https://www.w3.org/TR/json-ld11-api/#iri-expansion) against this context
+    ///
+    /// Returns `None` if the value is mapped to `null` or has the form of an unknown keyword.
+    pub fn expand_iri(&self, value: &str, document_relative: bool, vocab: bool) -> Option<String> {
+        if is_keyword(value) {
+            return Some(value.to_owned());
+        }
+        if has_keyword_form(value) {
+            return None;
+        }
+        if let Some(definition) = self.term_definitions.get(value) {
+            if let Some(iri) = &definition.iri_mapping {
+                if is_keyword(iri) {
+                    return Some(iri.clone());
+                }
+            }
+            if vocab {
+                return definition.iri_mapping.clone();
+            }
+        }
+        if let Some((prefix, suffix)) = split_compact_iri(value) {
+            if prefix == "_" || suffix.starts_with("//") {
+                return Some(value.to_owned());
+            }
+            if let Some(definition) = self.term_definitions.get(prefix) {
+                if let Some(iri) = &definition.iri_mapping {
+                    if definition.prefix {
+                        return Some(format!("{}{}", iri, suffix));
+                    }
+                }
+            }
+            if Iri::parse(value).is_ok() {
+                return Some(value.to_owned());
+            }
+        }
+        if vocab {
+            if let Some(vocabulary_mapping) = &self.vocabulary_mapping {
+                return Some(format!("{}{}", vocabulary_mapping, value));
+            }
+        }
+        if document_relative {
+            if let Some(base_iri) = &self.base_iri {
+                if let Ok(iri) = base_iri.resolve(value) {
+                    return Some(iri.into_inner());
+                }
+            }
+        }
+        Some(value.to_owned())
+    }
+}
+
+/// The [context processing algorithm](https://www.w3.org/TR/json-ld11-api/#context-processing-algorithm)
+#[allow(clippy::too_many_arguments)]
+pub fn process_context(
+    active_context: &JsonLdContext,
+    local_context: &JsonValue,
+    base_url: Option<&Iri<String>>,
+    remote_contexts: &mut Vec<String>,
+    override_protected: bool,
+    mut propagate: bool,
+    validate_scoped_context: bool,
+    loader: &dyn JsonLdContextLoader,
+) -> Result<JsonLdContext, io::Error> {
+    let mut result = active_context.clone();
+    if let JsonValue::Object(local_context) = local_context {
+        if let Some(value) = local_context.get("@propagate") {
+            if let JsonValue::Boolean(value) = value {
+                propagate = *value;
+            } else {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD @propagate value: it should be a boolean",
+                ));
+            }
+        }
+    }
+    if !propagate && result.previous_context.is_none() {
+        result.previous_context = Some(Box::new(active_context.clone()));
+    }
+
+    for context in local_context.as_slice() {
+        match context {
+            JsonValue::Null => {
+                if !override_protected && result.term_definitions.values().any(|d| d.protected) {
+                    return Err(invalid_data_error(
+                        "Invalid JSON-LD context nullification: the active context contains protected terms",
+                    ));
+                }
+                let previous_result = result;
+                result = JsonLdContext::new(active_context.original_base_iri.clone());
+                if !propagate {
+                    result.previous_context = Some(Box::new(previous_result));
+                }
+            }
+            JsonValue::String(iri) => {
+                let iri = resolve_iri(base_url, iri)?;
+                if !validate_scoped_context && remote_contexts.iter().any(|c| c == iri.as_str()) {
+                    continue;
+                }
+                if remote_contexts.len() >= MAX_REMOTE_CONTEXTS {
+                    return Err(invalid_data_error(format!(
+                        "JSON-LD context overflow: more than {} remote contexts are referenced",
+                        MAX_REMOTE_CONTEXTS
+                    )));
+                }
+                remote_contexts.push(iri.as_str().to_owned());
+                let context = load_remote_context(&iri, loader)?;
+                result = process_context(
+                    &result,
+                    &context,
+                    Some(&iri),
+                    &mut remote_contexts.clone(),
+                    false,
+                    true,
+                    validate_scoped_context,
+                    loader,
+                )?;
+            }
+            JsonValue::Object(context) => {
+                let mut context = context.clone();
+                if let Some(version) = context.get("@version") {
+                    if *version != JsonValue::Number("1.1".into()) {
+                        return Err(invalid_data_error(
+                            "Invalid JSON-LD @version value: only 1.1 is supported",
+                        ));
+                    }
+                }
+                if let Some(import) = context.remove("@import") {
+                    let import = import.as_str().ok_or_else(|| {
+                        invalid_data_error("Invalid JSON-LD @import value: it should be a string")
+                    })?;
+                    let iri = resolve_iri(base_url, import)?;
+                    if let JsonValue::Object(import) = load_remote_context(&iri, loader)? {
+                        if import.contains_key("@import") {
+                            return Err(invalid_data_error(format!(
+                                "Invalid JSON-LD context entry: the imported context {} contains an @import entry",
+                                iri
+                            )));
+                        }
+                        for (key, value) in import {
+                            context.entry(key).or_insert(value);
+                        }
+                    } else {
+                        return Err(invalid_data_error(format!(
+                            "Invalid JSON-LD remote context: the imported context {} should be an object",
+                            iri
+                        )));
+                    }
+                }
+                if let Some(value) = context.get("@base") {
+                    if remote_contexts.is_empty() {
+                        match value {
+                            JsonValue::Null => result.base_iri = None,
+                            JsonValue::String(value) => {
+                                result.base_iri = Some(
+                                    if let Some(base_iri) = &result.base_iri {
+                                        base_iri.resolve(value)
+                                    } else {
+                                        Iri::parse(value.clone())
+                                    }
+                                    .map_err(|e| {
+                                        invalid_data_error(format!(
+                                            "Invalid JSON-LD base IRI {}: {}",
+                                            value, e
+                                        ))
+                                    })?,
+                                )
+                            }
+                            _ => {
+                                return Err(invalid_data_error(
+                                    "Invalid JSON-LD base IRI: it should be a string",
+                                ))
+                            }
+                        }
+                    }
+                }
+                if let Some(value) = context.get("@vocab") {
+                    match value {
+                        JsonValue::Null => result.vocabulary_mapping = None,
+                        JsonValue::String(value) => {
+                            result.vocabulary_mapping =
+                                Some(result.expand_iri(value, true, true).ok_or_else(|| {
+                                    invalid_data_error(format!(
+                                        "Invalid JSON-LD vocab mapping: {}",
+                                        value
+                                    ))
+                                })?)
+                        }
+                        _ => {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD vocab mapping: it should be a string",
+                            ))
+                        }
+                    }
+                }
+                if let Some(value) = context.get("@language") {
+                    match value {
+                        JsonValue::Null => result.default_language = None,
+                        JsonValue::String(value) => {
+                            result.default_language = Some(value.to_ascii_lowercase())
+                        }
+                        _ => {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD default language: it should be a string",
+                            ))
+                        }
+                    }
+                }
+                if let Some(value) = context.get("@direction") {
+                    result.default_direction = parse_direction(value)?;
+                }
+                let protected = match context.get("@protected") {
+                    Some(JsonValue::Boolean(value)) => *value,
+                    Some(_) => {
+                        return Err(invalid_data_error(
+                            "Invalid JSON-LD @protected value: it should be a boolean",
+                        ))
+                    }
+                    None => false,
+                };
+                let mut processor = TermDefinitionsProcessor {
+                    local_context: &context,
+                    defined: HashMap::new(),
+                    protected,
+                    override_protected,
+                    base_url,
+                    remote_contexts,
+                    loader,
+                };
+                for term in context.keys() {
+                    if !matches!(
+                        term.as_str(),
+                        "@base"
+                            | "@direction"
+                            | "@import"
+                            | "@language"
+                            | "@propagate"
+                            | "@protected"
+                            | "@version"
+                            | "@vocab"
+                    ) {
+                        processor.create_term_definition(&mut result, term)?;
+                    }
+                }
+            }
+            _ => {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD local context: it should be an object, a string or null",
+                ))
+            }
+        }
+    }
+    Ok(result)
+}
+
+struct TermDefinitionsProcessor<'a> {
+    local_context: &'a JsonObject,
+    defined: HashMap<String, bool>,
+    protected: bool,
+    override_protected: bool,
+    base_url: Option<&'a Iri<String>>,
+    remote_contexts: &'a [String],
+    loader: &'a dyn JsonLdContextLoader,
+}
+
+impl<'a> TermDefinitionsProcessor<'a> {
+    /// The [create term definition algorithm](https://www.w3.org/TR/json-ld11-api/#create-term-definition)
+    fn create_term_definition(
+        &mut self,
+        active_context: &mut JsonLdContext,
+        term: &str,
+    ) -> Result<(), io::Error> {
+        match self.defined.get(term) {
+            Some(true) => return Ok(()),
+            Some(false) => {
+                return Err(invalid_data_error(format!(
+                    "JSON-LD cyclic IRI mapping: the definition of the term '{}' depends on itself",
+                    term
+                )))
+            }
+            None => (),
+        }
+        if term.is_empty() {
+            return Err(invalid_data_error(
+                "Invalid JSON-LD term definition: the empty string is not a valid term",
+            ));
+        }
+        self.defined.insert(term.to_owned(), false);
+        let local_context = self.local_context;
+        let value = local_context.get(term).unwrap_or(&JsonValue::Null);
+
+        if term == "@type" {
+            let is_valid = if let JsonValue::Object(value) = value {
+                !value.is_empty()
+                    && value.iter().all(|(k, v)| match k.as_str() {
+                        "@container" => v.as_str() == Some("@set"),
+                        "@protected" => true,
+                        _ => false,
+                    })
+            } else {
+                false
+            };
+            if !is_valid {
+                return Err(invalid_data_error(
+                    "JSON-LD keyword redefinition: @type could only be redefined with an @container set to @set",
+                ));
+            }
+        } else if is_keyword(term) {
+            return Err(invalid_data_error(format!(
+                "JSON-LD keyword redefinition: {} could not be redefined",
+                term
+            )));
+        } else if has_keyword_form(term) {
+            return Ok(());
+        }
+
+        let previous_definition = active_context.term_definitions.remove(term);
+        let (value, simple_term) = match value {
+            JsonValue::Null => {
+                let mut value = JsonObject::new();
+                value.insert("@id".into(), JsonValue::Null);
+                (value, false)
+            }
+            JsonValue::String(id) => {
+                let mut value = JsonObject::new();
+                value.insert("@id".into(), id.as_str().into());
+                (value, true)
+            }
+            JsonValue::Object(value) => (value.clone(), false),
+            _ => {
+                return Err(invalid_data_error(format!(
+                    "Invalid JSON-LD term definition for '{}': it should be a string, an object or null",
+                    term
+                )))
+            }
+        };
+
+        let mut definition = TermDefinition {
+            protected: match value.get("@protected") {
+                Some(JsonValue::Boolean(value)) => *value,
+                Some(_) => {
+                    return Err(invalid_data_error(
+                        "Invalid JSON-LD @protected value: it should be a boolean",
+                    ))
+                }
+                None => self.protected,
+            },
+            ..TermDefinition::default()
+        };
+
+        if let Some(type_mapping) = value.get("@type") {
+            let type_mapping = type_mapping
+                .as_str()
+                .and_then(|t| self.expand_iri(active_context, t, false, true).transpose())
+                .transpose()?
+                .filter(|t| {
+                    matches!(t.as_str(), "@id" | "@json" | "@none" | "@vocab")
+                        || Iri::parse(t.as_str()).is_ok()
+                })
+                .ok_or_else(|| {
+                    invalid_data_error(format!("Invalid JSON-LD type mapping for term '{}'", term))
+                })?;
+            definition.type_mapping = Some(type_mapping);
+        }
+
+        if let Some(reverse) = value.get("@reverse") {
+            if value.contains_key("@id") || value.contains_key("@nest") {
+                return Err(invalid_data_error(format!(
+                    "Invalid JSON-LD reverse property '{}': it should not have an @id or @nest entry",
+                    term
+                )));
+            }
+            let reverse = reverse.as_str().ok_or_else(|| {
+                invalid_data_error(format!(
+                    "Invalid JSON-LD IRI mapping for term '{}': @reverse should be a string",
+                    term
+                ))
+            })?;
+            if has_keyword_form(reverse) {
+                return Ok(());
+            }
+            let iri = self
+                .expand_iri(active_context, reverse, false, true)?
+                .filter(|iri| is_iri_or_blank_node(iri))
+                .ok_or_else(|| {
+                    invalid_data_error(format!(
+                        "Invalid JSON-LD IRI mapping for the reverse property '{}'",
+                        term
+                    ))
+                })?;
+            definition.iri_mapping = Some(iri);
+            match value.get("@container") {
+                None | Some(JsonValue::Null) => (),
+                Some(JsonValue::String(c)) if c == "@set" => {
+                    definition.container_mapping = vec!["@set"]
+                }
+                Some(JsonValue::String(c)) if c == "@index" => {
+                    definition.container_mapping = vec!["@index"]
+                }
+                _ => {
+                    return Err(invalid_data_error(format!(
+                        "Invalid JSON-LD reverse property '{}': only @set and @index containers are allowed",
+                        term
+                    )))
+                }
+            }
+            definition.reverse_property = true;
+            active_context
+                .term_definitions
+                .insert(term.to_owned(), definition);
+            self.defined.insert(term.to_owned(), true);
+            return Ok(());
+        }
+
+        match value.get("@id") {
+            Some(id) if id.as_str() != Some(term) => match id {
+                JsonValue::Null => definition.iri_mapping = None,
+                JsonValue::String(id) => {
+                    if !is_keyword(id) && has_keyword_form(id) {
+                        return Ok(());
+                    }
+                    let iri = self
+                        .expand_iri(active_context, id, false, true)?
+                        .filter(|iri| is_keyword(iri) || is_iri_or_blank_node(iri))
+                        .ok_or_else(|| {
+                            invalid_data_error(format!(
+                                "Invalid JSON-LD IRI mapping for term '{}'",
+                                term
+                            ))
+                        })?;
+                    if iri == "@context" {
+                        return Err(invalid_data_error(
+                            "Invalid JSON-LD keyword alias: @context could not be aliased",
+                        ));
+                    }
+                    let inner_term =
+                        matches!(term.get(1..term.len() - 1), Some(t) if t.contains(':'));
+                    if inner_term || term.contains('/') {
+                        self.defined.insert(term.to_owned(), true);
+                        if self.expand_iri(active_context, term, false, true)?.as_ref()
+                            != Some(&iri)
+                        {
+                            return Err(invalid_data_error(format!(
+                                "Invalid JSON-LD IRI mapping: the term '{}' does not expand to {}",
+                                term, iri
+                            )));
+                        }
+                    }
+                    if !term.contains(':')
+                        && !term.contains('/')
+                        && simple_term
+                        && (iri.ends_with(&[':', '/', '?', '#', '[', ']', '@'][..])
+                            || iri.starts_with("_:"))
+                    {
+                        definition.prefix = true;
+                    }
+                    definition.iri_mapping = Some(iri);
+                }
+                _ => {
+                    return Err(invalid_data_error(format!(
+                        "Invalid JSON-LD IRI mapping for term '{}': @id should be a string",
+                        term
+                    )))
+                }
+            },
+            _ => {
+                definition.iri_mapping = Some(
+                    if let Some((prefix, suffix)) = split_compact_iri(term) {
+                        if local_context.contains_key(prefix) {
+                            self.create_term_definition(active_context, prefix)?;
+                        }
+                        if let Some(iri) = active_context
+                            .term_definitions
+                            .get(prefix)
+                            .and_then(|d| d.iri_mapping.as_ref())
+                        {
+                            format!("{}{}", iri, suffix)
+                        } else {
+                            term.to_owned()
+                        }
+                    } else if term.contains('/') {
+                        self.expand_iri(active_context, term, false, true)?
+                            .filter(|iri| Iri::parse(iri.as_str()).is_ok())
+                            .ok_or_else(|| {
+                                invalid_data_error(format!(
+                                    "Invalid JSON-LD IRI mapping: the term '{}' is not an IRI",
+                                    term
+                                ))
+                            })?
+                    } else if term == "@type" {
+                        "@type".to_owned()
+                    } else if let Some(vocabulary_mapping) = &active_context.vocabulary_mapping {
+                        format!("{}{}", vocabulary_mapping, term)
+                    } else {
+                        return Err(invalid_data_error(format!(
+                        "Invalid JSON-LD IRI mapping: the term '{}' could not be mapped to an IRI without a vocabulary mapping",
+                        term
+                    )));
+                    },
+                )
+            }
+        }
+
+        if let Some(container) = value.get("@container") {
+            for container in container.as_slice() {
+                let container = container
+                    .as_str()
+                    .and_then(|c| CONTAINERS.iter().find(|k| **k == c))
+                    .ok_or_else(|| {
+                        invalid_data_error(format!(
+                            "Invalid JSON-LD container mapping for term '{}'",
+                            term
+                        ))
+                    })?;
+                definition.container_mapping.push(container);
+            }
+            definition.container_mapping.sort_unstable();
+            if definition.has_container("@type") {
+                let type_mapping = definition.type_mapping.get_or_insert_with(|| "@id".into());
+                if type_mapping != "@id" && type_mapping != "@vocab" {
+                    return Err(invalid_data_error(format!(
+                        "Invalid JSON-LD type mapping for term '{}': @type containers require the @id or @vocab type",
+                        term
+                    )));
+                }
+            }
+        }
+
+        if let Some(index) = value.get("@index") {
+            match index {
+                JsonValue::String(index)
+                    if definition.has_container("@index") && !is_keyword(index) =>
+                {
+                    definition.index_mapping = Some(index.clone())
+                }
+                _ => {
+                    return Err(invalid_data_error(format!(
+                        "Invalid JSON-LD term definition for '{}': invalid @index entry",
+                        term
+                    )))
+                }
+            }
+        }
+
+        if let Some(context) = value.get("@context") {
+            process_context(
+                active_context,
+                context,
+                self.base_url,
+                &mut self.remote_contexts.to_vec(),
+                true,
+                true,
+                false,
+                self.loader,
+            )
+            .map_err(|e| {
+                invalid_data_error(format!(
+                    "Invalid JSON-LD scoped context for term '{}': {}",
+                    term, e
+                ))
+            })?;
+            definition.context = Some(context.clone());
+            definition.base_iri = self.base_url.cloned();
+        }
+
+        if !value.contains_key("@type") {
+            if let Some(language) = value.get("@language") {
+                definition.language_mapping = Some(match language {
+                    JsonValue::Null => None,
+                    JsonValue::String(language) => Some(language.to_ascii_lowercase()),
+                    _ => {
+                        return Err(invalid_data_error(format!(
+                            "Invalid JSON-LD language mapping for term '{}'",
+                            term
+                        )))
+                    }
+                })
+            }
+            if let Some(direction) = value.get("@direction") {
+                definition.direction_mapping = Some(parse_direction(direction)?);
+            }
+        }
+
+        if let Some(nest) = value.get("@nest") {
+            match nest {
+                JsonValue::String(nest) if nest == "@nest" || !is_keyword(nest) => {
+                    definition.nest_value = Some(nest.clone())
+                }
+                _ => {
+                    return Err(invalid_data_error(format!(
+                        "Invalid JSON-LD @nest value for term '{}'",
+                        term
+                    )))
+                }
+            }
+        }
+
+        if let Some(prefix) = value.get("@prefix") {
+            if term.contains(':') || term.contains('/') {
+                return Err(invalid_data_error(format!(
+                    "Invalid JSON-LD term definition: the term '{}' could not be used as a prefix",
+                    term
+                )));
+            }
+            if let JsonValue::Boolean(prefix) = prefix {
+                definition.prefix = *prefix;
+            } else {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD @prefix value: it should be a boolean",
+                ));
+            }
+            if definition.prefix && matches!(&definition.iri_mapping, Some(iri) if is_keyword(iri))
+            {
+                return Err(invalid_data_error(format!(
+                    "Invalid JSON-LD term definition: the keyword alias '{}' could not be used as a prefix",
+                    term
+                )));
+            }
+        }
+
+        if let Some(key) = value.keys().find(|k| {
+            !matches!(
+                k.as_str(),
+                "@id"
+                    | "@reverse"
+                    | "@container"
+                    | "@context"
+                    | "@direction"
+                    | "@index"
+                    | "@language"
+                    | "@nest"
+                    | "@prefix"
+                    | "@protected"
+                    | "@type"
+            )
+        }) {
+            return Err(invalid_data_error(format!(
+                "Invalid JSON-LD term definition for '{}': unexpected entry {}",
+                term, key
+            )));
+        }
+
+        if !self.override_protected {
+            if let Some(previous_definition) = previous_definition {
+                if previous_definition.protected {
+                    definition.protected = true;
+                    if definition != previous_definition {
+                        return Err(invalid_data_error(format!(
+                            "JSON-LD protected term redefinition: the term '{}' is protected",
+                            term
+                        )));
+                    }
+                }
+            }
+        }
+
+        active_context
+            .term_definitions
+            .insert(term.to_owned(), definition);
+        self.defined.insert(term.to_owned(), true);
+        Ok(())
+    }
+
+    /// IRI expansion that creates the needed term definitions from the local context
+    fn expand_iri(
+        &mut self,
+        active_context: &mut JsonLdContext,
+        value: &str,
+        document_relative: bool,
+        vocab: bool,
+    ) -> Result<Option<String>, io::Error> {
+        if self.local_context.contains_key(value) && self.defined.get(value) != Some(&true) {
+            self.create_term_definition(active_context, value)?;
+        }
+        if let Some((prefix, _)) = split_compact_iri(value) {
+            if self.local_context.contains_key(prefix) && self.defined.get(prefix) != Some(&true) {
+                self.create_term_definition(active_context, prefix)?;
+            }
+        }
+        Ok(active_context.expand_iri(value, document_relative, vocab))
+    }
+}
+
+fn load_remote_context(
+    iri: &Iri<String>,
+    loader: &dyn JsonLdContextLoader,
+) -> Result<JsonValue, io::Error> {
+    let document = loader.load(iri.as_str())?;
+    if let JsonValue::Object(mut document) = read_json(Cursor::new(document))? {
+        if let Some(context) = document.remove("@context") {
+            return Ok(context);
+        }
+    }
+    Err(invalid_data_error(format!(
+        "Invalid JSON-LD remote context: {} should be an object with an @context entry",
+        iri
+    )))
+}
+
+fn resolve_iri(base_url: Option<&Iri<String>>, iri: &str) -> Result<Iri<String>, io::Error> {
+    if let Some(base_url) = base_url {
+        base_url.resolve(iri)
+    } else {
+        Iri::parse(iri.to_owned())
+    }
+    .map_err(|e| invalid_data_error(format!("Invalid JSON-LD remote context IRI {}: {}", iri, e)))
+}
+
+fn parse_direction(value: &JsonValue) -> Result<Option<String>, io::Error> {
+    match value {
+        JsonValue::Null => Ok(None),
+        JsonValue::String(value) if value == "ltr" || value == "rtl" => Ok(Some(value.clone())),
+        _ => Err(invalid_data_error(
+            "Invalid JSON-LD base direction: it should be 'ltr', 'rtl' or null",
+        )),
+    }
+}
+
+/// Splits a compact IRI into its prefix and suffix
+pub fn split_compact_iri(value: &str) -> Option<(&str, &str)> {
+    let position = value.find(':')?;
+    if position == 0 {
+        None
+    } else {
+        Some((&value[..position], &value[position + 1..]))
+    }
+}
+
+pub fn is_keyword(value: &str) -> bool {
+    matches!(
+        value,
+        "@base"
+            | "@container"
+            | "@context"
+            | "@direction"
+            | "@graph"
+            | "@id"
+            | "@import"
+            | "@included"
+            | "@index"
+            | "@json"
+            | "@language"
+            | "@list"
+            | "@nest"
+            | "@none"
+            | "@prefix"
+            | "@propagate"
+            | "@protected"
+            | "@reverse"
+            | "@set"
+            | "@type"
+            | "@value"
+            | "@version"
+            | "@vocab"
+    )
+}
+
+/// Checks if the value matches the `@[a-zA-Z]+` regular expression
+pub fn has_keyword_form(value: &str) -> bool {
+    value.len() > 1 && value.starts_with('@') && value[1..].bytes().all(|c| c.is_ascii_alphabetic())
+}
+
+pub fn is_iri_or_blank_node(value: &str) -> bool {
+    value.starts_with("_:") || Iri::parse(value).is_ok()
+}
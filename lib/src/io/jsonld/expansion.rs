@@ -0,0 +1,753 @@
+//! Implementation of the [JSON-LD expansion algorithm](https://www.w3.org/TR/json-ld11-api/#expansion-algorithm)
+
+use crate::error::invalid_data_error;
+use crate::io::jsonld::context::{is_keyword, process_context, JsonLdContext};
+use crate::io::jsonld::json::{JsonObject, JsonValue};
+use crate::io::jsonld::JsonLdContextLoader;
+use oxiri::Iri;
+use std::borrow::Cow;
+use std::io;
+
+/// Expands a JSON-LD document and returns its top-level node objects
+pub fn expand_document(
+    document: &JsonValue,
+    base_iri: Option<Iri<String>>,
+    loader: &dyn JsonLdContextLoader,
+) -> Result<Vec<JsonValue>, io::Error> {
+    let expander = Expander {
+        base_url: base_iri.clone(),
+        loader,
+    };
+    let active_context = JsonLdContext::new(base_iri);
+    Ok(
+        match expander.expand(&active_context, None, document, false)? {
+            Some(JsonValue::Object(mut object))
+                if object.len() == 1 && object.contains_key("@graph") =>
+            {
+                object
+                    .remove("@graph")
+                    .unwrap_or(JsonValue::Null)
+                    .into_vec()
+            }
+            Some(value) => value.into_vec(),
+            None => Vec::new(),
+        },
+    )
+}
+
+struct Expander<'a> {
+    base_url: Option<Iri<String>>,
+    loader: &'a dyn JsonLdContextLoader,
+}
+
+impl<'a> Expander<'a> {
+    fn expand(
+        &self,
+        active_context: &JsonLdContext,
+        active_property: Option<&str>,
+        element: &JsonValue,
+        from_map: bool,
+    ) -> Result<Option<JsonValue>, io::Error> {
+        let property_definition = active_property.and_then(|p| active_context.term_definition(p));
+        match element {
+            JsonValue::Null => Ok(None),
+            JsonValue::Array(items) => {
+                let is_list = matches!(property_definition, Some(d) if d.has_container("@list"));
+                let mut result = Vec::with_capacity(items.len());
+                for item in items {
+                    match self.expand(active_context, active_property, item, from_map)? {
+                        Some(JsonValue::Array(expanded)) if is_list => {
+                            result.push(new_object("@list", JsonValue::Array(expanded)).into())
+                        }
+                        Some(JsonValue::Array(expanded)) => result.extend(expanded),
+                        Some(expanded) => result.push(expanded),
+                        None => (),
+                    }
+                }
+                Ok(Some(JsonValue::Array(result)))
+            }
+            JsonValue::Object(element) => {
+                self.expand_object(active_context, active_property, element, from_map)
+            }
+            _ => {
+                let active_property = match active_property {
+                    Some(p) if p != "@graph" => p,
+                    _ => return Ok(None), // Free floating scalar
+                };
+                if let Some(definition) = property_definition {
+                    if let Some(context) = &definition.context {
+                        let active_context = self.process_context(
+                            active_context,
+                            context,
+                            definition.base_iri.as_ref(),
+                            false,
+                            true,
+                        )?;
+                        return Ok(Some(expand_value(
+                            &active_context,
+                            active_property,
+                            element,
+                        )));
+                    }
+                }
+                Ok(Some(expand_value(active_context, active_property, element)))
+            }
+        }
+    }
+
+    fn expand_object(
+        &self,
+        active_context: &JsonLdContext,
+        active_property: Option<&str>,
+        element: &JsonObject,
+        from_map: bool,
+    ) -> Result<Option<JsonValue>, io::Error> {
+        let mut active_context = Cow::Borrowed(active_context);
+
+        // We revert to the previous context if the current one should not be propagated
+        if let Some(previous_context) = &active_context.previous_context {
+            if !from_map {
+                let expanded_keys = element
+                    .keys()
+                    .map(|k| active_context.expand_iri(k, false, true))
+                    .collect::<Vec<_>>();
+                let has_value = expanded_keys.iter().any(|k| k.as_deref() == Some("@value"));
+                let is_reference =
+                    expanded_keys.len() == 1 && expanded_keys[0].as_deref() == Some("@id");
+                if !has_value && !is_reference {
+                    active_context = Cow::Owned(previous_context.as_ref().clone());
+                }
+            }
+        }
+
+        // Property-scoped context
+        if let Some(definition) = active_property.and_then(|p| active_context.term_definition(p)) {
+            if let Some(context) = &definition.context {
+                let base_url = definition.base_iri.clone();
+                active_context = Cow::Owned(self.process_context(
+                    &active_context,
+                    context,
+                    base_url.as_ref(),
+                    true,
+                    true,
+                )?);
+            }
+        }
+
+        // Embedded context
+        if let Some(context) = element.get("@context") {
+            active_context = Cow::Owned(self.process_context(
+                &active_context,
+                context,
+                self.base_url.as_ref(),
+                false,
+                true,
+            )?);
+        }
+
+        // Type-scoped contexts
+        let type_scoped_context = active_context.clone().into_owned();
+        let type_keys = element
+            .keys()
+            .filter(|k| active_context.expand_iri(k, false, true).as_deref() == Some("@type"))
+            .collect::<Vec<_>>();
+        for key in &type_keys {
+            let mut terms = element[key.as_str()]
+                .as_slice()
+                .iter()
+                .filter_map(JsonValue::as_str)
+                .collect::<Vec<_>>();
+            terms.sort_unstable();
+            for term in terms {
+                if let Some(definition) = type_scoped_context.term_definition(term) {
+                    if let Some(context) = &definition.context {
+                        active_context = Cow::Owned(self.process_context(
+                            &active_context,
+                            context,
+                            definition.base_iri.as_ref(),
+                            false,
+                            false,
+                        )?);
+                    }
+                }
+            }
+        }
+        let input_type = type_keys
+            .first()
+            .and_then(|k| element[k.as_str()].as_slice().last())
+            .and_then(JsonValue::as_str)
+            .and_then(|t| type_scoped_context.expand_iri(t, false, true));
+
+        let mut result = JsonObject::new();
+        self.expand_object_entries(
+            &active_context,
+            &type_scoped_context,
+            active_property,
+            element,
+            input_type.as_deref(),
+            &mut result,
+        )?;
+
+        if let Some(value) = result.get("@value") {
+            if result.keys().any(|k| {
+                !matches!(
+                    k.as_str(),
+                    "@direction" | "@index" | "@language" | "@type" | "@value"
+                )
+            }) || (result.contains_key("@type")
+                && (result.contains_key("@language") || result.contains_key("@direction")))
+            {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD value object: it contains unexpected entries",
+                ));
+            }
+            if let Some(types) = result.get("@type") {
+                let datatype = match types.as_slice() {
+                    [JsonValue::String(datatype)]
+                        if datatype == "@json" || Iri::parse(datatype.as_str()).is_ok() =>
+                    {
+                        datatype.clone()
+                    }
+                    _ => {
+                        return Err(invalid_data_error(
+                            "Invalid JSON-LD typed value: the datatype should be an IRI",
+                        ))
+                    }
+                };
+                if datatype != "@json" && value.is_null() {
+                    return Ok(None);
+                }
+                result.insert("@type".into(), datatype.into());
+            } else if value.is_null() {
+                return Ok(None);
+            } else if value.as_str().is_none() && result.contains_key("@language") {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD language-tagged value: the value should be a string",
+                ));
+            } else if !value.is_scalar() {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD value object value: it should be a scalar",
+                ));
+            }
+        } else if result.contains_key("@set") || result.contains_key("@list") {
+            if result.len() > 2 || (result.len() == 2 && !result.contains_key("@index")) {
+                return Err(invalid_data_error(
+                    "Invalid JSON-LD set or list object: it contains unexpected entries",
+                ));
+            }
+            if let Some(set) = result.remove("@set") {
+                return Ok(Some(set));
+            }
+        }
+        if result.len() == 1 && result.contains_key("@language") {
+            return Ok(None);
+        }
+        if matches!(active_property, None | Some("@graph"))
+            && (result.is_empty()
+                || result.contains_key("@value")
+                || result.contains_key("@list")
+                || (result.len() == 1 && result.contains_key("@id")))
+        {
+            return Ok(None);
+        }
+        Ok(Some(JsonValue::Object(result)))
+    }
+
+    fn expand_object_entries(
+        &self,
+        active_context: &JsonLdContext,
+        type_scoped_context: &JsonLdContext,
+        active_property: Option<&str>,
+        element: &JsonObject,
+        input_type: Option<&str>,
+        result: &mut JsonObject,
+    ) -> Result<(), io::Error> {
+        let mut nests = Vec::new();
+        for (key, value) in element {
+            if key == "@context" {
+                continue;
+            }
+            let expanded_property = match active_context.expand_iri(key, false, true) {
+                Some(p) if p.contains(':') || is_keyword(&p) => p,
+                _ => continue, // We drop the properties that are not mapped to IRIs
+            };
+
+            if is_keyword(&expanded_property) {
+                if active_property == Some("@reverse") {
+                    return Err(invalid_data_error(
+                        "Invalid JSON-LD reverse property map: it should not contain keywords",
+                    ));
+                }
+                if result.contains_key(&expanded_property)
+                    && expanded_property != "@included"
+                    && expanded_property != "@type"
+                {
+                    return Err(invalid_data_error(format!(
+                        "JSON-LD colliding keywords: {} is used multiple times",
+                        expanded_property
+                    )));
+                }
+                let expanded_value = match expanded_property.as_str() {
+                    "@id" => {
+                        if let JsonValue::String(id) = value {
+                            active_context
+                                .expand_iri(id, true, false)
+                                .map(JsonValue::String)
+                        } else {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD @id value: it should be a string",
+                            ));
+                        }
+                    }
+                    "@type" => {
+                        let mut types = result
+                            .remove("@type")
+                            .map(JsonValue::into_vec)
+                            .unwrap_or_default();
+                        for t in value.as_slice() {
+                            if let JsonValue::String(t) = t {
+                                types.extend(
+                                    type_scoped_context
+                                        .expand_iri(t, true, true)
+                                        .map(JsonValue::String),
+                                );
+                            } else {
+                                return Err(invalid_data_error(
+                                    "Invalid JSON-LD type value: it should be a string or an array of strings",
+                                ));
+                            }
+                        }
+                        Some(JsonValue::Array(types))
+                    }
+                    "@graph" => Some(JsonValue::Array(
+                        self.expand(active_context, Some("@graph"), value, false)?
+                            .map(JsonValue::into_vec)
+                            .unwrap_or_default(),
+                    )),
+                    "@included" => {
+                        let mut included = result
+                            .remove("@included")
+                            .map(JsonValue::into_vec)
+                            .unwrap_or_default();
+                        for item in self
+                            .expand(active_context, None, value, false)?
+                            .map(JsonValue::into_vec)
+                            .unwrap_or_default()
+                        {
+                            if !is_node_object(&item) {
+                                return Err(invalid_data_error(
+                                    "Invalid JSON-LD @included value: it should only contain node objects",
+                                ));
+                            }
+                            included.push(item);
+                        }
+                        Some(JsonValue::Array(included))
+                    }
+                    "@value" => {
+                        if input_type == Some("@json") || value.is_null() || value.is_scalar() {
+                            result.insert("@value".into(), value.clone());
+                            continue;
+                        } else {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD value object value: it should be a scalar or null",
+                            ));
+                        }
+                    }
+                    "@language" => {
+                        if let JsonValue::String(language) = value {
+                            Some(language.to_ascii_lowercase().into())
+                        } else {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD language-tagged string: @language should be a string",
+                            ));
+                        }
+                    }
+                    "@direction" => match value.as_str() {
+                        Some("ltr") | Some("rtl") => Some(value.clone()),
+                        _ => {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD base direction: it should be 'ltr' or 'rtl'",
+                            ))
+                        }
+                    },
+                    "@index" => {
+                        if value.as_str().is_some() {
+                            Some(value.clone())
+                        } else {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD @index value: it should be a string",
+                            ));
+                        }
+                    }
+                    "@list" => {
+                        if matches!(active_property, None | Some("@graph")) {
+                            continue; // Free floating list
+                        }
+                        Some(JsonValue::Array(
+                            self.expand(active_context, active_property, value, false)?
+                                .map(JsonValue::into_vec)
+                                .unwrap_or_default(),
+                        ))
+                    }
+                    "@set" => self.expand(active_context, active_property, value, false)?,
+                    "@reverse" => {
+                        if value.as_object().is_none() {
+                            return Err(invalid_data_error(
+                                "Invalid JSON-LD @reverse value: it should be an object",
+                            ));
+                        }
+                        if let Some(JsonValue::Object(mut expanded)) =
+                            self.expand(active_context, Some("@reverse"), value, false)?
+                        {
+                            if let Some(JsonValue::Object(reverse)) = expanded.remove("@reverse") {
+                                for (property, items) in reverse {
+                                    add_values(result, property, items.into_vec());
+                                }
+                            }
+                            if !expanded.is_empty() {
+                                let reverse_map = reverse_map(result);
+                                for (property, items) in expanded {
+                                    let items = items.into_vec();
+                                    if items
+                                        .iter()
+                                        .any(|i| is_value_object(i) || is_list_object(i))
+                                    {
+                                        return Err(invalid_data_error(
+                                            "Invalid JSON-LD reverse property value: it should be a node object",
+                                        ));
+                                    }
+                                    add_values(reverse_map, property, items);
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    "@nest" => {
+                        nests.push(key.as_str());
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if let Some(expanded_value) = expanded_value {
+                    result.insert(expanded_property, expanded_value);
+                }
+                continue;
+            }
+
+            let key_definition = active_context.term_definition(key);
+            let has_container =
+                |container: &str| matches!(key_definition, Some(d) if d.has_container(container));
+            let expanded_value = if key_definition.and_then(|d| d.type_mapping.as_deref())
+                == Some("@json")
+            {
+                let mut value_object = new_object("@value", value.clone());
+                value_object.insert("@type".into(), "@json".into());
+                Some(JsonValue::Object(value_object))
+            } else if let (true, JsonValue::Object(language_map)) =
+                (has_container("@language"), value)
+            {
+                let direction = match key_definition.and_then(|d| d.direction_mapping.clone()) {
+                    Some(direction) => direction,
+                    None => active_context.default_direction.clone(),
+                };
+                let mut items = Vec::new();
+                for (language, language_values) in language_map {
+                    for item in language_values.as_slice() {
+                        match item {
+                            JsonValue::Null => (),
+                            JsonValue::String(_) => {
+                                let mut item = new_object("@value", item.clone());
+                                if active_context.expand_iri(language, false, true).as_deref()
+                                    != Some("@none")
+                                {
+                                    item.insert(
+                                        "@language".into(),
+                                        language.to_ascii_lowercase().into(),
+                                    );
+                                }
+                                if let Some(direction) = &direction {
+                                    item.insert("@direction".into(), direction.as_str().into());
+                                }
+                                items.push(JsonValue::Object(item));
+                            }
+                            _ => {
+                                return Err(invalid_data_error(
+                                    "Invalid JSON-LD language map value: it should be a string",
+                                ))
+                            }
+                        }
+                    }
+                }
+                Some(JsonValue::Array(items))
+            } else if let (true, JsonValue::Object(map)) = (
+                has_container("@index") || has_container("@type") || has_container("@id"),
+                value,
+            ) {
+                let index_key = key_definition
+                    .and_then(|d| d.index_mapping.as_deref())
+                    .unwrap_or("@index");
+                let mut items = Vec::new();
+                for (index, index_value) in map {
+                    let mut map_context = Cow::Borrowed(active_context);
+                    if has_container("@id") || has_container("@type") {
+                        if let Some(previous_context) = &active_context.previous_context {
+                            map_context = Cow::Borrowed(previous_context);
+                        }
+                    }
+                    if has_container("@type") {
+                        if let Some(definition) = map_context.term_definition(index) {
+                            if let Some(context) = &definition.context {
+                                let base_url = definition.base_iri.clone();
+                                map_context = Cow::Owned(self.process_context(
+                                    &map_context,
+                                    context,
+                                    base_url.as_ref(),
+                                    false,
+                                    true,
+                                )?);
+                            }
+                        }
+                    }
+                    let expanded_index = active_context.expand_iri(index, false, true);
+                    let is_none = expanded_index.as_deref() == Some("@none");
+                    let index_items = self
+                        .expand(
+                            &map_context,
+                            Some(key),
+                            &JsonValue::Array(index_value.as_slice().to_vec()),
+                            true,
+                        )?
+                        .map(JsonValue::into_vec)
+                        .unwrap_or_default();
+                    for item in index_items {
+                        let mut item = if let JsonValue::Object(item) = item {
+                            item
+                        } else {
+                            continue;
+                        };
+                        if has_container("@graph") && !is_graph_object(&item) {
+                            item = new_object("@graph", JsonValue::Array(vec![item.into()]));
+                        }
+                        if has_container("@index") && index_key != "@index" && !is_none {
+                            let reexpanded_index =
+                                expand_value(active_context, index_key, &index.as_str().into());
+                            if let Some(expanded_index_key) =
+                                active_context.expand_iri(index_key, false, true)
+                            {
+                                let mut values = vec![reexpanded_index];
+                                values.extend(
+                                    item.remove(&expanded_index_key)
+                                        .map(JsonValue::into_vec)
+                                        .unwrap_or_default(),
+                                );
+                                item.insert(expanded_index_key, JsonValue::Array(values));
+                            }
+                            if item.contains_key("@value") {
+                                return Err(invalid_data_error(
+                                    "Invalid JSON-LD value object: property-valued indexes could not be used on values",
+                                ));
+                            }
+                        } else if has_container("@index")
+                            && !item.contains_key("@index")
+                            && !is_none
+                        {
+                            item.insert("@index".into(), index.as_str().into());
+                        } else if has_container("@id") && !item.contains_key("@id") && !is_none {
+                            if let Some(id) = active_context.expand_iri(index, true, false) {
+                                item.insert("@id".into(), id.into());
+                            }
+                        } else if has_container("@type") && !is_none {
+                            if let Some(expanded_index) = &expanded_index {
+                                let mut types = vec![expanded_index.as_str().into()];
+                                types.extend(
+                                    item.remove("@type")
+                                        .map(JsonValue::into_vec)
+                                        .unwrap_or_default(),
+                                );
+                                item.insert("@type".into(), JsonValue::Array(types));
+                            }
+                        }
+                        items.push(JsonValue::Object(item));
+                    }
+                }
+                Some(JsonValue::Array(items))
+            } else {
+                self.expand(active_context, Some(key), value, false)?
+            };
+
+            let mut expanded_value = if let Some(expanded_value) = expanded_value {
+                expanded_value
+            } else {
+                continue;
+            };
+            if has_container("@list") && !is_list_object(&expanded_value) {
+                expanded_value =
+                    new_object("@list", JsonValue::Array(expanded_value.into_vec())).into();
+            }
+            if has_container("@graph") && !has_container("@id") && !has_container("@index") {
+                expanded_value = JsonValue::Array(
+                    expanded_value
+                        .into_vec()
+                        .into_iter()
+                        .map(|v| new_object("@graph", JsonValue::Array(v.into_vec())).into())
+                        .collect(),
+                );
+            }
+            if matches!(key_definition, Some(d) if d.reverse_property) {
+                let items = expanded_value.into_vec();
+                if items
+                    .iter()
+                    .any(|i| is_value_object(i) || is_list_object(i))
+                {
+                    return Err(invalid_data_error(
+                        "Invalid JSON-LD reverse property value: it should be a node object",
+                    ));
+                }
+                add_values(reverse_map(result), expanded_property, items);
+            } else {
+                add_values(result, expanded_property, expanded_value.into_vec());
+            }
+        }
+
+        for nesting_key in nests {
+            for nested_value in element[nesting_key].as_slice() {
+                let nested_value = nested_value
+                    .as_object()
+                    .filter(|v| {
+                        v.keys().all(|k| {
+                            active_context.expand_iri(k, false, true).as_deref() != Some("@value")
+                        })
+                    })
+                    .ok_or_else(|| {
+                        invalid_data_error(
+                            "Invalid JSON-LD @nest value: it should be a node object",
+                        )
+                    })?;
+                self.expand_object_entries(
+                    active_context,
+                    type_scoped_context,
+                    active_property,
+                    nested_value,
+                    input_type,
+                    result,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn process_context(
+        &self,
+        active_context: &JsonLdContext,
+        local_context: &JsonValue,
+        base_url: Option<&Iri<String>>,
+        override_protected: bool,
+        propagate: bool,
+    ) -> Result<JsonLdContext, io::Error> {
+        process_context(
+            active_context,
+            local_context,
+            base_url,
+            &mut Vec::new(),
+            override_protected,
+            propagate,
+            true,
+            self.loader,
+        )
+    }
+}
+
+/// The [value expansion algorithm](https://www.w3.org/TR/json-ld11-api/#value-expansion)
+fn expand_value(
+    active_context: &JsonLdContext,
+    active_property: &str,
+    value: &JsonValue,
+) -> JsonValue {
+    let definition = active_context.term_definition(active_property);
+    let type_mapping = definition.and_then(|d| d.type_mapping.as_deref());
+    if let JsonValue::String(id) = value {
+        let id = match type_mapping {
+            Some("@id") => Some(active_context.expand_iri(id, true, false)),
+            Some("@vocab") => Some(active_context.expand_iri(id, true, true)),
+            _ => None,
+        };
+        if let Some(id) = id {
+            return id.map_or(JsonValue::Null, |id| new_object("@id", id.into()).into());
+        }
+    }
+    let mut result = new_object("@value", value.clone());
+    match type_mapping {
+        Some(type_mapping) if !matches!(type_mapping, "@id" | "@vocab" | "@none") => {
+            result.insert("@type".into(), type_mapping.into());
+        }
+        _ => {
+            if value.as_str().is_some() {
+                let language = match definition.and_then(|d| d.language_mapping.as_ref()) {
+                    Some(language) => language.as_ref(),
+                    None => active_context.default_language.as_ref(),
+                };
+                if let Some(language) = language {
+                    result.insert("@language".into(), language.as_str().into());
+                }
+                let direction = match definition.and_then(|d| d.direction_mapping.as_ref()) {
+                    Some(direction) => direction.as_ref(),
+                    None => active_context.default_direction.as_ref(),
+                };
+                if let Some(direction) = direction {
+                    result.insert("@direction".into(), direction.as_str().into());
+                }
+            }
+        }
+    }
+    result.into()
+}
+
+fn new_object(key: &str, value: JsonValue) -> JsonObject {
+    let mut object = JsonObject::new();
+    object.insert(key.into(), value);
+    object
+}
+
+fn add_values(object: &mut JsonObject, key: String, values: Vec<JsonValue>) {
+    if let JsonValue::Array(existing) = object
+        .entry(key)
+        .or_insert_with(|| JsonValue::Array(Vec::new()))
+    {
+        existing.extend(values);
+    }
+}
+
+fn reverse_map(object: &mut JsonObject) -> &mut JsonObject {
+    let reverse = object
+        .entry("@reverse".into())
+        .or_insert_with(|| JsonValue::Object(JsonObject::new()));
+    if !matches!(reverse, JsonValue::Object(_)) {
+        *reverse = JsonValue::Object(JsonObject::new());
+    }
+    match reverse {
+        JsonValue::Object(reverse) => reverse,
+        _ => unreachable!(),
+    }
+}
+
+fn is_value_object(value: &JsonValue) -> bool {
+    matches!(value.as_object(), Some(o) if o.contains_key("@value"))
+}
+
+fn is_list_object(value: &JsonValue) -> bool {
+    matches!(value.as_object(), Some(o) if o.contains_key("@list"))
+}
+
+fn is_node_object(value: &JsonValue) -> bool {
+    matches!(
+        value.as_object(),
+        Some(o) if !o.contains_key("@value") && !o.contains_key("@list") && !o.contains_key("@set")
+    )
+}
+
+fn is_graph_object(value: &JsonObject) -> bool {
+    value.contains_key("@graph")
+        && value
+            .keys()
+            .all(|k| matches!(k.as_str(), "@graph" | "@id" | "@index" | "@context"))
+}
@@ -0,0 +1,204 @@
+//! Implementation of the [RDF to JSON-LD algorithm](https://www.w3.org/TR/json-ld11-api/#serialize-rdf-as-json-ld-algorithm)
+
+use crate::io::jsonld::json::{read_json, JsonObject, JsonValue};
+use crate::model::vocab::{rdf, xsd};
+use crate::model::*;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+
+const RDF_JSON: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON";
+
+/// Converts RDF quads to expanded JSON-LD node objects
+pub fn quads_to_expanded(quads: &[Quad]) -> Vec<JsonValue> {
+    let mut graphs = BTreeMap::<String, GraphNodes>::new();
+    graphs.insert("@default".into(), GraphNodes::default());
+    for quad in quads {
+        let graph_key = match &quad.graph_name {
+            GraphName::NamedNode(node) => node.as_str().to_owned(),
+            GraphName::BlankNode(node) => format!("_:{}", node.as_str()),
+            GraphName::DefaultGraph => "@default".into(),
+        };
+        graphs.entry(graph_key).or_default().add_quad(quad);
+    }
+
+    let mut default_graph = graphs.remove("@default").unwrap_or_default();
+    default_graph.fold_lists();
+    for (graph_name, mut graph) in graphs {
+        graph.fold_lists();
+        let nodes = graph.into_nodes();
+        default_graph
+            .node(&graph_name)
+            .insert("@graph".into(), JsonValue::Array(nodes));
+    }
+    default_graph.into_nodes()
+}
+
+#[derive(Default)]
+struct GraphNodes {
+    nodes: BTreeMap<String, JsonObject>,
+    /// Where each blank node is used as an object
+    usages: HashMap<String, Vec<Usage>>,
+    nil_usages: Vec<Usage>,
+}
+
+#[derive(Clone)]
+struct Usage {
+    node: String,
+    property: String,
+    index: usize,
+}
+
+impl GraphNodes {
+    fn node(&mut self, id: &str) -> &mut JsonObject {
+        self.nodes.entry(id.to_owned()).or_insert_with(|| {
+            let mut node = JsonObject::new();
+            node.insert("@id".into(), id.into());
+            node
+        })
+    }
+
+    fn add_quad(&mut self, quad: &Quad) {
+        let subject = node_id(&quad.subject);
+        let (object, object_id) = match &quad.object {
+            Term::NamedNode(node) => {
+                if quad.predicate == rdf::TYPE {
+                    push_value(self.node(&subject), "@type", node.as_str().into());
+                    return;
+                }
+                (
+                    reference(node.as_str().to_owned()),
+                    Some(node.as_str().to_owned()),
+                )
+            }
+            Term::BlankNode(node) => {
+                let id = format!("_:{}", node.as_str());
+                if quad.predicate == rdf::TYPE {
+                    push_value(self.node(&subject), "@type", id.into());
+                    return;
+                }
+                (reference(id.clone()), Some(id))
+            }
+            Term::Literal(literal) => (convert_literal(literal), None),
+        };
+        let property = quad.predicate.as_str().to_owned();
+        let index = push_value(self.node(&subject), &property, object);
+        if let Some(object_id) = object_id {
+            self.node(&object_id);
+            let usage = Usage {
+                node: subject,
+                property,
+                index,
+            };
+            if object_id == rdf::NIL.as_str() {
+                self.nil_usages.push(usage);
+            } else if object_id.starts_with("_:") {
+                self.usages.entry(object_id).or_default().push(usage);
+            }
+        }
+    }
+
+    /// Converts `rdf:first`/`rdf:rest` chains into `@list` objects
+    fn fold_lists(&mut self) {
+        for nil_usage in self.nil_usages.clone() {
+            let mut usage = nil_usage;
+            let mut list = Vec::new();
+            let mut list_nodes = Vec::new();
+            while usage.property == rdf::REST.as_str() && self.is_list_node(&usage.node) {
+                let node = &self.nodes[&usage.node];
+                list.push(node[rdf::FIRST.as_str()].as_slice()[0].clone());
+                list_nodes.push(usage.node.clone());
+                usage = self.usages[&usage.node][0].clone();
+            }
+            list.reverse();
+            if let Some(JsonValue::Array(values)) = self
+                .nodes
+                .get_mut(&usage.node)
+                .and_then(|n| n.get_mut(&usage.property))
+            {
+                let mut list_object = JsonObject::new();
+                list_object.insert("@list".into(), JsonValue::Array(list));
+                values[usage.index] = JsonValue::Object(list_object);
+            }
+            for list_node in list_nodes {
+                self.nodes.remove(&list_node);
+            }
+        }
+    }
+
+    fn is_list_node(&self, id: &str) -> bool {
+        if !id.starts_with("_:") || self.usages.get(id).map_or(0, Vec::len) != 1 {
+            return false;
+        }
+        let node = if let Some(node) = self.nodes.get(id) {
+            node
+        } else {
+            return false;
+        };
+        node.iter().all(|(key, value)| match key.as_str() {
+            "@id" => true,
+            "@type" => value.as_slice() == [JsonValue::from(rdf::LIST.as_str())],
+            key if key == rdf::FIRST.as_str() || key == rdf::REST.as_str() => {
+                value.as_slice().len() == 1
+            }
+            _ => false,
+        }) && node.contains_key(rdf::FIRST.as_str())
+            && node.contains_key(rdf::REST.as_str())
+    }
+
+    fn into_nodes(self) -> Vec<JsonValue> {
+        self.nodes
+            .into_iter()
+            .filter(|(_, node)| node.len() > 1)
+            .map(|(_, node)| JsonValue::Object(node))
+            .collect()
+    }
+}
+
+fn node_id(node: &NamedOrBlankNode) -> String {
+    match node {
+        NamedOrBlankNode::NamedNode(node) => node.as_str().to_owned(),
+        NamedOrBlankNode::BlankNode(node) => format!("_:{}", node.as_str()),
+    }
+}
+
+fn reference(id: String) -> JsonValue {
+    let mut object = JsonObject::new();
+    object.insert("@id".into(), id.into());
+    object.into()
+}
+
+/// Adds a value to a node property and returns its position
+fn push_value(node: &mut JsonObject, property: &str, value: JsonValue) -> usize {
+    if let JsonValue::Array(values) = node
+        .entry(property.to_owned())
+        .or_insert_with(|| JsonValue::Array(Vec::new()))
+    {
+        if let Some(position) = values.iter().position(|v| *v == value) {
+            return position;
+        }
+        values.push(value);
+        values.len() - 1
+    } else {
+        0
+    }
+}
+
+fn convert_literal(literal: &Literal) -> JsonValue {
+    let mut object = JsonObject::new();
+    if let Some(language) = literal.language() {
+        object.insert("@value".into(), literal.value().into());
+        object.insert("@language".into(), language.into());
+    } else if literal.datatype() == xsd::STRING {
+        object.insert("@value".into(), literal.value().into());
+    } else if let Some(json) = Some(literal)
+        .filter(|l| l.datatype().as_str() == RDF_JSON)
+        .and_then(|l| read_json(Cursor::new(l.value())).ok())
+    {
+        object.insert("@value".into(), json);
+        object.insert("@type".into(), "@json".into());
+    } else {
+        object.insert("@value".into(), literal.value().into());
+        object.insert("@type".into(), literal.datatype().as_str().into());
+    }
+    object.into()
+}
@@ -0,0 +1,187 @@
+//! A minimal in-memory JSON tree used by the JSON-LD algorithms
+
+use crate::error::invalid_data_error;
+use json_event_parser::{JsonEvent, JsonReader, JsonWriter};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{BufRead, Write};
+
+pub type JsonObject = BTreeMap<String, JsonValue>;
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum JsonValue {
+    Null,
+    Boolean(bool),
+    /// A number in its lexical form
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(JsonObject),
+}
+
+impl JsonValue {
+    pub fn as_str(&self) -> Option<&str> {
+        if let JsonValue::String(value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&JsonObject> {
+        if let JsonValue::Object(value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == JsonValue::Null
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        matches!(
+            self,
+            JsonValue::Boolean(_) | JsonValue::Number(_) | JsonValue::String(_)
+        )
+    }
+
+    /// Returns the value as a list of values (i.e. itself if it is an array)
+    pub fn as_slice(&self) -> &[JsonValue] {
+        if let JsonValue::Array(values) = self {
+            values
+        } else {
+            std::slice::from_ref(self)
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<JsonValue> {
+        match self {
+            JsonValue::Array(values) => values,
+            JsonValue::Null => Vec::new(),
+            value => vec![value],
+        }
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> Self {
+        JsonValue::String(value.to_owned())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(value: String) -> Self {
+        JsonValue::String(value)
+    }
+}
+
+impl From<Vec<JsonValue>> for JsonValue {
+    fn from(value: Vec<JsonValue>) -> Self {
+        JsonValue::Array(value)
+    }
+}
+
+impl From<JsonObject> for JsonValue {
+    fn from(value: JsonObject) -> Self {
+        JsonValue::Object(value)
+    }
+}
+
+pub fn read_json(source: impl BufRead) -> Result<JsonValue, io::Error> {
+    let mut reader = JsonReader::from_reader(source);
+    let mut buffer = Vec::new();
+    let value = read_value(&mut reader, &mut buffer)?;
+    if reader.read_event(&mut buffer)? != JsonEvent::Eof {
+        return Err(invalid_data_error(
+            "The JSON document should contain a single value",
+        ));
+    }
+    Ok(value)
+}
+
+fn read_value<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<JsonValue, io::Error> {
+    match reader.read_event(buffer)? {
+        JsonEvent::StartArray => read_array(reader, buffer),
+        JsonEvent::StartObject => read_object(reader, buffer),
+        event => read_scalar(event),
+    }
+}
+
+fn read_array<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<JsonValue, io::Error> {
+    let mut values = Vec::new();
+    loop {
+        values.push(match reader.read_event(buffer)? {
+            JsonEvent::EndArray => return Ok(JsonValue::Array(values)),
+            JsonEvent::StartArray => read_array(reader, buffer)?,
+            JsonEvent::StartObject => read_object(reader, buffer)?,
+            event => read_scalar(event)?,
+        })
+    }
+}
+
+fn read_object<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<JsonValue, io::Error> {
+    let mut object = JsonObject::new();
+    loop {
+        let key = match reader.read_event(buffer)? {
+            JsonEvent::EndObject => return Ok(JsonValue::Object(object)),
+            JsonEvent::ObjectKey(key) => key.to_owned(),
+            _ => return Err(invalid_data_error("Invalid JSON object")),
+        };
+        let value = read_value(reader, buffer)?;
+        object.insert(key, value);
+    }
+}
+
+fn read_scalar(event: JsonEvent<'_>) -> Result<JsonValue, io::Error> {
+    match event {
+        JsonEvent::Null => Ok(JsonValue::Null),
+        JsonEvent::Boolean(value) => Ok(JsonValue::Boolean(value)),
+        JsonEvent::Number(value) => Ok(JsonValue::Number(value.to_owned())),
+        JsonEvent::String(value) => Ok(JsonValue::String(value.to_owned())),
+        JsonEvent::Eof => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Unexpected end of JSON file",
+        )),
+        _ => Err(invalid_data_error("Invalid JSON value")),
+    }
+}
+
+pub fn write_json(value: &JsonValue, sink: impl Write) -> Result<(), io::Error> {
+    let mut writer = JsonWriter::from_writer(sink);
+    write_value(value, &mut writer)
+}
+
+fn write_value<W: Write>(value: &JsonValue, writer: &mut JsonWriter<W>) -> Result<(), io::Error> {
+    match value {
+        JsonValue::Null => writer.write_event(JsonEvent::Null),
+        JsonValue::Boolean(value) => writer.write_event(JsonEvent::Boolean(*value)),
+        JsonValue::Number(value) => writer.write_event(JsonEvent::Number(value)),
+        JsonValue::String(value) => writer.write_event(JsonEvent::String(value)),
+        JsonValue::Array(values) => {
+            writer.write_event(JsonEvent::StartArray)?;
+            for value in values {
+                write_value(value, writer)?;
+            }
+            writer.write_event(JsonEvent::EndArray)
+        }
+        JsonValue::Object(object) => {
+            writer.write_event(JsonEvent::StartObject)?;
+            for (key, value) in object {
+                writer.write_event(JsonEvent::ObjectKey(key))?;
+                write_value(value, writer)?;
+            }
+            writer.write_event(JsonEvent::EndObject)
+        }
+    }
+}
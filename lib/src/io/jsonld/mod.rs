@@ -0,0 +1,310 @@
+//! Implementation of the [JSON-LD 1.1](https://www.w3.org/TR/json-ld11/) format
+//!
+//! Parsing is done using the [expansion algorithm](https://www.w3.org/TR/json-ld11-api/#expansion)
+//! followed by the conversion to RDF. Serialization does the reverse conversion
+//! and compacts the result if a context is provided.
+
+mod compaction;
+mod context;
+mod expansion;
+mod from_rdf;
+mod json;
+mod to_rdf;
+
+use crate::error::{invalid_data_error, invalid_input_error};
+use crate::io::jsonld::compaction::compact;
+use crate::io::jsonld::context::{process_context, JsonLdContext};
+use crate::io::jsonld::expansion::expand_document;
+use crate::io::jsonld::from_rdf::quads_to_expanded;
+use crate::io::jsonld::json::{read_json, write_json, JsonValue};
+pub(crate) use crate::io::jsonld::to_rdf::ExpandedToQuads;
+use crate::model::*;
+use oxiri::Iri;
+use std::io;
+use std::io::{BufRead, Cursor, Write};
+use std::sync::Arc;
+
+/// Loads remote [JSON-LD contexts](https://www.w3.org/TR/json-ld11/#the-context).
+///
+/// It is given the absolute IRI of the context and should return the JSON document content.
+///
+/// It is implemented by closures:
+/// ```
+/// use oxigraph::io::{DatasetFormat, DatasetParser};
+/// use std::io::{Cursor, Error, ErrorKind};
+///
+/// let file = r#"{"@context": "http://example.com/context.jsonld", "@id": "s", "p": {"@id": "o"}}"#;
+///
+/// let parser = DatasetParser::from_format(DatasetFormat::JsonLd)
+///     .with_base_iri("http://example.com/")?
+///     .with_json_ld_context_loader(|iri: &str| if iri == "http://example.com/context.jsonld" {
+///         Ok(r#"{"@context": {"@vocab": "http://example.com/"}}"#.to_owned())
+///     } else {
+///         Err(Error::new(ErrorKind::NotFound, "Unknown context"))
+///     });
+/// let quads = parser.read_quads(Cursor::new(file))?.collect::<Result<Vec<_>,_>>()?;
+///
+/// assert_eq!(quads.len(), 1);
+/// assert_eq!(quads[0].to_string(), "<http://example.com/s> <http://example.com/p> <http://example.com/o> .");
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+pub trait JsonLdContextLoader: Send + Sync {
+    /// Returns the content of the JSON-LD document at the given IRI
+    fn load(&self, iri: &str) -> Result<String, io::Error>;
+}
+
+impl<F: Fn(&str) -> Result<String, io::Error> + Send + Sync> JsonLdContextLoader for F {
+    fn load(&self, iri: &str) -> Result<String, io::Error> {
+        self(iri)
+    }
+}
+
+/// The default loader: it does not do any network access
+pub(crate) struct NoContextLoader;
+
+impl JsonLdContextLoader for NoContextLoader {
+    fn load(&self, iri: &str) -> Result<String, io::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Remote JSON-LD contexts are not loaded by default, the context {} could not be retrieved",
+                iri
+            ),
+        ))
+    }
+}
+
+/// Parses a JSON-LD document and returns its quads
+///
+/// The JSON document is fully loaded in memory in order to be expanded.
+/// The quads are then generated one top-level node object at a time.
+pub(crate) fn read_json_ld(
+    source: impl BufRead,
+    base_iri: Option<Iri<String>>,
+    loader: &dyn JsonLdContextLoader,
+) -> Result<ExpandedToQuads, io::Error> {
+    let document = read_json(source)?;
+    Ok(ExpandedToQuads::new(expand_document(
+        &document, base_iri, loader,
+    )?))
+}
+
+/// Converts a quad read from a JSON-LD document used as a graph serialization to a triple
+///
+/// Named graphs are not allowed in graph serializations.
+pub(crate) fn json_ld_triple(quad: Quad) -> Result<Triple, io::Error> {
+    if quad.graph_name.is_default_graph() {
+        Ok(quad.into())
+    } else {
+        Err(invalid_data_error(format!(
+            "The JSON-LD document contains the named graph {}, it should be read as a dataset",
+            quad.graph_name
+        )))
+    }
+}
+
+/// A processed context used for compaction
+pub(crate) struct JsonLdSerializerContext {
+    value: JsonValue,
+    context: JsonLdContext,
+}
+
+impl JsonLdSerializerContext {
+    /// Parses a context, given either directly or as the `@context` entry of a JSON object
+    pub fn parse(context: &str) -> Result<Self, io::Error> {
+        let mut value = read_json(Cursor::new(context)).map_err(invalid_input_error)?;
+        if let JsonValue::Object(object) = &mut value {
+            if let Some(inner) = object.remove("@context") {
+                value = inner;
+            }
+        }
+        let context = process_context(
+            &JsonLdContext::default(),
+            &value,
+            None,
+            &mut Vec::new(),
+            false,
+            true,
+            true,
+            &NoContextLoader,
+        )
+        .map_err(invalid_input_error)?;
+        Ok(Self { value, context })
+    }
+}
+
+/// Writes quads as a JSON-LD document
+///
+/// The quads are buffered and the document is written by [`finish`](JsonLdWriter::finish).
+pub(crate) struct JsonLdWriter<W: Write> {
+    sink: W,
+    quads: Vec<Quad>,
+    context: Option<Arc<JsonLdSerializerContext>>,
+}
+
+impl<W: Write> JsonLdWriter<W> {
+    pub fn new(sink: W, context: Option<Arc<JsonLdSerializerContext>>) -> Self {
+        Self {
+            sink,
+            quads: Vec::new(),
+            context,
+        }
+    }
+
+    pub fn write(&mut self, quad: QuadRef<'_>) {
+        self.quads.push(quad.into_owned())
+    }
+
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        let expanded = quads_to_expanded(&self.quads);
+        let document = if let Some(context) = &self.context {
+            compact(&expanded, &context.context, &context.value)
+        } else {
+            JsonValue::Array(expanded)
+        };
+        write_json(&document, &mut self.sink)?;
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(document: &str) -> Vec<Quad> {
+        read_json_ld(
+            Cursor::new(document),
+            Some(Iri::parse("http://example.com/".to_owned()).unwrap()),
+            &NoContextLoader,
+        )
+        .unwrap()
+        .collect()
+    }
+
+    fn sorted_strings(quads: &[Quad]) -> Vec<String> {
+        let mut quads = quads.iter().map(|q| q.to_string()).collect::<Vec<_>>();
+        quads.sort();
+        quads
+    }
+
+    fn serialize(quads: &[Quad], context: Option<&str>) -> String {
+        let mut buffer = Vec::new();
+        let mut writer = JsonLdWriter::new(
+            &mut buffer,
+            context.map(|c| Arc::new(JsonLdSerializerContext::parse(c).unwrap())),
+        );
+        for quad in quads {
+            writer.write(quad.as_ref());
+        }
+        writer.finish().unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_expansion() {
+        let quads = parse(
+            r#"{
+                "@context": {
+                    "@vocab": "http://schema.org/",
+                    "ex": "http://example.com/",
+                    "knows": {"@type": "@id"},
+                    "label": {"@language": "en"},
+                    "data": {"@type": "@json"}
+                },
+                "@id": "ex:alice",
+                "@type": "Person",
+                "name": "Alice",
+                "age": 42,
+                "height": 1.5,
+                "label": "Al",
+                "knows": "bob",
+                "data": {"b": 1, "a": [null]},
+                "@graph": {"@id": "ex:s", "ex:p": {"@id": "ex:o"}}
+            }"#,
+        );
+        assert_eq!(
+            sorted_strings(&quads),
+            vec![
+                "<http://example.com/alice> <http://schema.org/age> \"42\"^^<http://www.w3.org/2001/XMLSchema#integer> .",
+                "<http://example.com/alice> <http://schema.org/data> \"{\\\"a\\\":[null],\\\"b\\\":1}\"^^<http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON> .",
+                "<http://example.com/alice> <http://schema.org/height> \"1.5E0\"^^<http://www.w3.org/2001/XMLSchema#double> .",
+                "<http://example.com/alice> <http://schema.org/knows> <http://example.com/bob> .",
+                "<http://example.com/alice> <http://schema.org/label> \"Al\"@en .",
+                "<http://example.com/alice> <http://schema.org/name> \"Alice\" .",
+                "<http://example.com/alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://schema.org/Person> .",
+                "<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/alice> .",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_expansion() {
+        let quads = parse(
+            r#"{
+                "@context": {"tags": {"@id": "http://example.com/tags", "@container": "@list"}},
+                "@id": "http://example.com/s",
+                "tags": ["a", "b"]
+            }"#,
+        );
+        assert_eq!(quads.len(), 5);
+        let firsts = quads
+            .iter()
+            .filter(|q| q.predicate == vocab::rdf::FIRST)
+            .map(|q| q.object.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(firsts, vec!["\"a\"", "\"b\""]);
+        assert!(quads
+            .iter()
+            .any(|q| q.predicate == vocab::rdf::REST && q.object == vocab::rdf::NIL.into()));
+    }
+
+    #[test]
+    fn test_remote_context_is_not_loaded_by_default() {
+        assert!(read_json_ld(
+            Cursor::new(r#"{"@context": "http://example.com/context.jsonld"}"#),
+            None,
+            &NoContextLoader
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_expanded_serialization() {
+        let quads = parse(
+            r#"{"@id": "http://example.com/s", "http://example.com/p": [{"@id": "http://example.com/o"}, {"@value": "foo", "@language": "en"}]}"#,
+        );
+        assert_eq!(
+            serialize(&quads, None),
+            r#"[{"@id":"http://example.com/s","http://example.com/p":[{"@id":"http://example.com/o"},{"@language":"en","@value":"foo"}]}]"#
+        );
+    }
+
+    #[test]
+    fn test_compacted_serialization() {
+        let context = r#"{"@context": {
+            "ex": "http://example.com/",
+            "knows": {"@id": "http://example.com/knows", "@type": "@id"},
+            "tags": {"@id": "http://example.com/tags", "@container": "@list"}
+        }}"#;
+        let document = r#"{
+            "@context": {
+                "ex": "http://example.com/",
+                "knows": {"@id": "http://example.com/knows", "@type": "@id"},
+                "tags": {"@id": "http://example.com/tags", "@container": "@list"}
+            },
+            "@id": "ex:s",
+            "@type": "ex:Person",
+            "knows": "ex:o",
+            "tags": ["a", "b"],
+            "ex:name": "foo"
+        }"#;
+        let quads = parse(document);
+        let serialized = serialize(&quads, Some(context));
+        assert_eq!(
+            serialized,
+            r#"{"@context":{"ex":"http://example.com/","knows":{"@id":"http://example.com/knows","@type":"@id"},"tags":{"@container":"@list","@id":"http://example.com/tags"}},"@id":"ex:s","@type":"ex:Person","ex:name":"foo","knows":"ex:o","tags":["a","b"]}"#
+        );
+        assert_eq!(parse(&serialized).len(), quads.len());
+    }
+}
@@ -0,0 +1,277 @@
+//! Implementation of the [JSON-LD to RDF algorithm](https://www.w3.org/TR/json-ld11-api/#deserialize-json-ld-to-rdf-algorithm)
+
+use crate::io::jsonld::json::{write_json, JsonObject, JsonValue};
+use crate::model::vocab::{rdf, xsd};
+use crate::model::*;
+use std::collections::{HashMap, VecDeque};
+
+/// Converts expanded JSON-LD node objects to RDF quads
+///
+/// The quads are generated lazily, one top-level node object at a time.
+pub struct ExpandedToQuads {
+    elements: std::vec::IntoIter<JsonValue>,
+    converter: RdfConverter,
+}
+
+impl ExpandedToQuads {
+    pub fn new(elements: Vec<JsonValue>) -> Self {
+        Self {
+            elements: elements.into_iter(),
+            converter: RdfConverter::default(),
+        }
+    }
+}
+
+impl Iterator for ExpandedToQuads {
+    type Item = Quad;
+
+    fn next(&mut self) -> Option<Quad> {
+        loop {
+            if let Some(quad) = self.converter.quads.pop_front() {
+                return Some(quad);
+            }
+            if let JsonValue::Object(node) = self.elements.next()? {
+                self.converter.convert_node(&node, &GraphName::DefaultGraph);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct RdfConverter {
+    quads: VecDeque<Quad>,
+    blank_nodes: HashMap<String, BlankNode>,
+}
+
+impl RdfConverter {
+    /// Converts a node object and returns its identifier
+    fn convert_node(
+        &mut self,
+        node: &JsonObject,
+        graph_name: &GraphName,
+    ) -> Option<NamedOrBlankNode> {
+        let subject = match node.get("@id").and_then(JsonValue::as_str) {
+            Some(id) => self.convert_id(id)?,
+            None => BlankNode::default().into(),
+        };
+
+        if let Some(graph) = node.get("@graph") {
+            let inner_graph_name = GraphName::from(subject.clone());
+            for element in graph.as_slice() {
+                if let JsonValue::Object(element) = element {
+                    self.convert_node(element, &inner_graph_name);
+                }
+            }
+        }
+        if let Some(included) = node.get("@included") {
+            for element in included.as_slice() {
+                if let JsonValue::Object(element) = element {
+                    self.convert_node(element, graph_name);
+                }
+            }
+        }
+        if let Some(types) = node.get("@type") {
+            for t in types.as_slice() {
+                if let Some(t) = t.as_str().and_then(|t| self.convert_id(t)) {
+                    self.quads.push_back(Quad::new(
+                        subject.clone(),
+                        rdf::TYPE,
+                        t,
+                        graph_name.clone(),
+                    ));
+                }
+            }
+        }
+        if let Some(JsonValue::Object(reverse)) = node.get("@reverse") {
+            for (property, values) in reverse {
+                let predicate = if let Some(predicate) = convert_predicate(property) {
+                    predicate
+                } else {
+                    continue;
+                };
+                for value in values.as_slice() {
+                    if let JsonValue::Object(value) = value {
+                        if let Some(object) = self.convert_node(value, graph_name) {
+                            self.quads.push_back(Quad::new(
+                                object,
+                                predicate.clone(),
+                                subject.clone(),
+                                graph_name.clone(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        for (property, values) in node {
+            if property.starts_with('@') {
+                continue;
+            }
+            let predicate = if let Some(predicate) = convert_predicate(property) {
+                predicate
+            } else {
+                continue;
+            };
+            for value in values.as_slice() {
+                if let Some(object) = self.convert_object(value, graph_name) {
+                    self.quads.push_back(Quad::new(
+                        subject.clone(),
+                        predicate.clone(),
+                        object,
+                        graph_name.clone(),
+                    ));
+                }
+            }
+        }
+        Some(subject)
+    }
+
+    fn convert_object(&mut self, value: &JsonValue, graph_name: &GraphName) -> Option<Term> {
+        let value = value.as_object()?;
+        if value.contains_key("@value") {
+            convert_value(value)
+        } else if let Some(list) = value.get("@list") {
+            self.convert_list(list.as_slice(), graph_name)
+        } else {
+            self.convert_node(value, graph_name).map(Term::from)
+        }
+    }
+
+    fn convert_list(&mut self, items: &[JsonValue], graph_name: &GraphName) -> Option<Term> {
+        let objects = items
+            .iter()
+            .filter_map(|item| self.convert_object(item, graph_name))
+            .collect::<Vec<_>>();
+        let nodes = objects
+            .iter()
+            .map(|_| BlankNode::default())
+            .collect::<Vec<_>>();
+        for (i, object) in objects.into_iter().enumerate() {
+            self.quads.push_back(Quad::new(
+                nodes[i].clone(),
+                rdf::FIRST,
+                object,
+                graph_name.clone(),
+            ));
+            let rest: NamedOrBlankNode = match nodes.get(i + 1) {
+                Some(next) => next.clone().into(),
+                None => rdf::NIL.into_owned().into(),
+            };
+            self.quads.push_back(Quad::new(
+                nodes[i].clone(),
+                rdf::REST,
+                rest,
+                graph_name.clone(),
+            ));
+        }
+        Some(match nodes.into_iter().next() {
+            Some(head) => head.into(),
+            None => rdf::NIL.into_owned().into(),
+        })
+    }
+
+    fn convert_id(&mut self, id: &str) -> Option<NamedOrBlankNode> {
+        if let Some(label) = id.strip_prefix("_:") {
+            Some(
+                self.blank_nodes
+                    .entry(label.to_owned())
+                    .or_default()
+                    .clone()
+                    .into(),
+            )
+        } else {
+            Some(NamedNode::new(id).ok()?.into())
+        }
+    }
+}
+
+fn convert_predicate(property: &str) -> Option<NamedNode> {
+    // Blank node predicates are not allowed in RDF 1.1
+    if property.starts_with("_:") {
+        None
+    } else {
+        NamedNode::new(property).ok()
+    }
+}
+
+fn convert_value(value: &JsonObject) -> Option<Term> {
+    let datatype = value.get("@type").and_then(JsonValue::as_str);
+    let language = value.get("@language").and_then(JsonValue::as_str);
+    let value = value.get("@value")?;
+    if datatype == Some("@json") {
+        let mut json = Vec::new();
+        write_json(&canonicalize_json(value), &mut json).ok()?;
+        return Some(
+            Literal::new_typed_literal(
+                String::from_utf8(json).ok()?,
+                NamedNode::new_unchecked("http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON"),
+            )
+            .into(),
+        );
+    }
+    let datatype = match datatype {
+        Some(datatype) => Some(NamedNode::new(datatype).ok()?),
+        None => None,
+    };
+    let (lexical_form, default_datatype) = match value {
+        JsonValue::String(value) => (value.clone(), xsd::STRING),
+        JsonValue::Boolean(value) => (value.to_string(), xsd::BOOLEAN),
+        JsonValue::Number(value) => {
+            let is_double = matches!(&datatype, Some(d) if d.as_ref() == xsd::DOUBLE);
+            if !is_double && !value.contains(&['.', 'e', 'E'][..]) {
+                // We keep the lexical form to avoid losing precision on big integers
+                (value.clone(), xsd::INTEGER)
+            } else if let Ok(number) = value.parse::<f64>() {
+                if number.fract() != 0. || number.abs() >= 1e21 || is_double {
+                    (canonical_double(number), xsd::DOUBLE)
+                } else {
+                    (number.to_string(), xsd::INTEGER)
+                }
+            } else {
+                return None;
+            }
+        }
+        _ => return None,
+    };
+    Some(
+        if let Some(datatype) = datatype {
+            Literal::new_typed_literal(lexical_form, datatype)
+        } else if let Some(language) = language {
+            Literal::new_language_tagged_literal(lexical_form, language).ok()?
+        } else {
+            Literal::new_typed_literal(lexical_form, default_datatype)
+        }
+        .into(),
+    )
+}
+
+/// Formats a number in the canonical `xsd:double` form (e.g. `1.1E0`)
+fn canonical_double(value: f64) -> String {
+    let value = format!("{:E}", value);
+    match value.find('E') {
+        Some(position) if !value[..position].contains('.') => {
+            format!("{}.0{}", &value[..position], &value[position..])
+        }
+        _ => value,
+    }
+}
+
+/// Canonical JSON serialization: object keys are already sorted by the `BTreeMap`
+fn canonicalize_json(value: &JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Number(number) => match number.parse::<f64>() {
+            Ok(number) => JsonValue::Number(number.to_string()),
+            Err(_) => value.clone(),
+        },
+        JsonValue::Array(values) => {
+            JsonValue::Array(values.iter().map(canonicalize_json).collect())
+        }
+        JsonValue::Object(object) => JsonValue::Object(
+            object
+                .iter()
+                .map(|(k, v)| (k.clone(), canonicalize_json(v)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
@@ -1,11 +1,14 @@
 //! Utilities to read and write RDF graphs and datasets
 
 mod format;
+pub(crate) mod jsonld;
 pub mod read;
+mod turtle;
 pub mod write;
 
 pub use self::format::DatasetFormat;
 pub use self::format::GraphFormat;
+pub use self::jsonld::JsonLdContextLoader;
 pub use self::read::DatasetParser;
 pub use self::read::GraphParser;
 pub use self::write::DatasetSerializer;
@@ -1,15 +1,23 @@
 //! Utilities to read RDF graphs and datasets
 
-use crate::io::{DatasetFormat, GraphFormat};
+use crate::error::invalid_input_error;
+use crate::io::jsonld::{json_ld_triple, read_json_ld, ExpandedToQuads, NoContextLoader};
+use crate::io::{DatasetFormat, GraphFormat, JsonLdContextLoader};
 use crate::model::*;
+use memchr::memchr_iter;
 use oxiri::{Iri, IriParseError};
 use rio_api::model as rio;
-use rio_api::parser::{QuadsParser, TriplesParser};
+use rio_api::parser::{ParseError, QuadsParser, TriplesParser};
 use rio_turtle::{NQuadsParser, NTriplesParser, TriGParser, TurtleParser};
 use rio_xml::RdfXmlParser;
-use std::collections::HashMap;
-use std::io;
-use std::io::BufRead;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{BufRead, Read};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::{fmt, io};
 
 /// Parsers for RDF graph serialization formats.
 ///
@@ -17,6 +25,12 @@ use std::io::BufRead;
 /// * [N-Triples](https://www.w3.org/TR/n-triples/) ([`GraphFormat::NTriples`](super::GraphFormat::NTriples))
 /// * [Turtle](https://www.w3.org/TR/turtle/) ([`GraphFormat::Turtle`](super::GraphFormat::Turtle))
 /// * [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/) ([`GraphFormat::RdfXml`](super::GraphFormat::RdfXml))
+/// * [JSON-LD](https://www.w3.org/TR/json-ld/) ([`GraphFormat::JsonLd`](super::GraphFormat::JsonLd))
+///
+/// JSON-LD documents are fully loaded in memory before the triples are returned.
+/// The triples are then generated one top-level node object at a time.
+/// Documents with named graphs should be read as datasets using [`DatasetParser`].
+/// Remote contexts are not loaded unless a loader is provided with [`with_json_ld_context_loader`](GraphParser::with_json_ld_context_loader).
 ///
 /// ```
 /// use oxigraph::io::{GraphFormat, GraphParser};
@@ -32,8 +46,10 @@ use std::io::BufRead;
 /// # std::io::Result::Ok(())
 /// ```
 pub struct GraphParser {
-    format: GraphFormat,
-    base_iri: Option<Iri<String>>,
+    pub(crate) format: GraphFormat,
+    pub(crate) base_iri: Option<Iri<String>>,
+    pub(crate) json_ld_context_loader: Option<Arc<dyn JsonLdContextLoader>>,
+    pub(crate) on_syntax_error: Option<SyntaxErrorHandler>,
 }
 
 impl GraphParser {
@@ -42,6 +58,8 @@ impl GraphParser {
         Self {
             format,
             base_iri: None,
+            json_ld_context_loader: None,
+            on_syntax_error: None,
         }
     }
 
@@ -65,10 +83,64 @@ impl GraphParser {
         Ok(self)
     }
 
+    /// Provides a loader for the remote contexts referred from JSON-LD documents.
+    ///
+    /// By default remote contexts are not loaded and documents using them fail to parse.
+    /// See [`JsonLdContextLoader`] for an example.
+    pub fn with_json_ld_context_loader(
+        mut self,
+        loader: impl JsonLdContextLoader + 'static,
+    ) -> Self {
+        self.json_ld_context_loader = Some(Arc::new(loader));
+        self
+    }
+
+    /// Enables the lenient mode: the invalid statements are skipped and their errors are given to `on_error` instead of being returned by the reader.
+    ///
+    /// Only the line based format [N-Triples](https://www.w3.org/TR/n-triples/) is able to recover from a syntax error:
+    /// the end of the invalid line is ignored and the parsing restarts on the next line.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphParser};
+    /// use std::io::Cursor;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let file = "<http://example.com/s> <http://example.com/p> \"foo\" .
+    /// <http://example.com/s> <http://example.com/p> bar .
+    /// <http://example.com/s> <http://example.com/p> \"baz\" .";
+    ///
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let errors_sink = errors.clone();
+    /// let parser = GraphParser::from_format(GraphFormat::NTriples)
+    ///     .with_lenient_mode(move |error| errors_sink.lock().unwrap().push(error))?;
+    /// let triples = parser.read_triples(Cursor::new(file))?.collect::<Result<Vec<_>,_>>()?;
+    ///
+    ///assert_eq!(triples.len(), 2);
+    ///let errors = errors.lock().unwrap();
+    ///assert_eq!(errors.len(), 1);
+    ///assert_eq!(errors[0].line(), Some(2));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn with_lenient_mode(
+        mut self,
+        on_error: impl Fn(SyntaxError) + Send + Sync + 'static,
+    ) -> Result<Self, io::Error> {
+        if self.format != GraphFormat::NTriples {
+            return Err(lenient_mode_not_supported(self.format.media_type()));
+        }
+        self.on_syntax_error = Some(Arc::new(on_error));
+        Ok(self)
+    }
+
     /// Executes the parsing itself on a [`BufRead`](std::io::BufRead) implementation and returns an iterator of triples
     pub fn read_triples<R: BufRead>(&self, reader: R) -> Result<TripleReader<R>, io::Error> {
+        let line_starts = LineStarts::new();
+        let reader = line_starts.track(reader);
         Ok(TripleReader {
             mapper: RioMapper::default(),
+            on_syntax_error: self.on_syntax_error.clone(),
+            line_starts,
             parser: match self.format {
                 GraphFormat::NTriples => TripleReaderKind::NTriples(NTriplesParser::new(reader)),
                 GraphFormat::Turtle => {
@@ -77,10 +149,22 @@ impl GraphParser {
                 GraphFormat::RdfXml => {
                     TripleReaderKind::RdfXml(RdfXmlParser::new(reader, self.base_iri.clone()))
                 }
+                GraphFormat::JsonLd => TripleReaderKind::JsonLd(read_json_ld(
+                    reader,
+                    self.base_iri.clone(),
+                    self.json_ld_context_loader(),
+                )?),
             },
             buffer: Vec::new(),
         })
     }
+
+    pub(crate) fn json_ld_context_loader(&self) -> &dyn JsonLdContextLoader {
+        match &self.json_ld_context_loader {
+            Some(loader) => loader.as_ref(),
+            None => &NoContextLoader,
+        }
+    }
 }
 
 /// An iterator yielding read triples.
@@ -104,12 +188,15 @@ pub struct TripleReader<R: BufRead> {
     mapper: RioMapper,
     parser: TripleReaderKind<R>,
     buffer: Vec<Triple>,
+    line_starts: LineStarts,
+    on_syntax_error: Option<SyntaxErrorHandler>,
 }
 
 enum TripleReaderKind<R: BufRead> {
-    NTriples(NTriplesParser<R>),
-    Turtle(TurtleParser<R>),
-    RdfXml(RdfXmlParser<R>),
+    NTriples(NTriplesParser<LineTrackingReader<R>>),
+    Turtle(TurtleParser<LineTrackingReader<R>>),
+    RdfXml(RdfXmlParser<LineTrackingReader<R>>),
+    JsonLd(ExpandedToQuads),
 }
 
 impl<R: BufRead> Iterator for TripleReader<R> {
@@ -131,8 +218,12 @@ impl<R: BufRead> Iterator for TripleReader<R> {
                 TripleReaderKind::RdfXml(parser) => {
                     Self::read(parser, &mut self.buffer, &mut self.mapper)
                 }
+                TripleReaderKind::JsonLd(quads) => return quads.next().map(json_ld_triple),
             }? {
-                return Some(Err(error));
+                let error = self.line_starts.locate(error);
+                if let Err(error) = recover_from_syntax_error(error, &self.on_syntax_error) {
+                    return Some(Err(error));
+                }
             }
         }
     }
@@ -145,15 +236,15 @@ impl<R: BufRead> TripleReader<R> {
         mapper: &mut RioMapper,
     ) -> Option<Result<(), io::Error>>
     where
-        io::Error: From<P::Error>,
+        P::Error: ParseError + Into<io::Error>,
     {
         if parser.is_end() {
             None
-        } else if let Err(e) = parser.parse_step(&mut |t| {
+        } else if let Err(e) = parser.parse_step(&mut |t| -> Result<(), P::Error> {
             buffer.push(mapper.triple(&t));
             Ok(())
         }) {
-            Some(Err(e))
+            Some(Err(syntax_error(e)))
         } else {
             Some(Ok(()))
         }
@@ -165,6 +256,11 @@ impl<R: BufRead> TripleReader<R> {
 /// It currently supports the following formats:
 /// * [N-Quads](https://www.w3.org/TR/n-quads/) ([`DatasetFormat::NQuads`](super::DatasetFormat::NQuads))
 /// * [TriG](https://www.w3.org/TR/trig/) ([`DatasetFormat::TriG`](super::DatasetFormat::TriG))
+/// * [JSON-LD](https://www.w3.org/TR/json-ld/) ([`DatasetFormat::JsonLd`](super::DatasetFormat::JsonLd))
+///
+/// JSON-LD documents are fully loaded in memory before the quads are returned.
+/// The quads are then generated one top-level node object at a time.
+/// Remote contexts are not loaded unless a loader is provided with [`with_json_ld_context_loader`](DatasetParser::with_json_ld_context_loader).
 ///
 /// ```
 /// use oxigraph::io::{DatasetFormat, DatasetParser};
@@ -180,8 +276,10 @@ impl<R: BufRead> TripleReader<R> {
 /// # std::io::Result::Ok(())
 /// ```
 pub struct DatasetParser {
-    format: DatasetFormat,
-    base_iri: Option<Iri<String>>,
+    pub(crate) format: DatasetFormat,
+    pub(crate) base_iri: Option<Iri<String>>,
+    pub(crate) json_ld_context_loader: Option<Arc<dyn JsonLdContextLoader>>,
+    pub(crate) on_syntax_error: Option<SyntaxErrorHandler>,
 }
 
 impl DatasetParser {
@@ -190,6 +288,8 @@ impl DatasetParser {
         Self {
             format,
             base_iri: None,
+            json_ld_context_loader: None,
+            on_syntax_error: None,
         }
     }
 
@@ -213,19 +313,84 @@ impl DatasetParser {
         Ok(self)
     }
 
+    /// Provides a loader for the remote contexts referred from JSON-LD documents.
+    ///
+    /// By default remote contexts are not loaded and documents using them fail to parse.
+    /// See [`JsonLdContextLoader`] for an example.
+    pub fn with_json_ld_context_loader(
+        mut self,
+        loader: impl JsonLdContextLoader + 'static,
+    ) -> Self {
+        self.json_ld_context_loader = Some(Arc::new(loader));
+        self
+    }
+
+    /// Enables the lenient mode: the invalid statements are skipped and their errors are given to `on_error` instead of being returned by the reader.
+    ///
+    /// Only the line based format [N-Quads](https://www.w3.org/TR/n-quads/) is able to recover from a syntax error:
+    /// the end of the invalid line is ignored and the parsing restarts on the next line.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// ```
+    /// use oxigraph::io::{DatasetFormat, DatasetParser};
+    /// use std::io::Cursor;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let file = "<http://example.com/s> <http://example.com/p> \"foo\"@ <http://example.com/g> .
+    /// <http://example.com/s> <http://example.com/p> \"bar\" <http://example.com/g> .";
+    ///
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let errors_sink = errors.clone();
+    /// let parser = DatasetParser::from_format(DatasetFormat::NQuads)
+    ///     .with_lenient_mode(move |error| errors_sink.lock().unwrap().push(error))?;
+    /// let quads = parser.read_quads(Cursor::new(file))?.collect::<Result<Vec<_>,_>>()?;
+    ///
+    ///assert_eq!(quads.len(), 1);
+    ///let errors = errors.lock().unwrap();
+    ///assert_eq!(errors.len(), 1);
+    ///assert_eq!(errors[0].line(), Some(1));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn with_lenient_mode(
+        mut self,
+        on_error: impl Fn(SyntaxError) + Send + Sync + 'static,
+    ) -> Result<Self, io::Error> {
+        if self.format != DatasetFormat::NQuads {
+            return Err(lenient_mode_not_supported(self.format.media_type()));
+        }
+        self.on_syntax_error = Some(Arc::new(on_error));
+        Ok(self)
+    }
+
     /// Executes the parsing itself on a [`BufRead`](std::io::BufRead) implementation and returns an iterator of quads
     pub fn read_quads<R: BufRead>(&self, reader: R) -> Result<QuadReader<R>, io::Error> {
+        let line_starts = LineStarts::new();
+        let reader = line_starts.track(reader);
         Ok(QuadReader {
             mapper: RioMapper::default(),
+            on_syntax_error: self.on_syntax_error.clone(),
+            line_starts,
             parser: match self.format {
                 DatasetFormat::NQuads => QuadReaderKind::NQuads(NQuadsParser::new(reader)),
                 DatasetFormat::TriG => {
                     QuadReaderKind::TriG(TriGParser::new(reader, self.base_iri.clone()))
                 }
+                DatasetFormat::JsonLd => QuadReaderKind::JsonLd(read_json_ld(
+                    reader,
+                    self.base_iri.clone(),
+                    self.json_ld_context_loader(),
+                )?),
             },
             buffer: Vec::new(),
         })
     }
+
+    pub(crate) fn json_ld_context_loader(&self) -> &dyn JsonLdContextLoader {
+        match &self.json_ld_context_loader {
+            Some(loader) => loader.as_ref(),
+            None => &NoContextLoader,
+        }
+    }
 }
 
 /// An iterator yielding read quads.
@@ -249,11 +414,14 @@ pub struct QuadReader<R: BufRead> {
     mapper: RioMapper,
     parser: QuadReaderKind<R>,
     buffer: Vec<Quad>,
+    line_starts: LineStarts,
+    on_syntax_error: Option<SyntaxErrorHandler>,
 }
 
 enum QuadReaderKind<R: BufRead> {
-    NQuads(NQuadsParser<R>),
-    TriG(TriGParser<R>),
+    NQuads(NQuadsParser<LineTrackingReader<R>>),
+    TriG(TriGParser<LineTrackingReader<R>>),
+    JsonLd(ExpandedToQuads),
 }
 
 impl<R: BufRead> Iterator for QuadReader<R> {
@@ -272,8 +440,12 @@ impl<R: BufRead> Iterator for QuadReader<R> {
                 QuadReaderKind::TriG(parser) => {
                     Self::read(parser, &mut self.buffer, &mut self.mapper)
                 }
+                QuadReaderKind::JsonLd(quads) => return quads.next().map(Ok),
             }? {
-                return Some(Err(error));
+                let error = self.line_starts.locate(error);
+                if let Err(error) = recover_from_syntax_error(error, &self.on_syntax_error) {
+                    return Some(Err(error));
+                }
             }
         }
     }
@@ -286,21 +458,292 @@ impl<R: BufRead> QuadReader<R> {
         mapper: &mut RioMapper,
     ) -> Option<Result<(), io::Error>>
     where
-        io::Error: From<P::Error>,
+        P::Error: ParseError + Into<io::Error>,
     {
         if parser.is_end() {
             None
-        } else if let Err(e) = parser.parse_step(&mut |t| {
+        } else if let Err(e) = parser.parse_step(&mut |t| -> Result<(), P::Error> {
             buffer.push(mapper.quad(&t));
             Ok(())
         }) {
-            Some(Err(e))
+            Some(Err(syntax_error(e)))
         } else {
             Some(Ok(()))
         }
     }
 }
 
+/// A syntax error in a parsed file.
+///
+/// The readers and the store loading methods return it wrapped in an [`io::Error`](std::io::Error)
+/// with the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) kinds:
+/// ```
+/// use oxigraph::io::{GraphFormat, GraphParser};
+/// use oxigraph::io::read::SyntaxError;
+/// use std::io::Cursor;
+///
+/// let file = "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n<http://example.com/s> <http://example.com/p> \"foo .";
+///
+/// let parser = GraphParser::from_format(GraphFormat::NTriples);
+/// let error = parser.read_triples(Cursor::new(file))?.collect::<Result<Vec<_>,_>>().unwrap_err();
+/// let error = error.get_ref().unwrap().downcast_ref::<SyntaxError>().unwrap();
+///
+///assert_eq!(error.line(), Some(2));
+///assert_eq!(error.column(), Some(53));
+///assert_eq!(error.byte_offset(), Some(123));
+/// # std::io::Result::Ok(())
+/// ```
+///
+/// The position is not known for the [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/) and [JSON-LD](https://www.w3.org/TR/json-ld/) formats.
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    message: String,
+    kind: io::ErrorKind,
+    line: Option<u64>,
+    column: Option<u64>,
+    byte_offset: Option<u64>,
+}
+
+impl SyntaxError {
+    /// The error message, without the position
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The line of the error, starting from 1
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
+    /// The column of the error in bytes from the start of the line, starting from 1
+    pub fn column(&self) -> Option<u64> {
+        self.column
+    }
+
+    /// The offset of the error in bytes from the start of the file, starting from 0
+    pub fn byte_offset(&self) -> Option<u64> {
+        self.byte_offset
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " on line {} at column {}", line, column)?;
+        }
+        if let Some(byte_offset) = self.byte_offset {
+            write!(f, " (byte {})", byte_offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SyntaxError {}
+
+impl From<SyntaxError> for io::Error {
+    fn from(error: SyntaxError) -> Self {
+        io::Error::new(error.kind, error)
+    }
+}
+
+type SyntaxErrorHandler = Arc<dyn Fn(SyntaxError) + Send + Sync>;
+
+/// Converts a parser error to an [`io::Error`], wrapping a [`SyntaxError`] if it is not an I/O error
+pub(crate) fn syntax_error(error: impl ParseError + Into<io::Error>) -> io::Error {
+    let position = error.textual_position();
+    let error = error.into();
+    match error.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof if error.get_ref().is_some() => {
+            let mut message = error.to_string();
+            if let Some(position) = position {
+                // The rio errors already contain their position
+                let suffix = format!(
+                    " on line {} at position {}",
+                    position.line_number(),
+                    position.byte_number()
+                );
+                if message.ends_with(&suffix) {
+                    message.truncate(message.len() - suffix.len());
+                }
+            }
+            SyntaxError {
+                message,
+                kind: error.kind(),
+                line: position.map(|p| p.line_number()),
+                column: position.map(|p| p.byte_number()),
+                byte_offset: None,
+            }
+            .into()
+        }
+        _ => error,
+    }
+}
+
+/// The error returned when the lenient mode is requested for a format that is not able to recover from syntax errors
+pub(crate) fn lenient_mode_not_supported(media_type: &str) -> io::Error {
+    invalid_input_error(format!(
+        "The lenient mode is only supported by the line based formats, not by {}",
+        media_type
+    ))
+}
+
+/// Gives the syntax errors the parser is able to recover from to the lenient mode callback
+///
+/// The other errors are returned.
+pub(crate) fn recover_from_syntax_error(
+    error: io::Error,
+    on_syntax_error: &Option<SyntaxErrorHandler>,
+) -> Result<(), io::Error> {
+    if let Some(on_syntax_error) = on_syntax_error {
+        handle_syntax_error(error, &mut |e| on_syntax_error(e))
+    } else {
+        Err(error)
+    }
+}
+
+pub(crate) fn handle_syntax_error(
+    error: io::Error,
+    on_syntax_error: &mut dyn FnMut(SyntaxError),
+) -> Result<(), io::Error> {
+    match error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<SyntaxError>())
+    {
+        // Without position the error is not emitted by the line based parsers
+        Some(syntax_error) if syntax_error.line.is_some() => {
+            on_syntax_error(syntax_error.clone());
+            Ok(())
+        }
+        _ => Err(error),
+    }
+}
+
+/// Keeps track of where the recently read lines start in order to compute the byte offsets of the syntax errors
+///
+/// The line starts are written by the [`LineTrackingReader`] living in the same parser.
+/// Only the last [`LINE_STARTS_CAPACITY`] line starts are kept.
+/// The parsers buffer far less lines than that so the erroneous lines are almost always kept.
+/// If it is not the case, the error byte offset is not filled.
+#[derive(Clone)]
+pub(crate) struct LineStarts {
+    inner: Rc<RefCell<LineStartsState>>,
+}
+
+struct LineStartsState {
+    /// The start offsets of the last recorded lines
+    starts: VecDeque<u64>,
+    /// The number of lines (starting from 0) before the first one of `starts`
+    dropped: u64,
+    line_offset: u64,
+}
+
+const LINE_STARTS_CAPACITY: usize = 8 * 1024;
+
+impl LineStarts {
+    pub fn new() -> Self {
+        Self::starting_at(0, 0)
+    }
+
+    /// For readers starting at the beginning of the line `line_offset + 1` of the file, at the byte `byte_offset`.
+    ///
+    /// The line numbers of the located errors are shifted accordingly.
+    #[cfg_attr(not(feature = "rocksdb"), allow(dead_code))]
+    pub fn starting_at(line_offset: u64, byte_offset: u64) -> Self {
+        let mut starts = VecDeque::new();
+        starts.push_back(byte_offset);
+        Self {
+            inner: Rc::new(RefCell::new(LineStartsState {
+                starts,
+                dropped: 0,
+                line_offset,
+            })),
+        }
+    }
+
+    /// Wraps a reader in order to record the start of its lines
+    ///
+    /// There should be only one tracked reader for a given [`LineStarts`].
+    pub fn track<R>(&self, inner: R) -> LineTrackingReader<R> {
+        let state = self.inner.borrow();
+        LineTrackingReader {
+            inner,
+            recorder: LineRecorder {
+                line_starts: self.clone(),
+                read: state.starts.back().copied().unwrap_or(0),
+            },
+        }
+    }
+
+    /// Fills the byte offset of the error if it is a [`SyntaxError`]
+    pub fn locate(&self, mut error: io::Error) -> io::Error {
+        if let Some(syntax_error) = error
+            .get_mut()
+            .and_then(|e| e.downcast_mut::<SyntaxError>())
+        {
+            let state = self.inner.borrow();
+            if let (Some(line), Some(column)) = (syntax_error.line, syntax_error.column) {
+                syntax_error.byte_offset = line
+                    .checked_sub(1 + state.dropped)
+                    .and_then(|i| usize::try_from(i).ok())
+                    .and_then(|i| state.starts.get(i))
+                    .map(|start| start + column - 1);
+            }
+            if let Some(line) = &mut syntax_error.line {
+                *line += state.line_offset;
+            }
+        }
+        error
+    }
+}
+
+/// A reader recording the start of its lines in a [`LineStarts`]
+pub(crate) struct LineTrackingReader<R> {
+    inner: R,
+    recorder: LineRecorder,
+}
+
+struct LineRecorder {
+    line_starts: LineStarts,
+    /// The number of bytes read, including the `LineStarts` starting byte offset
+    read: u64,
+}
+
+impl LineRecorder {
+    fn record(&mut self, data: &[u8]) {
+        let mut state = self.line_starts.inner.borrow_mut();
+        for i in memchr_iter(b'\n', data) {
+            if state.starts.len() == LINE_STARTS_CAPACITY {
+                state.starts.pop_front();
+                state.dropped += 1;
+            }
+            state.starts.push_back(self.read + i as u64 + 1);
+        }
+        self.read += data.len() as u64;
+    }
+}
+
+impl<R: Read> Read for LineTrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.recorder.record(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for LineTrackingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.recorder.record(&buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt)
+    }
+}
+
 #[derive(Default)]
 struct RioMapper {
     bnode_map: HashMap<String, BlankNode>,
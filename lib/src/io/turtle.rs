@@ -0,0 +1,773 @@
+//! A "pretty" [Turtle](https://www.w3.org/TR/turtle/) and [TriG](https://www.w3.org/TR/trig/) serializer
+//!
+//! Contrary to the Rio formatters it buffers the triples in order to group them by subject,
+//! abbreviate IRIs using prefixes and write blank nodes and lists using the `[ ]` and `( )` syntaxes.
+
+use crate::error::invalid_input_error;
+use crate::model::vocab::{rdf, xsd};
+use crate::model::*;
+use oxiri::Iri;
+use rio_api::model as rio;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io;
+use std::io::Write;
+
+const INDENT: &str = "    ";
+
+/// The prefixes and the base IRI used by the serializer
+#[derive(Default, Clone)]
+pub(crate) struct TurtlePrefixes {
+    base_iri: Option<Iri<String>>,
+    prefixes: Vec<(String, String)>,
+}
+
+impl TurtlePrefixes {
+    pub fn is_empty(&self) -> bool {
+        self.base_iri.is_none() && self.prefixes.is_empty()
+    }
+
+    pub fn set_base_iri(&mut self, base_iri: Iri<String>) {
+        self.base_iri = Some(base_iri);
+    }
+
+    pub fn add_prefix(&mut self, prefix_name: String, prefix_iri: String) -> Result<(), io::Error> {
+        if !is_valid_prefix_name(&prefix_name) {
+            return Err(invalid_input_error(format!(
+                "Invalid Turtle prefix name: '{}'",
+                prefix_name
+            )));
+        }
+        Iri::parse(prefix_iri.as_str()).map_err(invalid_input_error)?;
+        self.prefixes.retain(|(name, _)| *name != prefix_name);
+        self.prefixes.push((prefix_name, prefix_iri));
+        self.prefixes.sort();
+        Ok(())
+    }
+
+    /// Writes an IRI using a prefixed name or an IRI relative to the base IRI if possible
+    fn format_iri(&self, iri: &str) -> String {
+        let prefixed_name = self
+            .prefixes
+            .iter()
+            .filter_map(|(name, prefix_iri)| {
+                let local_name = iri.strip_prefix(prefix_iri.as_str())?;
+                if is_valid_local_name(local_name) {
+                    Some((prefix_iri.len(), name, local_name))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(len, _, _)| *len);
+        if let Some((_, name, local_name)) = prefixed_name {
+            return format!("{}:{}", name, local_name);
+        }
+        if let Some(base_iri) = &self.base_iri {
+            if let Some(relative) = relative_iri(base_iri, iri) {
+                return format!("<{}>", relative);
+            }
+        }
+        format!("<{}>", iri)
+    }
+}
+
+/// Writes triples or quads in Turtle or TriG
+///
+/// The quads are buffered and the document is written by [`finish`](TurtleWriter::finish).
+pub(crate) struct TurtleWriter<W: Write> {
+    sink: W,
+    prefixes: TurtlePrefixes,
+    graphs: Vec<GraphDescription>,
+    graph_positions: HashMap<GraphName, usize>,
+}
+
+struct GraphDescription {
+    name: GraphName,
+    subjects: Vec<SubjectDescription>,
+    subject_positions: HashMap<NamedOrBlankNode, usize>,
+}
+
+struct SubjectDescription {
+    subject: NamedOrBlankNode,
+    predicates: Vec<(NamedNode, Vec<Term>)>,
+    predicate_positions: HashMap<NamedNode, usize>,
+    /// The (predicate, object) pairs already written in order to skip duplicates
+    written: HashSet<(NamedNode, Term)>,
+}
+
+impl<W: Write> TurtleWriter<W> {
+    pub fn new(sink: W, prefixes: TurtlePrefixes) -> Self {
+        Self {
+            sink,
+            prefixes,
+            graphs: Vec::new(),
+            graph_positions: HashMap::new(),
+        }
+    }
+
+    pub fn write(&mut self, quad: QuadRef<'_>) {
+        let graph_name = quad.graph_name.into_owned();
+        let graph_position = if let Some(position) = self.graph_positions.get(&graph_name) {
+            *position
+        } else {
+            self.graphs.push(GraphDescription {
+                name: graph_name.clone(),
+                subjects: Vec::new(),
+                subject_positions: HashMap::new(),
+            });
+            self.graph_positions
+                .insert(graph_name, self.graphs.len() - 1);
+            self.graphs.len() - 1
+        };
+        let graph = &mut self.graphs[graph_position];
+        let subject = quad.subject.into_owned();
+        let subject_position = if let Some(position) = graph.subject_positions.get(&subject) {
+            *position
+        } else {
+            graph.subjects.push(SubjectDescription {
+                subject: subject.clone(),
+                predicates: Vec::new(),
+                predicate_positions: HashMap::new(),
+                written: HashSet::new(),
+            });
+            graph
+                .subject_positions
+                .insert(subject, graph.subjects.len() - 1);
+            graph.subjects.len() - 1
+        };
+        let description = &mut graph.subjects[subject_position];
+        let predicate = quad.predicate.into_owned();
+        let object = quad.object.into_owned();
+        if !description
+            .written
+            .insert((predicate.clone(), object.clone()))
+        {
+            return;
+        }
+        if let Some(position) = description.predicate_positions.get(&predicate) {
+            description.predicates[*position].1.push(object);
+        } else {
+            description
+                .predicate_positions
+                .insert(predicate.clone(), description.predicates.len());
+            description.predicates.push((predicate, vec![object]));
+        }
+    }
+
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        let mut output = String::new();
+        if let Some(base_iri) = &self.prefixes.base_iri {
+            writeln!(output, "@base <{}> .", base_iri.as_str()).unwrap();
+        }
+        for (name, iri) in &self.prefixes.prefixes {
+            writeln!(output, "@prefix {}: <{}> .", name, iri).unwrap();
+        }
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        self.sink.write_all(output.as_bytes())?;
+
+        // rdf:type is always written first
+        for graph in &mut self.graphs {
+            for description in &mut graph.subjects {
+                description.predicates.sort_by_key(|(p, _)| *p != rdf::TYPE);
+            }
+        }
+
+        // The default graph is written first without braces
+        self.graphs
+            .sort_by_key(|g| g.name != GraphName::DefaultGraph);
+        let usages = BlankNodeUsages::new(&self.graphs);
+        for (i, graph) in self.graphs.iter().enumerate() {
+            let mut output = String::new();
+            if i > 0 {
+                output.push('\n');
+            }
+            let indent = if graph.name.is_default_graph() {
+                0
+            } else {
+                output.push_str(&match &graph.name {
+                    GraphName::NamedNode(node) => self.prefixes.format_iri(node.as_str()),
+                    GraphName::BlankNode(node) => format!("_:{}", node.as_str()),
+                    GraphName::DefaultGraph => String::new(),
+                });
+                output.push_str(" {\n");
+                1
+            };
+            GraphFormatter::new(graph, &usages, &self.prefixes).format(&mut output, indent);
+            if indent > 0 {
+                output.push_str("}\n");
+            }
+            self.sink.write_all(output.as_bytes())?;
+        }
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+}
+
+/// How blank nodes are used in the whole dataset
+struct BlankNodeUsages<'a> {
+    /// The number of times each blank node is used as an object
+    object_usages: HashMap<&'a BlankNode, usize>,
+    /// The blank nodes used in more than one graph or as graph names
+    shared: HashSet<&'a BlankNode>,
+}
+
+impl<'a> BlankNodeUsages<'a> {
+    fn new(graphs: &'a [GraphDescription]) -> Self {
+        let mut object_usages = HashMap::new();
+        let mut shared = HashSet::new();
+        let mut graph_of = HashMap::new();
+        let mut add = |node: &'a BlankNode, graph: usize| {
+            if *graph_of.entry(node).or_insert(graph) != graph {
+                shared.insert(node);
+            }
+        };
+        for (i, graph) in graphs.iter().enumerate() {
+            for description in &graph.subjects {
+                if let NamedOrBlankNode::BlankNode(subject) = &description.subject {
+                    add(subject, i);
+                }
+                for (_, objects) in &description.predicates {
+                    for object in objects {
+                        if let Term::BlankNode(object) = object {
+                            add(object, i);
+                            *object_usages.entry(object).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+        }
+        for graph in graphs {
+            if let GraphName::BlankNode(node) = &graph.name {
+                shared.insert(node);
+            }
+        }
+        Self {
+            object_usages,
+            shared,
+        }
+    }
+
+    fn object_usages(&self, node: &BlankNode) -> usize {
+        self.object_usages.get(node).copied().unwrap_or(0)
+    }
+
+    /// The blank node could be written using the `[ ]` syntax where it is used
+    fn is_inlinable(&self, node: &BlankNode) -> bool {
+        self.object_usages(node) == 1 && !self.shared.contains(node)
+    }
+}
+
+struct GraphFormatter<'a> {
+    graph: &'a GraphDescription,
+    usages: &'a BlankNodeUsages<'a>,
+    prefixes: &'a TurtlePrefixes,
+    /// Items of the lists that could be written using the `( )` syntax, by head node
+    lists: HashMap<&'a BlankNode, Vec<&'a Term>>,
+    list_nodes: HashSet<&'a BlankNode>,
+    /// The blank nodes already written using the `[ ]` or `( )` syntaxes
+    inlined: HashSet<&'a BlankNode>,
+    /// The blank nodes written with their label
+    labelled: HashSet<&'a BlankNode>,
+}
+
+impl<'a> GraphFormatter<'a> {
+    fn new(
+        graph: &'a GraphDescription,
+        usages: &'a BlankNodeUsages<'a>,
+        prefixes: &'a TurtlePrefixes,
+    ) -> Self {
+        let mut formatter = Self {
+            graph,
+            usages,
+            prefixes,
+            lists: HashMap::new(),
+            list_nodes: HashSet::new(),
+            inlined: HashSet::new(),
+            labelled: HashSet::new(),
+        };
+        formatter.find_lists();
+        formatter
+    }
+
+    fn description(&self, node: &BlankNode) -> Option<&'a SubjectDescription> {
+        let position = self
+            .graph
+            .subject_positions
+            .get(&NamedOrBlankNode::from(node.clone()))?;
+        Some(&self.graph.subjects[*position])
+    }
+
+    /// Returns the `rdf:first` and `rdf:rest` values if the blank node is a well formed list node
+    fn list_node(&self, node: &BlankNode) -> Option<(&'a Term, &'a Term)> {
+        if !self.usages.is_inlinable(node) {
+            return None;
+        }
+        let description = self.description(node)?;
+        let mut first = None;
+        let mut rest = None;
+        for (predicate, objects) in &description.predicates {
+            if objects.len() != 1 {
+                return None;
+            }
+            if *predicate == rdf::FIRST {
+                first = Some(&objects[0]);
+            } else if *predicate == rdf::REST {
+                rest = Some(&objects[0]);
+            } else {
+                return None;
+            }
+        }
+        Some((first?, rest?))
+    }
+
+    fn find_lists(&mut self) {
+        let mut rest_of_list_node = HashSet::new();
+        let mut candidates = Vec::new();
+        for description in &self.graph.subjects {
+            if let NamedOrBlankNode::BlankNode(head) = &description.subject {
+                let mut items = Vec::new();
+                let mut nodes = vec![head];
+                let mut current = head;
+                let is_list = loop {
+                    if let Some((first, rest)) = self.list_node(current) {
+                        items.push(first);
+                        match rest {
+                            Term::NamedNode(rest) if *rest == rdf::NIL => break true,
+                            Term::BlankNode(rest) if !nodes.contains(&rest) => {
+                                nodes.push(rest);
+                                current = rest;
+                            }
+                            _ => break false,
+                        }
+                    } else {
+                        break false;
+                    }
+                };
+                if is_list {
+                    rest_of_list_node.extend(nodes[1..].iter().copied());
+                    candidates.push((head, items, nodes));
+                }
+            }
+        }
+        for (head, items, nodes) in candidates {
+            // We only keep the heads
+            if !rest_of_list_node.contains(head) {
+                self.list_nodes.extend(nodes);
+                self.lists.insert(head, items);
+            }
+        }
+    }
+
+    fn format(&mut self, output: &mut String, indent: usize) {
+        let graph = self.graph;
+        let mut is_first = true;
+        for description in &graph.subjects {
+            match &description.subject {
+                NamedOrBlankNode::BlankNode(node) => {
+                    if self.list_nodes.contains(node) || self.usages.is_inlinable(node) {
+                        continue; // Written where it is used
+                    }
+                    if self.usages.object_usages(node) == 0 && !self.usages.shared.contains(node) {
+                        // Never referenced: we could use the [ ] syntax
+                        self.inlined.insert(node);
+                        if !is_first {
+                            output.push('\n');
+                        }
+                        is_first = false;
+                        write_indent(output, indent);
+                        self.format_blank_node_content(output, description, indent);
+                        output.push_str(" .\n");
+                        continue;
+                    }
+                    self.labelled.insert(node);
+                }
+                NamedOrBlankNode::NamedNode(_) => (),
+            }
+            if !is_first {
+                output.push('\n');
+            }
+            is_first = false;
+            self.format_subject(output, description, indent);
+        }
+
+        // Blank nodes only referenced in cycles of blank nodes are written with their label
+        for description in &graph.subjects {
+            if let NamedOrBlankNode::BlankNode(node) = &description.subject {
+                if !self.list_nodes.contains(node)
+                    && !self.inlined.contains(node)
+                    && !self.labelled.contains(node)
+                {
+                    self.labelled.insert(node);
+                    if !is_first {
+                        output.push('\n');
+                    }
+                    is_first = false;
+                    self.format_subject(output, description, indent);
+                }
+            }
+        }
+    }
+
+    fn format_subject(
+        &mut self,
+        output: &mut String,
+        description: &'a SubjectDescription,
+        indent: usize,
+    ) {
+        write_indent(output, indent);
+        match &description.subject {
+            NamedOrBlankNode::NamedNode(node) => {
+                output.push_str(&self.prefixes.format_iri(node.as_str()))
+            }
+            NamedOrBlankNode::BlankNode(node) => write!(output, "_:{}", node.as_str()).unwrap(),
+        }
+        output.push(' ');
+        self.format_predicates(output, description, indent + 1);
+        output.push_str(" .\n");
+    }
+
+    /// Writes the predicate object list, the first predicate is written on the current line
+    fn format_predicates(
+        &mut self,
+        output: &mut String,
+        description: &'a SubjectDescription,
+        indent: usize,
+    ) {
+        for (i, (predicate, objects)) in description.predicates.iter().enumerate() {
+            if i > 0 {
+                output.push_str(" ;\n");
+                write_indent(output, indent);
+            }
+            if *predicate == rdf::TYPE {
+                output.push('a');
+            } else {
+                output.push_str(&self.prefixes.format_iri(predicate.as_str()));
+            }
+            for (j, object) in objects.iter().enumerate() {
+                output.push_str(if j > 0 { " , " } else { " " });
+                self.format_object(output, object, indent);
+            }
+        }
+    }
+
+    fn format_object(&mut self, output: &mut String, object: &'a Term, indent: usize) {
+        match object {
+            Term::NamedNode(node) => output.push_str(&self.prefixes.format_iri(node.as_str())),
+            Term::BlankNode(node) => {
+                if self.labelled.contains(node) || self.inlined.contains(node) {
+                    write!(output, "_:{}", node.as_str()).unwrap();
+                } else if let Some(items) = self.lists.get(node).cloned() {
+                    self.inlined.insert(node);
+                    output.push('(');
+                    for item in items {
+                        output.push(' ');
+                        self.format_object(output, item, indent);
+                    }
+                    output.push_str(" )");
+                } else if self.usages.is_inlinable(node) {
+                    self.inlined.insert(node);
+                    if let Some(description) = self.description(node) {
+                        self.format_blank_node_content(output, description, indent);
+                    } else {
+                        output.push_str("[]");
+                    }
+                } else {
+                    write!(output, "_:{}", node.as_str()).unwrap();
+                }
+            }
+            Term::Literal(literal) => self.format_literal(output, literal),
+        }
+    }
+
+    fn format_blank_node_content(
+        &mut self,
+        output: &mut String,
+        description: &'a SubjectDescription,
+        indent: usize,
+    ) {
+        output.push_str("[\n");
+        write_indent(output, indent + 1);
+        self.format_predicates(output, description, indent + 1);
+        output.push('\n');
+        write_indent(output, indent);
+        output.push(']');
+    }
+
+    fn format_literal(&self, output: &mut String, literal: &Literal) {
+        let value = literal.value();
+        let datatype = literal.datatype();
+        let is_bare = (datatype == xsd::BOOLEAN && (value == "true" || value == "false"))
+            || (datatype == xsd::INTEGER && is_turtle_integer(value))
+            || (datatype == xsd::DECIMAL && is_turtle_decimal(value))
+            || (datatype == xsd::DOUBLE && is_turtle_double(value));
+        if is_bare {
+            output.push_str(value);
+            return;
+        }
+        write!(output, "{}", rio::Literal::Simple { value }).unwrap();
+        if let Some(language) = literal.language() {
+            write!(output, "@{}", language).unwrap();
+        } else if datatype != xsd::STRING {
+            output.push_str("^^");
+            output.push_str(&self.prefixes.format_iri(datatype.as_str()));
+        }
+    }
+}
+
+/// Looks for a relative IRI that resolves to the given IRI against the base IRI
+fn relative_iri<'a>(base_iri: &Iri<String>, iri: &'a str) -> Option<&'a str> {
+    let base = base_iri.as_str();
+    let mut candidates = Vec::new();
+    if iri == base {
+        candidates.push("");
+    }
+    if let Some(relative) = iri.strip_prefix(base) {
+        candidates.push(relative);
+    }
+    if let Some(position) = base.rfind('/') {
+        if let Some(relative) = iri.strip_prefix(&base[..=position]) {
+            candidates.push(relative);
+        }
+    }
+    candidates.into_iter().find(|relative| {
+        !relative.starts_with('/')
+            && matches!(base_iri.resolve(relative), Ok(resolved) if resolved.as_str() == iri)
+    })
+}
+
+fn write_indent(output: &mut String, indent: usize) {
+    for _ in 0..indent {
+        output.push_str(INDENT);
+    }
+}
+
+/// Checks the Turtle `PN_PREFIX` production
+fn is_valid_prefix_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        None => true,
+        Some(c) if is_pn_chars_base(c) => {
+            !name.ends_with('.') && chars.all(|c| is_pn_chars(c) || c == '.')
+        }
+        Some(_) => false,
+    }
+}
+
+/// Checks the Turtle `PN_LOCAL` production without the `\` escapes
+fn is_valid_local_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let mut is_first = true;
+    while let Some(c) = chars.next() {
+        let is_valid = if c == '%' {
+            // PERCENT
+            matches!(chars.next(), Some(c) if c.is_ascii_hexdigit())
+                && matches!(chars.next(), Some(c) if c.is_ascii_hexdigit())
+        } else if is_first {
+            is_pn_chars_u(c) || c == ':' || c.is_ascii_digit()
+        } else {
+            is_pn_chars(c) || c == ':' || c == '.'
+        };
+        if !is_valid {
+            return false;
+        }
+        is_first = false;
+    }
+    !name.ends_with('.')
+}
+
+fn is_pn_chars_base(c: char) -> bool {
+    matches!(c,
+        'A'..='Z'
+        | 'a'..='z'
+        | '\u{00C0}'..='\u{00D6}'
+        | '\u{00D8}'..='\u{00F6}'
+        | '\u{00F8}'..='\u{02FF}'
+        | '\u{0370}'..='\u{037D}'
+        | '\u{037F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}')
+}
+
+fn is_pn_chars_u(c: char) -> bool {
+    is_pn_chars_base(c) || c == '_'
+}
+
+fn is_pn_chars(c: char) -> bool {
+    is_pn_chars_u(c)
+        || matches!(c,
+            '-' | '0'..='9' | '\u{00B7}' | '\u{0300}'..='\u{036F}' | '\u{203F}'..='\u{2040}')
+}
+
+fn is_turtle_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(&['+', '-'][..]).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit())
+}
+
+fn is_turtle_decimal(value: &str) -> bool {
+    let value = value.strip_prefix(&['+', '-'][..]).unwrap_or(value);
+    if let Some(position) = value.find('.') {
+        let (integer, fraction) = (&value[..position], &value[position + 1..]);
+        integer.bytes().all(|c| c.is_ascii_digit())
+            && !fraction.is_empty()
+            && fraction.bytes().all(|c| c.is_ascii_digit())
+    } else {
+        false
+    }
+}
+
+fn is_turtle_double(value: &str) -> bool {
+    let value = value.strip_prefix(&['+', '-'][..]).unwrap_or(value);
+    if let Some(position) = value.find(&['e', 'E'][..]) {
+        let (mantissa, exponent) = (&value[..position], &value[position + 1..]);
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(position) => (&mantissa[..position], &mantissa[position + 1..]),
+            None => (mantissa, ""),
+        };
+        (!integer.is_empty() || !fraction.is_empty())
+            && integer.bytes().all(|c| c.is_ascii_digit())
+            && fraction.bytes().all(|c| c.is_ascii_digit())
+            && is_turtle_integer(exponent)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialize(quads: &[Quad], prefixes: &[(&str, &str)], base_iri: Option<&str>) -> String {
+        let mut config = TurtlePrefixes::default();
+        for (name, iri) in prefixes {
+            config
+                .add_prefix((*name).to_owned(), (*iri).to_owned())
+                .unwrap();
+        }
+        if let Some(base_iri) = base_iri {
+            config.set_base_iri(Iri::parse(base_iri.to_owned()).unwrap());
+        }
+        let mut writer = TurtleWriter::new(Vec::new(), config);
+        for quad in quads {
+            writer.write(quad.as_ref());
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    fn ex(name: &str) -> NamedNode {
+        NamedNode::new(format!("http://example.com/{}", name)).unwrap()
+    }
+
+    #[test]
+    fn test_grouping_and_prefixes() {
+        let quads = vec![
+            Quad::new(ex("s"), ex("p"), ex("o1"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), rdf::TYPE, ex("T"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), ex("o2"), GraphName::DefaultGraph),
+            Quad::new(
+                ex("s"),
+                ex("q"),
+                Literal::new_typed_literal("1", xsd::INTEGER),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                ex("s"),
+                ex("q"),
+                Literal::new_typed_literal("a\"b", ex("dt")),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                NamedNode::new("http://example.org/doc/a").unwrap(),
+                ex("p"),
+                Literal::new_language_tagged_literal("foo", "en").unwrap(),
+                GraphName::DefaultGraph,
+            ),
+        ];
+        assert_eq!(
+            serialize(
+                &quads,
+                &[("ex", "http://example.com/")],
+                Some("http://example.org/doc/b")
+            ),
+            "@base <http://example.org/doc/b> .\n\
+             @prefix ex: <http://example.com/> .\n\
+             \n\
+             ex:s a ex:T ;\n    ex:p ex:o1 , ex:o2 ;\n    ex:q 1 , \"a\\\"b\"^^ex:dt .\n\
+             \n\
+             <a> ex:p \"foo\"@en .\n"
+        );
+    }
+
+    #[test]
+    fn test_blank_nodes_and_lists() {
+        let b1 = BlankNode::new_unchecked("b1");
+        let b2 = BlankNode::new_unchecked("b2");
+        let l1 = BlankNode::new_unchecked("l1");
+        let l2 = BlankNode::new_unchecked("l2");
+        let c1 = BlankNode::new_unchecked("c1");
+        let c2 = BlankNode::new_unchecked("c2");
+        let quads = vec![
+            Quad::new(ex("s"), ex("p"), b1.clone(), GraphName::DefaultGraph),
+            Quad::new(b1.clone(), ex("p"), b2.clone(), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("l"), l1.clone(), GraphName::DefaultGraph),
+            Quad::new(l1.clone(), rdf::FIRST, ex("a"), GraphName::DefaultGraph),
+            Quad::new(l1.clone(), rdf::REST, l2.clone(), GraphName::DefaultGraph),
+            Quad::new(l2.clone(), rdf::FIRST, ex("b"), GraphName::DefaultGraph),
+            Quad::new(l2.clone(), rdf::REST, rdf::NIL, GraphName::DefaultGraph),
+            Quad::new(c1.clone(), ex("p"), c2.clone(), GraphName::DefaultGraph),
+            Quad::new(c2.clone(), ex("p"), c1.clone(), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), ex("o"), ex("g")),
+        ];
+        assert_eq!(
+            serialize(&quads, &[("", "http://example.com/")], None),
+            "@prefix : <http://example.com/> .\n\
+             \n\
+             :s :p [\n        :p []\n    ] ;\n    :l ( :a :b ) .\n\
+             \n\
+             _:c1 :p [\n        :p _:c1\n    ] .\n\
+             \n\
+             :g {\n    :s :p :o .\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(is_valid_prefix_name(""));
+        assert!(is_valid_prefix_name("ex"));
+        assert!(is_valid_prefix_name("e.x-1"));
+        assert!(is_valid_prefix_name("\u{00E9}t\u{00B7}"));
+        assert!(!is_valid_prefix_name("_ex"));
+        assert!(!is_valid_prefix_name("1ex"));
+        assert!(!is_valid_prefix_name("ex."));
+        assert!(!is_valid_prefix_name("e\u{00D7}"));
+
+        assert!(is_valid_local_name(""));
+        assert!(is_valid_local_name("s"));
+        assert!(is_valid_local_name("1"));
+        assert!(is_valid_local_name("_:a.b-"));
+        assert!(is_valid_local_name("a%20b"));
+        assert!(is_valid_local_name("a\u{203F}"));
+        assert!(!is_valid_local_name("-a"));
+        assert!(!is_valid_local_name(".a"));
+        assert!(!is_valid_local_name("a."));
+        assert!(!is_valid_local_name("a%2"));
+        assert!(!is_valid_local_name("a%2g"));
+        assert!(!is_valid_local_name("a/b"));
+        assert!(!is_valid_local_name("a\u{00D7}"));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        assert_eq!(
+            serialize(&[quad.clone(), quad], &[], None),
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n"
+        );
+    }
+}
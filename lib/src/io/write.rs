@@ -1,12 +1,16 @@
 //! Utilities to write RDF graphs and datasets
 
+use crate::io::jsonld::{JsonLdSerializerContext, JsonLdWriter};
+use crate::io::turtle::{TurtlePrefixes, TurtleWriter};
 use crate::io::{DatasetFormat, GraphFormat};
 use crate::model::*;
+use oxiri::{Iri, IriParseError};
 use rio_api::formatter::{QuadsFormatter, TriplesFormatter};
 use rio_turtle::{NQuadsFormatter, NTriplesFormatter, TriGFormatter, TurtleFormatter};
 use rio_xml::RdfXmlFormatter;
 use std::io;
 use std::io::Write;
+use std::sync::Arc;
 
 /// A serializer for RDF graph serialization formats.
 ///
@@ -14,6 +18,15 @@ use std::io::Write;
 /// * [N-Triples](https://www.w3.org/TR/n-triples/) ([`GraphFormat::NTriples`](super::GraphFormat::NTriples))
 /// * [Turtle](https://www.w3.org/TR/turtle/) ([`GraphFormat::Turtle`](super::GraphFormat::Turtle))
 /// * [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/) ([`GraphFormat::RdfXml`](super::GraphFormat::RdfXml))
+/// * [JSON-LD](https://www.w3.org/TR/json-ld/) ([`GraphFormat::JsonLd`](super::GraphFormat::JsonLd))
+///
+/// If prefixes or a base IRI are provided, Turtle is written in a more readable way:
+/// the triples are grouped by subject and the blank nodes and lists are nested when possible.
+/// The triples are then kept in memory until [`finish`](TripleWriter::finish()) is called.
+///
+/// JSON-LD documents are written in the [expanded form](https://www.w3.org/TR/json-ld11/#expanded-document-form)
+/// unless a context is provided with [`with_json_ld_context`](GraphSerializer::with_json_ld_context).
+/// The triples are kept in memory until [`finish`](TripleWriter::finish()) is called.
 ///
 /// ```
 /// use oxigraph::io::{GraphFormat, GraphSerializer};
@@ -31,15 +44,85 @@ use std::io::Write;
 ///assert_eq!(buffer.as_slice(), "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n".as_bytes());
 /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
 /// ```
-#[allow(missing_copy_implementations)]
 pub struct GraphSerializer {
     format: GraphFormat,
+    json_ld_context: Option<Arc<JsonLdSerializerContext>>,
+    turtle_prefixes: TurtlePrefixes,
 }
 
 impl GraphSerializer {
     /// Builds a serializer for the given format
     pub fn from_format(format: GraphFormat) -> Self {
-        Self { format }
+        Self {
+            format,
+            json_ld_context: None,
+            turtle_prefixes: TurtlePrefixes::default(),
+        }
+    }
+
+    /// Adds a prefix used to abbreviate the IRIs in Turtle
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = GraphSerializer::from_format(GraphFormat::Turtle)
+    ///     .with_prefix("ex", "http://example.com/")?
+    ///     .triple_writer(&mut buffer)?;
+    /// let s = NamedNode::new("http://example.com/s")?;
+    /// let p = NamedNode::new("http://example.com/p")?;
+    /// writer.write(&Triple::new(s.clone(), p.clone(), NamedNode::new("http://example.com/o1")?))?;
+    /// writer.write(&Triple::new(s.clone(), p.clone(), NamedNode::new("http://example.com/o2")?))?;
+    /// writer.write(&Triple::new(s, p, BlankNode::default()))?;
+    /// writer.finish()?;
+    ///
+    ///assert_eq!(buffer.as_slice(), "@prefix ex: <http://example.com/> .\n\nex:s ex:p ex:o1 , ex:o2 , [] .\n".as_bytes());
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn with_prefix(
+        mut self,
+        prefix_name: impl Into<String>,
+        prefix_iri: impl Into<String>,
+    ) -> Result<Self, io::Error> {
+        self.turtle_prefixes
+            .add_prefix(prefix_name.into(), prefix_iri.into())?;
+        Ok(self)
+    }
+
+    /// Provides a base IRI used to write relative IRIs in Turtle
+    pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Result<Self, IriParseError> {
+        self.turtle_prefixes
+            .set_base_iri(Iri::parse(base_iri.into())?);
+        Ok(self)
+    }
+
+    /// Provides a [JSON-LD context](https://www.w3.org/TR/json-ld11/#the-context) used to write a [compacted](https://www.w3.org/TR/json-ld11/#compacted-document-form) JSON-LD document
+    ///
+    /// The context could be given directly or as the `@context` entry of a JSON object.
+    /// Remote contexts are not supported.
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = GraphSerializer::from_format(GraphFormat::JsonLd)
+    ///     .with_json_ld_context(r#"{"ex": "http://example.com/"}"#)?
+    ///     .triple_writer(&mut buffer)?;
+    /// writer.write(&Triple {
+    ///    subject: NamedNode::new("http://example.com/s")?.into(),
+    ///    predicate: NamedNode::new("http://example.com/p")?,
+    ///    object: NamedNode::new("http://example.com/o")?.into(),
+    /// })?;
+    /// writer.finish()?;
+    ///
+    ///assert_eq!(buffer.as_slice(), r#"{"@context":{"ex":"http://example.com/"},"@id":"ex:s","ex:p":{"@id":"ex:o"}}"#.as_bytes());
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn with_json_ld_context(mut self, context: &str) -> Result<Self, io::Error> {
+        self.json_ld_context = Some(Arc::new(JsonLdSerializerContext::parse(context)?));
+        Ok(self)
     }
 
     /// Returns a `TripleWriter` allowing writing triples into the given [`Write`](std::io::Write) implementation
@@ -47,8 +130,21 @@ impl GraphSerializer {
         Ok(TripleWriter {
             formatter: match self.format {
                 GraphFormat::NTriples => TripleWriterKind::NTriples(NTriplesFormatter::new(writer)),
-                GraphFormat::Turtle => TripleWriterKind::Turtle(TurtleFormatter::new(writer)),
+                GraphFormat::Turtle => {
+                    if self.turtle_prefixes.is_empty() {
+                        TripleWriterKind::Turtle(TurtleFormatter::new(writer))
+                    } else {
+                        TripleWriterKind::PrettyTurtle(TurtleWriter::new(
+                            writer,
+                            self.turtle_prefixes.clone(),
+                        ))
+                    }
+                }
                 GraphFormat::RdfXml => TripleWriterKind::RdfXml(RdfXmlFormatter::new(writer)?),
+                GraphFormat::JsonLd => TripleWriterKind::JsonLd(JsonLdWriter::new(
+                    writer,
+                    self.json_ld_context.clone(),
+                )),
             },
         })
     }
@@ -83,7 +179,9 @@ pub struct TripleWriter<W: Write> {
 enum TripleWriterKind<W: Write> {
     NTriples(NTriplesFormatter<W>),
     Turtle(TurtleFormatter<W>),
+    PrettyTurtle(TurtleWriter<W>),
     RdfXml(RdfXmlFormatter<W>),
+    JsonLd(JsonLdWriter<W>),
 }
 
 impl<W: Write> TripleWriter<W> {
@@ -93,7 +191,13 @@ impl<W: Write> TripleWriter<W> {
         match &mut self.formatter {
             TripleWriterKind::NTriples(formatter) => formatter.format(&triple.into())?,
             TripleWriterKind::Turtle(formatter) => formatter.format(&triple.into())?,
+            TripleWriterKind::PrettyTurtle(writer) => {
+                writer.write(triple.in_graph(GraphNameRef::DefaultGraph))
+            }
             TripleWriterKind::RdfXml(formatter) => formatter.format(&triple.into())?,
+            TripleWriterKind::JsonLd(writer) => {
+                writer.write(triple.in_graph(GraphNameRef::DefaultGraph))
+            }
         }
         Ok(())
     }
@@ -103,7 +207,9 @@ impl<W: Write> TripleWriter<W> {
         match self.formatter {
             TripleWriterKind::NTriples(formatter) => formatter.finish(),
             TripleWriterKind::Turtle(formatter) => formatter.finish()?,
+            TripleWriterKind::PrettyTurtle(writer) => writer.finish()?,
             TripleWriterKind::RdfXml(formatter) => formatter.finish()?,
+            TripleWriterKind::JsonLd(writer) => writer.finish()?,
         };
         Ok(())
     }
@@ -114,6 +220,15 @@ impl<W: Write> TripleWriter<W> {
 /// It currently supports the following formats:
 /// * [N-Quads](https://www.w3.org/TR/n-quads/) ([`DatasetFormat::NQuads`](super::DatasetFormat::NQuads))
 /// * [TriG](https://www.w3.org/TR/trig/) ([`DatasetFormat::TriG`](super::DatasetFormat::TriG))
+/// * [JSON-LD](https://www.w3.org/TR/json-ld/) ([`DatasetFormat::JsonLd`](super::DatasetFormat::JsonLd))
+///
+/// If prefixes or a base IRI are provided, TriG is written in a more readable way:
+/// the triples are grouped by graph and subject and the blank nodes and lists are nested when possible.
+/// The quads are then kept in memory until [`finish`](QuadWriter::finish()) is called.
+///
+/// JSON-LD documents are written in the [expanded form](https://www.w3.org/TR/json-ld11/#expanded-document-form)
+/// unless a context is provided with [`with_json_ld_context`](DatasetSerializer::with_json_ld_context).
+/// The quads are kept in memory until [`finish`](QuadWriter::finish()) is called.
 ///
 /// ```
 /// use oxigraph::io::{DatasetFormat, DatasetSerializer};
@@ -132,15 +247,87 @@ impl<W: Write> TripleWriter<W> {
 ///assert_eq!(buffer.as_slice(), "<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/g> .\n".as_bytes());
 /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
 /// ```
-#[allow(missing_copy_implementations)]
 pub struct DatasetSerializer {
     format: DatasetFormat,
+    json_ld_context: Option<Arc<JsonLdSerializerContext>>,
+    turtle_prefixes: TurtlePrefixes,
 }
 
 impl DatasetSerializer {
     /// Builds a serializer for the given format
     pub fn from_format(format: DatasetFormat) -> Self {
-        Self { format }
+        Self {
+            format,
+            json_ld_context: None,
+            turtle_prefixes: TurtlePrefixes::default(),
+        }
+    }
+
+    /// Adds a prefix used to abbreviate the IRIs in TriG
+    ///
+    /// ```
+    /// use oxigraph::io::{DatasetFormat, DatasetSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = DatasetSerializer::from_format(DatasetFormat::TriG)
+    ///     .with_prefix("ex", "http://example.com/")?
+    ///     .quad_writer(&mut buffer)?;
+    /// writer.write(&Quad {
+    ///    subject: NamedNode::new("http://example.com/s")?.into(),
+    ///    predicate: NamedNode::new("http://example.com/p")?,
+    ///    object: NamedNode::new("http://example.com/o")?.into(),
+    ///    graph_name: NamedNode::new("http://example.com/g")?.into(),
+    /// })?;
+    /// writer.finish()?;
+    ///
+    ///assert_eq!(buffer.as_slice(), "@prefix ex: <http://example.com/> .\n\nex:g {\n    ex:s ex:p ex:o .\n}\n".as_bytes());
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn with_prefix(
+        mut self,
+        prefix_name: impl Into<String>,
+        prefix_iri: impl Into<String>,
+    ) -> Result<Self, io::Error> {
+        self.turtle_prefixes
+            .add_prefix(prefix_name.into(), prefix_iri.into())?;
+        Ok(self)
+    }
+
+    /// Provides a base IRI used to write relative IRIs in TriG
+    pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Result<Self, IriParseError> {
+        self.turtle_prefixes
+            .set_base_iri(Iri::parse(base_iri.into())?);
+        Ok(self)
+    }
+
+    /// Provides a [JSON-LD context](https://www.w3.org/TR/json-ld11/#the-context) used to write a [compacted](https://www.w3.org/TR/json-ld11/#compacted-document-form) JSON-LD document
+    ///
+    /// The context could be given directly or as the `@context` entry of a JSON object.
+    /// Remote contexts are not supported.
+    ///
+    /// ```
+    /// use oxigraph::io::{DatasetFormat, DatasetSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = DatasetSerializer::from_format(DatasetFormat::JsonLd)
+    ///     .with_json_ld_context(r#"{"ex": "http://example.com/"}"#)?
+    ///     .quad_writer(&mut buffer)?;
+    /// writer.write(&Quad {
+    ///    subject: NamedNode::new("http://example.com/s")?.into(),
+    ///    predicate: NamedNode::new("http://example.com/p")?,
+    ///    object: NamedNode::new("http://example.com/o")?.into(),
+    ///    graph_name: GraphName::DefaultGraph,
+    /// })?;
+    /// writer.finish()?;
+    ///
+    ///assert_eq!(buffer.as_slice(), r#"{"@context":{"ex":"http://example.com/"},"@id":"ex:s","ex:p":{"@id":"ex:o"}}"#.as_bytes());
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn with_json_ld_context(mut self, context: &str) -> Result<Self, io::Error> {
+        self.json_ld_context = Some(Arc::new(JsonLdSerializerContext::parse(context)?));
+        Ok(self)
     }
 
     /// Returns a `QuadWriter` allowing writing triples into the given [`Write`](std::io::Write) implementation
@@ -148,7 +335,19 @@ impl DatasetSerializer {
         Ok(QuadWriter {
             formatter: match self.format {
                 DatasetFormat::NQuads => QuadWriterKind::NQuads(NQuadsFormatter::new(writer)),
-                DatasetFormat::TriG => QuadWriterKind::TriG(TriGFormatter::new(writer)),
+                DatasetFormat::TriG => {
+                    if self.turtle_prefixes.is_empty() {
+                        QuadWriterKind::TriG(TriGFormatter::new(writer))
+                    } else {
+                        QuadWriterKind::PrettyTriG(TurtleWriter::new(
+                            writer,
+                            self.turtle_prefixes.clone(),
+                        ))
+                    }
+                }
+                DatasetFormat::JsonLd => {
+                    QuadWriterKind::JsonLd(JsonLdWriter::new(writer, self.json_ld_context.clone()))
+                }
             },
         })
     }
@@ -184,6 +383,8 @@ pub struct QuadWriter<W: Write> {
 enum QuadWriterKind<W: Write> {
     NQuads(NQuadsFormatter<W>),
     TriG(TriGFormatter<W>),
+    PrettyTriG(TurtleWriter<W>),
+    JsonLd(JsonLdWriter<W>),
 }
 
 impl<W: Write> QuadWriter<W> {
@@ -193,6 +394,8 @@ impl<W: Write> QuadWriter<W> {
         match &mut self.formatter {
             QuadWriterKind::NQuads(formatter) => formatter.format(&quad.into())?,
             QuadWriterKind::TriG(formatter) => formatter.format(&quad.into())?,
+            QuadWriterKind::PrettyTriG(writer) => writer.write(quad),
+            QuadWriterKind::JsonLd(writer) => writer.write(quad),
         }
         Ok(())
     }
@@ -202,6 +405,8 @@ impl<W: Write> QuadWriter<W> {
         match self.formatter {
             QuadWriterKind::NQuads(formatter) => formatter.finish(),
             QuadWriterKind::TriG(formatter) => formatter.finish()?,
+            QuadWriterKind::PrettyTriG(writer) => writer.finish()?,
+            QuadWriterKind::JsonLd(writer) => writer.finish()?,
         };
         Ok(())
     }
@@ -0,0 +1,77 @@
+//! Custom SPARQL aggregate functions
+
+use crate::model::Term;
+
+/// An accumulator computing the value of a custom SPARQL aggregate function for a group of solutions.
+///
+/// A new accumulator is created for each group using the factory registered with
+/// [`QueryOptions::with_custom_aggregate`](super::QueryOptions::with_custom_aggregate()).
+/// The query should be parsed with [`Query::parse_with_custom_aggregates`](super::Query::parse_with_custom_aggregates())
+/// and the update with [`Update::parse_with_custom_aggregates`](super::Update::parse_with_custom_aggregates())
+/// in order for the calls to the aggregate IRI not to be considered as custom function calls.
+/// If the aggregate is called with `DISTINCT`, the duplicated elements are filtered out before calling [`add`](CustomAccumulator::add()).
+///
+/// Example of a median computation:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{CustomAccumulator, Query, QueryOptions, QueryResults};
+///
+/// #[derive(Default)]
+/// struct MedianAccumulator {
+///     values: Vec<f64>,
+///     error: bool,
+/// }
+///
+/// impl CustomAccumulator for MedianAccumulator {
+///     fn add(&mut self, element: Option<Term>) {
+///         match element {
+///             Some(Term::Literal(literal)) => match literal.value().parse() {
+///                 Ok(value) => self.values.push(value),
+///                 Err(_) => self.error = true,
+///             },
+///             _ => self.error = true,
+///         }
+///     }
+///
+///     fn state(&self) -> Option<Term> {
+///         if self.error || self.values.is_empty() {
+///             return None;
+///         }
+///         let mut values = self.values.clone();
+///         values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+///         let middle = values.len() / 2;
+///         Some(Literal::from(if values.len() % 2 == 0 {
+///             (values[middle - 1] + values[middle]) / 2.
+///         } else {
+///             values[middle]
+///         }).into())
+///     }
+/// }
+///
+/// let store = MemoryStore::new();
+/// store.update("INSERT DATA { <http://example.com/a> <http://example.com/p> 1, 2, 4, 10 }")?;
+///
+/// let median = NamedNode::new("http://example.com/median")?;
+/// let query = Query::parse_with_custom_aggregates(
+///     "SELECT (<http://example.com/median>(?o) AS ?median) WHERE { ?s ?p ?o }",
+///     None,
+///     vec![median.clone()]
+/// )?;
+/// if let QueryResults::Solutions(mut solutions) = store.query_opt(
+///     query,
+///     QueryOptions::default().with_custom_aggregate(median, || Box::new(MedianAccumulator::default()))
+/// )? {
+///     assert_eq!(solutions.next().unwrap()?.get("median"), Some(&Literal::from(3.).into()));
+/// }
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+pub trait CustomAccumulator: Send {
+    /// Adds an element of the group.
+    ///
+    /// The element is `None` if the aggregated expression is unbound or fails to evaluate for the current solution.
+    fn add(&mut self, element: Option<Term>);
+
+    /// Returns the value of the aggregate for the elements added until now or `None` on error
+    fn state(&self) -> Option<Term>;
+}
@@ -9,11 +9,11 @@ use crate::sparql::model::*;
 use crate::sparql::parser::{parse_query, parse_update, ParseError};
 use oxiri::Iri;
 use rio_api::model as rio;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
-use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// A parsed [SPARQL query](https://www.w3.org/TR/sparql11-query/)
 ///
@@ -67,7 +67,7 @@ pub enum Query {
         /// The [query dataset specification](https://www.w3.org/TR/sparql11-query/#specifyingDataset)
         dataset: QueryDataset,
         /// The query selection graph pattern
-        pattern: Rc<GraphPattern>,
+        pattern: Arc<GraphPattern>,
         /// The query base IRI
         base_iri: Option<Iri<String>>,
     },
@@ -76,7 +76,23 @@ pub enum Query {
 impl Query {
     /// Parses a SPARQL query with an optional base IRI to resolve relative IRIs in the query
     pub fn parse(query: &str, base_iri: Option<&str>) -> Result<Self, ParseError> {
-        parse_query(query, base_iri)
+        parse_query(query, base_iri, HashSet::default())
+    }
+
+    /// Parses a SPARQL query with an optional base IRI and a set of custom aggregate functions.
+    ///
+    /// Calls to the given IRIs are parsed as aggregates instead of custom function calls.
+    /// The aggregate implementations should be registered using [`QueryOptions::with_custom_aggregate`](super::QueryOptions::with_custom_aggregate()).
+    pub fn parse_with_custom_aggregates(
+        query: &str,
+        base_iri: Option<&str>,
+        custom_aggregate_functions: impl IntoIterator<Item = NamedNode>,
+    ) -> Result<Self, ParseError> {
+        parse_query(
+            query,
+            base_iri,
+            custom_aggregate_functions.into_iter().collect(),
+        )
     }
 
     /// Returns [the query dataset specification](https://www.w3.org/TR/sparql11-query/#specifyingDataset)
@@ -222,7 +238,23 @@ pub struct Update {
 impl Update {
     /// Parses a SPARQL update with an optional base IRI to resolve relative IRIs in the query
     pub fn parse(update: &str, base_iri: Option<&str>) -> Result<Self, ParseError> {
-        parse_update(update, base_iri)
+        parse_update(update, base_iri, HashSet::default())
+    }
+
+    /// Parses a SPARQL update with an optional base IRI and a set of custom aggregate functions.
+    ///
+    /// Calls to the given IRIs are parsed as aggregates instead of custom function calls.
+    /// The aggregate implementations should be registered using [`QueryOptions::with_custom_aggregate`](super::QueryOptions::with_custom_aggregate()).
+    pub fn parse_with_custom_aggregates(
+        update: &str,
+        base_iri: Option<&str>,
+        custom_aggregate_functions: impl IntoIterator<Item = NamedNode>,
+    ) -> Result<Self, ParseError> {
+        parse_update(
+            update,
+            base_iri,
+            custom_aggregate_functions.into_iter().collect(),
+        )
     }
 }
 
@@ -0,0 +1,195 @@
+//! Interruption of the SPARQL evaluation
+
+use crate::sparql::eval::EncodedTuplesIterator;
+use crate::sparql::EvaluationError;
+use crate::store::numeric_encoder::StrId;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of checks between two reads of the clock
+const DEADLINE_CHECK_INTERVAL: u32 = 256;
+
+/// A handle allowing to cancel SPARQL query and update evaluations.
+///
+/// It could be cloned and sent to other threads.
+/// When [`cancel`](CancellationToken::cancel()) is called, the evaluations using this token
+/// return an [`EvaluationError::Cancelled`] error as soon as possible.
+///
+/// Usage example:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::sparql::{CancellationToken, EvaluationError, QueryOptions, QueryResults};
+///
+/// let store = MemoryStore::new();
+/// let token = CancellationToken::new();
+/// let results = store.query_opt("SELECT * WHERE { ?s ?p ?o }", QueryOptions::default().with_cancellation_token(token.clone()))?;
+/// token.cancel();
+/// if let QueryResults::Solutions(mut solutions) = results {
+///     assert!(matches!(solutions.next(), Some(Err(EvaluationError::Cancelled))));
+/// }
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that is not cancelled
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the evaluations using this token
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    /// Checks if [`cancel`](CancellationToken::cancel()) has been called
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Checks if the evaluation should be interrupted because of a timeout or a cancellation
+pub(crate) struct InterruptionChecker {
+    deadline: Option<Instant>,
+    token: Option<CancellationToken>,
+    checks_before_clock_read: AtomicU32,
+    timed_out: AtomicBool,
+}
+
+impl InterruptionChecker {
+    /// Builds a checker if there is something to check
+    pub fn new(timeout: Option<Duration>, token: Option<CancellationToken>) -> Option<Arc<Self>> {
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        if deadline.is_none() && token.is_none() {
+            return None;
+        }
+        Some(Arc::new(Self {
+            deadline,
+            token,
+            checks_before_clock_read: AtomicU32::new(0),
+            timed_out: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn check(&self) -> Result<(), EvaluationError> {
+        if let Some(token) = &self.token {
+            if token.is_cancelled() {
+                return Err(EvaluationError::Cancelled);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if self.timed_out.load(Ordering::Relaxed) {
+                return Err(EvaluationError::Timeout);
+            }
+            let checks = self.checks_before_clock_read.load(Ordering::Relaxed);
+            if checks == 0 {
+                self.checks_before_clock_read
+                    .store(DEADLINE_CHECK_INTERVAL, Ordering::Relaxed);
+                if Instant::now() >= deadline {
+                    self.timed_out.store(true, Ordering::Relaxed);
+                    return Err(EvaluationError::Timeout);
+                }
+            } else {
+                self.checks_before_clock_read
+                    .store(checks - 1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Wraps an iterator in order to check for interruptions before each call to `next`
+    ///
+    /// After an interruption, the iterator returns the interruption error and then ends.
+    pub fn wrap<I: StrId + 'static>(
+        self: &Arc<Self>,
+        inner: EncodedTuplesIterator<I>,
+    ) -> EncodedTuplesIterator<I> {
+        Box::new(InterruptibleIterator {
+            inner,
+            checker: self.clone(),
+            interrupted: false,
+        })
+    }
+}
+
+struct InterruptibleIterator<I: StrId> {
+    inner: EncodedTuplesIterator<I>,
+    checker: Arc<InterruptionChecker>,
+    interrupted: bool,
+}
+
+impl<I: StrId> Iterator for InterruptibleIterator<I> {
+    type Item = <EncodedTuplesIterator<I> as Iterator>::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.interrupted {
+            return None;
+        }
+        if let Err(error) = self.checker.check() {
+            self.interrupted = true;
+            return Some(Err(error));
+        }
+        self.inner.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparql::{QueryOptions, QueryResults, UpdateOptions};
+    use crate::MemoryStore;
+
+    #[test]
+    fn timeout_interrupts_query() -> Result<(), EvaluationError> {
+        let store = MemoryStore::new();
+        store.update("INSERT DATA { <http://example.com/a> <http://example.com/p> <http://example.com/b> . <http://example.com/b> <http://example.com/p> <http://example.com/a> }")?;
+        let results = store.query_opt(
+            "SELECT * WHERE { ?s <http://example.com/p>* ?o }",
+            QueryOptions::default().with_timeout(Duration::from_secs(0)),
+        )?;
+        if let QueryResults::Solutions(solutions) = results {
+            let solutions = solutions.collect::<Vec<_>>();
+            assert!(matches!(
+                solutions.as_slice(),
+                [Err(EvaluationError::Timeout)]
+            ));
+        } else {
+            panic!("Unexpected query results")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn timeout_interrupts_update() {
+        let store = MemoryStore::new();
+        assert!(matches!(
+            store.update_opt(
+                "INSERT DATA { <http://example.com/a> <http://example.com/p> <http://example.com/b> }",
+                UpdateOptions::default().with_timeout(Duration::from_secs(0)),
+            ),
+            Err(EvaluationError::Timeout)
+        ));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn cancellation_interrupts_update() {
+        let store = MemoryStore::new();
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(matches!(
+            store.update_opt(
+                "INSERT { ?s ?p ?o } WHERE { ?s ?p ?o }",
+                UpdateOptions::default().with_cancellation_token(token),
+            ),
+            Err(EvaluationError::Cancelled)
+        ));
+    }
+}
@@ -4,9 +4,10 @@ use crate::error::invalid_data_error;
 use crate::model::{vocab::xsd, *};
 use crate::sparql::error::EvaluationError;
 use crate::sparql::model::*;
+use std::collections::HashMap;
 use std::io::{self, BufRead, Write};
-use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub fn write_csv_results(
     results: QueryResults,
@@ -26,6 +27,15 @@ pub fn write_csv_results(
                 }
                 sink.write_all(variable.as_str().as_bytes())?;
             }
+            if solutions.variables().is_empty() {
+                // Each solution is an empty line: we end the header line to not lose the last one
+                sink.write_all(b"\r\n")?;
+                for solution in solutions {
+                    solution?;
+                    sink.write_all(b"\r\n")?;
+                }
+                return Ok(());
+            }
 
             for solution in solutions {
                 let solution = solution?;
@@ -161,7 +171,9 @@ fn write_tsv_term<'a>(term: impl Into<TermRef<'a>>, mut sink: impl Write) -> io:
     }
 }
 
-pub fn read_tsv_results(mut source: impl BufRead + 'static) -> Result<QueryResults, io::Error> {
+pub fn read_tsv_results(
+    mut source: impl BufRead + Send + 'static,
+) -> Result<QueryResults, io::Error> {
     let mut buffer = String::new();
 
     // We read the header
@@ -178,7 +190,7 @@ pub fn read_tsv_results(mut source: impl BufRead + 'static) -> Result<QueryResul
         .collect::<Result<Vec<_>, io::Error>>()?;
 
     Ok(QueryResults::Solutions(QuerySolutionIter::new(
-        Rc::new(variables),
+        Arc::new(variables),
         Box::new(TsvResultsIterator { buffer, source }),
     )))
 }
@@ -218,15 +230,184 @@ impl<R: BufRead> TsvResultsIterator<R> {
     }
 }
 
+pub fn read_csv_results(
+    mut source: impl BufRead + Send + 'static,
+    mut hints: HashMap<Variable, CsvTermHint>,
+) -> Result<QueryResults, io::Error> {
+    let mut buffer = String::new();
+
+    // We read the header
+    let header = read_csv_record(&mut source, &mut buffer)?
+        .ok_or_else(|| invalid_data_error("Empty SPARQL CSV results"))?;
+    // The CSV format has no boolean serialization: the header always contains variables
+    let variables = if header.len() == 1 && header[0].trim().is_empty() {
+        // An empty header line means that there are no variables
+        Vec::new()
+    } else {
+        header
+            .into_iter()
+            .map(|v| Variable::new(v.trim()).map_err(invalid_data_error))
+            .collect::<Result<Vec<_>, io::Error>>()?
+    };
+    let hints = variables.iter().map(|v| hints.remove(v)).collect();
+
+    Ok(QueryResults::Solutions(QuerySolutionIter::new(
+        Arc::new(variables),
+        Box::new(CsvResultsIterator {
+            source,
+            buffer,
+            hints,
+        }),
+    )))
+}
+
+struct CsvResultsIterator<R: BufRead> {
+    source: R,
+    buffer: String,
+    hints: Vec<Option<CsvTermHint>>,
+}
+
+impl<R: BufRead> Iterator for CsvResultsIterator<R> {
+    type Item = Result<Vec<Option<Term>>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<Vec<Option<Term>>, EvaluationError>> {
+        self.read_next().transpose()
+    }
+}
+
+impl<R: BufRead> CsvResultsIterator<R> {
+    fn read_next(&mut self) -> Result<Option<Vec<Option<Term>>>, EvaluationError> {
+        let record = if let Some(record) = read_csv_record(&mut self.source, &mut self.buffer)? {
+            record
+        } else {
+            return Ok(None);
+        };
+        if self.hints.is_empty() && record.len() == 1 && record[0].is_empty() {
+            // Solution without variables
+            return Ok(Some(Vec::new()));
+        }
+        if record.len() != self.hints.len() {
+            return Err(invalid_data_error(format!(
+                "This SPARQL CSV results row has {} values but {} variables are defined in the header",
+                record.len(),
+                self.hints.len()
+            ))
+            .into());
+        }
+        Ok(Some(
+            record
+                .into_iter()
+                .zip(&self.hints)
+                .map(|(value, hint)| {
+                    if value.is_empty() {
+                        Ok(None)
+                    } else {
+                        Ok(Some(parse_csv_term(value, hint.as_ref())?))
+                    }
+                })
+                .collect::<Result<Vec<_>, EvaluationError>>()?,
+        ))
+    }
+}
+
+/// Reads a CSV record following [RFC 4180](https://tools.ietf.org/html/rfc4180).
+///
+/// Returns `None` if the end of the file has been reached.
+/// An empty line is a record with a single empty field.
+fn read_csv_record(
+    source: &mut impl BufRead,
+    buffer: &mut String,
+) -> Result<Option<Vec<String>>, io::Error> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    loop {
+        buffer.clear();
+        if source.read_line(buffer)? == 0 {
+            if in_quotes {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unterminated quoted value in SPARQL CSV results",
+                ));
+            }
+            return Ok(if fields.is_empty() && field.is_empty() {
+                None
+            } else {
+                fields.push(field);
+                Some(fields)
+            });
+        }
+        let mut chars = buffer.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_quotes {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                        field.push('"');
+                    } else {
+                        in_quotes = false;
+                    }
+                } else {
+                    field.push(c);
+                }
+            } else {
+                match c {
+                    '"' if field.is_empty() => in_quotes = true,
+                    ',' => fields.push(std::mem::take(&mut field)),
+                    '\r' | '\n' => (),
+                    c => field.push(c),
+                }
+            }
+        }
+        if !in_quotes {
+            fields.push(field);
+            return Ok(Some(fields));
+        }
+    }
+}
+
+fn parse_csv_term(value: String, hint: Option<&CsvTermHint>) -> Result<Term, io::Error> {
+    Ok(match hint {
+        Some(CsvTermHint::NamedNode) => NamedNode::new(value).map_err(invalid_data_error)?.into(),
+        Some(CsvTermHint::BlankNode) => BlankNode::new(value.strip_prefix("_:").unwrap_or(&value))
+            .map_err(invalid_data_error)?
+            .into(),
+        Some(CsvTermHint::SimpleLiteral) => Literal::new_simple_literal(value).into(),
+        Some(CsvTermHint::LanguageTaggedLiteral(language)) => {
+            Literal::new_language_tagged_literal(value, language)
+                .map_err(invalid_data_error)?
+                .into()
+        }
+        Some(CsvTermHint::TypedLiteral(datatype)) => {
+            Literal::new_typed_literal(value, datatype.clone()).into()
+        }
+        None => {
+            // We infer the term kind from the way the CSV format serializes it
+            if let Some(id) = value.strip_prefix("_:") {
+                if let Ok(node) = BlankNode::new(id) {
+                    return Ok(node.into());
+                }
+            }
+            if value.contains(':') {
+                if let Ok(node) = NamedNode::new(value.as_str()) {
+                    return Ok(node.into());
+                }
+            }
+            Literal::new_simple_literal(value).into()
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::rc::Rc;
+    use std::io::Cursor;
     use std::str;
+    use std::sync::Arc;
 
     fn build_example() -> QueryResults {
         QuerySolutionIter::new(
-            Rc::new(vec![
+            Arc::new(vec![
                 Variable::new_unchecked("x"),
                 Variable::new_unchecked("literal"),
             ]),
@@ -287,4 +468,170 @@ mod tests {
         write_tsv_results(build_example(), &mut sink).unwrap();
         assert_eq!(str::from_utf8(&sink).unwrap(), "?x\t?literal\n<http://example/x>\t\"String\"\n<http://example/x>\t\"String-with-dquote\\\"\"\n_:b0\t\"Blank node\"\n\t\"Missing 'x'\"\n\t\n<http://example/x>\t\n_:b1\t\"String-with-lang\"@en\n_:b1\t123");
     }
+
+    #[test]
+    fn test_csv_parsing() -> Result<(), EvaluationError> {
+        let mut sink = Vec::new();
+        write_csv_results(build_example(), &mut sink)?;
+        let mut hints = HashMap::new();
+        hints.insert(
+            Variable::new_unchecked("literal"),
+            CsvTermHint::SimpleLiteral,
+        );
+        if let QueryResults::Solutions(solutions) = read_csv_results(Cursor::new(sink), hints)? {
+            assert_eq!(
+                solutions.variables(),
+                &[
+                    Variable::new_unchecked("x"),
+                    Variable::new_unchecked("literal")
+                ]
+            );
+            let solutions = solutions
+                .map(|s| Ok(s?.values().map(|v| v.cloned()).collect::<Vec<_>>()))
+                .collect::<Result<Vec<_>, EvaluationError>>()?;
+            assert_eq!(
+                solutions,
+                vec![
+                    vec![
+                        Some(NamedNode::new_unchecked("http://example/x").into()),
+                        Some(Literal::new_simple_literal("String").into()),
+                    ],
+                    vec![
+                        Some(NamedNode::new_unchecked("http://example/x").into()),
+                        Some(Literal::new_simple_literal("String-with-dquote\"").into()),
+                    ],
+                    vec![
+                        Some(BlankNode::new_unchecked("b0").into()),
+                        Some(Literal::new_simple_literal("Blank node").into()),
+                    ],
+                    vec![
+                        None,
+                        Some(Literal::new_simple_literal("Missing 'x'").into()),
+                    ],
+                    vec![None, None],
+                    vec![
+                        Some(NamedNode::new_unchecked("http://example/x").into()),
+                        None,
+                    ],
+                    vec![
+                        Some(BlankNode::new_unchecked("b1").into()),
+                        Some(Literal::new_simple_literal("String-with-lang").into()),
+                    ],
+                    vec![
+                        Some(BlankNode::new_unchecked("b1").into()),
+                        Some(Literal::new_simple_literal("123").into()),
+                    ],
+                ]
+            );
+        } else {
+            panic!("Solutions expected")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_parsing_empty_lines() -> Result<(), EvaluationError> {
+        if let QueryResults::Solutions(solutions) = read_csv_results(
+            Cursor::new("x\r\nhttp://e/a\r\n\r\nhttp://e/b\r\n"),
+            HashMap::new(),
+        )? {
+            let solutions = solutions
+                .map(|s| Ok(s?.get("x").cloned()))
+                .collect::<Result<Vec<_>, EvaluationError>>()?;
+            assert_eq!(
+                solutions,
+                vec![
+                    Some(NamedNode::new_unchecked("http://e/a").into()),
+                    None,
+                    Some(NamedNode::new_unchecked("http://e/b").into()),
+                ]
+            );
+        } else {
+            panic!("Solutions expected")
+        }
+        // An empty line is not a valid row if there are multiple variables
+        if let QueryResults::Solutions(solutions) =
+            read_csv_results(Cursor::new("x,y\r\na,b\r\n\r\nc,d\r\n"), HashMap::new())?
+        {
+            let solutions = solutions.collect::<Vec<_>>();
+            assert_eq!(solutions.len(), 3);
+            assert!(solutions[0].is_ok());
+            assert!(solutions[1].is_err());
+        } else {
+            panic!("Solutions expected")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_parsing_no_variables() -> Result<(), EvaluationError> {
+        for count in 0..3 {
+            let mut sink = Vec::new();
+            write_csv_results(
+                QueryResults::Solutions(QuerySolutionIter::new(
+                    Arc::new(Vec::new()),
+                    Box::new((0..count).map(|_| Ok(Vec::new()))),
+                )),
+                &mut sink,
+            )?;
+            // The header line is ended so that each solution is an empty line
+            assert_eq!(sink, "\r\n".repeat(count + 1).as_bytes());
+            if let QueryResults::Solutions(solutions) =
+                read_csv_results(Cursor::new(sink), HashMap::new())?
+            {
+                assert!(solutions.variables().is_empty());
+                let solutions = solutions.collect::<Result<Vec<_>, EvaluationError>>()?;
+                assert_eq!(solutions.len(), count);
+                assert!(solutions.iter().all(|s| s.values().next().is_none()));
+            } else {
+                panic!("Solutions expected")
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_parsing_true_variable() -> Result<(), EvaluationError> {
+        if let QueryResults::Solutions(solutions) =
+            read_csv_results(Cursor::new("true\r\na\r\n"), HashMap::new())?
+        {
+            assert_eq!(solutions.variables(), &[Variable::new_unchecked("true")]);
+            assert_eq!(solutions.count(), 1);
+        } else {
+            panic!("Solutions expected")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_parsing_with_hints() -> Result<(), EvaluationError> {
+        let mut hints = HashMap::new();
+        hints.insert(
+            Variable::new_unchecked("n"),
+            CsvTermHint::TypedLiteral(xsd::INTEGER.into()),
+        );
+        hints.insert(Variable::new_unchecked("b"), CsvTermHint::BlankNode);
+        hints.insert(Variable::new_unchecked("l"), CsvTermHint::SimpleLiteral);
+        if let QueryResults::Solutions(mut solutions) =
+            read_csv_results(Cursor::new("n,b,l\r\n1,b1,\"a:b\r\nc\"\r\n"), hints)?
+        {
+            let solution = solutions.next().unwrap()?;
+            assert_eq!(
+                solution.get("n"),
+                Some(&Literal::new_typed_literal("1", xsd::INTEGER).into())
+            );
+            assert_eq!(
+                solution.get("b"),
+                Some(&BlankNode::new_unchecked("b1").into())
+            );
+            assert_eq!(
+                solution.get("l"),
+                Some(&Literal::new_simple_literal("a:b\r\nc").into())
+            );
+            assert!(solutions.next().is_none());
+        } else {
+            panic!("Solutions expected")
+        }
+        Ok(())
+    }
 }
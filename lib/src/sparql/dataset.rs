@@ -1,16 +1,19 @@
+use crate::error::invalid_data_error;
 use crate::sparql::algebra::QueryDataset;
 use crate::sparql::EvaluationError;
 use crate::store::numeric_encoder::{
     EncodedQuad, EncodedTerm, ReadEncoder, StrContainer, StrEncodingAware, StrId, StrLookup,
 };
 use crate::store::ReadableEncodedStore;
-use lasso::{Rodeo, Spur};
-use std::cell::RefCell;
+use lasso::{Key, Spur, ThreadedRodeo};
+use std::convert::TryFrom;
+use std::io;
+use std::io::Read;
 use std::iter::{empty, once, Once};
 
 pub(crate) struct DatasetView<S: ReadableEncodedStore> {
     store: S,
-    extra: RefCell<Rodeo>,
+    extra: ThreadedRodeo,
     dataset: EncodedDatasetSpec<S::StrId>,
 }
 
@@ -44,11 +47,21 @@ impl<S: ReadableEncodedStore> DatasetView<S> {
         };
         Ok(Self {
             store,
-            extra: RefCell::new(Rodeo::default()),
+            extra: ThreadedRodeo::default(),
             dataset,
         })
     }
 
+    /// Returns the identifier of the store dictionary, see [`ReadableEncodedStore::dictionary_id`]
+    pub fn store_dictionary_id(&self) -> Option<u64> {
+        self.store.dictionary_id()
+    }
+
+    /// Checks if some strings have been encoded with ids that are only valid in this view
+    pub fn has_temporary_strings(&self) -> bool {
+        !self.extra.is_empty()
+    }
+
     #[allow(clippy::needless_collect)]
     fn encoded_quads_for_pattern_in_dataset(
         &self,
@@ -56,7 +69,7 @@ impl<S: ReadableEncodedStore> DatasetView<S> {
         predicate: Option<EncodedTerm<S::StrId>>,
         object: Option<EncodedTerm<S::StrId>>,
         graph_name: Option<EncodedTerm<S::StrId>>,
-    ) -> Box<dyn Iterator<Item = Result<EncodedQuad<DatasetStrId<S::StrId>>, EvaluationError>>>
+    ) -> Box<dyn Iterator<Item = Result<EncodedQuad<DatasetStrId<S::StrId>>, EvaluationError>> + Send>
     {
         if let Some(graph_name) = graph_name {
             if graph_name.is_default_graph() {
@@ -149,6 +162,59 @@ impl<S: ReadableEncodedStore> DatasetView<S> {
             )
         }
     }
+
+    fn encoded_quads_for_pattern_cardinality_in_dataset(
+        &self,
+        subject: Option<EncodedTerm<S::StrId>>,
+        predicate: Option<EncodedTerm<S::StrId>>,
+        object: Option<EncodedTerm<S::StrId>>,
+        graph_name: Option<EncodedTerm<S::StrId>>,
+    ) -> Option<u64> {
+        if let Some(graph_name) = graph_name {
+            if graph_name.is_default_graph() {
+                if let Some(default_graph_graphs) = &self.dataset.default {
+                    self.sum_cardinalities(subject, predicate, object, default_graph_graphs)
+                } else {
+                    self.store
+                        .encoded_quads_for_pattern_cardinality(subject, predicate, object, None)
+                }
+            } else if self.dataset.named.iter().all(|d| d.contains(&graph_name)) {
+                self.store.encoded_quads_for_pattern_cardinality(
+                    subject,
+                    predicate,
+                    object,
+                    Some(graph_name),
+                )
+            } else {
+                Some(0)
+            }
+        } else if let Some(named_graphs) = &self.dataset.named {
+            self.sum_cardinalities(subject, predicate, object, named_graphs)
+        } else {
+            // The default graph quads are also counted
+            self.store
+                .encoded_quads_for_pattern_cardinality(subject, predicate, object, None)
+        }
+    }
+
+    fn sum_cardinalities(
+        &self,
+        subject: Option<EncodedTerm<S::StrId>>,
+        predicate: Option<EncodedTerm<S::StrId>>,
+        object: Option<EncodedTerm<S::StrId>>,
+        graph_names: &[EncodedTerm<S::StrId>],
+    ) -> Option<u64> {
+        graph_names.iter().try_fold(0, |sum: u64, graph_name| {
+            Some(
+                sum.saturating_add(self.store.encoded_quads_for_pattern_cardinality(
+                    subject,
+                    predicate,
+                    object,
+                    Some(*graph_name),
+                )?),
+            )
+        })
+    }
 }
 
 impl<S: ReadableEncodedStore> StrEncodingAware for DatasetView<S> {
@@ -160,14 +226,12 @@ impl<S: ReadableEncodedStore> StrLookup for DatasetView<S> {
     fn get_str(&self, id: DatasetStrId<S::StrId>) -> Result<Option<String>, EvaluationError> {
         match id {
             DatasetStrId::Store(id) => self.store.get_str(id).map_err(|e| e.into()),
-            DatasetStrId::Temporary(id) => {
-                Ok(self.extra.borrow().try_resolve(&id).map(|e| e.to_owned()))
-            }
+            DatasetStrId::Temporary(id) => Ok(self.extra.try_resolve(&id).map(|e| e.to_owned())),
         }
     }
 
     fn get_str_id(&self, value: &str) -> Result<Option<DatasetStrId<S::StrId>>, EvaluationError> {
-        if let Some(id) = self.extra.borrow().get(value) {
+        if let Some(id) = self.extra.get(value) {
             Ok(Some(DatasetStrId::Temporary(id)))
         } else {
             Ok(self
@@ -180,8 +244,9 @@ impl<S: ReadableEncodedStore> StrLookup for DatasetView<S> {
 }
 
 impl<S: ReadableEncodedStore> ReadableEncodedStore for DatasetView<S> {
-    type QuadsIter =
-        Box<dyn Iterator<Item = Result<EncodedQuad<DatasetStrId<S::StrId>>, EvaluationError>>>;
+    type QuadsIter = Box<
+        dyn Iterator<Item = Result<EncodedQuad<DatasetStrId<S::StrId>>, EvaluationError>> + Send,
+    >;
     type GraphsIter = Once<Result<EncodedTerm<DatasetStrId<S::StrId>>, EvaluationError>>;
 
     fn encoded_quads_for_pattern(
@@ -190,7 +255,7 @@ impl<S: ReadableEncodedStore> ReadableEncodedStore for DatasetView<S> {
         predicate: Option<EncodedTerm<Self::StrId>>,
         object: Option<EncodedTerm<Self::StrId>>,
         graph_name: Option<EncodedTerm<Self::StrId>>,
-    ) -> Box<dyn Iterator<Item = Result<EncodedQuad<DatasetStrId<S::StrId>>, EvaluationError>>>
+    ) -> Box<dyn Iterator<Item = Result<EncodedQuad<DatasetStrId<S::StrId>>, EvaluationError>> + Send>
     {
         if let Some((subject, predicate, object, graph_name)) =
             try_map_quad_pattern(subject, predicate, object, graph_name)
@@ -201,6 +266,25 @@ impl<S: ReadableEncodedStore> ReadableEncodedStore for DatasetView<S> {
         }
     }
 
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        subject: Option<EncodedTerm<Self::StrId>>,
+        predicate: Option<EncodedTerm<Self::StrId>>,
+        object: Option<EncodedTerm<Self::StrId>>,
+        graph_name: Option<EncodedTerm<Self::StrId>>,
+    ) -> Option<u64> {
+        if let Some((subject, predicate, object, graph_name)) =
+            try_map_quad_pattern(subject, predicate, object, graph_name)
+        {
+            self.encoded_quads_for_pattern_cardinality_in_dataset(
+                subject, predicate, object, graph_name,
+            )
+        } else {
+            // The terms that are not in the store do not match anything
+            Some(0)
+        }
+    }
+
     fn encoded_named_graphs(&self) -> Self::GraphsIter {
         once(Err(EvaluationError::msg(
             "Graphs lookup is not implemented by DatasetView",
@@ -272,9 +356,7 @@ impl<'a, S: ReadableEncodedStore> StrContainer for &'a DatasetView<S> {
         if let Some(id) = self.store.get_str_id(value).map_err(|e| e.into())? {
             Ok(DatasetStrId::Store(id))
         } else {
-            Ok(DatasetStrId::Temporary(
-                self.extra.borrow_mut().get_or_intern(value),
-            ))
+            Ok(DatasetStrId::Temporary(self.extra.get_or_intern(value)))
         }
     }
 }
@@ -285,7 +367,38 @@ pub enum DatasetStrId<I: StrId> {
     Temporary(Spur),
 }
 
-impl<I: StrId> StrId for DatasetStrId<I> {}
+impl<I: StrId> StrId for DatasetStrId<I> {
+    fn write(self, sink: &mut Vec<u8>) {
+        match self {
+            Self::Store(id) => {
+                sink.push(0);
+                id.write(sink)
+            }
+            Self::Temporary(id) => {
+                sink.push(1);
+                sink.extend_from_slice(&(id.into_usize() as u64).to_be_bytes())
+            }
+        }
+    }
+
+    fn read(source: &mut impl Read) -> io::Result<Self> {
+        let mut kind = [0];
+        source.read_exact(&mut kind)?;
+        match kind[0] {
+            0 => Ok(Self::Store(I::read(source)?)),
+            1 => {
+                let mut buffer = [0; 8];
+                source.read_exact(&mut buffer)?;
+                usize::try_from(u64::from_be_bytes(buffer))
+                    .ok()
+                    .and_then(Spur::try_from_usize)
+                    .map(Self::Temporary)
+                    .ok_or_else(|| invalid_data_error("Invalid temporary string id"))
+            }
+            _ => Err(invalid_data_error("Invalid dataset string id kind")),
+        }
+    }
+}
 
 struct EncodedDatasetSpec<I: StrId> {
     default: Option<Vec<EncodedTerm<I>>>,
@@ -0,0 +1,127 @@
+//! Strategies to build the results of SPARQL `DESCRIBE` queries
+
+use crate::model::{NamedNode, Term, Triple};
+use crate::sparql::error::EvaluationError;
+use std::fmt;
+use std::sync::Arc;
+
+/// The strategy used to build the description of the resources returned by SPARQL `DESCRIBE` queries.
+///
+/// The descriptions are built from the default graph and the named graphs of the query dataset.
+/// It is set using [`QueryOptions::with_describe_strategy`](super::QueryOptions::with_describe_strategy()).
+///
+/// Usage example:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::sparql::{DescribeStrategy, QueryOptions, QueryResults};
+///
+/// let store = MemoryStore::new();
+/// store.update("INSERT DATA { <http://example.com/a> <http://example.com/address> [ <http://example.com/city> \"Paris\" ] }")?;
+///
+/// if let QueryResults::Graph(triples) = store.query_opt(
+///     "DESCRIBE <http://example.com/a>",
+///     QueryOptions::default().with_describe_strategy(DescribeStrategy::ConciseBoundedDescription)
+/// )? {
+///     assert_eq!(triples.count(), 2);
+/// }
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Clone)]
+pub enum DescribeStrategy {
+    /// The triples whose subject is the described resource.
+    ///
+    /// It is the default strategy.
+    Subject,
+    /// The [Concise Bounded Description](https://www.w3.org/Submission/CBD/):
+    /// the triples whose subject is the described resource and, recursively,
+    /// the triples whose subject is a blank node object of an already included triple.
+    ConciseBoundedDescription,
+    /// The [Symmetric Concise Bounded Description](https://www.w3.org/Submission/CBD/#alternatives):
+    /// the triples whose subject or object is the described resource and, recursively,
+    /// the triples whose subject or object is a blank node of an already included triple.
+    SymmetricConciseBoundedDescription,
+    /// A strategy implemented by a callback, see [`DescribeStrategy::custom`].
+    Custom(
+        Arc<
+            dyn Fn(&Term, &DescribeGraph<'_>) -> Result<Vec<Triple>, EvaluationError> + Send + Sync,
+        >,
+    ),
+}
+
+impl DescribeStrategy {
+    /// Builds a strategy from a callback returning the description of a resource.
+    ///
+    /// The callback is given the described resource and a [`DescribeGraph`] allowing to read the queried data.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::{DescribeStrategy, QueryOptions, QueryResults};
+    ///
+    /// let store = MemoryStore::new();
+    /// store.update("INSERT DATA { <http://example.com/a> <http://example.com/name> \"a\" ; <http://example.com/p> <http://example.com/b> }")?;
+    ///
+    /// // Only returns the names of the resources
+    /// let name = NamedNode::new("http://example.com/name")?;
+    /// let strategy = DescribeStrategy::custom(move |resource, graph| {
+    ///     graph.triples_for_pattern(Some(resource), Some(&name), None).collect()
+    /// });
+    /// if let QueryResults::Graph(triples) = store.query_opt(
+    ///     "DESCRIBE <http://example.com/a>",
+    ///     QueryOptions::default().with_describe_strategy(strategy)
+    /// )? {
+    ///     assert_eq!(triples.count(), 1);
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn custom(
+        describe: impl Fn(&Term, &DescribeGraph<'_>) -> Result<Vec<Triple>, EvaluationError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self::Custom(Arc::new(describe))
+    }
+}
+
+impl Default for DescribeStrategy {
+    #[inline]
+    fn default() -> Self {
+        Self::Subject
+    }
+}
+
+impl fmt::Debug for DescribeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Subject => f.write_str("Subject"),
+            Self::ConciseBoundedDescription => f.write_str("ConciseBoundedDescription"),
+            Self::SymmetricConciseBoundedDescription => {
+                f.write_str("SymmetricConciseBoundedDescription")
+            }
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// Read access to the data described by a [`DescribeStrategy::custom`] callback:
+/// the default graph and the named graphs of the query dataset.
+pub struct DescribeGraph<'a> {
+    pub(super) triples_for_pattern:
+        &'a dyn Fn(Option<&Term>, Option<&NamedNode>, Option<&Term>) -> TriplesIterator,
+}
+
+type TriplesIterator = Box<dyn Iterator<Item = Result<Triple, EvaluationError>>>;
+
+impl DescribeGraph<'_> {
+    /// Retrieves the triples matching a pattern
+    pub fn triples_for_pattern(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&NamedNode>,
+        object: Option<&Term>,
+    ) -> impl Iterator<Item = Result<Triple, EvaluationError>> {
+        (self.triples_for_pattern)(subject, predicate, object)
+    }
+}
@@ -16,6 +16,10 @@ pub enum EvaluationError {
     Io(io::Error),
     /// An error returned during the query evaluation itself
     Query(QueryError),
+    /// The evaluation has taken longer than the timeout set using [`QueryOptions::with_timeout`](super::QueryOptions::with_timeout())
+    Timeout,
+    /// The evaluation has been cancelled using a [`CancellationToken`](super::CancellationToken)
+    Cancelled,
     /// A conflict during a transaction
     #[doc(hidden)]
     Conflict,
@@ -38,6 +42,8 @@ impl fmt::Display for EvaluationError {
             Self::Parsing(error) => error.fmt(f),
             Self::Io(error) => error.fmt(f),
             Self::Query(error) => error.fmt(f),
+            Self::Timeout => write!(f, "The evaluation timeout has been reached"),
+            Self::Cancelled => write!(f, "The evaluation has been cancelled"),
             Self::Conflict => write!(f, "Transaction conflict"),
         }
     }
@@ -1,12 +1,20 @@
 use crate::model::vocab::{rdf, xsd};
 use crate::model::xsd::*;
-use crate::model::Triple;
-use crate::model::{BlankNode, LiteralRef, NamedNodeRef};
+use crate::model::{BlankNode, Literal, LiteralRef, NamedNodeRef};
+use crate::model::{NamedNode, Term, Triple};
+use crate::sparql::aggregate::CustomAccumulator;
 use crate::sparql::algebra::{GraphPattern, Query, QueryDataset};
+use crate::sparql::cancellation::InterruptionChecker;
+use crate::sparql::describe::{DescribeGraph, DescribeStrategy};
 use crate::sparql::error::EvaluationError;
+use crate::sparql::explanation::PlanStatistics;
 use crate::sparql::model::*;
 use crate::sparql::plan::*;
 use crate::sparql::service::ServiceHandler;
+use crate::sparql::spill::{
+    key_memory_size, tuple_memory_size, SortedRuns, SpillingDeduplicate, TuplePartitions,
+};
+use crate::sparql::QueryOptions;
 use crate::store::numeric_encoder::*;
 use crate::store::small_string::SmallString;
 use crate::store::ReadableEncodedStore;
@@ -24,18 +32,55 @@ use std::convert::{TryFrom, TryInto};
 use std::hash::Hash;
 use std::iter::Iterator;
 use std::iter::{empty, once};
-use std::rc::Rc;
 use std::str;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::{self, JoinHandle};
 
 const REGEX_SIZE_LIMIT: usize = 1_000_000;
 
-type EncodedTuplesIterator<I> = Box<dyn Iterator<Item = Result<EncodedTuple<I>, EvaluationError>>>;
+pub(crate) type EncodedTuplesIterator<I> =
+    Box<dyn Iterator<Item = Result<EncodedTuple<I>, EvaluationError>> + Send>;
+
+/// The custom functions callable from SPARQL expressions, indexed by name
+pub(crate) type CustomFunctionRegistry =
+    HashMap<NamedNode, Arc<dyn Fn(&[Term]) -> Option<Term> + Send + Sync>>;
+
+/// The factories of the custom aggregate functions accumulators, indexed by name
+pub(crate) type CustomAggregateRegistry =
+    HashMap<NamedNode, Arc<dyn Fn() -> Box<dyn CustomAccumulator> + Send + Sync>>;
+
+/// Rough estimation of the memory used by an aggregate accumulator
+const ACCUMULATOR_MEMORY_ESTIMATE: usize = 64;
+
+/// Default maximal number of bindings sent together to a SERVICE
+pub(crate) const DEFAULT_SERVICE_BATCH_SIZE: usize = 100;
+
+/// Number of results a thread evaluating a plan node could compute in advance
+#[cfg(not(target_arch = "wasm32"))]
+const THREAD_CHANNEL_CAPACITY: usize = 1024;
 
 pub(crate) struct SimpleEvaluator<S> {
-    dataset: Rc<S>,
-    base_iri: Option<Rc<Iri<String>>>,
+    dataset: Arc<S>,
+    base_iri: Option<Arc<Iri<String>>>,
     now: DateTime,
-    service_handler: Rc<dyn ServiceHandler<Error = EvaluationError>>,
+    service_handler: Arc<dyn ServiceHandler<Error = EvaluationError>>,
+    statistics: Option<Arc<PlanStatistics>>,
+    interruption_checker: Option<Arc<InterruptionChecker>>,
+    custom_functions: Arc<CustomFunctionRegistry>,
+    custom_aggregates: Arc<CustomAggregateRegistry>,
+    memory_limit: Option<usize>,
+    /// Maximal number of threads evaluating the query at the same time, including the calling thread
+    max_threads: usize,
+    /// Number of threads currently evaluating plan nodes of the query, shared by the evaluator clones
+    spawned_threads: Arc<AtomicUsize>,
+    parameters: Arc<Vec<Term>>,
+    describe_strategy: DescribeStrategy,
+    service_batch_size: usize,
 }
 
 impl<S> Clone for SimpleEvaluator<S> {
@@ -45,31 +90,57 @@ impl<S> Clone for SimpleEvaluator<S> {
             base_iri: self.base_iri.clone(),
             now: self.now,
             service_handler: self.service_handler.clone(),
+            statistics: self.statistics.clone(),
+            interruption_checker: self.interruption_checker.clone(),
+            custom_functions: self.custom_functions.clone(),
+            custom_aggregates: self.custom_aggregates.clone(),
+            memory_limit: self.memory_limit,
+            max_threads: self.max_threads,
+            spawned_threads: self.spawned_threads.clone(),
+            parameters: self.parameters.clone(),
+            describe_strategy: self.describe_strategy.clone(),
+            service_batch_size: self.service_batch_size,
         }
     }
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> SimpleEvaluator<S>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> SimpleEvaluator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
 {
     pub fn new(
-        dataset: Rc<S>,
-        base_iri: Option<Rc<Iri<String>>>,
-        service_handler: Rc<dyn ServiceHandler<Error = EvaluationError>>,
+        dataset: Arc<S>,
+        base_iri: Option<Arc<Iri<String>>>,
+        service_handler: Arc<dyn ServiceHandler<Error = EvaluationError>>,
     ) -> Self {
         Self {
             dataset,
             base_iri,
             now: DateTime::now().unwrap(),
             service_handler,
+            statistics: None,
+            interruption_checker: None,
+            custom_functions: Arc::default(),
+            custom_aggregates: Arc::default(),
+            memory_limit: None,
+            max_threads: 1,
+            spawned_threads: Arc::default(),
+            parameters: Arc::default(),
+            describe_strategy: DescribeStrategy::default(),
+            service_batch_size: DEFAULT_SERVICE_BATCH_SIZE,
         }
     }
 
+    /// Records the evaluation statistics of the plan nodes registered in the given [`PlanStatistics`]
+    pub fn with_statistics(mut self, statistics: Arc<PlanStatistics>) -> Self {
+        self.statistics = Some(statistics);
+        self
+    }
+
     pub fn evaluate_select_plan(
         &self,
         plan: &PlanNode<S::StrId>,
-        variables: Rc<Vec<Variable>>,
+        variables: Arc<Vec<Variable>>,
     ) -> Result<QueryResults, EvaluationError> {
         let iter = self.eval_plan(plan, EncodedTuple::with_capacity(variables.len()));
         Ok(QueryResults::Solutions(
@@ -115,47 +186,128 @@ where
             iter: Box::new(DescribeIterator {
                 eval: self.clone(),
                 iter: self.eval_plan(plan, from),
-                quads: Box::new(empty()),
+                triples: Box::new(empty()),
             }),
         }))
     }
 
+    /// Uses the given strategy to describe the resources returned by `DESCRIBE` queries
+    pub fn with_describe_strategy(mut self, describe_strategy: DescribeStrategy) -> Self {
+        self.describe_strategy = describe_strategy;
+        self
+    }
+
+    /// Allows to call the given custom functions from the evaluated expressions
+    pub fn with_custom_functions(mut self, custom_functions: Arc<CustomFunctionRegistry>) -> Self {
+        self.custom_functions = custom_functions;
+        self
+    }
+
+    /// Allows to use the given custom aggregate functions
+    pub fn with_custom_aggregates(
+        mut self,
+        custom_aggregates: Arc<CustomAggregateRegistry>,
+    ) -> Self {
+        self.custom_aggregates = custom_aggregates;
+        self
+    }
+
+    /// Writes the intermediate results of ORDER BY, GROUP BY and DISTINCT to temporary files
+    /// when they use more than approximately the given number of bytes
+    pub fn with_memory_limit(mut self, memory_limit: Option<usize>) -> Self {
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    /// Evaluates the UNION branches and one side of the joins in separated threads, using at most `max_threads` threads at the same time
+    pub fn with_parallel_evaluation(mut self, max_threads: usize) -> Self {
+        self.max_threads = max_threads.max(1);
+        self
+    }
+
+    /// Returns an evaluator that does not spawn threads, for the plan nodes evaluated once per input tuple
+    fn sequential(&self) -> Self {
+        let mut eval = self.clone();
+        eval.max_threads = 1;
+        eval
+    }
+
+    /// Sets the maximal number of bindings sent together to the services of the [`PlanNode::ServiceJoin`] nodes
+    pub fn with_service_batch_size(mut self, service_batch_size: usize) -> Self {
+        self.service_batch_size = service_batch_size.max(1);
+        self
+    }
+
+    /// Sets the values bound by the [`PlanNode::Parameters`] nodes
+    pub fn with_parameters(mut self, parameters: Arc<Vec<Term>>) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Checks regularly during the evaluation if it should be interrupted
+    pub fn with_interruption_checker(mut self, checker: Arc<InterruptionChecker>) -> Self {
+        self.interruption_checker = Some(checker);
+        self
+    }
+
     pub fn eval_plan(
         &self,
         node: &PlanNode<S::StrId>,
         from: EncodedTuple<S::StrId>,
+    ) -> EncodedTuplesIterator<S::StrId> {
+        self.track_evaluation(node, || self.eval_plan_node(node, from))
+    }
+
+    /// Evaluates a [`PlanNode::Service`] for a batch of input bindings
+    fn eval_service(
+        &self,
+        node: &PlanNode<S::StrId>,
+        batch: Vec<EncodedTuple<S::StrId>>,
+    ) -> EncodedTuplesIterator<S::StrId> {
+        self.track_evaluation(node, || self.evaluate_service(node, batch))
+    }
+
+    fn track_evaluation(
+        &self,
+        node: &PlanNode<S::StrId>,
+        eval: impl FnOnce() -> EncodedTuplesIterator<S::StrId>,
+    ) -> EncodedTuplesIterator<S::StrId> {
+        let iter = if let Some(statistics) = &self.statistics {
+            statistics.track(node, eval)
+        } else {
+            eval()
+        };
+        if let Some(checker) = &self.interruption_checker {
+            checker.wrap(iter)
+        } else {
+            iter
+        }
+    }
+
+    fn check_interruption(&self) -> Result<(), EvaluationError> {
+        if let Some(checker) = &self.interruption_checker {
+            checker.check()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn eval_plan_node(
+        &self,
+        node: &PlanNode<S::StrId>,
+        from: EncodedTuple<S::StrId>,
     ) -> EncodedTuplesIterator<S::StrId> {
         match node {
             PlanNode::Init => Box::new(once(Ok(from))),
             PlanNode::StaticBindings { tuples } => Box::new(tuples.clone().into_iter().map(Ok)),
-            PlanNode::Service {
-                variables,
-                silent,
-                service_name,
-                graph_pattern,
-                ..
-            } => {
-                match self.evaluate_service(service_name, graph_pattern, variables.clone(), &from) {
-                    Ok(result) => Box::new(result.flat_map(move |binding| {
-                        binding
-                            .map(|binding| binding.combine_with(&from))
-                            .transpose()
-                    })),
-                    Err(e) => {
-                        if *silent {
-                            Box::new(once(Ok(from)))
-                        } else {
-                            Box::new(once(Err(e)))
-                        }
-                    }
-                }
-            }
+            PlanNode::Service { .. } => self.evaluate_service(node, vec![from]),
             PlanNode::QuadPatternJoin {
                 child,
                 subject,
                 predicate,
                 object,
                 graph_name,
+                ..
             } => {
                 let eval = self.clone();
                 let subject = *subject;
@@ -163,7 +315,7 @@ where
                 let object = *object;
                 let graph_name = *graph_name;
                 Box::new(self.eval_plan(child, from).flat_map_ok(move |tuple| {
-                    let mut iter: Box<dyn Iterator<Item = _>> =
+                    let mut iter: Box<dyn Iterator<Item = _> + Send> =
                         Box::new(eval.dataset.encoded_quads_for_pattern(
                             get_pattern_value(&subject, &tuple),
                             get_pattern_value(&predicate, &tuple),
@@ -289,24 +441,56 @@ where
                 }))
             }
             PlanNode::Join { left, right } => {
-                //TODO: very dumb implementation
-                let mut errors = Vec::default();
-                let left_values = self
-                    .eval_plan(left, from.clone())
-                    .filter_map(|result| match result {
-                        Ok(result) => Some(result),
-                        Err(error) => {
-                            errors.push(Err(error));
-                            None
+                // The joins without always bound shared variables are cartesian products:
+                // the right side is evaluated once and combined with each left tuple
+                let eval = self.clone();
+                let left = left.clone();
+                let right = right.clone();
+                Box::new(LazyTuplesIterator::new(move || {
+                    let left_iter = eval.spawn_plan_evaluation(&left, &from);
+                    let mut errors = Vec::default();
+                    let mut right_values = Vec::default();
+                    for result in eval.eval_plan(&right, from.clone()) {
+                        match result {
+                            Ok(result) => right_values.push(result),
+                            Err(error) => errors.push(Err(error)),
                         }
+                    }
+                    Box::new(JoinIterator {
+                        left_iter: left_iter.unwrap_or_else(|| eval.eval_plan(&left, from)),
+                        right: right_values,
+                        buffered_results: errors,
                     })
-                    .collect::<Vec<_>>();
-                Box::new(JoinIterator {
-                    left: left_values,
-                    right_iter: self.eval_plan(right, from),
-                    buffered_results: errors,
-                })
+                }))
             }
+            PlanNode::HashJoin { left, right, keys } => {
+                let eval = self.clone();
+                let left = left.clone();
+                let right = right.clone();
+                let keys = keys.clone();
+                Box::new(LazyTuplesIterator::new(move || {
+                    let right_iter = eval.spawn_plan_evaluation(&right, &from);
+                    let mut errors = Vec::default();
+                    let mut left_values = EncodedTupleSet::new(keys);
+                    for result in eval.eval_plan(&left, from.clone()) {
+                        match result {
+                            Ok(result) => left_values.insert(result),
+                            Err(error) => errors.push(Err(error)),
+                        }
+                    }
+                    Box::new(HashJoinIterator {
+                        left: left_values,
+                        right_iter: right_iter.unwrap_or_else(|| eval.eval_plan(&right, from)),
+                        buffered_results: errors,
+                    })
+                }))
+            }
+            PlanNode::ServiceJoin { left, right } => Box::new(ServiceJoinIterator {
+                eval: self.clone(),
+                service: right.clone(),
+                left_iter: self.eval_plan(left, from),
+                current: Box::new(empty()),
+            }),
             PlanNode::AntiJoin { left, right } => {
                 //TODO: dumb implementation
                 let right: Vec<_> = self
@@ -325,14 +509,14 @@ where
             } => {
                 if possible_problem_vars.is_empty() {
                     Box::new(LeftJoinIterator {
-                        eval: self.clone(),
+                        eval: self.sequential(),
                         right_plan: right.clone(),
                         left_iter: self.eval_plan(left, from),
                         current_right: Box::new(empty()),
                     })
                 } else {
                     Box::new(BadLeftJoinIterator {
-                        eval: self.clone(),
+                        eval: self.sequential(),
                         right_plan: right.clone(),
                         left_iter: self.eval_plan(left, from),
                         current_left: None,
@@ -341,6 +525,28 @@ where
                     })
                 }
             }
+            PlanNode::HashLeftJoin { left, right, keys } => {
+                let eval = self.clone();
+                let left = left.clone();
+                let right = right.clone();
+                let keys = keys.clone();
+                Box::new(LazyTuplesIterator::new(move || {
+                    let left_iter = eval.spawn_plan_evaluation(&left, &from);
+                    let mut errors = Vec::default();
+                    let mut right_values = EncodedTupleSet::new(keys);
+                    for result in eval.eval_plan(&right, from.clone()) {
+                        match result {
+                            Ok(result) => right_values.insert(result),
+                            Err(error) => errors.push(Err(error)),
+                        }
+                    }
+                    Box::new(HashLeftJoinIterator {
+                        left_iter: left_iter.unwrap_or_else(|| eval.eval_plan(&left, from)),
+                        right: right_values,
+                        buffered_results: errors,
+                    })
+                }))
+            }
             PlanNode::Filter { child, expression } => {
                 let eval = self.clone();
                 let expression = expression.clone();
@@ -354,13 +560,25 @@ where
                     }
                 }))
             }
-            PlanNode::Union { children } => Box::new(UnionIterator {
-                eval: self.clone(),
-                plans: children.clone(),
-                input: from,
-                current_iterator: Box::new(empty()),
-                current_plan: 0,
-            }),
+            PlanNode::Union { children } => {
+                if self.max_threads > 1 {
+                    let iters = children
+                        .iter()
+                        .map(|child| {
+                            self.spawn_plan_evaluation(child, &from)
+                                .unwrap_or_else(|| self.eval_plan(child, from.clone()))
+                        })
+                        .collect::<Vec<_>>();
+                    return Box::new(iters.into_iter().flatten());
+                }
+                Box::new(UnionIterator {
+                    eval: self.clone(),
+                    plans: children.clone(),
+                    input: from,
+                    current_iterator: Box::new(empty()),
+                    current_plan: 0,
+                })
+            }
             PlanNode::Extend {
                 child,
                 position,
@@ -379,45 +597,88 @@ where
             }
             PlanNode::Sort { child, by } => {
                 let mut errors = Vec::default();
-                let mut values = self
-                    .eval_plan(child, from)
-                    .filter_map(|result| match result {
-                        Ok(result) => Some(result),
-                        Err(error) => {
-                            errors.push(Err(error));
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                values.sort_unstable_by(|a, b| {
-                    for comp in by {
-                        match comp {
-                            Comparator::Asc(expression) => {
-                                match self.cmp_according_to_expression(a, b, expression) {
-                                    Ordering::Greater => return Ordering::Greater,
-                                    Ordering::Less => return Ordering::Less,
-                                    Ordering::Equal => (),
-                                }
-                            }
-                            Comparator::Desc(expression) => {
-                                match self.cmp_according_to_expression(a, b, expression) {
-                                    Ordering::Greater => return Ordering::Less,
-                                    Ordering::Less => return Ordering::Greater,
-                                    Ordering::Equal => (),
+                let mut values = Vec::default();
+                let mut values_size = 0;
+                let mut runs = None;
+                for result in self.eval_plan(child, from) {
+                    match result {
+                        Ok(tuple) => {
+                            if let Some(memory_limit) = self.memory_limit {
+                                values_size += tuple_memory_size(&tuple);
+                                if values_size > memory_limit && !values.is_empty() {
+                                    values.sort_unstable_by(|a, b| self.cmp_tuples(a, b, by));
+                                    let runs = runs.get_or_insert_with(|| {
+                                        let eval = self.clone();
+                                        let by = by.clone();
+                                        SortedRuns::new(move |a, b| eval.cmp_tuples(a, b, &by))
+                                    });
+                                    if let Err(error) = runs.push(values.drain(..)) {
+                                        errors.push(Err(error));
+                                    }
+                                    values_size = tuple_memory_size(&tuple);
                                 }
                             }
+                            values.push(tuple)
                         }
+                        Err(error) => errors.push(Err(error)),
                     }
-                    Ordering::Equal
-                });
-                Box::new(errors.into_iter().chain(values.into_iter().map(Ok)))
+                }
+                values.sort_unstable_by(|a, b| self.cmp_tuples(a, b, by));
+                if let Some(runs) = runs {
+                    Box::new(
+                        errors
+                            .into_iter()
+                            .chain(runs.merge(Box::new(values.into_iter().map(Ok)))),
+                    )
+                } else {
+                    Box::new(errors.into_iter().chain(values.into_iter().map(Ok)))
+                }
             }
             PlanNode::HashDeduplicate { child } => {
-                Box::new(hash_deduplicate(self.eval_plan(child, from)))
+                if let Some(memory_limit) = self.memory_limit {
+                    Box::new(SpillingDeduplicate::new(
+                        self.eval_plan(child, from),
+                        memory_limit,
+                    ))
+                } else {
+                    Box::new(hash_deduplicate(self.eval_plan(child, from)))
+                }
             }
             PlanNode::Skip { child, count } => Box::new(self.eval_plan(child, from).skip(*count)),
             PlanNode::Limit { child, count } => Box::new(self.eval_plan(child, from).take(*count)),
-            PlanNode::Project { child, mapping } => {
+            PlanNode::Parameters { child, keys } => {
+                let mut encoder = self.dataset.as_ref();
+                let mut input = from;
+                let mut bindings = Vec::with_capacity(keys.len());
+                for (key, value) in keys.iter().zip(self.parameters.iter()) {
+                    let value = match encoder.encode_term(value.as_ref()) {
+                        Ok(value) => value,
+                        Err(error) => return Box::new(once(Err(error.into()))),
+                    };
+                    match input.get(*key) {
+                        Some(previous) if previous != value => return Box::new(empty()),
+                        _ => input.set(*key, value),
+                    }
+                    bindings.push((*key, value));
+                }
+                // The child might not keep the input bindings, for example if it is a sub-query
+                Box::new(
+                    self.eval_plan(child, input)
+                        .filter_map(move |tuple| match tuple {
+                            Ok(mut tuple) => {
+                                for (key, value) in &bindings {
+                                    match tuple.get(*key) {
+                                        Some(previous) if previous != *value => return None,
+                                        _ => tuple.set(*key, *value),
+                                    }
+                                }
+                                Some(Ok(tuple))
+                            }
+                            Err(error) => Some(Err(error)),
+                        }),
+                )
+            }
+            PlanNode::Project { child, mapping, .. } => {
                 //TODO: use from somewhere?
                 let mapping = mapping.clone();
                 Box::new(
@@ -438,97 +699,402 @@ where
                 child,
                 key_mapping,
                 aggregates,
+                ..
             } => {
                 let tuple_size = from.capacity(); //TODO: not nice
-                let key_mapping = key_mapping.clone();
-                let aggregates = aggregates.clone();
-                let mut errors = Vec::default();
-                let mut accumulators_for_group = HashMap::<
-                    Vec<Option<EncodedTerm<S::StrId>>>,
-                    Vec<Box<dyn Accumulator<S::StrId>>>,
-                >::default();
-                self.eval_plan(child, from)
-                    .filter_map(|result| match result {
-                        Ok(result) => Some(result),
-                        Err(error) => {
-                            errors.push(error);
-                            None
+                self.eval_aggregate(
+                    self.eval_plan(child, from),
+                    tuple_size,
+                    key_mapping.clone(),
+                    aggregates.clone(),
+                    0,
+                )
+            }
+        }
+    }
+
+    /// Starts the evaluation of a plan node in a new thread if the parallel evaluation is enabled
+    ///
+    /// Returns `None` if all the evaluation threads are busy: the node should then be evaluated by the calling thread.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_plan_evaluation(
+        &self,
+        node: &Arc<PlanNode<S::StrId>>,
+        from: &EncodedTuple<S::StrId>,
+    ) -> Option<EncodedTuplesIterator<S::StrId>> {
+        let permit = EvaluationThreadPermit::acquire(&self.spawned_threads, self.max_threads)?;
+        let (sender, receiver) = sync_channel(THREAD_CHANNEL_CAPACITY);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let eval = self.clone();
+        let node = node.clone();
+        let from = from.clone();
+        let thread_cancelled = cancelled.clone();
+        let handle = thread::Builder::new()
+            .spawn(move || {
+                let _permit = permit;
+                for result in eval.eval_plan(&node, from) {
+                    if thread_cancelled.load(AtomicOrdering::Relaxed)
+                        || sender.send(result).is_err()
+                    {
+                        break; // The results are not read anymore
+                    }
+                }
+            })
+            .ok()?;
+        Some(Box::new(ThreadIterator {
+            receiver,
+            handle: Some(handle),
+            cancelled,
+        }))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn spawn_plan_evaluation(
+        &self,
+        _node: &Arc<PlanNode<S::StrId>>,
+        _from: &EncodedTuple<S::StrId>,
+    ) -> Option<EncodedTuplesIterator<S::StrId>> {
+        None
+    }
+
+    fn cmp_tuples(
+        &self,
+        a: &EncodedTuple<S::StrId>,
+        b: &EncodedTuple<S::StrId>,
+        by: &[Comparator<S::StrId>],
+    ) -> Ordering {
+        for comp in by {
+            match comp {
+                Comparator::Asc(expression) => {
+                    match self.cmp_according_to_expression(a, b, expression) {
+                        Ordering::Greater => return Ordering::Greater,
+                        Ordering::Less => return Ordering::Less,
+                        Ordering::Equal => (),
+                    }
+                }
+                Comparator::Desc(expression) => {
+                    match self.cmp_according_to_expression(a, b, expression) {
+                        Ordering::Greater => return Ordering::Less,
+                        Ordering::Less => return Ordering::Greater,
+                        Ordering::Equal => (),
+                    }
+                }
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Groups the input tuples and computes the aggregates for each group
+    ///
+    /// If the memory limit is reached, the tuples of the new groups are written into hash partitions
+    /// that are aggregated one after the other using an increased `level`.
+    fn eval_aggregate(
+        &self,
+        input: EncodedTuplesIterator<S::StrId>,
+        tuple_size: usize,
+        key_mapping: Arc<Vec<(usize, usize)>>,
+        aggregates: Arc<Vec<(PlanAggregation<S::StrId>, usize)>>,
+        level: u32,
+    ) -> EncodedTuplesIterator<S::StrId> {
+        let mut errors = Vec::default();
+        let mut accumulators_for_group = HashMap::<
+            Vec<Option<EncodedTerm<S::StrId>>>,
+            Vec<Box<dyn Accumulator<S::StrId>>>,
+        >::default();
+        let mut groups_size = 0;
+        let mut partitions = None;
+        for result in input {
+            let tuple = match result {
+                Ok(tuple) => tuple,
+                Err(error) => {
+                    errors.push(error);
+                    continue;
+                }
+            };
+            //TODO avoid copy for key?
+            let key = key_mapping
+                .iter()
+                .map(|(v, _)| tuple.get(*v))
+                .collect::<Vec<_>>();
+            if !accumulators_for_group.contains_key(&key) {
+                if let Some(memory_limit) = self.memory_limit {
+                    if groups_size > memory_limit {
+                        if partitions.is_none() {
+                            match TuplePartitions::new(level) {
+                                Ok(p) => partitions = Some(p),
+                                Err(error) => {
+                                    errors.push(error.into());
+                                    continue;
+                                }
+                            }
                         }
-                    })
-                    .for_each(|tuple| {
-                        //TODO avoid copy for key?
-                        let key = key_mapping.iter().map(|(v, _)| tuple.get(*v)).collect();
-
-                        let key_accumulators =
-                            accumulators_for_group.entry(key).or_insert_with(|| {
-                                aggregates
-                                    .iter()
-                                    .map(|(aggregate, _)| {
-                                        self.accumulator_for_aggregate(
-                                            &aggregate.function,
-                                            aggregate.distinct,
-                                        )
-                                    })
-                                    .collect::<Vec<_>>()
-                            });
-                        for (i, accumulator) in key_accumulators.iter_mut().enumerate() {
-                            let (aggregate, _) = &aggregates[i];
-                            accumulator.add(
-                                aggregate
-                                    .parameter
-                                    .as_ref()
-                                    .and_then(|parameter| self.eval_expression(parameter, &tuple)),
-                            );
+                        if let Some(partitions) = &mut partitions {
+                            if let Err(error) = partitions.write(&key, &tuple) {
+                                errors.push(error.into());
+                            }
                         }
-                    });
-                if accumulators_for_group.is_empty() {
-                    // There is always at least one group
-                    accumulators_for_group.insert(vec![None; key_mapping.len()], Vec::default());
+                        continue;
+                    }
+                    groups_size +=
+                        key_memory_size(&key) + aggregates.len() * ACCUMULATOR_MEMORY_ESTIMATE;
                 }
-                Box::new(
-                    errors
+            }
+            let key_accumulators = accumulators_for_group.entry(key).or_insert_with(|| {
+                aggregates
+                    .iter()
+                    .map(|(aggregate, _)| {
+                        self.accumulator_for_aggregate(&aggregate.function, aggregate.distinct)
+                    })
+                    .collect::<Vec<_>>()
+            });
+            for (i, accumulator) in key_accumulators.iter_mut().enumerate() {
+                let (aggregate, _) = &aggregates[i];
+                accumulator.add(
+                    aggregate
+                        .parameter
+                        .as_ref()
+                        .and_then(|parameter| self.eval_expression(parameter, &tuple)),
+                );
+            }
+        }
+        if accumulators_for_group.is_empty() {
+            // There is always at least one group
+            accumulators_for_group.insert(vec![None; key_mapping.len()], Vec::default());
+        }
+        let partitions = match partitions.map(TuplePartitions::finish).transpose() {
+            Ok(partitions) => partitions.unwrap_or_default(),
+            Err(error) => {
+                errors.push(error.into());
+                Vec::default()
+            }
+        };
+        let eval = self.clone();
+        let partitions_key_mapping = key_mapping.clone();
+        let partitions_aggregates = aggregates.clone();
+        Box::new(
+            errors
+                .into_iter()
+                .map(Err)
+                .chain(
+                    accumulators_for_group
                         .into_iter()
-                        .map(Err)
-                        .chain(accumulators_for_group.into_iter().map(
-                            move |(key, accumulators)| {
-                                let mut result = EncodedTuple::with_capacity(tuple_size);
-                                for (from_position, to_position) in key_mapping.iter() {
-                                    if let Some(value) = key[*from_position] {
-                                        result.set(*to_position, value);
-                                    }
+                        .map(move |(key, accumulators)| {
+                            let mut result = EncodedTuple::with_capacity(tuple_size);
+                            for (from_position, to_position) in key_mapping.iter() {
+                                if let Some(value) = key[*from_position] {
+                                    result.set(*to_position, value);
                                 }
-                                for (i, accumulator) in accumulators.into_iter().enumerate() {
-                                    if let Some(value) = accumulator.state() {
-                                        result.set(aggregates[i].1, value);
-                                    }
+                            }
+                            for (i, accumulator) in accumulators.into_iter().enumerate() {
+                                if let Some(value) = accumulator.state() {
+                                    result.set(aggregates[i].1, value);
                                 }
-                                Ok(result)
-                            },
-                        )),
+                            }
+                            Ok(result)
+                        }),
                 )
+                .chain(partitions.into_iter().flat_map(move |partition| {
+                    eval.eval_aggregate(
+                        Box::new(partition),
+                        tuple_size,
+                        partitions_key_mapping.clone(),
+                        partitions_aggregates.clone(),
+                        level + 1,
+                    )
+                })),
+        )
+    }
+
+    /// Evaluates a SERVICE call for each binding of the batch
+    ///
+    /// The service is called once for each service name used by the batch bindings
+    /// and the returned solutions are joined with the batch bindings.
+    fn evaluate_service(
+        &self,
+        node: &PlanNode<S::StrId>,
+        batch: Vec<EncodedTuple<S::StrId>>,
+    ) -> EncodedTuplesIterator<S::StrId> {
+        let (service_name, variables, graph_pattern, silent) = if let PlanNode::Service {
+            service_name,
+            variables,
+            graph_pattern,
+            silent,
+            ..
+        } = node
+        {
+            (
+                service_name,
+                variables.clone(),
+                graph_pattern.clone(),
+                *silent,
+            )
+        } else {
+            unreachable!("Only SERVICE plan nodes could be evaluated as services")
+        };
+        let mut groups: Vec<(Option<EncodedTerm<S::StrId>>, Vec<EncodedTuple<S::StrId>>)> =
+            Vec::new();
+        for tuple in batch {
+            let name = get_pattern_value(service_name, &tuple);
+            if let Some((_, tuples)) = groups.iter_mut().find(|(n, _)| *n == name) {
+                tuples.push(tuple);
+            } else {
+                groups.push((name, vec![tuple]));
             }
         }
+        let eval = self.clone();
+        Box::new(groups.into_iter().flat_map(
+            move |(name, tuples)| -> EncodedTuplesIterator<S::StrId> {
+                let tuples = Arc::new(tuples);
+                match eval.call_service(name, &graph_pattern, variables.clone(), tuples.clone()) {
+                    Ok(results) => results,
+                    Err(error) => {
+                        if silent {
+                            Box::new((*tuples).clone().into_iter().map(Ok))
+                        } else {
+                            Box::new(once(Err(error)))
+                        }
+                    }
+                }
+            },
+        ))
     }
 
-    fn evaluate_service(
+    /// Calls a service with the input bindings and joins its results with them
+    ///
+    /// The input bindings of the variables used by the service pattern are sent in a `VALUES` clause
+    /// with an extra variable giving the input binding position.
+    /// The service pattern is evaluated in a sub-query in order to be evaluated independently of the `VALUES` clause.
+    /// Blank nodes are not sent because they are local to the query.
+    fn call_service(
         &self,
-        service_name: &PatternValue<S::StrId>,
+        service_name: Option<EncodedTerm<S::StrId>>,
         graph_pattern: &GraphPattern,
-        variables: Rc<Vec<Variable>>,
-        from: &EncodedTuple<S::StrId>,
+        variables: Arc<Vec<Variable>>,
+        tuples: Arc<Vec<EncodedTuple<S::StrId>>>,
     ) -> Result<EncodedTuplesIterator<S::StrId>, EvaluationError> {
-        if let QueryResults::Solutions(iter) = self.service_handler.handle(
-            self.dataset.decode_named_node(
-                get_pattern_value(service_name, from)
-                    .ok_or_else(|| EvaluationError::msg("The SERVICE name is not bound"))?,
-            )?,
+        let service_name = self.dataset.decode_named_node(
+            service_name.ok_or_else(|| EvaluationError::msg("The SERVICE name is not bound"))?,
+        )?;
+        let pattern_variables = graph_pattern.visible_variables();
+        let sent_variables = variables
+            .iter()
+            .enumerate()
+            .filter(|(key, variable)| {
+                pattern_variables.contains(variable)
+                    && tuples.iter().any(
+                        |tuple| matches!(tuple.get(*key), Some(value) if !value.is_blank_node()),
+                    )
+            })
+            .map(|(key, variable)| (key, variable.clone()))
+            .collect::<Vec<_>>();
+
+        if sent_variables.is_empty() {
+            let solutions = self.call_service_with_pattern(service_name, graph_pattern.clone())?;
+            return Ok(Box::new(
+                self.encode_bindings(variables, solutions)
+                    .flat_map(move |binding| match binding {
+                        Ok(binding) => tuples
+                            .iter()
+                            .filter_map(|tuple| binding.combine_with(tuple))
+                            .map(Ok)
+                            .collect::<Vec<_>>(),
+                        Err(error) => vec![Err(error)],
+                    }),
+            ));
+        }
+
+        // The position variable must not be used by the service pattern
+        let mut position_variable = Variable::new_unchecked("position");
+        let mut i = 0;
+        while pattern_variables.contains(&position_variable) {
+            i += 1;
+            position_variable = Variable::new_unchecked(format!("position{}", i));
+        }
+        let rows = tuples
+            .iter()
+            .enumerate()
+            .map(|(position, tuple)| {
+                sent_variables
+                    .iter()
+                    .map(|(key, _)| match tuple.get(*key) {
+                        Some(value) if !value.is_blank_node() => {
+                            Ok(Some(self.dataset.decode_term(value)?))
+                        }
+                        _ => Ok(None),
+                    })
+                    .chain(once(Ok(Some(Literal::from(position as u64).into()))))
+                    .collect::<Result<Vec<_>, EvaluationError>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let pattern = GraphPattern::Join {
+            left: Box::new(GraphPattern::Table {
+                variables: sent_variables
+                    .into_iter()
+                    .map(|(_, variable)| variable)
+                    .chain(once(position_variable.clone()))
+                    .collect(),
+                rows,
+            }),
+            right: Box::new(GraphPattern::Project {
+                inner: Box::new(graph_pattern.clone()),
+                projection: pattern_variables.into_iter().cloned().collect(),
+            }),
+        };
+        let solutions = self.call_service_with_pattern(service_name, pattern)?;
+
+        let eval = self.clone();
+        Ok(Box::new(
+            solutions
+                .map(move |solution| {
+                    let mut encoder = eval.dataset.as_ref();
+                    let mut encoded_terms = EncodedTuple::with_capacity(variables.len());
+                    let mut input = None;
+                    for (variable, term) in solution?.iter() {
+                        if *variable == position_variable {
+                            input = if let Term::Literal(position) = term {
+                                position
+                                    .value()
+                                    .parse::<usize>()
+                                    .ok()
+                                    .and_then(|position| tuples.get(position))
+                            } else {
+                                None
+                            };
+                        } else {
+                            put_variable_value(
+                                variable,
+                                &variables,
+                                encoder.encode_term(term.as_ref()).map_err(|e| e.into())?,
+                                &mut encoded_terms,
+                            )
+                        }
+                    }
+                    let input = input.ok_or_else(|| {
+                        EvaluationError::msg(
+                            "The service returned a solution without a valid input binding position",
+                        )
+                    })?;
+                    Ok(encoded_terms.combine_with(input))
+                })
+                .filter_map(Result::transpose),
+        ))
+    }
+
+    fn call_service_with_pattern(
+        &self,
+        service_name: NamedNode,
+        pattern: GraphPattern,
+    ) -> Result<QuerySolutionIter, EvaluationError> {
+        if let QueryResults::Solutions(iter) = self.service_handler.handle_with_options(
+            service_name,
             Query::Select {
                 dataset: QueryDataset::default(),
-                pattern: graph_pattern.clone(),
+                pattern,
                 base_iri: self.base_iri.as_ref().map(|iri| iri.as_ref().clone()),
             },
+            &self.service_query_options(),
         )? {
-            Ok(self.encode_bindings(variables, iter))
+            Ok(iter)
         } else {
             Err(EvaluationError::msg(
                 "The service call has not returned a set of solutions",
@@ -536,6 +1102,22 @@ where
         }
     }
 
+    /// The options given to the service handler, the ones used by the current evaluation
+    fn service_query_options(&self) -> QueryOptions {
+        QueryOptions {
+            service_handler: self.service_handler.clone(),
+            timeout: None,
+            cancellation_token: None,
+            parent_interruption_checker: self.interruption_checker.clone(),
+            custom_functions: self.custom_functions.clone(),
+            custom_aggregates: self.custom_aggregates.clone(),
+            memory_limit: self.memory_limit,
+            max_threads: self.max_threads,
+            describe_strategy: self.describe_strategy.clone(),
+            service_batch_size: self.service_batch_size,
+        }
+    }
+
     fn accumulator_for_aggregate(
         &self,
         function: &PlanAggregationFunction,
@@ -576,6 +1158,17 @@ where
                     Box::new(GroupConcatAccumulator::new(self.clone(), separator.clone()))
                 }
             }
+            PlanAggregationFunction::Custom(name) => {
+                let accumulator = CustomAccumulatorAdapter::new(
+                    self.clone(),
+                    self.custom_aggregates.get(name).map(|factory| factory()),
+                );
+                if distinct {
+                    Box::new(DistinctAccumulator::new(accumulator))
+                } else {
+                    Box::new(accumulator)
+                }
+            }
         }
     }
 
@@ -584,7 +1177,10 @@ where
         path: &PlanPropertyPath<S::StrId>,
         start: EncodedTerm<S::StrId>,
         graph_name: EncodedTerm<S::StrId>,
-    ) -> Box<dyn Iterator<Item = Result<EncodedTerm<S::StrId>, EvaluationError>>> {
+    ) -> Box<dyn Iterator<Item = Result<EncodedTerm<S::StrId>, EvaluationError>> + Send> {
+        if let Err(e) = self.check_interruption() {
+            return Box::new(once(Err(e)));
+        }
         match path {
             PlanPropertyPath::Path(p) => Box::new(
                 self.dataset
@@ -647,7 +1243,10 @@ where
         path: &PlanPropertyPath<S::StrId>,
         end: EncodedTerm<S::StrId>,
         graph_name: EncodedTerm<S::StrId>,
-    ) -> Box<dyn Iterator<Item = Result<EncodedTerm<S::StrId>, EvaluationError>>> {
+    ) -> Box<dyn Iterator<Item = Result<EncodedTerm<S::StrId>, EvaluationError>> + Send> {
+        if let Err(e) = self.check_interruption() {
+            return Box::new(once(Err(e)));
+        }
         match path {
             PlanPropertyPath::Path(p) => Box::new(
                 self.dataset
@@ -710,10 +1309,12 @@ where
         path: &PlanPropertyPath<S::StrId>,
         graph_name: EncodedTerm<S::StrId>,
     ) -> Box<
-        dyn Iterator<
-            Item = Result<(EncodedTerm<S::StrId>, EncodedTerm<S::StrId>), EvaluationError>,
-        >,
+        dyn Iterator<Item = Result<(EncodedTerm<S::StrId>, EncodedTerm<S::StrId>), EvaluationError>>
+            + Send,
     > {
+        if let Err(e) = self.check_interruption() {
+            return Box::new(once(Err(e)));
+        }
         match path {
             PlanPropertyPath::Path(p) => Box::new(
                 self.dataset
@@ -805,9 +1406,13 @@ where
         match expression {
             PlanExpression::Constant(t) => Some(*t),
             PlanExpression::Variable(v) => tuple.get(*v),
-            PlanExpression::Exists(node) => {
-                Some(self.eval_plan(node, tuple.clone()).next().is_some().into())
-            }
+            PlanExpression::Exists(node) => Some(
+                self.sequential()
+                    .eval_plan(node, tuple.clone())
+                    .next()
+                    .is_some()
+                    .into(),
+            ),
             PlanExpression::Or(a, b) => {
                 match self.eval_expression(a, tuple).and_then(|v| self.to_bool(v)) {
                     Some(true) => Some(true.into()),
@@ -1562,9 +2167,177 @@ where
                     self.to_string_id(self.eval_expression(e, tuple)?)?,
                 ))
             }
+            PlanExpression::CustomFunction(function_name, parameters) => {
+                let function = self.custom_functions.get(function_name)?;
+                let parameters = parameters
+                    .iter()
+                    .map(|p| {
+                        self.dataset
+                            .decode_term(self.eval_expression(p, tuple)?)
+                            .ok()
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let mut encoder = self.dataset.as_ref();
+                encoder.encode_term(function(&parameters)?.as_ref()).ok()
+            }
         }
     }
 
+    /// Returns the description of a resource for `DESCRIBE` queries
+    fn describe(
+        &self,
+        resource: EncodedTerm<S::StrId>,
+    ) -> Box<dyn Iterator<Item = Result<Triple, EvaluationError>> + Send> {
+        match &self.describe_strategy {
+            DescribeStrategy::Subject => {
+                Box::new(self.decode_triples(self.describe_quads(Some(resource), None, None)))
+            }
+            DescribeStrategy::ConciseBoundedDescription => {
+                match self.concise_bounded_description(resource, false) {
+                    Ok(quads) => Box::new(self.decode_triples(quads.into_iter().map(Ok))),
+                    Err(error) => Box::new(once(Err(error))),
+                }
+            }
+            DescribeStrategy::SymmetricConciseBoundedDescription => {
+                match self.concise_bounded_description(resource, true) {
+                    Ok(quads) => Box::new(self.decode_triples(quads.into_iter().map(Ok))),
+                    Err(error) => Box::new(once(Err(error))),
+                }
+            }
+            DescribeStrategy::Custom(describe) => {
+                let resource = match self.dataset.decode_term(resource) {
+                    Ok(resource) => resource,
+                    Err(error) => return Box::new(once(Err(error.into()))),
+                };
+                let triples_for_pattern = |subject: Option<&Term>,
+                                           predicate: Option<&NamedNode>,
+                                           object: Option<&Term>|
+                 -> Box<
+                    dyn Iterator<Item = Result<Triple, EvaluationError>>,
+                > {
+                    match self.encode_describe_pattern(subject, predicate, object) {
+                        Ok(Some((subject, predicate, object))) => Box::new(
+                            self.decode_triples(self.describe_quads(subject, predicate, object)),
+                        ),
+                        Ok(None) => Box::new(empty()),
+                        Err(error) => Box::new(once(Err(error))),
+                    }
+                };
+                match describe(
+                    &resource,
+                    &DescribeGraph {
+                        triples_for_pattern: &triples_for_pattern,
+                    },
+                ) {
+                    Ok(triples) => Box::new(triples.into_iter().map(Ok)),
+                    Err(error) => Box::new(once(Err(error))),
+                }
+            }
+        }
+    }
+
+    /// Builds the Concise Bounded Description of a resource, following the incoming arcs too if `symmetric` is set
+    fn concise_bounded_description(
+        &self,
+        resource: EncodedTerm<S::StrId>,
+        symmetric: bool,
+    ) -> Result<Vec<EncodedQuad<S::StrId>>, EvaluationError> {
+        let mut description = Vec::new();
+        let mut seen_triples = HashSet::new();
+        let mut seen_blank_nodes = HashSet::new();
+        let mut to_describe = vec![resource];
+        while let Some(node) = to_describe.pop() {
+            self.check_interruption()?;
+            let outgoing = self.describe_quads(Some(node), None, None);
+            let incoming: Box<dyn Iterator<Item = _>> = if symmetric {
+                Box::new(self.describe_quads(None, None, Some(node)))
+            } else {
+                Box::new(empty())
+            };
+            for quad in outgoing.chain(incoming) {
+                let quad = quad?;
+                if !seen_triples.insert((quad.subject, quad.predicate, quad.object)) {
+                    continue;
+                }
+                for term in &[quad.subject, quad.object] {
+                    if *term != node && term.is_blank_node() && seen_blank_nodes.insert(*term) {
+                        to_describe.push(*term);
+                    }
+                }
+                description.push(quad);
+            }
+        }
+        Ok(description)
+    }
+
+    /// Returns the quads of the default graph and of the named graphs matching a pattern
+    fn describe_quads(
+        &self,
+        subject: Option<EncodedTerm<S::StrId>>,
+        predicate: Option<EncodedTerm<S::StrId>>,
+        object: Option<EncodedTerm<S::StrId>>,
+    ) -> impl Iterator<Item = Result<EncodedQuad<S::StrId>, EvaluationError>> + Send {
+        self.dataset
+            .encoded_quads_for_pattern(subject, predicate, object, Some(EncodedTerm::DefaultGraph))
+            .chain(
+                self.dataset
+                    .encoded_quads_for_pattern(subject, predicate, object, None),
+            )
+    }
+
+    fn decode_triples(
+        &self,
+        quads: impl Iterator<Item = Result<EncodedQuad<S::StrId>, EvaluationError>> + Send,
+    ) -> impl Iterator<Item = Result<Triple, EvaluationError>> + Send {
+        let eval = self.clone();
+        quads.map(move |quad| Ok(eval.dataset.decode_quad(&quad?)?.into()))
+    }
+
+    /// Encodes a triple pattern, returning `None` if one of the terms is not in the dataset
+    #[allow(clippy::type_complexity)]
+    fn encode_describe_pattern(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&NamedNode>,
+        object: Option<&Term>,
+    ) -> Result<
+        Option<(
+            Option<EncodedTerm<S::StrId>>,
+            Option<EncodedTerm<S::StrId>>,
+            Option<EncodedTerm<S::StrId>>,
+        )>,
+        EvaluationError,
+    > {
+        let subject = if let Some(subject) = subject {
+            if let Some(subject) = self.dataset.get_encoded_term(subject.as_ref())? {
+                Some(subject)
+            } else {
+                return Ok(None);
+            }
+        } else {
+            None
+        };
+        let predicate = if let Some(predicate) = predicate {
+            if let Some(predicate) = self.dataset.get_encoded_named_node(predicate.as_ref())? {
+                Some(predicate)
+            } else {
+                return Ok(None);
+            }
+        } else {
+            None
+        };
+        let object = if let Some(object) = object {
+            if let Some(object) = self.dataset.get_encoded_term(object.as_ref())? {
+                Some(object)
+            } else {
+                return Ok(None);
+            }
+        } else {
+            None
+        };
+        Ok(Some((subject, predicate, object)))
+    }
+
     fn to_bool(&self, term: EncodedTerm<S::StrId>) -> Option<bool> {
         match term {
             EncodedTerm::BooleanLiteral(value) => Some(value),
@@ -1817,7 +2590,7 @@ where
     fn decode_bindings(
         &self,
         iter: EncodedTuplesIterator<S::StrId>,
-        variables: Rc<Vec<Variable>>,
+        variables: Arc<Vec<Variable>>,
     ) -> QuerySolutionIter {
         let eval = self.clone();
         let tuple_size = variables.len();
@@ -1838,7 +2611,7 @@ where
     // this is used to encode results from a BindingIterator into an EncodedTuplesIterator. This happens when SERVICE clauses are evaluated
     fn encode_bindings(
         &self,
-        variables: Rc<Vec<Variable>>,
+        variables: Arc<Vec<Variable>>,
         iter: QuerySolutionIter,
     ) -> EncodedTuplesIterator<S::StrId> {
         let eval = self.clone();
@@ -2463,15 +3236,118 @@ pub fn are_compatible_and_not_disjointed<I: StrId>(
     found_intersection
 }
 
+/// Creates the iterator on the first call to `next`
+///
+/// It allows the joins to only evaluate the side they keep in memory when their first result is requested.
+struct LazyTuplesIterator<I: StrId> {
+    create: Option<Box<dyn FnOnce() -> EncodedTuplesIterator<I> + Send>>,
+    iter: Option<EncodedTuplesIterator<I>>,
+}
+
+impl<I: StrId> LazyTuplesIterator<I> {
+    fn new(create: impl FnOnce() -> EncodedTuplesIterator<I> + Send + 'static) -> Self {
+        Self {
+            create: Some(Box::new(create)),
+            iter: None,
+        }
+    }
+}
+
+impl<I: StrId> Iterator for LazyTuplesIterator<I> {
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        if let Some(create) = self.create.take() {
+            self.iter = Some(create());
+        }
+        self.iter.as_mut()?.next()
+    }
+}
+
 struct JoinIterator<I: StrId> {
-    left: Vec<EncodedTuple<I>>,
-    right_iter: EncodedTuplesIterator<I>,
+    left_iter: EncodedTuplesIterator<I>,
+    right: Vec<EncodedTuple<I>>,
     buffered_results: Vec<Result<EncodedTuple<I>, EvaluationError>>,
 }
 
 impl<I: StrId> Iterator for JoinIterator<I> {
     type Item = Result<EncodedTuple<I>, EvaluationError>;
 
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        loop {
+            if let Some(result) = self.buffered_results.pop() {
+                return Some(result);
+            }
+            let left_tuple = match self.left_iter.next()? {
+                Ok(left_tuple) => left_tuple,
+                Err(error) => return Some(Err(error)),
+            };
+            for right_tuple in &self.right {
+                if let Some(result_tuple) = left_tuple.combine_with(right_tuple) {
+                    self.buffered_results.push(Ok(result_tuple))
+                }
+            }
+        }
+    }
+}
+
+/// Tuples indexed by the values of some variables
+struct EncodedTupleSet<I: StrId> {
+    keys: Arc<Vec<usize>>,
+    map: HashMap<Vec<EncodedTerm<I>>, Vec<EncodedTuple<I>>>,
+    // Tuples where some of the keys are not bound
+    unkeyed: Vec<EncodedTuple<I>>,
+}
+
+impl<I: StrId> EncodedTupleSet<I> {
+    fn new(keys: Arc<Vec<usize>>) -> Self {
+        Self {
+            keys,
+            map: HashMap::default(),
+            unkeyed: Vec::default(),
+        }
+    }
+
+    fn tuple_key(&self, tuple: &EncodedTuple<I>) -> Option<Vec<EncodedTerm<I>>> {
+        self.keys.iter().map(|key| tuple.get(*key)).collect()
+    }
+
+    fn insert(&mut self, tuple: EncodedTuple<I>) {
+        if let Some(key) = self.tuple_key(&tuple) {
+            self.map.entry(key).or_default().push(tuple)
+        } else {
+            self.unkeyed.push(tuple)
+        }
+    }
+
+    /// Returns the tuples that might be compatible with the given one
+    fn candidates<'a>(
+        &'a self,
+        tuple: &EncodedTuple<I>,
+    ) -> Box<dyn Iterator<Item = &'a EncodedTuple<I>> + 'a> {
+        if let Some(key) = self.tuple_key(tuple) {
+            Box::new(
+                self.map
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .chain(&self.unkeyed),
+            )
+        } else {
+            Box::new(self.map.values().flatten().chain(&self.unkeyed))
+        }
+    }
+}
+
+struct HashJoinIterator<I: StrId> {
+    left: EncodedTupleSet<I>,
+    right_iter: EncodedTuplesIterator<I>,
+    buffered_results: Vec<Result<EncodedTuple<I>, EvaluationError>>,
+}
+
+impl<I: StrId> Iterator for HashJoinIterator<I> {
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
     fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
         loop {
             if let Some(result) = self.buffered_results.pop() {
@@ -2481,7 +3357,7 @@ impl<I: StrId> Iterator for JoinIterator<I> {
                 Ok(right_tuple) => right_tuple,
                 Err(error) => return Some(Err(error)),
             };
-            for left_tuple in &self.left {
+            for left_tuple in self.left.candidates(&right_tuple) {
                 if let Some(result_tuple) = left_tuple.combine_with(&right_tuple) {
                     self.buffered_results.push(Ok(result_tuple))
                 }
@@ -2490,6 +3366,36 @@ impl<I: StrId> Iterator for JoinIterator<I> {
     }
 }
 
+struct HashLeftJoinIterator<I: StrId> {
+    left_iter: EncodedTuplesIterator<I>,
+    right: EncodedTupleSet<I>,
+    buffered_results: Vec<Result<EncodedTuple<I>, EvaluationError>>,
+}
+
+impl<I: StrId> Iterator for HashLeftJoinIterator<I> {
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        if let Some(result) = self.buffered_results.pop() {
+            return Some(result);
+        }
+        let left_tuple = match self.left_iter.next()? {
+            Ok(left_tuple) => left_tuple,
+            Err(error) => return Some(Err(error)),
+        };
+        for right_tuple in self.right.candidates(&left_tuple) {
+            if let Some(result_tuple) = left_tuple.combine_with(right_tuple) {
+                self.buffered_results.push(Ok(result_tuple))
+            }
+        }
+        if let Some(result) = self.buffered_results.pop() {
+            Some(result)
+        } else {
+            Some(Ok(left_tuple))
+        }
+    }
+}
+
 struct AntiJoinIterator<I: StrId> {
     left_iter: EncodedTuplesIterator<I>,
     right: Vec<EncodedTuple<I>>,
@@ -2515,14 +3421,55 @@ impl<I: StrId> Iterator for AntiJoinIterator<I> {
     }
 }
 
-struct LeftJoinIterator<S: ReadableEncodedStore + 'static> {
+struct ServiceJoinIterator<S: ReadableEncodedStore + Send + Sync + 'static> {
+    eval: SimpleEvaluator<S>,
+    service: Arc<PlanNode<S::StrId>>,
+    left_iter: EncodedTuplesIterator<S::StrId>,
+    current: EncodedTuplesIterator<S::StrId>,
+}
+
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Iterator
+    for ServiceJoinIterator<S>
+where
+    for<'a> &'a S: StrContainer<StrId = S::StrId>,
+{
+    type Item = Result<EncodedTuple<S::StrId>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<S::StrId>, EvaluationError>> {
+        loop {
+            if let Some(result) = self.current.next() {
+                return Some(result);
+            }
+            let mut batch = Vec::new();
+            while batch.len() < self.eval.service_batch_size {
+                match self.left_iter.next() {
+                    Some(Ok(tuple)) => batch.push(tuple),
+                    Some(Err(error)) => {
+                        if !batch.is_empty() {
+                            self.current = self.eval.eval_service(&self.service, batch);
+                        }
+                        return Some(Err(error));
+                    }
+                    None => break,
+                }
+            }
+            if batch.is_empty() {
+                return None;
+            }
+            self.current = self.eval.eval_service(&self.service, batch);
+        }
+    }
+}
+
+struct LeftJoinIterator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
-    right_plan: Rc<PlanNode<S::StrId>>,
+    right_plan: Arc<PlanNode<S::StrId>>,
     left_iter: EncodedTuplesIterator<S::StrId>,
     current_right: EncodedTuplesIterator<S::StrId>,
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Iterator for LeftJoinIterator<S>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Iterator
+    for LeftJoinIterator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
 {
@@ -2546,16 +3493,17 @@ where
     }
 }
 
-struct BadLeftJoinIterator<S: ReadableEncodedStore + 'static> {
+struct BadLeftJoinIterator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
-    right_plan: Rc<PlanNode<S::StrId>>,
+    right_plan: Arc<PlanNode<S::StrId>>,
     left_iter: EncodedTuplesIterator<S::StrId>,
     current_left: Option<EncodedTuple<S::StrId>>,
     current_right: EncodedTuplesIterator<S::StrId>,
-    problem_vars: Rc<Vec<usize>>,
+    problem_vars: Arc<Vec<usize>>,
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Iterator for BadLeftJoinIterator<S>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Iterator
+    for BadLeftJoinIterator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
 {
@@ -2601,15 +3549,16 @@ where
     }
 }
 
-struct UnionIterator<S: ReadableEncodedStore + 'static> {
+struct UnionIterator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
-    plans: Vec<Rc<PlanNode<S::StrId>>>,
+    plans: Vec<Arc<PlanNode<S::StrId>>>,
     input: EncodedTuple<S::StrId>,
     current_iterator: EncodedTuplesIterator<S::StrId>,
     current_plan: usize,
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Iterator for UnionIterator<S>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Iterator
+    for UnionIterator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
 {
@@ -2631,7 +3580,77 @@ where
     }
 }
 
-struct ConstructIterator<S: ReadableEncodedStore + 'static> {
+/// Allows a thread to evaluate a plan node of a query, released when dropped
+#[cfg(not(target_arch = "wasm32"))]
+struct EvaluationThreadPermit {
+    spawned_threads: Arc<AtomicUsize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl EvaluationThreadPermit {
+    /// Returns `None` if the query already uses `max_threads` threads, the calling thread included
+    fn acquire(spawned_threads: &Arc<AtomicUsize>, max_threads: usize) -> Option<Self> {
+        spawned_threads
+            .fetch_update(AtomicOrdering::AcqRel, AtomicOrdering::Acquire, |count| {
+                if count + 1 < max_threads {
+                    Some(count + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| Self {
+                spawned_threads: spawned_threads.clone(),
+            })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for EvaluationThreadPermit {
+    fn drop(&mut self) {
+        self.spawned_threads.fetch_sub(1, AtomicOrdering::AcqRel);
+    }
+}
+
+/// Reads the results computed by an other thread
+///
+/// The thread stops computing results when the iterator is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+struct ThreadIterator<I: StrId> {
+    receiver: Receiver<Result<EncodedTuple<I>, EvaluationError>>,
+    handle: Option<JoinHandle<()>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<I: StrId> Drop for ThreadIterator<I> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, AtomicOrdering::Relaxed);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<I: StrId> Iterator for ThreadIterator<I> {
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        match self.receiver.recv() {
+            Ok(result) => Some(result),
+            Err(_) => {
+                // The thread has ended, we check that it has not panicked
+                if self.handle.take()?.join().is_err() {
+                    Some(Err(EvaluationError::msg(
+                        "The thread evaluating a part of the query has panicked",
+                    )))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+struct ConstructIterator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
     iter: EncodedTuplesIterator<S::StrId>,
     template: Vec<TripleTemplate<S::StrId>>,
@@ -2639,7 +3658,9 @@ struct ConstructIterator<S: ReadableEncodedStore + 'static> {
     bnodes: Vec<EncodedTerm<S::StrId>>,
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Iterator for ConstructIterator<S> {
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Iterator
+    for ConstructIterator<S>
+{
     type Item = Result<Triple, EvaluationError>;
 
     fn next(&mut self) -> Option<Result<Triple, EvaluationError>> {
@@ -2706,46 +3727,35 @@ fn decode_triple<D: Decoder>(
     ))
 }
 
-struct DescribeIterator<S: ReadableEncodedStore + 'static> {
+struct DescribeIterator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
     iter: EncodedTuplesIterator<S::StrId>,
-    quads: Box<dyn Iterator<Item = Result<EncodedQuad<S::StrId>, EvaluationError>>>,
+    triples: Box<dyn Iterator<Item = Result<Triple, EvaluationError>> + Send>,
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Iterator for DescribeIterator<S> {
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Iterator
+    for DescribeIterator<S>
+where
+    for<'a> &'a S: StrContainer<StrId = S::StrId>,
+{
     type Item = Result<Triple, EvaluationError>;
 
     fn next(&mut self) -> Option<Result<Triple, EvaluationError>> {
         loop {
-            if let Some(quad) = self.quads.next() {
-                return Some(match quad {
-                    Ok(quad) => self
-                        .eval
-                        .dataset
-                        .decode_quad(&quad)
-                        .map(|q| q.into())
-                        .map_err(|e| e.into()),
-                    Err(error) => Err(error),
-                });
+            if let Some(triple) = self.triples.next() {
+                return Some(triple);
             }
             let tuple = match self.iter.next()? {
                 Ok(tuple) => tuple,
                 Err(error) => return Some(Err(error)),
             };
             let eval = self.eval.clone();
-            self.quads = Box::new(tuple.into_iter().flatten().flat_map(move |subject| {
-                eval.dataset
-                    .encoded_quads_for_pattern(
-                        Some(subject),
-                        None,
-                        None,
-                        Some(EncodedTerm::DefaultGraph),
-                    )
-                    .chain(
-                        eval.dataset
-                            .encoded_quads_for_pattern(Some(subject), None, None, None),
-                    )
-            }));
+            self.triples = Box::new(
+                tuple
+                    .into_iter()
+                    .flatten()
+                    .flat_map(move |resource| eval.describe(resource)),
+            );
         }
     }
 }
@@ -2894,7 +3904,7 @@ impl<
     }
 }
 
-trait Accumulator<I: StrId> {
+trait Accumulator<I: StrId>: Send {
     fn add(&mut self, element: Option<EncodedTerm<I>>);
 
     fn state(&self) -> Option<EncodedTerm<I>>;
@@ -3022,18 +4032,18 @@ impl<I: StrId> Accumulator<I> for AvgAccumulator<I> {
 }
 
 #[allow(clippy::option_option)]
-struct MinAccumulator<S: ReadableEncodedStore + 'static> {
+struct MinAccumulator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
     min: Option<Option<EncodedTerm<S::StrId>>>,
 }
 
-impl<S: ReadableEncodedStore + 'static> MinAccumulator<S> {
+impl<S: ReadableEncodedStore + Send + Sync + 'static> MinAccumulator<S> {
     fn new(eval: SimpleEvaluator<S>) -> Self {
         Self { eval, min: None }
     }
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Accumulator<S::StrId>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Accumulator<S::StrId>
     for MinAccumulator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
@@ -3054,18 +4064,18 @@ where
 }
 
 #[allow(clippy::option_option)]
-struct MaxAccumulator<S: ReadableEncodedStore + 'static> {
+struct MaxAccumulator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
     max: Option<Option<EncodedTerm<S::StrId>>>,
 }
 
-impl<S: ReadableEncodedStore + 'static> MaxAccumulator<S> {
+impl<S: ReadableEncodedStore + Send + Sync + 'static> MaxAccumulator<S> {
     fn new(eval: SimpleEvaluator<S>) -> Self {
         Self { eval, max: None }
     }
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Accumulator<S::StrId>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Accumulator<S::StrId>
     for MaxAccumulator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
@@ -3109,15 +4119,15 @@ impl<I: StrId> Accumulator<I> for SampleAccumulator<I> {
 }
 
 #[allow(clippy::option_option)]
-struct GroupConcatAccumulator<S: ReadableEncodedStore + 'static> {
+struct GroupConcatAccumulator<S: ReadableEncodedStore + Send + Sync + 'static> {
     eval: SimpleEvaluator<S>,
     concat: Option<String>,
     language: Option<Option<SmallStringOrId<S::StrId>>>,
-    separator: Rc<String>,
+    separator: Arc<String>,
 }
 
-impl<S: ReadableEncodedStore + 'static> GroupConcatAccumulator<S> {
-    fn new(eval: SimpleEvaluator<S>, separator: Rc<String>) -> Self {
+impl<S: ReadableEncodedStore + Send + Sync + 'static> GroupConcatAccumulator<S> {
+    fn new(eval: SimpleEvaluator<S>, separator: Arc<String>) -> Self {
         Self {
             eval,
             concat: Some("".to_owned()),
@@ -3127,7 +4137,7 @@ impl<S: ReadableEncodedStore + 'static> GroupConcatAccumulator<S> {
     }
 }
 
-impl<S: ReadableEncodedStore<Error = EvaluationError> + 'static> Accumulator<S::StrId>
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Accumulator<S::StrId>
     for GroupConcatAccumulator<S>
 where
     for<'a> &'a S: StrContainer<StrId = S::StrId>,
@@ -3158,6 +4168,37 @@ where
     }
 }
 
+/// Calls a [`CustomAccumulator`] with decoded terms
+struct CustomAccumulatorAdapter<S: ReadableEncodedStore + Send + Sync + 'static> {
+    eval: SimpleEvaluator<S>,
+    inner: Option<Box<dyn CustomAccumulator>>,
+}
+
+impl<S: ReadableEncodedStore + Send + Sync + 'static> CustomAccumulatorAdapter<S> {
+    fn new(eval: SimpleEvaluator<S>, inner: Option<Box<dyn CustomAccumulator>>) -> Self {
+        Self { eval, inner }
+    }
+}
+
+impl<S: ReadableEncodedStore<Error = EvaluationError> + Send + Sync + 'static> Accumulator<S::StrId>
+    for CustomAccumulatorAdapter<S>
+where
+    for<'a> &'a S: StrContainer<StrId = S::StrId>,
+{
+    fn add(&mut self, element: Option<EncodedTerm<S::StrId>>) {
+        let element = element.and_then(|element| self.eval.dataset.decode_term(element).ok());
+        if let Some(inner) = &mut self.inner {
+            inner.add(element)
+        }
+    }
+
+    fn state(&self) -> Option<EncodedTerm<S::StrId>> {
+        let term = self.inner.as_ref()?.state()?;
+        let mut encoder = self.eval.dataset.as_ref();
+        encoder.encode_term(term.as_ref()).ok()
+    }
+}
+
 fn generate_uuid(buffer: &mut String) {
     let mut uuid = random::<u128>().to_ne_bytes();
     uuid[6] = (uuid[6] & 0x0F) | 0x40;
@@ -0,0 +1,496 @@
+//! Explanation of the SPARQL query evaluation plans
+
+use crate::sparql::eval::EncodedTuplesIterator;
+use crate::sparql::json_results::write_escaped_json_string;
+use crate::sparql::model::Variable;
+use crate::sparql::plan::*;
+use crate::store::numeric_encoder::{Decoder, EncodedTerm, StrId};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The explanation of the evaluation of a SPARQL query.
+///
+/// It contains the tree of operations executed by the query evaluator.
+/// If it has been built with statistics, each operation also reports the number of rows it has returned
+/// and the time spent evaluating it, including the time spent in its children.
+/// The statistics are updated while the query results are consumed.
+///
+/// It is written in a human-readable form using [`Display`](fmt::Display) and in JSON using [`write_in_json`](QueryExplanation::write_in_json()).
+///
+/// Usage example:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{QueryOptions, QueryResults};
+///
+/// let store = MemoryStore::new();
+/// let ex = NamedNode::new("http://example.com")?;
+/// store.insert(Quad::new(ex.clone(), ex.clone(), ex.clone(), None));
+///
+/// let (results, explanation) = store.explain_query_opt("SELECT ?s WHERE { ?s ?p ?o }", QueryOptions::default(), true)?;
+/// if let Some(QueryResults::Solutions(solutions)) = results.transpose()? {
+///     assert_eq!(solutions.count(), 1);
+/// }
+/// assert!(explanation.to_string().contains("QuadPattern ?s ?p ?o"));
+/// assert!(explanation.to_string().contains("rows: 1"));
+///
+/// let mut json = Vec::new();
+/// explanation.write_in_json(&mut json)?;
+/// assert!(json.starts_with(b"{\"name\":\"Project\""));
+///
+/// // Without statistics, the query is not evaluated
+/// let (results, explanation) = store.explain_query_opt("SELECT ?s WHERE { ?s ?p ?o }", QueryOptions::default(), false)?;
+/// assert!(results.is_none());
+/// assert!(explanation.to_string().contains("QuadPattern ?s ?p ?o"));
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+pub struct QueryExplanation {
+    root: ExplanationNode,
+}
+
+impl QueryExplanation {
+    /// Writes the explanation as a JSON tree.
+    ///
+    /// Each node is an object with the keys `name`, `details` and `children`.
+    /// If statistics are collected, it also has the keys `executions`, `rows` and `duration_seconds`.
+    pub fn write_in_json(&self, mut writer: impl Write) -> io::Result<()> {
+        self.root.write_in_json(&mut writer)
+    }
+}
+
+impl fmt::Display for QueryExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root.fmt_with_indent(f, 0)
+    }
+}
+
+impl fmt::Debug for QueryExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+struct ExplanationNode {
+    name: &'static str,
+    details: String,
+    children: Vec<ExplanationNode>,
+    stats: Option<Arc<PlanNodeStats>>,
+}
+
+impl ExplanationNode {
+    fn fmt_with_indent(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}", "", self.name, indent = indent)?;
+        if !self.details.is_empty() {
+            write!(f, " {}", self.details)?;
+        }
+        if let Some(stats) = &self.stats {
+            write!(
+                f,
+                " [executions: {}, rows: {}, duration: {:?}]",
+                stats.executions.load(Ordering::Relaxed),
+                stats.rows.load(Ordering::Relaxed),
+                stats.duration()
+            )?;
+        }
+        writeln!(f)?;
+        for child in &self.children {
+            child.fmt_with_indent(f, indent + 2)?;
+        }
+        Ok(())
+    }
+
+    fn write_in_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(b"{\"name\":")?;
+        write_escaped_json_string(self.name, &mut *writer)?;
+        writer.write_all(b",\"details\":")?;
+        write_escaped_json_string(&self.details, &mut *writer)?;
+        if let Some(stats) = &self.stats {
+            write!(
+                writer,
+                ",\"executions\":{},\"rows\":{},\"duration_seconds\":{}",
+                stats.executions.load(Ordering::Relaxed),
+                stats.rows.load(Ordering::Relaxed),
+                stats.duration().as_secs_f64()
+            )?;
+        }
+        writer.write_all(b",\"children\":[")?;
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            child.write_in_json(writer)?;
+        }
+        writer.write_all(b"]}")
+    }
+}
+
+/// Statistics about the evaluation of a plan node
+#[derive(Default)]
+struct PlanNodeStats {
+    executions: AtomicU64,
+    rows: AtomicU64,
+    duration_nanos: AtomicU64,
+}
+
+impl PlanNodeStats {
+    fn add_duration(&self, start: Instant) {
+        let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.duration_nanos.fetch_add(elapsed, Ordering::Relaxed);
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::from_nanos(self.duration_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Statistics collected during the evaluation of a plan, indexed by the plan nodes addresses
+#[derive(Default)]
+pub(crate) struct PlanStatistics {
+    nodes: HashMap<usize, Arc<PlanNodeStats>>,
+}
+
+impl PlanStatistics {
+    /// Evaluates a plan node using the given function and records its statistics if the node is tracked
+    pub fn track<I: StrId + 'static>(
+        &self,
+        node: &PlanNode<I>,
+        eval: impl FnOnce() -> EncodedTuplesIterator<I>,
+    ) -> EncodedTuplesIterator<I> {
+        if let Some(stats) = self.nodes.get(&node_key(node)) {
+            let start = Instant::now();
+            let inner = eval();
+            stats.executions.fetch_add(1, Ordering::Relaxed);
+            stats.add_duration(start);
+            Box::new(StatsIterator {
+                inner,
+                stats: stats.clone(),
+            })
+        } else {
+            eval()
+        }
+    }
+}
+
+fn node_key<I: StrId>(node: &PlanNode<I>) -> usize {
+    let node: *const PlanNode<I> = node;
+    node as usize
+}
+
+struct StatsIterator<I: StrId> {
+    inner: EncodedTuplesIterator<I>,
+    stats: Arc<PlanNodeStats>,
+}
+
+impl<I: StrId> Iterator for StatsIterator<I> {
+    type Item = <EncodedTuplesIterator<I> as Iterator>::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let result = self.inner.next();
+        self.stats.add_duration(start);
+        if matches!(result, Some(Ok(_))) {
+            self.stats.rows.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// Builds the explanation of a plan
+///
+/// If `statistics` is set, the plan nodes are registered in it.
+/// The plan should not be moved during the evaluation in order for the statistics to be collected.
+pub(crate) fn build_explanation<D: Decoder>(
+    plan: &PlanNode<D::StrId>,
+    variables: &[Variable],
+    decoder: &D,
+    statistics: Option<&mut PlanStatistics>,
+) -> QueryExplanation {
+    QueryExplanation {
+        root: ExplanationBuilder {
+            decoder,
+            statistics,
+        }
+        .build(plan, variables),
+    }
+}
+
+struct ExplanationBuilder<'a, D: Decoder> {
+    decoder: &'a D,
+    statistics: Option<&'a mut PlanStatistics>,
+}
+
+impl<'a, D: Decoder> ExplanationBuilder<'a, D> {
+    fn build(&mut self, node: &PlanNode<D::StrId>, variables: &[Variable]) -> ExplanationNode {
+        let mut children = Vec::new();
+        let (name, details) = match node {
+            PlanNode::Init => ("Init", String::new()),
+            PlanNode::StaticBindings { tuples } => {
+                let mut bound = tuples
+                    .iter()
+                    .flat_map(|tuple| (0..tuple.capacity()).filter(move |i| tuple.contains(*i)))
+                    .collect::<Vec<_>>();
+                bound.sort_unstable();
+                bound.dedup();
+                (
+                    "StaticBindings",
+                    format!(
+                        "{} ({} rows)",
+                        self.variables(&bound, variables),
+                        tuples.len()
+                    ),
+                )
+            }
+            PlanNode::Service {
+                service_name,
+                silent,
+                ..
+            } => (
+                "Service",
+                format!(
+                    "{}{}",
+                    self.pattern_value(service_name, variables),
+                    if *silent { " SILENT" } else { "" }
+                ),
+            ),
+            PlanNode::QuadPatternJoin {
+                child,
+                subject,
+                predicate,
+                object,
+                graph_name,
+                ..
+            } => {
+                self.add_child_if_not_init(child, variables, &mut children);
+                (
+                    "QuadPattern",
+                    format!(
+                        "{} {} {}{}",
+                        self.pattern_value(subject, variables),
+                        self.pattern_value(predicate, variables),
+                        self.pattern_value(object, variables),
+                        self.graph_name(graph_name, variables)
+                    ),
+                )
+            }
+            PlanNode::PathPatternJoin {
+                child,
+                subject,
+                path,
+                object,
+                graph_name,
+            } => {
+                self.add_child_if_not_init(child, variables, &mut children);
+                (
+                    "PathPattern",
+                    format!(
+                        "{} {} {}{}",
+                        self.pattern_value(subject, variables),
+                        self.path(path),
+                        self.pattern_value(object, variables),
+                        self.graph_name(graph_name, variables)
+                    ),
+                )
+            }
+            PlanNode::Join { left, right } => {
+                children.push(self.build(left, variables));
+                children.push(self.build(right, variables));
+                ("Join", String::new())
+            }
+            PlanNode::HashJoin { left, right, keys } => {
+                children.push(self.build(left, variables));
+                children.push(self.build(right, variables));
+                (
+                    "HashJoin",
+                    format!("keys: {}", self.variables(keys, variables)),
+                )
+            }
+            PlanNode::ServiceJoin { left, right } => {
+                children.push(self.build(left, variables));
+                children.push(self.build(right, variables));
+                ("ServiceJoin", String::new())
+            }
+            PlanNode::AntiJoin { left, right } => {
+                children.push(self.build(left, variables));
+                children.push(self.build(right, variables));
+                ("AntiJoin", String::new())
+            }
+            PlanNode::LeftJoin { left, right, .. } => {
+                children.push(self.build(left, variables));
+                children.push(self.build(right, variables));
+                ("LeftJoin", String::new())
+            }
+            PlanNode::HashLeftJoin { left, right, keys } => {
+                children.push(self.build(left, variables));
+                children.push(self.build(right, variables));
+                (
+                    "HashLeftJoin",
+                    format!("keys: {}", self.variables(keys, variables)),
+                )
+            }
+            PlanNode::Filter { child, expression } => {
+                children.push(self.build(child, variables));
+                let mut used = Default::default();
+                expression.add_maybe_bound_variables(&mut used);
+                (
+                    "Filter",
+                    format!(
+                        "using: {}",
+                        self.variables(&used.into_iter().collect::<Vec<_>>(), variables)
+                    ),
+                )
+            }
+            PlanNode::Union { children: plans } => {
+                for plan in plans {
+                    children.push(self.build(plan, variables));
+                }
+                ("Union", String::new())
+            }
+            PlanNode::Extend {
+                child, position, ..
+            } => {
+                children.push(self.build(child, variables));
+                ("Extend", self.variable(*position, variables))
+            }
+            PlanNode::Sort { child, by } => {
+                children.push(self.build(child, variables));
+                ("Sort", format!("{} comparators", by.len()))
+            }
+            PlanNode::HashDeduplicate { child } => {
+                children.push(self.build(child, variables));
+                ("HashDeduplicate", String::new())
+            }
+            PlanNode::Skip { child, count } => {
+                children.push(self.build(child, variables));
+                ("Skip", count.to_string())
+            }
+            PlanNode::Limit { child, count } => {
+                children.push(self.build(child, variables));
+                ("Limit", count.to_string())
+            }
+            PlanNode::Parameters { child, keys } => {
+                children.push(self.build(child, variables));
+                ("Parameters", self.variables(keys, variables))
+            }
+            PlanNode::Project {
+                child,
+                mapping,
+                variables: child_variables,
+            } => {
+                children.push(self.build(child, child_variables));
+                (
+                    "Project",
+                    self.variables(
+                        &mapping.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+                        child_variables,
+                    ),
+                )
+            }
+            PlanNode::Aggregate {
+                child,
+                key_mapping,
+                aggregates,
+                variables: child_variables,
+            } => {
+                children.push(self.build(child, child_variables));
+                (
+                    "Aggregate",
+                    format!(
+                        "keys: {}, aggregates: {}",
+                        self.variables(
+                            &key_mapping.iter().map(|(_, o)| *o).collect::<Vec<_>>(),
+                            variables
+                        ),
+                        self.variables(
+                            &aggregates.iter().map(|(_, v)| *v).collect::<Vec<_>>(),
+                            variables
+                        )
+                    ),
+                )
+            }
+        };
+        let stats = self
+            .statistics
+            .as_mut()
+            .map(|statistics| statistics.nodes.entry(node_key(node)).or_default().clone());
+        ExplanationNode {
+            name,
+            details,
+            children,
+            stats,
+        }
+    }
+
+    fn add_child_if_not_init(
+        &mut self,
+        child: &PlanNode<D::StrId>,
+        variables: &[Variable],
+        children: &mut Vec<ExplanationNode>,
+    ) {
+        if !matches!(child, PlanNode::Init) {
+            children.push(self.build(child, variables))
+        }
+    }
+
+    fn variable(&self, key: usize, variables: &[Variable]) -> String {
+        if let Some(variable) = variables.get(key) {
+            variable.to_string()
+        } else {
+            format!("?{}", key)
+        }
+    }
+
+    fn variables(&self, keys: &[usize], variables: &[Variable]) -> String {
+        keys.iter()
+            .map(|key| self.variable(*key, variables))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn term(&self, term: EncodedTerm<D::StrId>) -> String {
+        self.decoder
+            .decode_term(term)
+            .map_or_else(|_| "[unknown term]".to_owned(), |term| term.to_string())
+    }
+
+    fn pattern_value(&self, value: &PatternValue<D::StrId>, variables: &[Variable]) -> String {
+        match value {
+            PatternValue::Constant(term) => self.term(*term),
+            PatternValue::Variable(key) => self.variable(*key, variables),
+        }
+    }
+
+    fn graph_name(&self, value: &PatternValue<D::StrId>, variables: &[Variable]) -> String {
+        if *value == PatternValue::Constant(EncodedTerm::DefaultGraph) {
+            String::new()
+        } else {
+            format!(" GRAPH {}", self.pattern_value(value, variables))
+        }
+    }
+
+    fn path(&self, path: &PlanPropertyPath<D::StrId>) -> String {
+        match path {
+            PlanPropertyPath::Path(p) => self.term(*p),
+            PlanPropertyPath::Reverse(p) => format!("^({})", self.path(p)),
+            PlanPropertyPath::Sequence(a, b) => format!("({} / {})", self.path(a), self.path(b)),
+            PlanPropertyPath::Alternative(a, b) => {
+                format!("({} | {})", self.path(a), self.path(b))
+            }
+            PlanPropertyPath::ZeroOrMore(p) => format!("({})*", self.path(p)),
+            PlanPropertyPath::OneOrMore(p) => format!("({})+", self.path(p)),
+            PlanPropertyPath::ZeroOrOne(p) => format!("({})?", self.path(p)),
+            PlanPropertyPath::NegatedPropertySet(ps) => format!(
+                "!({})",
+                ps.iter()
+                    .map(|p| self.term(*p))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ),
+        }
+    }
+}
@@ -15,7 +15,7 @@ impl Client {
     pub fn request(
         &self,
         _request: &Request<Option<Vec<u8>>>,
-    ) -> io::Result<Response<Box<dyn BufRead>>> {
+    ) -> io::Result<Response<Box<dyn BufRead + Send>>> {
         Err(invalid_input_error(
             "HTTP client is not available. Enable the feature 'simple_http'",
         ))
@@ -1,10 +1,14 @@
 //! Implementation of [SPARQL Query Results JSON Format](https://www.w3.org/TR/sparql11-results-json/)
 
-use crate::error::invalid_input_error;
+use crate::error::{invalid_data_error, invalid_input_error};
 use crate::model::*;
 use crate::sparql::error::EvaluationError;
 use crate::sparql::model::*;
-use std::io::Write;
+use json_event_parser::{JsonEvent, JsonReader};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
 
 pub fn write_json_results(
     results: QueryResults,
@@ -84,7 +88,7 @@ pub fn write_json_results(
     }
 }
 
-fn write_escaped_json_string(s: &str, mut sink: impl Write) -> Result<(), EvaluationError> {
+pub(crate) fn write_escaped_json_string(s: &str, mut sink: impl Write) -> io::Result<()> {
     sink.write_all(b"\"")?;
     for c in s.chars() {
         match c {
@@ -115,6 +119,381 @@ fn write_escaped_json_string(s: &str, mut sink: impl Write) -> Result<(), Evalua
             }
         }?;
     }
-    sink.write_all(b"\"")?;
-    Ok(())
+    sink.write_all(b"\"")
+}
+
+pub fn read_json_results(source: impl BufRead + Send + 'static) -> Result<QueryResults, io::Error> {
+    let mut reader = JsonReader::from_reader(source);
+    let mut buffer = Vec::default();
+    let mut variables = None;
+    let mut buffered_bindings: Option<Vec<BTreeMap<String, Term>>> = None;
+
+    if reader.read_event(&mut buffer)? != JsonEvent::StartObject {
+        return Err(invalid_data_error(
+            "SPARQL JSON results should be an object",
+        ));
+    }
+
+    loop {
+        let key = match reader.read_event(&mut buffer)? {
+            JsonEvent::ObjectKey(key) => key.to_owned(),
+            JsonEvent::EndObject => break,
+            _ => return Err(invalid_data_error("Invalid SPARQL JSON results")),
+        };
+        match key.as_str() {
+            "head" => variables = Some(read_head(&mut reader, &mut buffer)?),
+            "results" => {
+                if reader.read_event(&mut buffer)? != JsonEvent::StartObject {
+                    return Err(invalid_data_error("'results' should be an object"));
+                }
+                loop {
+                    let key = match reader.read_event(&mut buffer)? {
+                        JsonEvent::ObjectKey(key) => key.to_owned(),
+                        JsonEvent::EndObject => break,
+                        _ => return Err(invalid_data_error("Invalid 'results' object")),
+                    };
+                    if key != "bindings" {
+                        ignore_value(&mut reader, &mut buffer)?;
+                        continue;
+                    }
+                    if reader.read_event(&mut buffer)? != JsonEvent::StartArray {
+                        return Err(invalid_data_error("'bindings' should be an array"));
+                    }
+                    if let Some(variables) = variables {
+                        // We are able to stream the solutions
+                        let mut mapping = BTreeMap::default();
+                        for (i, var) in variables.iter().enumerate() {
+                            mapping.insert(var.as_str().to_owned(), i);
+                        }
+                        return Ok(QueryResults::Solutions(QuerySolutionIter::new(
+                            Arc::new(variables),
+                            Box::new(JsonResultsIterator {
+                                reader,
+                                buffer,
+                                mapping,
+                            }),
+                        )));
+                    }
+                    // The head has not been read yet, we have to keep the solutions in memory
+                    let mut bindings = Vec::new();
+                    while let Some(binding) = read_binding(&mut reader, &mut buffer)? {
+                        bindings.push(binding);
+                    }
+                    buffered_bindings = Some(bindings);
+                }
+            }
+            "boolean" => {
+                return match reader.read_event(&mut buffer)? {
+                    JsonEvent::Boolean(v) => Ok(QueryResults::Boolean(v)),
+                    _ => Err(invalid_data_error("Unexpected boolean value")),
+                }
+            }
+            _ => ignore_value(&mut reader, &mut buffer)?,
+        }
+    }
+
+    match (variables, buffered_bindings) {
+        (Some(variables), Some(bindings)) => {
+            let mut mapping = BTreeMap::default();
+            for (i, var) in variables.iter().enumerate() {
+                mapping.insert(var.as_str(), i);
+            }
+            let solutions = bindings
+                .into_iter()
+                .map(|binding| {
+                    let mut new_bindings = vec![None; mapping.len()];
+                    for (var, value) in binding {
+                        new_bindings[*mapping.get(var.as_str()).ok_or_else(|| {
+                            invalid_data_error(format!(
+                                "The variable {} has not been defined in the header",
+                                var
+                            ))
+                        })?] = Some(value);
+                    }
+                    Ok(new_bindings)
+                })
+                .collect::<Result<Vec<_>, io::Error>>()?;
+            Ok(QueryResults::Solutions(QuerySolutionIter::new(
+                Arc::new(variables),
+                Box::new(solutions.into_iter().map(Ok)),
+            )))
+        }
+        (Some(_), None) => Err(invalid_data_error(
+            "SPARQL JSON results should contain a 'results' or a 'boolean' key",
+        )),
+        (None, _) => Err(invalid_data_error(
+            "SPARQL JSON results should contain a 'head' key",
+        )),
+    }
+}
+
+fn read_head<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<Vec<Variable>, io::Error> {
+    if reader.read_event(buffer)? != JsonEvent::StartObject {
+        return Err(invalid_data_error("'head' should be an object"));
+    }
+    let mut variables = Vec::new();
+    loop {
+        let key = match reader.read_event(buffer)? {
+            JsonEvent::ObjectKey(key) => key.to_owned(),
+            JsonEvent::EndObject => return Ok(variables),
+            _ => return Err(invalid_data_error("Invalid 'head' object")),
+        };
+        if key != "vars" {
+            ignore_value(reader, buffer)?;
+            continue;
+        }
+        if reader.read_event(buffer)? != JsonEvent::StartArray {
+            return Err(invalid_data_error("'vars' should be an array"));
+        }
+        loop {
+            match reader.read_event(buffer)? {
+                JsonEvent::String(name) => {
+                    variables.push(Variable::new(name).map_err(invalid_data_error)?)
+                }
+                JsonEvent::EndArray => break,
+                _ => return Err(invalid_data_error("Variable names should be strings")),
+            }
+        }
+    }
+}
+
+struct JsonResultsIterator<R: BufRead> {
+    reader: JsonReader<R>,
+    buffer: Vec<u8>,
+    mapping: BTreeMap<String, usize>,
+}
+
+impl<R: BufRead> Iterator for JsonResultsIterator<R> {
+    type Item = Result<Vec<Option<Term>>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<Vec<Option<Term>>, EvaluationError>> {
+        self.read_next().transpose()
+    }
+}
+
+impl<R: BufRead> JsonResultsIterator<R> {
+    fn read_next(&mut self) -> Result<Option<Vec<Option<Term>>>, EvaluationError> {
+        let binding = if let Some(binding) = read_binding(&mut self.reader, &mut self.buffer)? {
+            binding
+        } else {
+            return Ok(None);
+        };
+        let mut new_bindings = vec![None; self.mapping.len()];
+        for (var, value) in binding {
+            new_bindings[*self.mapping.get(&var).ok_or_else(|| {
+                invalid_data_error(format!(
+                    "The variable {} has not been defined in the header",
+                    var
+                ))
+            })?] = Some(value);
+        }
+        Ok(Some(new_bindings))
+    }
+}
+
+/// Reads a solution from the 'bindings' array or returns `None` if the end of the array is reached
+fn read_binding<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<Option<BTreeMap<String, Term>>, io::Error> {
+    match reader.read_event(buffer)? {
+        JsonEvent::StartObject => (),
+        JsonEvent::EndArray => return Ok(None),
+        _ => return Err(invalid_data_error("Solutions should be objects")),
+    }
+    let mut binding = BTreeMap::default();
+    loop {
+        let var = match reader.read_event(buffer)? {
+            JsonEvent::ObjectKey(key) => key.to_owned(),
+            JsonEvent::EndObject => return Ok(Some(binding)),
+            _ => return Err(invalid_data_error("Invalid solution object")),
+        };
+        let term = read_term(reader, buffer)?;
+        binding.insert(var, term);
+    }
+}
+
+fn read_term<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<Term, io::Error> {
+    if reader.read_event(buffer)? != JsonEvent::StartObject {
+        return Err(invalid_data_error("RDF terms should be objects"));
+    }
+    let mut type_ = None;
+    let mut value = None;
+    let mut lang = None;
+    let mut datatype = None;
+    loop {
+        let key = match reader.read_event(buffer)? {
+            JsonEvent::ObjectKey(key) => key.to_owned(),
+            JsonEvent::EndObject => break,
+            _ => return Err(invalid_data_error("Invalid RDF term object")),
+        };
+        let target = match key.as_str() {
+            "type" => &mut type_,
+            "value" => &mut value,
+            "xml:lang" => &mut lang,
+            "datatype" => &mut datatype,
+            _ => {
+                ignore_value(reader, buffer)?;
+                continue;
+            }
+        };
+        match reader.read_event(buffer)? {
+            JsonEvent::String(s) => *target = Some(s.to_owned()),
+            _ => {
+                return Err(invalid_data_error(format!(
+                    "The '{}' value of a RDF term should be a string",
+                    key
+                )))
+            }
+        }
+    }
+    let value = value.ok_or_else(|| invalid_data_error("RDF terms should have a 'value' key"))?;
+    match type_.as_deref() {
+        Some("uri") => Ok(NamedNode::new(value).map_err(invalid_data_error)?.into()),
+        Some("bnode") => Ok(BlankNode::new(value).map_err(invalid_data_error)?.into()),
+        Some("literal") | Some("typed-literal") => Ok(match datatype {
+            Some(datatype) => Literal::new_typed_literal(
+                value,
+                NamedNode::new(datatype).map_err(invalid_data_error)?,
+            ),
+            None => match lang {
+                Some(lang) => Literal::new_language_tagged_literal(value, &lang).map_err(|e| {
+                    invalid_data_error(format!("Invalid xml:lang value '{}': {}", lang, e))
+                })?,
+                None => Literal::new_simple_literal(value),
+            },
+        }
+        .into()),
+        Some(type_) => Err(invalid_data_error(format!(
+            "Unexpected RDF term type: '{}'",
+            type_
+        ))),
+        None => Err(invalid_data_error("RDF terms should have a 'type' key")),
+    }
+}
+
+fn ignore_value<R: BufRead>(
+    reader: &mut JsonReader<R>,
+    buffer: &mut Vec<u8>,
+) -> Result<(), io::Error> {
+    let mut nesting: usize = 0;
+    loop {
+        match reader.read_event(buffer)? {
+            JsonEvent::Boolean(_)
+            | JsonEvent::Null
+            | JsonEvent::Number(_)
+            | JsonEvent::String(_) => {
+                if nesting == 0 {
+                    return Ok(());
+                }
+            }
+            JsonEvent::ObjectKey(_) => (),
+            JsonEvent::StartArray | JsonEvent::StartObject => nesting += 1,
+            JsonEvent::EndArray | JsonEvent::EndObject => {
+                nesting = nesting
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid_data_error("Unexpected end of JSON array or object"))?;
+                if nesting == 0 {
+                    return Ok(());
+                }
+            }
+            JsonEvent::Eof => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unexpected end of file",
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::vocab::xsd;
+    use std::io::Cursor;
+
+    fn read_solutions(data: String) -> Result<Vec<Vec<Option<Term>>>, EvaluationError> {
+        if let QueryResults::Solutions(solutions) = read_json_results(Cursor::new(data))? {
+            solutions
+                .map(|s| Ok(s?.values().map(|v| v.cloned()).collect()))
+                .collect::<Result<Vec<_>, EvaluationError>>()
+        } else {
+            Err(EvaluationError::msg("Solutions expected"))
+        }
+    }
+
+    #[test]
+    fn test_json_boolean_parsing() -> Result<(), EvaluationError> {
+        for (data, expected) in &[
+            ("{\"head\":{},\"boolean\":true}", true),
+            ("{\"boolean\":false,\"head\":{\"link\":[]}}", false),
+        ] {
+            match read_json_results(Cursor::new(*data))? {
+                QueryResults::Boolean(value) => assert_eq!(value, *expected),
+                _ => panic!("Boolean expected"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_solutions_parsing() -> Result<(), EvaluationError> {
+        let expected = vec![
+            vec![
+                Some(NamedNode::new_unchecked("http://example.com/s").into()),
+                Some(Literal::new_language_tagged_literal_unchecked("foo", "en").into()),
+            ],
+            vec![
+                Some(BlankNode::new_unchecked("b0").into()),
+                Some(Literal::new_typed_literal("1", xsd::INTEGER).into()),
+            ],
+            vec![None, Some(Literal::new_simple_literal("bar").into())],
+        ];
+        let bindings = "[
+            {\"s\":{\"type\":\"uri\",\"value\":\"http://example.com/s\"},\"o\":{\"type\":\"literal\",\"value\":\"foo\",\"xml:lang\":\"en\"}},
+            {\"o\":{\"type\":\"typed-literal\",\"value\":\"1\",\"datatype\":\"http://www.w3.org/2001/XMLSchema#integer\"},\"s\":{\"type\":\"bnode\",\"value\":\"b0\"}},
+            {\"o\":{\"type\":\"literal\",\"value\":\"bar\"}}
+        ]";
+        assert_eq!(
+            read_solutions(format!(
+                "{{\"head\":{{\"vars\":[\"s\",\"o\"]}},\"results\":{{\"bindings\":{}}}}}",
+                bindings
+            ))?,
+            expected
+        );
+        assert_eq!(
+            read_solutions(format!(
+                "{{\"results\":{{\"bindings\":{}}},\"head\":{{\"vars\":[\"s\",\"o\"]}}}}",
+                bindings
+            ))?,
+            expected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_undefined_variable() {
+        assert!(read_solutions(
+            "{\"head\":{\"vars\":[\"s\"]},\"results\":{\"bindings\":[{\"o\":{\"type\":\"uri\",\"value\":\"http://example.com/o\"}}]}}".to_owned()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_json_unbalanced_ignored_value() {
+        for data in &[
+            "{\"head\":{\"vars\":[]},\"foo\":]}",
+            "{\"head\":{\"vars\":[]},\"foo\":}",
+            "{\"head\":{\"vars\":[]},\"results\":{\"foo\":]}}",
+        ] {
+            assert!(read_json_results(Cursor::new(*data)).is_err());
+        }
+    }
 }
@@ -2,25 +2,41 @@
 //!
 //! Stores execute SPARQL. See [`MemoryStore`](super::store::memory::MemoryStore::query()) for an example.
 
+mod aggregate;
 pub mod algebra;
+mod cancellation;
 mod csv_results;
 mod dataset;
+mod describe;
 mod error;
 mod eval;
+mod explanation;
 mod http;
 mod json_results;
 mod model;
 mod parser;
 mod plan;
 mod plan_builder;
+mod prepared;
 mod service;
+mod spill;
 mod update;
 mod xml_results;
 
+use crate::model::{NamedNode, Term};
+pub use crate::sparql::aggregate::CustomAccumulator;
 pub use crate::sparql::algebra::{Query, Update};
-use crate::sparql::dataset::DatasetView;
+pub use crate::sparql::cancellation::CancellationToken;
+use crate::sparql::cancellation::InterruptionChecker;
+use crate::sparql::dataset::{DatasetStrId, DatasetView};
+pub use crate::sparql::describe::{DescribeGraph, DescribeStrategy};
 pub use crate::sparql::error::EvaluationError;
-use crate::sparql::eval::SimpleEvaluator;
+use crate::sparql::eval::{
+    CustomAggregateRegistry, CustomFunctionRegistry, SimpleEvaluator, DEFAULT_SERVICE_BATCH_SIZE,
+};
+pub use crate::sparql::explanation::QueryExplanation;
+use crate::sparql::explanation::{build_explanation, PlanStatistics};
+pub use crate::sparql::model::CsvTermHint;
 pub use crate::sparql::model::QueryResults;
 pub use crate::sparql::model::QueryResultsFormat;
 pub use crate::sparql::model::QuerySolution;
@@ -28,80 +44,195 @@ pub use crate::sparql::model::QuerySolutionIter;
 pub use crate::sparql::model::QueryTripleIter;
 pub use crate::sparql::model::{Variable, VariableNameParseError};
 pub use crate::sparql::parser::ParseError;
+use crate::sparql::plan::{PlanNode, TripleTemplate};
 use crate::sparql::plan_builder::PlanBuilder;
-pub use crate::sparql::service::ServiceHandler;
+use crate::sparql::prepared::PlanCache;
+pub use crate::sparql::prepared::PreparedQuery;
 use crate::sparql::service::{EmptyServiceHandler, ErrorConversionServiceHandler};
+pub use crate::sparql::service::{LocalServiceHandler, ServiceHandler};
 use crate::sparql::update::SimpleUpdateEvaluator;
 use crate::store::numeric_encoder::StrContainer;
 use crate::store::{ReadableEncodedStore, StoreOrParseError, WritableEncodedStore};
 use std::convert::TryInto;
 use std::io;
-use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub(crate) fn evaluate_query<R: ReadableEncodedStore + 'static>(
+pub(crate) fn evaluate_query<R: ReadableEncodedStore + Send + Sync + 'static>(
     store: R,
     query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
     options: QueryOptions,
 ) -> Result<QueryResults, EvaluationError> {
-    match query.try_into().map_err(|e| e.into())? {
+    let query = query.try_into().map_err(|e| e.into())?;
+    let (dataset, plan) = build_query_plan(store, &query, &[], None, &options)?;
+    evaluate_query_plan(dataset, plan, &query, &[], options, None)
+}
+
+/// Returns the explanation of the evaluation of a query
+///
+/// The query is only evaluated if `with_stats` is set: its results are then returned with the explanation.
+/// The outer result contains the parsing and planning errors and the inner one the evaluation errors.
+pub(crate) fn explain_query<R: ReadableEncodedStore + Send + Sync + 'static>(
+    store: R,
+    query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    options: QueryOptions,
+    with_stats: bool,
+) -> Result<
+    (
+        Option<Result<QueryResults, EvaluationError>>,
+        QueryExplanation,
+    ),
+    EvaluationError,
+> {
+    let query = query.try_into().map_err(|e| e.into())?;
+    let (dataset, plan) = build_query_plan(store, &query, &[], None, &options)?;
+    if !with_stats {
+        let explanation = build_explanation(&plan.0, &plan.1, &dataset, None);
+        return Ok((None, explanation));
+    }
+    // The statistics are indexed by the plan nodes addresses so the plan should not move
+    let mut statistics = PlanStatistics::default();
+    let explanation = build_explanation(&plan.0, &plan.1, &dataset, Some(&mut statistics));
+    let results = evaluate_query_plan(
+        dataset,
+        plan,
+        &query,
+        &[],
+        options,
+        Some(Arc::new(statistics)),
+    );
+    Ok((Some(results), explanation))
+}
+
+/// Evaluates a prepared query, reusing its cached plan if possible
+pub(crate) fn evaluate_prepared_query<R: ReadableEncodedStore + Send + Sync + 'static>(
+    store: R,
+    query: &PreparedQuery,
+    options: QueryOptions,
+) -> Result<QueryResults, EvaluationError> {
+    let (dataset, plan) = build_query_plan(
+        store,
+        &query.query,
+        &query.bindings,
+        Some(&query.plans),
+        &options,
+    )?;
+    evaluate_query_plan(dataset, plan, &query.query, &query.bindings, options, None)
+}
+
+/// The plan of a query: the root node, the variables and the CONSTRUCT template
+type QueryPlan<I> = (
+    Arc<PlanNode<I>>,
+    Arc<Vec<Variable>>,
+    Option<Vec<TripleTemplate<I>>>,
+);
+
+/// Builds the plan of a query against the store or gets it from the `plans` cache
+fn build_query_plan<R: ReadableEncodedStore + Send + Sync + 'static>(
+    store: R,
+    query: &Query,
+    bindings: &[(Variable, Term)],
+    plans: Option<&PlanCache>,
+    options: &QueryOptions,
+) -> Result<(DatasetView<R>, QueryPlan<DatasetStrId<R::StrId>>), EvaluationError> {
+    let (pattern, dataset) = match query {
         Query::Select {
-            pattern,
-            base_iri,
-            dataset,
-        } => {
-            let dataset = DatasetView::new(store, &dataset)?;
-            let (plan, variables) = PlanBuilder::build(&dataset, &pattern)?;
-            SimpleEvaluator::new(
-                Rc::new(dataset),
-                base_iri.map(Rc::new),
-                options.service_handler,
-            )
-            .evaluate_select_plan(&plan, Rc::new(variables))
+            pattern, dataset, ..
+        }
+        | Query::Construct {
+            pattern, dataset, ..
         }
+        | Query::Describe {
+            pattern, dataset, ..
+        } => (pattern, dataset),
         Query::Ask {
-            pattern,
-            base_iri,
-            dataset,
-        } => {
-            let dataset = DatasetView::new(store, &dataset)?;
-            let (plan, _) = PlanBuilder::build(&dataset, &pattern)?;
-            SimpleEvaluator::new(
-                Rc::new(dataset),
-                base_iri.map(Rc::new),
-                options.service_handler,
-            )
-            .evaluate_ask_plan(&plan)
+            pattern, dataset, ..
+        } => (pattern.as_ref(), dataset),
+    };
+    let dataset = DatasetView::new(store, dataset)?;
+    let parameters = bindings
+        .iter()
+        .map(|(variable, _)| variable.clone())
+        .collect::<Vec<_>>();
+    // The dictionary id is read before building the plan in order to not miss concurrent changes
+    let dictionary_id = dataset.store_dictionary_id();
+    if let (Some(plans), Some(dictionary_id)) = (plans, dictionary_id) {
+        if let Some(plan) =
+            plans.get::<QueryPlan<DatasetStrId<R::StrId>>>(dictionary_id, &parameters, options)
+        {
+            let plan = plan.as_ref().clone();
+            return Ok((dataset, plan));
         }
-        Query::Construct {
+    }
+    let (plan, variables) = PlanBuilder::build(
+        &dataset,
+        pattern,
+        &parameters,
+        options.custom_functions.clone(),
+        options.custom_aggregates.clone(),
+    )?;
+    let construct = if let Query::Construct { template, .. } = query {
+        Some(PlanBuilder::build_graph_template(
+            &dataset,
             template,
-            pattern,
-            base_iri,
-            dataset,
-        } => {
-            let dataset = DatasetView::new(store, &dataset)?;
-            let (plan, variables) = PlanBuilder::build(&dataset, &pattern)?;
-            let construct = PlanBuilder::build_graph_template(&dataset, &template, variables)?;
-            SimpleEvaluator::new(
-                Rc::new(dataset),
-                base_iri.map(Rc::new),
-                options.service_handler,
-            )
-            .evaluate_construct_plan(&plan, construct)
+            variables.clone(),
+        )?)
+    } else {
+        None
+    };
+    let plan = (Arc::new(plan), Arc::new(variables), construct);
+    if let (Some(plans), Some(dictionary_id)) = (plans, dictionary_id) {
+        // The plans using ids only valid in the current dataset view could not be reused
+        if !dataset.has_temporary_strings() {
+            plans.set(dictionary_id, parameters, options, Arc::new(plan.clone()));
         }
-        Query::Describe {
-            pattern,
-            base_iri,
-            dataset,
-        } => {
-            let dataset = DatasetView::new(store, &dataset)?;
-            let (plan, _) = PlanBuilder::build(&dataset, &pattern)?;
-            SimpleEvaluator::new(
-                Rc::new(dataset),
-                base_iri.map(Rc::new),
-                options.service_handler,
-            )
-            .evaluate_describe_plan(&plan)
+    }
+    Ok((dataset, plan))
+}
+
+/// Evaluates a query plan built by [`build_query_plan`], collecting the given statistics if any
+fn evaluate_query_plan<R: ReadableEncodedStore + Send + Sync + 'static>(
+    dataset: DatasetView<R>,
+    (plan, variables, construct): QueryPlan<DatasetStrId<R::StrId>>,
+    query: &Query,
+    bindings: &[(Variable, Term)],
+    options: QueryOptions,
+    statistics: Option<Arc<PlanStatistics>>,
+) -> Result<QueryResults, EvaluationError> {
+    let base_iri = match query {
+        Query::Select { base_iri, .. }
+        | Query::Construct { base_iri, .. }
+        | Query::Describe { base_iri, .. }
+        | Query::Ask { base_iri, .. } => base_iri,
+    };
+    let interruption_checker = options.interruption_checker();
+    let mut evaluator = SimpleEvaluator::new(
+        Arc::new(dataset),
+        base_iri.clone().map(Arc::new),
+        options.service_handler,
+    )
+    .with_custom_functions(options.custom_functions)
+    .with_custom_aggregates(options.custom_aggregates)
+    .with_memory_limit(options.memory_limit)
+    .with_parallel_evaluation(options.max_threads)
+    .with_describe_strategy(options.describe_strategy)
+    .with_service_batch_size(options.service_batch_size)
+    .with_parameters(Arc::new(
+        bindings.iter().map(|(_, value)| value.clone()).collect(),
+    ));
+    if let Some(checker) = interruption_checker {
+        evaluator = evaluator.with_interruption_checker(checker);
+    }
+    if let Some(statistics) = statistics {
+        evaluator = evaluator.with_statistics(statistics);
+    }
+    match query {
+        Query::Select { .. } => evaluator.evaluate_select_plan(&plan, variables),
+        Query::Ask { .. } => evaluator.evaluate_ask_plan(&plan),
+        Query::Construct { .. } => {
+            evaluator.evaluate_construct_plan(&plan, construct.unwrap_or_default())
         }
+        Query::Describe { .. } => evaluator.evaluate_describe_plan(&plan),
     }
 }
 
@@ -112,7 +243,17 @@ pub(crate) fn evaluate_query<R: ReadableEncodedStore + 'static>(
 /// a simple HTTP 1.1 client is used to execute [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) SERVICE calls.
 #[derive(Clone)]
 pub struct QueryOptions {
-    pub(crate) service_handler: Rc<dyn ServiceHandler<Error = EvaluationError>>,
+    pub(crate) service_handler: Arc<dyn ServiceHandler<Error = EvaluationError>>,
+    timeout: Option<Duration>,
+    cancellation_token: Option<CancellationToken>,
+    /// The interruption checker of the query doing the SERVICE call, shared by the service query evaluation
+    parent_interruption_checker: Option<Arc<InterruptionChecker>>,
+    pub(crate) custom_functions: Arc<CustomFunctionRegistry>,
+    pub(crate) custom_aggregates: Arc<CustomAggregateRegistry>,
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) max_threads: usize,
+    pub(crate) describe_strategy: DescribeStrategy,
+    service_batch_size: usize,
 }
 
 impl Default for QueryOptions {
@@ -120,10 +261,19 @@ impl Default for QueryOptions {
     fn default() -> Self {
         Self {
             service_handler: if cfg!(feature = "http_client") {
-                Rc::new(service::SimpleServiceHandler::new())
+                Arc::new(service::SimpleServiceHandler::new())
             } else {
-                Rc::new(EmptyServiceHandler)
+                Arc::new(EmptyServiceHandler)
             },
+            timeout: None,
+            cancellation_token: None,
+            parent_interruption_checker: None,
+            custom_functions: Arc::default(),
+            custom_aggregates: Arc::default(),
+            memory_limit: None,
+            max_threads: 1,
+            describe_strategy: DescribeStrategy::default(),
+            service_batch_size: DEFAULT_SERVICE_BATCH_SIZE,
         }
     }
 }
@@ -132,16 +282,223 @@ impl QueryOptions {
     /// Use a given [`ServiceHandler`] to execute [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) SERVICE calls.
     #[inline]
     pub fn with_service_handler(mut self, service_handler: impl ServiceHandler + 'static) -> Self {
-        self.service_handler = Rc::new(ErrorConversionServiceHandler::wrap(service_handler));
+        self.service_handler = Arc::new(ErrorConversionServiceHandler::wrap(service_handler));
         self
     }
 
     /// Disables the `SERVICE` calls
     #[inline]
     pub fn without_service_handler(mut self) -> Self {
-        self.service_handler = Rc::new(EmptyServiceHandler);
+        self.service_handler = Arc::new(EmptyServiceHandler);
+        self
+    }
+
+    /// Sets the maximal number of solutions of the rest of the query sent together to a `SERVICE`.
+    ///
+    /// When a `SERVICE` is joined with other patterns, the solutions of these patterns are sent to the service
+    /// in a `VALUES` clause of at most this number of rows and the service answers are joined locally with them.
+    /// It avoids to send one request per solution. The default is 100.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::{EvaluationError, Query, QueryOptions, QueryResults, ServiceHandler};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// struct CountingServiceHandler {
+    ///     store: MemoryStore,
+    ///     calls: Arc<AtomicUsize>
+    /// }
+    ///
+    /// impl ServiceHandler for CountingServiceHandler {
+    ///     type Error = EvaluationError;
+    ///
+    ///     fn handle(&self, _: NamedNode, query: Query) -> Result<QueryResults, EvaluationError> {
+    ///         self.calls.fetch_add(1, Ordering::Relaxed);
+    ///         self.store.query(query)
+    ///     }
+    /// }
+    ///
+    /// let store = MemoryStore::new();
+    /// store.update("INSERT DATA { <http://example.com/a> <http://example.com/p> 1, 2, 3, 4, 5 }")?;
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let service = CountingServiceHandler { store: store.clone(), calls: calls.clone() };
+    ///
+    /// if let QueryResults::Solutions(solutions) = store.query_opt(
+    ///     "SELECT * WHERE { ?s <http://example.com/p> ?o SERVICE <http://example.com/service> { ?s ?p ?o } }",
+    ///     QueryOptions::default().with_service_handler(service).with_service_batch_size(2)
+    /// )? {
+    ///     assert_eq!(solutions.count(), 5);
+    /// }
+    /// assert_eq!(calls.load(Ordering::Relaxed), 3);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn with_service_batch_size(mut self, size: usize) -> Self {
+        self.service_batch_size = size;
+        self
+    }
+
+    /// Sets a maximal duration for the evaluation.
+    ///
+    /// The duration is counted from the start of the evaluation and includes the consumption of the results.
+    /// If it is exceeded, an [`EvaluationError::Timeout`] error is returned.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::sparql::{EvaluationError, QueryOptions, QueryResults};
+    /// use std::time::Duration;
+    ///
+    /// let store = MemoryStore::new();
+    /// let results = store.query_opt("SELECT * WHERE { ?s ?p ?o }", QueryOptions::default().with_timeout(Duration::from_secs(0)))?;
+    /// if let QueryResults::Solutions(mut solutions) = results {
+    ///     assert!(matches!(solutions.next(), Some(Err(EvaluationError::Timeout))));
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Allows to cancel the evaluation using the given [`CancellationToken`]
+    #[inline]
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Sets the approximate maximal number of bytes each `ORDER BY`, `GROUP BY` or `DISTINCT` operator keeps in memory.
+    ///
+    /// Above this limit, the intermediate results are written to temporary files in [`std::env::temp_dir`].
+    /// `ORDER BY` uses then an external merge sort and `GROUP BY` and `DISTINCT` partition the solutions by hash.
+    /// By default, there is no limit.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::sparql::{QueryOptions, QueryResults};
+    ///
+    /// let store = MemoryStore::new();
+    /// store.update("INSERT DATA { <http://example.com/a> <http://example.com/p> 3, 1, 2 }")?;
+    /// if let QueryResults::Solutions(solutions) = store.query_opt(
+    ///     "SELECT ?o WHERE { ?s ?p ?o } ORDER BY ?o",
+    ///     QueryOptions::default().with_memory_limit(1)
+    /// )? {
+    ///     let values = solutions.map(|s| Ok(s?.get("o").unwrap().to_string())).collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+    ///     assert_eq!(values, vec!["\"1\"^^<http://www.w3.org/2001/XMLSchema#integer>", "\"2\"^^<http://www.w3.org/2001/XMLSchema#integer>", "\"3\"^^<http://www.w3.org/2001/XMLSchema#integer>"]);
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// Evaluates the independent parts of the query in parallel threads, using at most `max_threads` threads at the same time, the calling thread included.
+    ///
+    /// The branches of `UNION` and one side of the joins are evaluated in new threads.
+    /// When `max_threads` threads are already evaluating the query, the operations are evaluated by the calling thread.
+    /// The default value, 1, disables the parallel evaluation.
+    /// The operations evaluated once per solution, like the right side of `OPTIONAL` or `EXISTS`, are always evaluated by the calling thread.
+    /// The threads stop as soon as their results are not read anymore.
+    /// The results are the same as with the sequential evaluation, in the same order.
+    /// It has no effect on WebAssembly.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::sparql::{QueryOptions, QueryResults};
+    ///
+    /// let store = MemoryStore::new();
+    /// store.update("INSERT DATA { <http://example.com/a> <http://example.com/p> 1 . <http://example.com/b> <http://example.com/q> 2 }")?;
+    /// if let QueryResults::Solutions(solutions) = store.query_opt(
+    ///     "SELECT ?o WHERE { { ?s <http://example.com/p> ?o } UNION { ?s <http://example.com/q> ?o } }",
+    ///     QueryOptions::default().with_parallel_evaluation(4)
+    /// )? {
+    ///     assert_eq!(solutions.count(), 2);
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn with_parallel_evaluation(mut self, max_threads: usize) -> Self {
+        self.max_threads = max_threads.max(1);
+        self
+    }
+
+    /// Sets the strategy used to build the description of the resources returned by `DESCRIBE` queries.
+    ///
+    /// By default, the triples whose subject is the described resource are returned.
+    /// See [`DescribeStrategy`] for a usage example.
+    #[inline]
+    pub fn with_describe_strategy(mut self, strategy: DescribeStrategy) -> Self {
+        self.describe_strategy = strategy;
+        self
+    }
+
+    /// Adds a custom SPARQL evaluation function.
+    ///
+    /// The function is called with the values of its arguments and returns the result of the call or `None` on error.
+    /// If one of the arguments is unbound or fails to evaluate, the function is not called and the evaluation error is propagated.
+    ///
+    /// Example with a function serializing terms to N-Triples:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::{QueryOptions, QueryResults};
+    ///
+    /// let store = MemoryStore::new();
+    ///
+    /// if let QueryResults::Solutions(mut solutions) = store.query_opt(
+    ///     "SELECT (<http://www.w3.org/ns/formats/N-Triples>(1) AS ?nt) WHERE {}",
+    ///     QueryOptions::default().with_custom_function(
+    ///         NamedNode::new("http://www.w3.org/ns/formats/N-Triples")?,
+    ///         |args| args.get(0).map(|t| Literal::from(t.to_string()).into())
+    ///     )
+    /// )? {
+    ///     assert_eq!(
+    ///         solutions.next().unwrap()?.get("nt"),
+    ///         Some(&Literal::from("\"1\"^^<http://www.w3.org/2001/XMLSchema#integer>").into())
+    ///     );
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn with_custom_function(
+        mut self,
+        name: NamedNode,
+        evaluator: impl Fn(&[Term]) -> Option<Term> + Send + Sync + 'static,
+    ) -> Self {
+        Arc::make_mut(&mut self.custom_functions).insert(name, Arc::new(evaluator));
         self
     }
+
+    /// Adds a custom SPARQL aggregate function.
+    ///
+    /// The factory is called to build a new [`CustomAccumulator`] for each group of solutions.
+    /// See [`CustomAccumulator`] for an example.
+    #[inline]
+    pub fn with_custom_aggregate(
+        mut self,
+        name: NamedNode,
+        factory: impl Fn() -> Box<dyn CustomAccumulator> + Send + Sync + 'static,
+    ) -> Self {
+        Arc::make_mut(&mut self.custom_aggregates).insert(name, Arc::new(factory));
+        self
+    }
+
+    /// Starts the timeout countdown and returns the checker of the interruptions if there is something to check
+    pub(crate) fn interruption_checker(&self) -> Option<Arc<InterruptionChecker>> {
+        if let Some(checker) = &self.parent_interruption_checker {
+            return Some(checker.clone());
+        }
+        InterruptionChecker::new(self.timeout, self.cancellation_token.clone())
+    }
 }
 
 /// Options for SPARQL update evaluation
@@ -162,6 +519,22 @@ impl UpdateOptions {
     pub fn query_options_mut(&mut self) -> &mut QueryOptions {
         &mut self.query_options
     }
+
+    /// Sets a maximal duration for the evaluation of the complete update.
+    ///
+    /// If it is exceeded, an [`EvaluationError::Timeout`] error is returned.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.query_options = self.query_options.with_timeout(timeout);
+        self
+    }
+
+    /// Allows to cancel the update evaluation using the given [`CancellationToken`]
+    #[inline]
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.query_options = self.query_options.with_cancellation_token(token);
+        self
+    }
 }
 
 impl Default for UpdateOptions {
@@ -181,7 +554,7 @@ impl From<QueryOptions> for UpdateOptions {
 }
 
 pub(crate) fn evaluate_update<
-    R: ReadableEncodedStore + Clone + 'static,
+    R: ReadableEncodedStore + Clone + Send + Sync + 'static,
     W: StrContainer<StrId = R::StrId> + WritableEncodedStore<StrId = R::StrId>,
 >(
     read: R,
@@ -192,6 +565,6 @@ pub(crate) fn evaluate_update<
 where
     io::Error: From<StoreOrParseError<W::Error>>,
 {
-    SimpleUpdateEvaluator::new(read, write, update.base_iri.map(Rc::new), options)
+    SimpleUpdateEvaluator::new(read, write, update.base_iri.map(Arc::new), options)
         .eval_all(&update.operations)
 }
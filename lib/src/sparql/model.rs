@@ -2,14 +2,17 @@ use crate::error::invalid_input_error;
 use crate::io::GraphFormat;
 use crate::io::GraphSerializer;
 use crate::model::*;
-use crate::sparql::csv_results::{read_tsv_results, write_csv_results, write_tsv_results};
+use crate::sparql::csv_results::{
+    read_csv_results, read_tsv_results, write_csv_results, write_tsv_results,
+};
 use crate::sparql::error::EvaluationError;
-use crate::sparql::json_results::write_json_results;
+use crate::sparql::json_results::{read_json_results, write_json_results};
 use crate::sparql::xml_results::{read_xml_results, write_xml_results};
 use rand::random;
+use std::collections::HashMap;
 use std::error::Error;
 use std::io::{BufRead, Write};
-use std::rc::Rc;
+use std::sync::Arc;
 use std::{fmt, io};
 
 /// Results of a [SPARQL query](https://www.w3.org/TR/sparql11-query/)
@@ -25,21 +28,46 @@ pub enum QueryResults {
 impl QueryResults {
     /// Reads a SPARQL query results serialization
     pub fn read(
-        reader: impl BufRead + 'static,
+        reader: impl BufRead + Send + 'static,
         format: QueryResultsFormat,
     ) -> Result<Self, io::Error> {
         match format {
             QueryResultsFormat::Xml => read_xml_results(reader),
-            QueryResultsFormat::Json => Err(invalid_input_error(
-                "JSON SPARQL results format parsing has not been implemented yet",
-            )), //TODO: implement
-            QueryResultsFormat::Csv => Err(invalid_input_error(
-                "CSV and TSV SPARQL results format parsing is not implemented",
-            )),
+            QueryResultsFormat::Json => read_json_results(reader),
+            QueryResultsFormat::Csv => read_csv_results(reader, HashMap::new()),
             QueryResultsFormat::Tsv => read_tsv_results(reader),
         }
     }
 
+    /// Reads a [SPARQL query results CSV](https://www.w3.org/TR/sparql11-results-csv-tsv/#csv) serialization
+    ///
+    /// The CSV format does not keep the kind of the RDF terms.
+    /// By default, values starting with `_:` are read as blank nodes, absolute IRIs as named nodes and everything else as simple literals.
+    /// The `hints` parameter allows to set the kind of the terms of some variables.
+    /// The CSV format has no boolean serialization so the first line is always read as the variables list.
+    ///
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::model::vocab::xsd;
+    /// use oxigraph::sparql::{CsvTermHint, QueryResults, Variable};
+    /// use std::io::Cursor;
+    ///
+    /// let csv = "s,count\r\nhttp://example.com,12\r\n";
+    /// let hints = vec![(Variable::new("count")?, CsvTermHint::TypedLiteral(xsd::INTEGER.into()))];
+    /// if let QueryResults::Solutions(mut solutions) = QueryResults::read_csv(Cursor::new(csv), hints)? {
+    ///     let solution = solutions.next().unwrap()?;
+    ///     assert_eq!(solution.get("s"), Some(&NamedNode::new("http://example.com")?.into()));
+    ///     assert_eq!(solution.get("count"), Some(&Literal::new_typed_literal("12", xsd::INTEGER).into()));
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn read_csv(
+        reader: impl BufRead + Send + 'static,
+        hints: impl IntoIterator<Item = (Variable, CsvTermHint)>,
+    ) -> Result<Self, io::Error> {
+        read_csv_results(reader, hints.into_iter().collect())
+    }
+
     /// Writes the query results (solutions or boolean)
     ///
     /// This method fails if it is called on the `Graph` results
@@ -214,6 +242,24 @@ impl QueryResultsFormat {
     }
 }
 
+/// The kind of RDF term a column of a [SPARQL query results CSV](https://www.w3.org/TR/sparql11-results-csv-tsv/#csv) serialization contains.
+///
+/// Used by [`QueryResults::read_csv`].
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+#[non_exhaustive]
+pub enum CsvTermHint {
+    /// The values are IRIs
+    NamedNode,
+    /// The values are blank node identifiers (with or without the `_:` prefix)
+    BlankNode,
+    /// The values are [simple literals](https://www.w3.org/TR/rdf11-concepts/#dfn-simple-literal)
+    SimpleLiteral,
+    /// The values are literals with the given [language tag](https://www.w3.org/TR/rdf11-concepts/#dfn-language-tag)
+    LanguageTaggedLiteral(String),
+    /// The values are literals with the given [datatype](https://www.w3.org/TR/rdf11-concepts/#dfn-datatype-iri)
+    TypedLiteral(NamedNode),
+}
+
 /// An iterator over [`QuerySolution`]s
 ///
 /// ```
@@ -229,14 +275,14 @@ impl QueryResultsFormat {
 /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
 /// ```
 pub struct QuerySolutionIter {
-    variables: Rc<Vec<Variable>>,
-    iter: Box<dyn Iterator<Item = Result<Vec<Option<Term>>, EvaluationError>>>,
+    variables: Arc<Vec<Variable>>,
+    iter: Box<dyn Iterator<Item = Result<Vec<Option<Term>>, EvaluationError>> + Send>,
 }
 
 impl QuerySolutionIter {
     pub fn new(
-        variables: Rc<Vec<Variable>>,
-        iter: Box<dyn Iterator<Item = Result<Vec<Option<Term>>, EvaluationError>>>,
+        variables: Arc<Vec<Variable>>,
+        iter: Box<dyn Iterator<Item = Result<Vec<Option<Term>>, EvaluationError>> + Send>,
     ) -> Self {
         Self { variables, iter }
     }
@@ -281,7 +327,7 @@ impl Iterator for QuerySolutionIter {
 /// It is the equivalent of a row in SQL.
 pub struct QuerySolution {
     values: Vec<Option<Term>>,
-    variables: Rc<Vec<Variable>>,
+    variables: Arc<Vec<Variable>>,
 }
 
 impl QuerySolution {
@@ -378,7 +424,7 @@ impl VariableSolutionIndex for Variable {
 /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
 /// ```
 pub struct QueryTripleIter {
-    pub(crate) iter: Box<dyn Iterator<Item = Result<Triple, EvaluationError>>>,
+    pub(crate) iter: Box<dyn Iterator<Item = Result<Triple, EvaluationError>> + Send>,
 }
 
 impl Iterator for QueryTripleIter {
@@ -9,13 +9,17 @@ use peg::str::LineCol;
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::rc::Rc;
 use std::str::Chars;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::{char, fmt};
 
 /// Parses a SPARQL query with an optional base IRI to resolve relative IRIs in the query
-pub fn parse_query(query: &str, base_iri: Option<&str>) -> Result<Query, ParseError> {
+pub fn parse_query(
+    query: &str,
+    base_iri: Option<&str>,
+    custom_aggregate_functions: HashSet<NamedNode>,
+) -> Result<Query, ParseError> {
     let mut state = ParserState {
         base_iri: if let Some(base_iri) = base_iri {
             Some(Iri::parse(base_iri.to_owned()).map_err(|e| ParseError {
@@ -28,6 +32,7 @@ pub fn parse_query(query: &str, base_iri: Option<&str>) -> Result<Query, ParseEr
         used_bnodes: HashSet::default(),
         currently_used_bnodes: HashSet::default(),
         aggregates: Vec::default(),
+        custom_aggregate_functions,
     };
 
     Ok(
@@ -40,7 +45,11 @@ pub fn parse_query(query: &str, base_iri: Option<&str>) -> Result<Query, ParseEr
 }
 
 /// Parses a SPARQL update with an optional base IRI to resolve relative IRIs in the query
-pub fn parse_update(update: &str, base_iri: Option<&str>) -> Result<Update, ParseError> {
+pub fn parse_update(
+    update: &str,
+    base_iri: Option<&str>,
+    custom_aggregate_functions: HashSet<NamedNode>,
+) -> Result<Update, ParseError> {
     let mut state = ParserState {
         base_iri: if let Some(base_iri) = base_iri {
             Some(Iri::parse(base_iri.to_owned()).map_err(|e| ParseError {
@@ -53,6 +62,7 @@ pub fn parse_update(update: &str, base_iri: Option<&str>) -> Result<Update, Pars
         used_bnodes: HashSet::default(),
         currently_used_bnodes: HashSet::default(),
         aggregates: Vec::default(),
+        custom_aggregate_functions,
     };
 
     let operations =
@@ -488,6 +498,7 @@ pub struct ParserState {
     used_bnodes: HashSet<BlankNode>,
     currently_used_bnodes: HashSet<BlankNode>,
     aggregates: Vec<Vec<(Variable, AggregationFunction)>>,
+    custom_aggregate_functions: HashSet<NamedNode>,
 }
 
 impl ParserState {
@@ -832,7 +843,7 @@ parser! {
         rule AskQuery() -> Query = i("ASK") _ d:DatasetClauses() w:WhereClause() _ g:GroupClause()? _ h:HavingClause()? _ o:OrderClause()? _ l:LimitOffsetClauses()? _ v:ValuesClause() {
             Query::Ask {
                 dataset: d,
-                pattern: Rc::new(build_select(Selection::default(), w, g, h, o, l, v, state)),
+                pattern: Arc::new(build_select(Selection::default(), w, g, h, o, l, v, state)),
                 base_iri: state.base_iri.clone()
             }
         }
@@ -1843,17 +1854,27 @@ parser! {
             i("GROUP_CONCAT") _ "(" _ i("DISTINCT") _ e:Expression() _ ";" _ i("SEPARATOR") _ "=" _ s:String() _ ")" { AggregationFunction::GroupConcat { expr: Box::new(e), distinct: true, separator: Some(s) } } /
             i("GROUP_CONCAT") _ "(" _ i("DISTINCT") _ e:Expression() _ ")" { AggregationFunction::GroupConcat { expr: Box::new(e), distinct: true, separator: None } } /
             i("GROUP_CONCAT") _ "(" _ e:Expression() _ ";" _ i("SEPARATOR") _ "=" _ s:String() _ ")" { AggregationFunction::GroupConcat { expr: Box::new(e), distinct: true, separator: Some(s) } } /
-            i("GROUP_CONCAT") _ "(" _ e:Expression() _ ")" { AggregationFunction::GroupConcat { expr: Box::new(e), distinct: false, separator: None } } /
-            name:iri() _ "(" _ i("DISTINCT") _ e:Expression() _ ")" { AggregationFunction::Custom { name, expr: Box::new(e), distinct: true } } /
-            name:iri() _ "(" _ e:Expression() _ ")" { AggregationFunction::Custom { name, expr: Box::new(e), distinct: false } }
+            i("GROUP_CONCAT") _ "(" _ e:Expression() _ ")" { AggregationFunction::GroupConcat { expr: Box::new(e), distinct: false, separator: None } }
+        rule CustomAggregate() -> AggregationFunction =
+            name:CustomAggregateFunction() _ "(" _ i("DISTINCT") _ e:Expression() _ ")" { AggregationFunction::Custom { name, expr: Box::new(e), distinct: true } } /
+            name:CustomAggregateFunction() _ "(" _ e:Expression() _ ")" { AggregationFunction::Custom { name, expr: Box::new(e), distinct: false } }
+        rule CustomAggregateFunction() -> NamedNode = name:iri() {?
+            if state.custom_aggregate_functions.contains(&name) {
+                Ok(name)
+            } else {
+                Err("custom aggregate function")
+            }
+        }
 
         //[128]
-        rule iriOrFunction() -> Expression = i: iri() _ a: ArgList()? {
-            match a {
-                Some(a) => Expression::FunctionCall(Function::Custom(i), a),
-                None => i.into()
+        rule iriOrFunction() -> Expression =
+            a:CustomAggregate() {? state.new_aggregation(a).map(|v| v.into()) } /
+            i: iri() _ a: ArgList()? {
+                match a {
+                    Some(a) => Expression::FunctionCall(Function::Custom(i), a),
+                    None => i.into()
+                }
             }
-        }
 
         //[129]
         rule RDFLiteral() -> Literal =
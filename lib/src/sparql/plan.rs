@@ -1,8 +1,14 @@
+use crate::error::invalid_data_error;
+use crate::model::NamedNode;
 use crate::sparql::algebra::GraphPattern;
 use crate::sparql::model::Variable;
+use crate::store::binary_encoder::{write_term, TermReader};
 use crate::store::numeric_encoder::{EncodedTerm, StrId};
 use std::collections::BTreeSet;
-use std::rc::Rc;
+use std::convert::TryFrom;
+use std::io;
+use std::io::Read;
+use std::sync::Arc;
 
 #[derive(Eq, PartialEq, Debug, Clone, Hash)]
 pub enum PlanNode<I: StrId> {
@@ -12,74 +18,101 @@ pub enum PlanNode<I: StrId> {
     },
     Service {
         service_name: PatternValue<I>,
-        variables: Rc<Vec<Variable>>,
-        child: Rc<PlanNode<I>>,
-        graph_pattern: Rc<GraphPattern>,
+        variables: Arc<Vec<Variable>>,
+        child: Arc<PlanNode<I>>,
+        graph_pattern: Arc<GraphPattern>,
         silent: bool,
     },
     QuadPatternJoin {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
         subject: PatternValue<I>,
         predicate: PatternValue<I>,
         object: PatternValue<I>,
         graph_name: PatternValue<I>,
+        /// The number of quads matching the pattern estimated by the store when the plan was built
+        cardinality: Option<u64>,
     },
     PathPatternJoin {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
         subject: PatternValue<I>,
-        path: Rc<PlanPropertyPath<I>>,
+        path: Arc<PlanPropertyPath<I>>,
         object: PatternValue<I>,
         graph_name: PatternValue<I>,
     },
+    /// Cartesian product where the right side is evaluated once and kept in memory
     Join {
-        left: Rc<PlanNode<I>>,
-        right: Rc<PlanNode<I>>,
+        left: Arc<PlanNode<I>>,
+        right: Arc<PlanNode<I>>,
+    },
+    /// Join where the left side is put in a hash table keyed on the given variables that are always bound by both sides
+    HashJoin {
+        left: Arc<PlanNode<I>>,
+        right: Arc<PlanNode<I>>,
+        keys: Arc<Vec<usize>>,
+    },
+    /// Join where the right side is a [`PlanNode::Service`] called once for each batch of left side results
+    ServiceJoin {
+        left: Arc<PlanNode<I>>,
+        right: Arc<PlanNode<I>>,
     },
     AntiJoin {
-        left: Rc<PlanNode<I>>,
-        right: Rc<PlanNode<I>>,
+        left: Arc<PlanNode<I>>,
+        right: Arc<PlanNode<I>>,
     },
     Filter {
-        child: Rc<PlanNode<I>>,
-        expression: Rc<PlanExpression<I>>,
+        child: Arc<PlanNode<I>>,
+        expression: Arc<PlanExpression<I>>,
     },
     Union {
-        children: Vec<Rc<PlanNode<I>>>,
+        children: Vec<Arc<PlanNode<I>>>,
     },
     LeftJoin {
-        left: Rc<PlanNode<I>>,
-        right: Rc<PlanNode<I>>,
-        possible_problem_vars: Rc<Vec<usize>>, //Variables that should not be part of the entry of the left join
+        left: Arc<PlanNode<I>>,
+        right: Arc<PlanNode<I>>,
+        possible_problem_vars: Arc<Vec<usize>>, //Variables that should not be part of the entry of the left join
+    },
+    /// Left join where the right side is evaluated independently and put in a hash table keyed on the given variables that are always bound by both sides
+    HashLeftJoin {
+        left: Arc<PlanNode<I>>,
+        right: Arc<PlanNode<I>>,
+        keys: Arc<Vec<usize>>,
     },
     Extend {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
         position: usize,
-        expression: Rc<PlanExpression<I>>,
+        expression: Arc<PlanExpression<I>>,
     },
     Sort {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
         by: Vec<Comparator<I>>,
     },
     HashDeduplicate {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
     },
     Skip {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
         count: usize,
     },
     Limit {
-        child: Rc<PlanNode<I>>,
+        child: Arc<PlanNode<I>>,
         count: usize,
     },
     Project {
-        child: Rc<PlanNode<I>>,
-        mapping: Rc<Vec<(usize, usize)>>, // pairs of (variable key in child, variable key in output)
+        child: Arc<PlanNode<I>>,
+        mapping: Arc<Vec<(usize, usize)>>, // pairs of (variable key in child, variable key in output)
+        variables: Arc<Vec<Variable>>,     // variables of the child, used for debugging
+    },
+    /// Binds the values of the prepared query parameters to the given variables before evaluating the child
+    Parameters {
+        child: Arc<PlanNode<I>>,
+        keys: Arc<Vec<usize>>,
     },
     Aggregate {
         // By definition the group by key are the range 0..key_mapping.len()
-        child: Rc<PlanNode<I>>,
-        key_mapping: Rc<Vec<(usize, usize)>>, // aggregate key pairs of (variable key in child, variable key in output)
-        aggregates: Rc<Vec<(PlanAggregation<I>, usize)>>,
+        child: Arc<PlanNode<I>>,
+        key_mapping: Arc<Vec<(usize, usize)>>, // aggregate key pairs of (variable key in child, variable key in output)
+        aggregates: Arc<Vec<(PlanAggregation<I>, usize)>>,
+        variables: Arc<Vec<Variable>>, // variables of the child, used for debugging
     },
 }
 
@@ -109,6 +142,7 @@ impl<I: StrId> PlanNode<I> {
                 predicate,
                 object,
                 graph_name,
+                ..
             } => {
                 if let PatternValue::Variable(var) = subject {
                     set.insert(*var);
@@ -152,8 +186,11 @@ impl<I: StrId> PlanNode<I> {
                 }
             }
             PlanNode::Join { left, right, .. }
+            | PlanNode::HashJoin { left, right, .. }
+            | PlanNode::ServiceJoin { left, right }
             | PlanNode::AntiJoin { left, right, .. }
-            | PlanNode::LeftJoin { left, right, .. } => {
+            | PlanNode::LeftJoin { left, right, .. }
+            | PlanNode::HashLeftJoin { left, right, .. } => {
                 left.add_maybe_bound_variables(set);
                 right.add_maybe_bound_variables(set);
             }
@@ -166,12 +203,16 @@ impl<I: StrId> PlanNode<I> {
                 expression.add_maybe_bound_variables(set);
                 child.add_maybe_bound_variables(set);
             }
+            PlanNode::Parameters { child, keys } => {
+                set.extend(keys.iter().copied());
+                child.add_maybe_bound_variables(set);
+            }
             PlanNode::Service { child, .. }
             | PlanNode::Sort { child, .. }
             | PlanNode::HashDeduplicate { child }
             | PlanNode::Skip { child, .. }
             | PlanNode::Limit { child, .. } => child.add_maybe_bound_variables(set),
-            PlanNode::Project { mapping, child } => {
+            PlanNode::Project { mapping, child, .. } => {
                 let child_bound = child.maybe_bound_variables();
                 for (child_i, output_i) in mapping.iter() {
                     if child_bound.contains(child_i) {
@@ -191,6 +232,109 @@ impl<I: StrId> PlanNode<I> {
             }
         }
     }
+
+    /// Returns variables that are bound in all the results
+    pub fn always_bound_variables(&self) -> BTreeSet<usize> {
+        match self {
+            PlanNode::Init | PlanNode::Service { .. } => BTreeSet::default(),
+            PlanNode::StaticBindings { tuples } => {
+                let mut tuples = tuples.iter();
+                let mut set = if let Some(first) = tuples.next() {
+                    (0..first.capacity())
+                        .filter(|key| first.contains(*key))
+                        .collect()
+                } else {
+                    BTreeSet::default()
+                };
+                for tuple in tuples {
+                    set.retain(|key| tuple.contains(*key));
+                }
+                set
+            }
+            PlanNode::QuadPatternJoin {
+                child,
+                subject,
+                predicate,
+                object,
+                graph_name,
+                ..
+            } => {
+                let mut set = child.always_bound_variables();
+                for value in &[subject, predicate, object, graph_name] {
+                    if let PatternValue::Variable(var) = value {
+                        set.insert(*var);
+                    }
+                }
+                set
+            }
+            PlanNode::PathPatternJoin {
+                child,
+                subject,
+                object,
+                graph_name,
+                ..
+            } => {
+                let mut set = child.always_bound_variables();
+                for value in &[subject, object, graph_name] {
+                    if let PatternValue::Variable(var) = value {
+                        set.insert(*var);
+                    }
+                }
+                set
+            }
+            PlanNode::Join { left, right } | PlanNode::HashJoin { left, right, .. } => {
+                let mut set = left.always_bound_variables();
+                set.extend(right.always_bound_variables());
+                set
+            }
+            PlanNode::ServiceJoin { left, .. }
+            | PlanNode::AntiJoin { left, .. }
+            | PlanNode::LeftJoin { left, .. }
+            | PlanNode::HashLeftJoin { left, .. } => left.always_bound_variables(),
+            PlanNode::Union { children } => {
+                let mut children = children.iter();
+                let mut set = if let Some(first) = children.next() {
+                    first.always_bound_variables()
+                } else {
+                    BTreeSet::default()
+                };
+                for child in children {
+                    let child_set = child.always_bound_variables();
+                    set.retain(|key| child_set.contains(key));
+                }
+                set
+            }
+            PlanNode::Filter { child, .. }
+            | PlanNode::Extend { child, .. }
+            | PlanNode::Sort { child, .. }
+            | PlanNode::HashDeduplicate { child }
+            | PlanNode::Skip { child, .. }
+            | PlanNode::Limit { child, .. } => child.always_bound_variables(),
+            PlanNode::Parameters { child, keys } => {
+                let mut set = child.always_bound_variables();
+                set.extend(keys.iter().copied());
+                set
+            }
+            PlanNode::Project { mapping, child, .. } => {
+                let child_bound = child.always_bound_variables();
+                mapping
+                    .iter()
+                    .filter(|(child_i, _)| child_bound.contains(child_i))
+                    .map(|(_, output_i)| *output_i)
+                    .collect()
+            }
+            PlanNode::Aggregate {
+                child, key_mapping, ..
+            } => {
+                let child_bound = child.always_bound_variables();
+                key_mapping
+                    .iter()
+                    .filter(|(child_i, _)| child_bound.contains(child_i))
+                    .map(|(_, output_i)| *output_i)
+                    .collect()
+            }
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
@@ -206,13 +350,20 @@ impl<I: StrId> PatternValue<I> {
             PatternValue::Variable(_) => true,
         }
     }
+
+    pub fn as_constant(&self) -> Option<EncodedTerm<I>> {
+        match self {
+            PatternValue::Constant(term) => Some(*term),
+            PatternValue::Variable(_) => None,
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Hash)]
 pub enum PlanExpression<I: StrId> {
     Constant(EncodedTerm<I>),
     Variable(usize),
-    Exists(Rc<PlanNode<I>>),
+    Exists(Arc<PlanNode<I>>),
     Or(Box<PlanExpression<I>>, Box<PlanExpression<I>>),
     And(Box<PlanExpression<I>>, Box<PlanExpression<I>>),
     Equal(Box<PlanExpression<I>>, Box<PlanExpression<I>>),
@@ -307,6 +458,7 @@ pub enum PlanExpression<I: StrId> {
     YearMonthDurationCast(Box<PlanExpression<I>>),
     DayTimeDurationCast(Box<PlanExpression<I>>),
     StringCast(Box<PlanExpression<I>>),
+    CustomFunction(NamedNode, Vec<PlanExpression<I>>),
 }
 
 impl<I: StrId> PlanExpression<I> {
@@ -406,7 +558,9 @@ impl<I: StrId> PlanExpression<I> {
                 d.add_maybe_bound_variables(set);
             }
 
-            PlanExpression::Concat(es) | PlanExpression::Coalesce(es) => {
+            PlanExpression::Concat(es)
+            | PlanExpression::Coalesce(es)
+            | PlanExpression::CustomFunction(_, es) => {
                 for e in es {
                     e.add_maybe_bound_variables(set);
                 }
@@ -439,19 +593,20 @@ pub enum PlanAggregationFunction {
     Max,
     Avg,
     Sample,
-    GroupConcat { separator: Rc<String> },
+    GroupConcat { separator: Arc<String> },
+    Custom(NamedNode),
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Hash)]
 pub enum PlanPropertyPath<I: StrId> {
     Path(EncodedTerm<I>),
-    Reverse(Rc<PlanPropertyPath<I>>),
-    Sequence(Rc<PlanPropertyPath<I>>, Rc<PlanPropertyPath<I>>),
-    Alternative(Rc<PlanPropertyPath<I>>, Rc<PlanPropertyPath<I>>),
-    ZeroOrMore(Rc<PlanPropertyPath<I>>),
-    OneOrMore(Rc<PlanPropertyPath<I>>),
-    ZeroOrOne(Rc<PlanPropertyPath<I>>),
-    NegatedPropertySet(Rc<Vec<EncodedTerm<I>>>),
+    Reverse(Arc<PlanPropertyPath<I>>),
+    Sequence(Arc<PlanPropertyPath<I>>, Arc<PlanPropertyPath<I>>),
+    Alternative(Arc<PlanPropertyPath<I>>, Arc<PlanPropertyPath<I>>),
+    ZeroOrMore(Arc<PlanPropertyPath<I>>),
+    OneOrMore(Arc<PlanPropertyPath<I>>),
+    ZeroOrOne(Arc<PlanPropertyPath<I>>),
+    NegatedPropertySet(Arc<Vec<EncodedTerm<I>>>),
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Hash)]
@@ -548,6 +703,44 @@ impl<I: StrId> EncodedTuple<I> {
             Some(EncodedTuple { inner: result })
         }
     }
+
+    /// Writes the tuple in a binary form readable by [`read`](EncodedTuple::read())
+    pub fn write(&self, sink: &mut Vec<u8>) {
+        sink.extend_from_slice(&(self.inner.capacity() as u64).to_be_bytes());
+        sink.extend_from_slice(&(self.inner.len() as u64).to_be_bytes());
+        for value in &self.inner {
+            match value {
+                None => sink.push(0),
+                Some(EncodedTerm::DefaultGraph) => sink.push(1),
+                Some(value) => {
+                    sink.push(2);
+                    write_term(sink, *value);
+                }
+            }
+        }
+    }
+
+    /// Reads a tuple written using [`write`](EncodedTuple::write())
+    pub fn read(source: &mut impl Read) -> io::Result<Self> {
+        let mut buffer = [0; 8];
+        source.read_exact(&mut buffer)?;
+        let mut inner = Vec::with_capacity(
+            usize::try_from(u64::from_be_bytes(buffer))
+                .map_err(|_| invalid_data_error("Invalid tuple capacity"))?,
+        );
+        source.read_exact(&mut buffer)?;
+        for _ in 0..u64::from_be_bytes(buffer) {
+            let mut kind = [0];
+            source.read_exact(&mut kind)?;
+            inner.push(match kind[0] {
+                0 => None,
+                1 => Some(EncodedTerm::DefaultGraph),
+                2 => Some(source.read_term()?),
+                _ => return Err(invalid_data_error("Invalid tuple value kind")),
+            })
+        }
+        Ok(Self { inner })
+    }
 }
 
 impl<I: StrId> IntoIterator for EncodedTuple<I> {
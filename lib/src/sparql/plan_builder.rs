@@ -1,27 +1,50 @@
 use crate::model::{BlankNode, Literal, NamedNode, Term};
 use crate::sparql::algebra::*;
 use crate::sparql::error::EvaluationError;
+use crate::sparql::eval::{CustomAggregateRegistry, CustomFunctionRegistry};
 use crate::sparql::model::*;
 use crate::sparql::plan::*;
-use crate::store::numeric_encoder::{EncodedTerm, WriteEncoder};
-use std::collections::{BTreeSet, HashSet};
-use std::rc::Rc;
+use crate::store::numeric_encoder::{EncodedTerm, StrId, WriteEncoder};
+use crate::store::ReadableEncodedStore;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Factor by which the cardinality of a triple pattern is assumed to be reduced for each variable bound by the previous patterns
+const BOUND_VARIABLE_SELECTIVITY: u64 = 1000;
 
 pub(crate) struct PlanBuilder<E: WriteEncoder> {
     encoder: E,
+    custom_functions: Arc<CustomFunctionRegistry>,
+    custom_aggregates: Arc<CustomAggregateRegistry>,
 }
 
-impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
+impl<E: WriteEncoder<Error = EvaluationError> + ReadableEncodedStore> PlanBuilder<E> {
+    /// Builds the plan of a query pattern
+    ///
+    /// The values of the `parameters` variables are bound at the beginning of the WHERE clause
+    /// by a [`PlanNode::Parameters`] node.
     pub fn build(
         encoder: E,
         pattern: &GraphPattern,
+        parameters: &[Variable],
+        custom_functions: Arc<CustomFunctionRegistry>,
+        custom_aggregates: Arc<CustomAggregateRegistry>,
     ) -> Result<(PlanNode<E::StrId>, Vec<Variable>), EvaluationError> {
         let mut variables = Vec::default();
-        let plan = PlanBuilder { encoder }.build_for_graph_pattern(
+        let mut plan = PlanBuilder {
+            encoder,
+            custom_functions,
+            custom_aggregates,
+        }
+        .build_for_graph_pattern(
             pattern,
             &mut variables,
             PatternValue::Constant(EncodedTerm::DefaultGraph),
         )?;
+        if !parameters.is_empty() {
+            plan = add_parameters(plan, parameters, &mut variables);
+        }
         Ok((plan, variables))
     }
 
@@ -30,7 +53,12 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
         template: &[TriplePattern],
         mut variables: Vec<Variable>,
     ) -> Result<Vec<TripleTemplate<E::StrId>>, EvaluationError> {
-        PlanBuilder { encoder }.build_for_graph_template(template, &mut variables)
+        PlanBuilder {
+            encoder,
+            custom_functions: Arc::default(),
+            custom_aggregates: Arc::default(),
+        }
+        .build_for_graph_template(template, &mut variables)
     }
 
     fn build_for_graph_pattern(
@@ -46,9 +74,9 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 path,
                 object,
             } => PlanNode::PathPatternJoin {
-                child: Rc::new(PlanNode::Init),
+                child: Arc::new(PlanNode::Init),
                 subject: self.pattern_value_from_term_or_variable(subject, variables)?,
-                path: Rc::new(self.build_for_path(path)?),
+                path: Arc::new(self.build_for_path(path)?),
                 object: self.pattern_value_from_term_or_variable(object, variables)?,
                 graph_name,
             },
@@ -62,17 +90,30 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 {
                     let left = self.build_for_graph_pattern(left, variables, graph_name)?;
                     PlanNode::PathPatternJoin {
-                        child: Rc::new(left),
+                        child: Arc::new(left),
                         subject: self.pattern_value_from_term_or_variable(subject, variables)?,
-                        path: Rc::new(self.build_for_path(path)?),
+                        path: Arc::new(self.build_for_path(path)?),
                         object: self.pattern_value_from_term_or_variable(object, variables)?,
                         graph_name,
                     }
                 } else {
-                    PlanNode::Join {
-                        left: Rc::new(self.build_for_graph_pattern(left, variables, graph_name)?),
-                        right: Rc::new(self.build_for_graph_pattern(right, variables, graph_name)?),
+                    //We flatten the joins in order to reorder them
+                    let mut stack: Vec<&GraphPattern> = vec![right, left];
+                    let mut children = vec![];
+                    while let Some(pattern) = stack.pop() {
+                        match pattern {
+                            GraphPattern::Join { left, right }
+                                if !matches!(right.as_ref(), GraphPattern::Path { .. }) =>
+                            {
+                                stack.push(right);
+                                stack.push(left);
+                            }
+                            pattern => children.push(
+                                self.build_for_graph_pattern(pattern, variables, graph_name)?,
+                            ),
+                        }
                     }
+                    self.build_join(children)
                 }
             }
             GraphPattern::LeftJoin { left, right, expr } => {
@@ -85,8 +126,8 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 //We add the extra filter if needed
                 let right = if let Some(expr) = expr {
                     PlanNode::Filter {
-                        child: Rc::new(right),
-                        expression: Rc::new(
+                        child: Arc::new(right),
+                        expression: Arc::new(
                             self.build_for_expression(expr, variables, graph_name)?,
                         ),
                     }
@@ -94,15 +135,26 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                     right
                 };
 
-                PlanNode::LeftJoin {
-                    left: Rc::new(left),
-                    right: Rc::new(right),
-                    possible_problem_vars: Rc::new(possible_problem_vars.into_iter().collect()),
+                if self.use_hash_left_join(&left, &right) {
+                    let keys = shared_always_bound_variables(&left, &right);
+                    PlanNode::HashLeftJoin {
+                        left: Arc::new(left),
+                        right: Arc::new(right),
+                        keys: Arc::new(keys),
+                    }
+                } else {
+                    PlanNode::LeftJoin {
+                        left: Arc::new(left),
+                        right: Arc::new(right),
+                        possible_problem_vars: Arc::new(
+                            possible_problem_vars.into_iter().collect(),
+                        ),
+                    }
                 }
             }
             GraphPattern::Filter { expr, inner } => PlanNode::Filter {
-                child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
-                expression: Rc::new(self.build_for_expression(expr, variables, graph_name)?),
+                child: Arc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
+                expression: Arc::new(self.build_for_expression(expr, variables, graph_name)?),
             },
             GraphPattern::Union { left, right } => {
                 //We flatten the UNIONs
@@ -115,7 +167,7 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                             stack.push(left);
                             stack.push(right);
                         }
-                        Some(p) => children.push(Rc::new(
+                        Some(p) => children.push(Arc::new(
                             self.build_for_graph_pattern(p, variables, graph_name)?,
                         )),
                     }
@@ -128,13 +180,13 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 self.build_for_graph_pattern(inner, variables, graph_name)?
             }
             GraphPattern::Extend { inner, var, expr } => PlanNode::Extend {
-                child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
+                child: Arc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
                 position: variable_key(variables, var),
-                expression: Rc::new(self.build_for_expression(expr, variables, graph_name)?),
+                expression: Arc::new(self.build_for_expression(expr, variables, graph_name)?),
             },
             GraphPattern::Minus { left, right } => PlanNode::AntiJoin {
-                left: Rc::new(self.build_for_graph_pattern(left, variables, graph_name)?),
-                right: Rc::new(self.build_for_graph_pattern(right, variables, graph_name)?),
+                left: Arc::new(self.build_for_graph_pattern(left, variables, graph_name)?),
+                right: Arc::new(self.build_for_graph_pattern(right, variables, graph_name)?),
             },
             GraphPattern::Service {
                 name,
@@ -147,9 +199,9 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                     self.pattern_value_from_named_node_or_variable(name, variables)?;
                 PlanNode::Service {
                     service_name,
-                    variables: Rc::new(variables.clone()),
-                    child: Rc::new(child),
-                    graph_pattern: Rc::new(*pattern.clone()),
+                    variables: Arc::new(variables.clone()),
+                    child: Arc::new(child),
+                    graph_pattern: Arc::new(*pattern.clone()),
                     silent: *silent,
                 }
             }
@@ -162,33 +214,31 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 let inner_graph_name =
                     self.convert_pattern_value_id(graph_name, variables, &mut inner_variables);
 
+                let child =
+                    self.build_for_graph_pattern(inner, &mut inner_variables, inner_graph_name)?;
+                let key_mapping = by
+                    .iter()
+                    .map(|k| {
+                        (
+                            variable_key(&mut inner_variables, k),
+                            variable_key(variables, k),
+                        )
+                    })
+                    .collect();
+                let aggregates = aggregates
+                    .iter()
+                    .map(|(v, a)| {
+                        Ok((
+                            self.build_for_aggregate(a, &mut inner_variables, graph_name)?,
+                            variable_key(variables, v),
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, EvaluationError>>()?;
                 PlanNode::Aggregate {
-                    child: Rc::new(self.build_for_graph_pattern(
-                        inner,
-                        &mut inner_variables,
-                        inner_graph_name,
-                    )?),
-                    key_mapping: Rc::new(
-                        by.iter()
-                            .map(|k| {
-                                (
-                                    variable_key(&mut inner_variables, k),
-                                    variable_key(variables, k),
-                                )
-                            })
-                            .collect(),
-                    ),
-                    aggregates: Rc::new(
-                        aggregates
-                            .iter()
-                            .map(|(v, a)| {
-                                Ok((
-                                    self.build_for_aggregate(a, &mut inner_variables, graph_name)?,
-                                    variable_key(variables, v),
-                                ))
-                            })
-                            .collect::<Result<Vec<_>, EvaluationError>>()?,
-                    ),
+                    child: Arc::new(child),
+                    key_mapping: Arc::new(key_mapping),
+                    aggregates: Arc::new(aggregates),
+                    variables: Arc::new(inner_variables),
                 }
             }
             GraphPattern::Table {
@@ -210,7 +260,7 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                     })
                     .collect();
                 PlanNode::Sort {
-                    child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
+                    child: Arc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
                     by: condition?,
                 }
             }
@@ -218,13 +268,12 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 let mut inner_variables = projection.clone();
                 let inner_graph_name =
                     self.convert_pattern_value_id(graph_name, variables, &mut inner_variables);
+                let child =
+                    self.build_for_graph_pattern(inner, &mut inner_variables, inner_graph_name)?;
                 PlanNode::Project {
-                    child: Rc::new(self.build_for_graph_pattern(
-                        inner,
-                        &mut inner_variables,
-                        inner_graph_name,
-                    )?),
-                    mapping: Rc::new(
+                    child: Arc::new(child),
+                    variables: Arc::new(inner_variables),
+                    mapping: Arc::new(
                         projection
                             .iter()
                             .enumerate()
@@ -236,7 +285,7 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 }
             }
             GraphPattern::Distinct { inner } => PlanNode::HashDeduplicate {
-                child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
+                child: Arc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
             },
             GraphPattern::Reduced { inner } => {
                 self.build_for_graph_pattern(inner, variables, graph_name)?
@@ -249,13 +298,13 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 let mut plan = self.build_for_graph_pattern(inner, variables, graph_name)?;
                 if *start > 0 {
                     plan = PlanNode::Skip {
-                        child: Rc::new(plan),
+                        child: Arc::new(plan),
                         count: *start,
                     };
                 }
                 if let Some(length) = length {
                     plan = PlanNode::Limit {
-                        child: Rc::new(plan),
+                        child: Arc::new(plan),
                         count: *length,
                     };
                 }
@@ -270,20 +319,177 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
         variables: &mut Vec<Variable>,
         graph_name: PatternValue<E::StrId>,
     ) -> Result<PlanNode<E::StrId>, EvaluationError> {
+        let patterns = p
+            .iter()
+            .map(|pattern| {
+                Ok((
+                    self.pattern_value_from_term_or_variable(&pattern.subject, variables)?,
+                    self.pattern_value_from_named_node_or_variable(&pattern.predicate, variables)?,
+                    self.pattern_value_from_term_or_variable(&pattern.object, variables)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, EvaluationError>>()?;
         let mut plan = PlanNode::Init;
-        for pattern in sort_bgp(p) {
+        for ((subject, predicate, object), cardinality) in self.sort_bgp(patterns, graph_name) {
             plan = PlanNode::QuadPatternJoin {
-                child: Rc::new(plan),
-                subject: self.pattern_value_from_term_or_variable(&pattern.subject, variables)?,
-                predicate: self
-                    .pattern_value_from_named_node_or_variable(&pattern.predicate, variables)?,
-                object: self.pattern_value_from_term_or_variable(&pattern.object, variables)?,
+                child: Arc::new(plan),
+                subject,
+                predicate,
+                object,
                 graph_name,
+                cardinality,
             }
         }
         Ok(plan)
     }
 
+    /// Orders the triple patterns of a basic graph pattern
+    ///
+    /// If the store provides cardinality estimates, the patterns with the smallest estimated number of results
+    /// given the already bound variables are evaluated first, while avoiding cartesian products.
+    /// If not, the patterns with the largest number of bound values are evaluated first.
+    ///
+    /// The patterns are returned with their estimated cardinalities.
+    fn sort_bgp(
+        &self,
+        mut patterns: Vec<TriplePatternValues<E::StrId>>,
+        graph_name: PatternValue<E::StrId>,
+    ) -> Vec<(TriplePatternValues<E::StrId>, Option<u64>)> {
+        let cardinalities = patterns
+            .iter()
+            .map(|(subject, predicate, object)| {
+                self.encoder.encoded_quads_for_pattern_cardinality(
+                    subject.as_constant(),
+                    predicate.as_constant(),
+                    object.as_constant(),
+                    graph_name.as_constant(),
+                )
+            })
+            .collect::<Option<Vec<_>>>();
+        let mut cardinalities = if let Some(cardinalities) = cardinalities {
+            cardinalities.into_iter().map(Some).collect()
+        } else {
+            vec![None; patterns.len()]
+        };
+
+        let mut assigned_variables = BTreeSet::default();
+        let mut sorted = Vec::with_capacity(patterns.len());
+        while !patterns.is_empty() {
+            let (best, _) = patterns
+                .iter()
+                .zip(&cardinalities)
+                .enumerate()
+                .min_by_key(|(_, (pattern, cardinality))| {
+                    let binds = count_pattern_binds(pattern, &assigned_variables);
+                    if let Some(cardinality) = cardinality {
+                        let bound_variables = count_bound_variables(pattern, &assigned_variables);
+                        (
+                            !assigned_variables.is_empty() && bound_variables == 0,
+                            cardinality
+                                / BOUND_VARIABLE_SELECTIVITY.saturating_pow(bound_variables),
+                            Reverse(binds),
+                        )
+                    } else {
+                        (false, 0, Reverse(binds))
+                    }
+                })
+                .unwrap();
+            let pattern = patterns.remove(best);
+            let cardinality = cardinalities.remove(best);
+            add_pattern_variables(&pattern, &mut assigned_variables);
+            sorted.push((pattern, cardinality));
+        }
+        sorted
+    }
+
+    /// Builds a join of all the given children
+    ///
+    /// The children with the smallest estimated cardinalities are joined first, while avoiding cartesian products.
+    fn build_join(&self, children: Vec<PlanNode<E::StrId>>) -> PlanNode<E::StrId> {
+        let mut children = children
+            .into_iter()
+            .map(|child| {
+                (
+                    estimate_cardinality(&child),
+                    child.maybe_bound_variables(),
+                    child,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut plan: Option<PlanNode<E::StrId>> = None;
+        let mut bound_variables = BTreeSet::default();
+        while !children.is_empty() {
+            let (best, _) = children
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (cardinality, variables, _))| {
+                    (
+                        plan.is_some() && bound_variables.is_disjoint(variables),
+                        cardinality.unwrap_or(u64::MAX),
+                    )
+                })
+                .unwrap();
+            let (_, variables, child) = children.remove(best);
+            bound_variables.extend(variables);
+            plan = Some(if let Some(plan) = plan {
+                let keys = shared_always_bound_variables(&plan, &child);
+                if let PlanNode::Service { .. } = child {
+                    // The service is called with batches of the left side bindings
+                    PlanNode::ServiceJoin {
+                        left: Arc::new(plan),
+                        right: Arc::new(child),
+                    }
+                } else {
+                    // The smaller side by estimated cardinality is the one kept in memory
+                    let (smaller, larger) = if estimate_cardinality(&child).unwrap_or(u64::MAX)
+                        < estimate_cardinality(&plan).unwrap_or(u64::MAX)
+                    {
+                        (child, plan)
+                    } else {
+                        (plan, child)
+                    };
+                    if keys.is_empty() {
+                        PlanNode::Join {
+                            left: Arc::new(larger),
+                            right: Arc::new(smaller),
+                        }
+                    } else {
+                        PlanNode::HashJoin {
+                            left: Arc::new(smaller),
+                            right: Arc::new(larger),
+                            keys: Arc::new(keys),
+                        }
+                    }
+                }
+            } else {
+                child
+            });
+        }
+        plan.unwrap_or(PlanNode::Init)
+    }
+
+    /// Decides if a left join should be evaluated by putting the right side in a hash table
+    ///
+    /// It requires the right side to be evaluable without the bindings of the left side:
+    /// it should share always bound variables with the left side and its filters should only use variables it always binds.
+    /// The hash left join is then used if the right side is estimated to be smaller than the left side
+    /// or if the right side is not a simple pattern that could be efficiently evaluated for each left tuple.
+    fn use_hash_left_join(&self, left: &PlanNode<E::StrId>, right: &PlanNode<E::StrId>) -> bool {
+        if shared_always_bound_variables(left, right).is_empty() {
+            return false;
+        }
+        let right_always_bound = right.always_bound_variables();
+        let mut filter_variables = BTreeSet::default();
+        add_filter_variables(right, &mut filter_variables);
+        if !filter_variables.is_subset(&right_always_bound) {
+            return false;
+        }
+        match (estimate_cardinality(left), estimate_cardinality(right)) {
+            (Some(left), Some(right)) => right <= left,
+            _ => !is_pattern_chain(right),
+        }
+    }
+
     fn build_for_path(
         &mut self,
         path: &PropertyPathExpression,
@@ -293,27 +499,27 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 PlanPropertyPath::Path(self.build_named_node(p)?)
             }
             PropertyPathExpression::Reverse(p) => {
-                PlanPropertyPath::Reverse(Rc::new(self.build_for_path(p)?))
+                PlanPropertyPath::Reverse(Arc::new(self.build_for_path(p)?))
             }
             PropertyPathExpression::Alternative(a, b) => PlanPropertyPath::Alternative(
-                Rc::new(self.build_for_path(a)?),
-                Rc::new(self.build_for_path(b)?),
+                Arc::new(self.build_for_path(a)?),
+                Arc::new(self.build_for_path(b)?),
             ),
             PropertyPathExpression::Sequence(a, b) => PlanPropertyPath::Sequence(
-                Rc::new(self.build_for_path(a)?),
-                Rc::new(self.build_for_path(b)?),
+                Arc::new(self.build_for_path(a)?),
+                Arc::new(self.build_for_path(b)?),
             ),
             PropertyPathExpression::ZeroOrMore(p) => {
-                PlanPropertyPath::ZeroOrMore(Rc::new(self.build_for_path(p)?))
+                PlanPropertyPath::ZeroOrMore(Arc::new(self.build_for_path(p)?))
             }
             PropertyPathExpression::OneOrMore(p) => {
-                PlanPropertyPath::OneOrMore(Rc::new(self.build_for_path(p)?))
+                PlanPropertyPath::OneOrMore(Arc::new(self.build_for_path(p)?))
             }
             PropertyPathExpression::ZeroOrOne(p) => {
-                PlanPropertyPath::ZeroOrOne(Rc::new(self.build_for_path(p)?))
+                PlanPropertyPath::ZeroOrOne(Arc::new(self.build_for_path(p)?))
             }
             PropertyPathExpression::NegatedPropertySet(p) => {
-                PlanPropertyPath::NegatedPropertySet(Rc::new(
+                PlanPropertyPath::NegatedPropertySet(Arc::new(
                     p.iter()
                         .map(|p| self.build_named_node(p))
                         .collect::<Result<Vec<_>, _>>()?,
@@ -606,7 +812,15 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                     },
                 ),
                 Function::Custom(name) => {
-                    if name == "http://www.w3.org/2001/XMLSchema#boolean" {
+                    if self.custom_functions.contains_key(name) {
+                        PlanExpression::CustomFunction(
+                            name.clone(),
+                            parameters
+                                .iter()
+                                .map(|p| self.build_for_expression(p, variables, graph_name))
+                                .collect::<Result<Vec<_>, EvaluationError>>()?,
+                        )
+                    } else if name == "http://www.w3.org/2001/XMLSchema#boolean" {
                         self.build_cast(
                             parameters,
                             PlanExpression::BooleanCast,
@@ -716,7 +930,7 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 Box::new(self.build_for_expression(b, variables, graph_name)?),
                 Box::new(self.build_for_expression(c, variables, graph_name)?),
             ),
-            Expression::Exists(n) => PlanExpression::Exists(Rc::new(
+            Expression::Exists(n) => PlanExpression::Exists(Arc::new(
                 self.build_for_graph_pattern(n, variables, graph_name)?,
             )),
             Expression::Coalesce(parameters) => {
@@ -862,14 +1076,29 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                 separator,
             } => Ok(PlanAggregation {
                 function: PlanAggregationFunction::GroupConcat {
-                    separator: Rc::new(separator.clone().unwrap_or_else(|| " ".to_string())),
+                    separator: Arc::new(separator.clone().unwrap_or_else(|| " ".to_string())),
                 },
                 parameter: Some(self.build_for_expression(expr, variables, graph_name)?),
                 distinct: *distinct,
             }),
-            AggregationFunction::Custom { .. } => Err(EvaluationError::msg(
-                "Custom aggregation functions are not supported yet",
-            )),
+            AggregationFunction::Custom {
+                name,
+                expr,
+                distinct,
+            } => {
+                if self.custom_aggregates.contains_key(name) {
+                    Ok(PlanAggregation {
+                        function: PlanAggregationFunction::Custom(name.clone()),
+                        parameter: Some(self.build_for_expression(expr, variables, graph_name)?),
+                        distinct: *distinct,
+                    })
+                } else {
+                    Err(EvaluationError::msg(format!(
+                        "Not supported custom aggregation function {}",
+                        name
+                    )))
+                }
+            }
         }
     }
 
@@ -985,14 +1214,16 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
                     self.add_left_join_problematic_variables(child, set);
                 }
             }
-            PlanNode::Join { left, right, .. } => {
+            PlanNode::Join { left, right, .. }
+            | PlanNode::HashJoin { left, right, .. }
+            | PlanNode::ServiceJoin { left, right } => {
                 self.add_left_join_problematic_variables(&*left, set);
                 self.add_left_join_problematic_variables(&*right, set);
             }
             PlanNode::AntiJoin { left, .. } => {
                 self.add_left_join_problematic_variables(&*left, set);
             }
-            PlanNode::LeftJoin { left, right, .. } => {
+            PlanNode::LeftJoin { left, right, .. } | PlanNode::HashLeftJoin { left, right, .. } => {
                 self.add_left_join_problematic_variables(&*left, set);
                 right.add_maybe_bound_variables(set);
             }
@@ -1007,10 +1238,11 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
             | PlanNode::Sort { child, .. }
             | PlanNode::HashDeduplicate { child }
             | PlanNode::Skip { child, .. }
-            | PlanNode::Limit { child, .. } => {
+            | PlanNode::Limit { child, .. }
+            | PlanNode::Parameters { child, .. } => {
                 self.add_left_join_problematic_variables(&*child, set)
             }
-            PlanNode::Project { mapping, child } => {
+            PlanNode::Project { mapping, child, .. } => {
                 let mut child_bound = BTreeSet::new();
                 self.add_left_join_problematic_variables(&*child, &mut child_bound);
                 for (child_i, output_i) in mapping.iter() {
@@ -1052,6 +1284,89 @@ impl<E: WriteEncoder<Error = EvaluationError>> PlanBuilder<E> {
     }
 }
 
+/// Returns the WHERE clause of a query, i.e. the pattern below the solution modifiers, the aggregations and the filters
+/// Wraps the WHERE clause part of the plan, below the solution modifiers, in a [`PlanNode::Parameters`] node binding the given variables
+fn add_parameters<I: StrId>(
+    node: PlanNode<I>,
+    parameters: &[Variable],
+    variables: &mut Vec<Variable>,
+) -> PlanNode<I> {
+    fn add_to_child<I: StrId>(
+        child: Arc<PlanNode<I>>,
+        parameters: &[Variable],
+        variables: &mut Vec<Variable>,
+    ) -> Arc<PlanNode<I>> {
+        let child = Arc::try_unwrap(child).unwrap_or_else(|child| child.as_ref().clone());
+        Arc::new(add_parameters(child, parameters, variables))
+    }
+
+    match node {
+        PlanNode::Project {
+            child,
+            variables: inner_variables,
+            mapping,
+        } => {
+            let mut inner_variables = inner_variables.as_ref().clone();
+            PlanNode::Project {
+                child: add_to_child(child, parameters, &mut inner_variables),
+                variables: Arc::new(inner_variables),
+                mapping,
+            }
+        }
+        PlanNode::Aggregate {
+            child,
+            key_mapping,
+            aggregates,
+            variables: inner_variables,
+        } => {
+            let mut inner_variables = inner_variables.as_ref().clone();
+            PlanNode::Aggregate {
+                child: add_to_child(child, parameters, &mut inner_variables),
+                key_mapping,
+                aggregates,
+                variables: Arc::new(inner_variables),
+            }
+        }
+        PlanNode::HashDeduplicate { child } => PlanNode::HashDeduplicate {
+            child: add_to_child(child, parameters, variables),
+        },
+        PlanNode::Skip { child, count } => PlanNode::Skip {
+            child: add_to_child(child, parameters, variables),
+            count,
+        },
+        PlanNode::Limit { child, count } => PlanNode::Limit {
+            child: add_to_child(child, parameters, variables),
+            count,
+        },
+        PlanNode::Sort { child, by } => PlanNode::Sort {
+            child: add_to_child(child, parameters, variables),
+            by,
+        },
+        PlanNode::Filter { child, expression } => PlanNode::Filter {
+            child: add_to_child(child, parameters, variables),
+            expression,
+        },
+        PlanNode::Extend {
+            child,
+            position,
+            expression,
+        } => PlanNode::Extend {
+            child: add_to_child(child, parameters, variables),
+            position,
+            expression,
+        },
+        node => PlanNode::Parameters {
+            child: Arc::new(node),
+            keys: Arc::new(
+                parameters
+                    .iter()
+                    .map(|variable| variable_key(variables, variable))
+                    .collect(),
+            ),
+        },
+    }
+}
+
 fn variable_key(variables: &mut Vec<Variable>, variable: &Variable) -> usize {
     match slice_key(variables, variable) {
         Some(key) => key,
@@ -1081,77 +1396,442 @@ fn slice_key<T: Eq>(slice: &[T], element: &T) -> Option<usize> {
     None
 }
 
-fn sort_bgp(p: &[TriplePattern]) -> Vec<&TriplePattern> {
-    let mut assigned_variables = HashSet::default();
-    let mut assigned_blank_nodes = HashSet::default();
-    let mut new_p: Vec<_> = p.iter().collect();
-
-    for i in 0..new_p.len() {
-        (&mut new_p[i..]).sort_by(|p1, p2| {
-            count_pattern_binds(p2, &assigned_variables, &assigned_blank_nodes).cmp(
-                &count_pattern_binds(p1, &assigned_variables, &assigned_blank_nodes),
+/// Estimates the number of results of a plan using the pattern cardinalities estimated when it was built
+fn estimate_cardinality<I: StrId>(node: &PlanNode<I>) -> Option<u64> {
+    match node {
+        PlanNode::Init => Some(1),
+        PlanNode::StaticBindings { tuples } => Some(tuples.len() as u64),
+        PlanNode::Service { .. } => None,
+        PlanNode::QuadPatternJoin {
+            child, cardinality, ..
+        } => {
+            let cardinality = (*cardinality)?;
+            if let PlanNode::Init = child.as_ref() {
+                Some(cardinality)
+            } else {
+                Some(estimate_cardinality(child)?.min(cardinality))
+            }
+        }
+        PlanNode::PathPatternJoin { child, .. } => {
+            if let PlanNode::Init = child.as_ref() {
+                None
+            } else {
+                estimate_cardinality(child)
+            }
+        }
+        PlanNode::Join { left, right }
+        | PlanNode::HashJoin { left, right, .. }
+        | PlanNode::ServiceJoin { left, right } => {
+            let left_cardinality = estimate_cardinality(left)?;
+            let right_cardinality = estimate_cardinality(right)?;
+            Some(
+                if left
+                    .maybe_bound_variables()
+                    .is_disjoint(&right.maybe_bound_variables())
+                {
+                    left_cardinality.saturating_mul(right_cardinality)
+                } else {
+                    left_cardinality.min(right_cardinality)
+                },
             )
-        });
-        add_pattern_variables(new_p[i], &mut assigned_variables, &mut assigned_blank_nodes);
+        }
+        PlanNode::AntiJoin { left, .. }
+        | PlanNode::LeftJoin { left, .. }
+        | PlanNode::HashLeftJoin { left, .. } => estimate_cardinality(left),
+        PlanNode::Filter { child, .. }
+        | PlanNode::Extend { child, .. }
+        | PlanNode::Sort { child, .. }
+        | PlanNode::HashDeduplicate { child }
+        | PlanNode::Skip { child, .. }
+        | PlanNode::Parameters { child, .. }
+        | PlanNode::Project { child, .. } => estimate_cardinality(child),
+        PlanNode::Limit { child, count } => Some(
+            estimate_cardinality(child)
+                .unwrap_or(u64::MAX)
+                .min(*count as u64),
+        ),
+        PlanNode::Union { children } => children.iter().try_fold(0, |sum: u64, child| {
+            Some(sum.saturating_add(estimate_cardinality(child)?))
+        }),
+        PlanNode::Aggregate {
+            child, key_mapping, ..
+        } => {
+            if key_mapping.is_empty() {
+                Some(1)
+            } else {
+                estimate_cardinality(child)
+            }
+        }
     }
+}
 
-    new_p
+/// Returns the variables always bound by both plans, usable as hash join keys
+fn shared_always_bound_variables<I: StrId>(left: &PlanNode<I>, right: &PlanNode<I>) -> Vec<usize> {
+    let right = right.always_bound_variables();
+    left.always_bound_variables()
+        .into_iter()
+        .filter(|v| right.contains(v))
+        .collect()
 }
 
-fn count_pattern_binds(
-    pattern: &TriplePattern,
-    assigned_variables: &HashSet<&Variable>,
-    assigned_blank_nodes: &HashSet<&BlankNode>,
-) -> u8 {
-    let mut count = 12;
-    if let TermOrVariable::Variable(v) = &pattern.subject {
-        if !assigned_variables.contains(v) {
-            count -= 4;
+/// Adds the variables used by the filters and the extensions of the plan that are evaluated before its results are returned
+fn add_filter_variables<I: StrId>(node: &PlanNode<I>, set: &mut BTreeSet<usize>) {
+    match node {
+        PlanNode::Init | PlanNode::StaticBindings { .. } => (),
+        PlanNode::Filter { child, expression }
+        | PlanNode::Extend {
+            child, expression, ..
+        } => {
+            expression.add_maybe_bound_variables(set);
+            add_filter_variables(child, set);
         }
-    } else if let TermOrVariable::Term(Term::BlankNode(bnode)) = &pattern.subject {
-        if !assigned_blank_nodes.contains(bnode) {
-            count -= 4;
+        PlanNode::QuadPatternJoin { child, .. } | PlanNode::PathPatternJoin { child, .. } => {
+            add_filter_variables(child, set)
         }
-    } else {
-        count -= 1;
-    }
-    if let NamedNodeOrVariable::Variable(v) = &pattern.predicate {
-        if !assigned_variables.contains(v) {
-            count -= 4;
+        PlanNode::Union { children } => {
+            for child in children {
+                add_filter_variables(child, set);
+            }
         }
-    } else {
-        count -= 1;
+        PlanNode::Join { left, right }
+        | PlanNode::HashJoin { left, right, .. }
+        | PlanNode::ServiceJoin { left, right }
+        | PlanNode::AntiJoin { left, right }
+        | PlanNode::HashLeftJoin { left, right, .. } => {
+            add_filter_variables(left, set);
+            add_filter_variables(right, set);
+        }
+        PlanNode::LeftJoin { left, right, .. } => {
+            // The right side is evaluated with the bindings of the left side
+            add_filter_variables(left, set);
+            right.add_maybe_bound_variables(set);
+        }
+        PlanNode::Service { service_name, .. } => {
+            if let PatternValue::Variable(v) = service_name {
+                set.insert(*v);
+            }
+        }
+        // It is evaluated independently of the given bindings
+        PlanNode::Project { .. } => (),
+        PlanNode::Sort { child, .. }
+        | PlanNode::Aggregate { child, .. }
+        | PlanNode::HashDeduplicate { child }
+        | PlanNode::Skip { child, .. }
+        | PlanNode::Limit { child, .. }
+        | PlanNode::Parameters { child, .. } => add_filter_variables(child, set),
     }
-    if let TermOrVariable::Variable(v) = &pattern.object {
-        if !assigned_variables.contains(v) {
-            count -= 4;
+}
+
+/// Checks if the plan is only made of triple patterns and property paths
+fn is_pattern_chain<I: StrId>(node: &PlanNode<I>) -> bool {
+    match node {
+        PlanNode::Init => true,
+        PlanNode::QuadPatternJoin { child, .. }
+        | PlanNode::PathPatternJoin { child, .. }
+        | PlanNode::Filter { child, .. } => is_pattern_chain(child),
+        _ => false,
+    }
+}
+
+type TriplePatternValues<I> = (PatternValue<I>, PatternValue<I>, PatternValue<I>);
+
+fn count_pattern_binds<I: StrId>(
+    (subject, predicate, object): &TriplePatternValues<I>,
+    assigned_variables: &BTreeSet<usize>,
+) -> u8 {
+    let mut count = 12;
+    for value in &[subject, predicate, object] {
+        match value {
+            PatternValue::Variable(v) => {
+                if !assigned_variables.contains(v) {
+                    count -= 4;
+                }
+            }
+            PatternValue::Constant(_) => count -= 1,
         }
-    } else if let TermOrVariable::Term(Term::BlankNode(bnode)) = &pattern.object {
-        if !assigned_blank_nodes.contains(bnode) {
-            count -= 4;
+    }
+    count
+}
+
+fn count_bound_variables<I: StrId>(
+    (subject, predicate, object): &TriplePatternValues<I>,
+    assigned_variables: &BTreeSet<usize>,
+) -> u32 {
+    let mut count = 0;
+    for value in &[subject, predicate, object] {
+        if let PatternValue::Variable(v) = value {
+            if assigned_variables.contains(v) {
+                count += 1;
+            }
         }
-    } else {
-        count -= 1;
     }
     count
 }
 
-fn add_pattern_variables<'a>(
-    pattern: &'a TriplePattern,
-    variables: &mut HashSet<&'a Variable>,
-    blank_nodes: &mut HashSet<&'a BlankNode>,
+fn add_pattern_variables<I: StrId>(
+    (subject, predicate, object): &TriplePatternValues<I>,
+    variables: &mut BTreeSet<usize>,
 ) {
-    if let TermOrVariable::Variable(v) = &pattern.subject {
-        variables.insert(v);
-    } else if let TermOrVariable::Term(Term::BlankNode(bnode)) = &pattern.subject {
-        blank_nodes.insert(bnode);
+    for value in &[subject, predicate, object] {
+        if let PatternValue::Variable(v) = value {
+            variables.insert(*v);
+        }
     }
-    if let NamedNodeOrVariable::Variable(v) = &pattern.predicate {
-        variables.insert(v);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{GraphNameRef, NamedNodeRef, QuadRef};
+    use crate::sparql::dataset::DatasetView;
+    use crate::sparql::{CustomAccumulator, QueryOptions, Update, UpdateOptions};
+    use crate::store::numeric_encoder::Decoder;
+    use crate::MemoryStore;
+
+    fn first_pattern_predicate(store: &MemoryStore, query: &str) -> Option<NamedNode> {
+        let query = Query::parse(query, None).unwrap();
+        if let Query::Select {
+            pattern, dataset, ..
+        } = query
+        {
+            let dataset = DatasetView::new(store.clone(), &dataset).unwrap();
+            let (mut plan, _) =
+                PlanBuilder::build(&dataset, &pattern, &[], Arc::default(), Arc::default())
+                    .unwrap();
+            let mut predicate = None;
+            loop {
+                plan = match plan {
+                    PlanNode::Project { child, .. } => child.as_ref().clone(),
+                    PlanNode::Join { left, .. } | PlanNode::HashJoin { left, .. } => {
+                        left.as_ref().clone()
+                    }
+                    PlanNode::QuadPatternJoin {
+                        child,
+                        predicate: p,
+                        ..
+                    } => {
+                        predicate = p.as_constant();
+                        child.as_ref().clone()
+                    }
+                    _ => return predicate.map(|p| dataset.decode_named_node(p).unwrap()),
+                }
+            }
+        } else {
+            unreachable!()
+        }
     }
-    if let TermOrVariable::Variable(v) = &pattern.object {
-        variables.insert(v);
-    } else if let TermOrVariable::Term(Term::BlankNode(bnode)) = &pattern.object {
-        blank_nodes.insert(bnode);
+
+    #[test]
+    fn test_bgp_ordering_uses_cardinalities() {
+        let store = MemoryStore::new();
+        let rare = NamedNode::new_unchecked("http://example.com/rare");
+        let common = NamedNode::new_unchecked("http://example.com/common");
+        for i in 0..100 {
+            let s = NamedNode::new_unchecked(format!("http://example.com/s{}", i));
+            let o = NamedNode::new_unchecked(format!("http://example.com/o{}", i));
+            store.insert(crate::model::Quad::new(s.clone(), common.clone(), o, None));
+            if i == 0 {
+                store.insert(crate::model::Quad::new(s, rare.clone(), rare.clone(), None));
+            }
+        }
+        let rare = Some(rare);
+        assert_eq!(
+            first_pattern_predicate(
+                &store,
+                "SELECT * WHERE { ?s <http://example.com/common> ?o . ?s <http://example.com/rare> ?r }"
+            ),
+            rare
+        );
+        assert_eq!(
+            first_pattern_predicate(
+                &store,
+                "SELECT * WHERE { { ?s <http://example.com/common> ?o } { ?s <http://example.com/rare> ?r } }"
+            ),
+            rare
+        );
+    }
+
+    fn plan_description(store: &MemoryStore, query: &str) -> String {
+        let query = Query::parse(query, None).unwrap();
+        if let Query::Select {
+            pattern, dataset, ..
+        } = query
+        {
+            let dataset = DatasetView::new(store.clone(), &dataset).unwrap();
+            format!(
+                "{:?}",
+                PlanBuilder::build(&dataset, &pattern, &[], Arc::default(), Arc::default())
+                    .unwrap()
+                    .0
+            )
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn count_solutions(store: &MemoryStore, query: &str, variable: &str) -> (usize, usize) {
+        if let QueryResults::Solutions(solutions) = store.query(query).unwrap() {
+            let solutions = solutions.collect::<Result<Vec<_>, _>>().unwrap();
+            let bound = solutions
+                .iter()
+                .filter(|s| s.get(variable).is_some())
+                .count();
+            (solutions.len(), bound)
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn join_test_store() -> MemoryStore {
+        let store = MemoryStore::new();
+        let p = NamedNode::new_unchecked("http://example.com/p");
+        let label = NamedNode::new_unchecked("http://example.com/label");
+        for i in 0..100 {
+            let s = NamedNode::new_unchecked(format!("http://example.com/s{}", i));
+            let o = NamedNode::new_unchecked(format!("http://example.com/o{}", i));
+            store.insert(crate::model::Quad::new(s.clone(), p.clone(), o, None));
+            if i % 2 == 0 {
+                store.insert(crate::model::Quad::new(
+                    s,
+                    label.clone(),
+                    Literal::new_simple_literal(format!("l{}", i)),
+                    None,
+                ));
+            }
+        }
+        store
+    }
+
+    #[test]
+    fn test_hash_join() {
+        let store = join_test_store();
+        let query = "PREFIX ex: <http://example.com/> SELECT * WHERE { { SELECT ?s ?o WHERE { ?s ex:p ?o } } { SELECT ?s ?l WHERE { ?s ex:label ?l } } }";
+        assert!(plan_description(&store, query).contains("HashJoin"));
+        assert_eq!(count_solutions(&store, query, "l"), (50, 50));
+
+        // No shared variable: cartesian product
+        let query = "PREFIX ex: <http://example.com/> SELECT * WHERE { { SELECT ?s WHERE { ?s ex:label ?l } } { SELECT ?o WHERE { ?o ex:label ?l } } }";
+        assert!(!plan_description(&store, query).contains("HashJoin"));
+        assert_eq!(count_solutions(&store, query, "o"), (2500, 2500));
+    }
+
+    #[test]
+    fn test_join_keeps_smaller_side_in_memory() {
+        let store = join_test_store();
+        let query = Query::parse("PREFIX ex: <http://example.com/> SELECT * WHERE { { SELECT ?s WHERE { ?s ex:p ?o } } { SELECT ?x WHERE { ?x ex:label ?l } } }", None).unwrap();
+        if let Query::Select {
+            pattern, dataset, ..
+        } = query
+        {
+            let dataset = DatasetView::new(store, &dataset).unwrap();
+            let mut plan =
+                PlanBuilder::build(&dataset, &pattern, &[], Arc::default(), Arc::default())
+                    .unwrap()
+                    .0;
+            while let PlanNode::Project { child, .. } = plan {
+                plan = child.as_ref().clone();
+            }
+            if let PlanNode::Join { left, right } = plan {
+                assert_eq!(estimate_cardinality(&left), Some(100));
+                assert_eq!(estimate_cardinality(&right), Some(50));
+            } else {
+                panic!("Unexpected plan {:?}", plan)
+            }
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_hash_left_join() {
+        let store = join_test_store();
+        let query = "PREFIX ex: <http://example.com/> SELECT * WHERE { ?s ex:p ?o OPTIONAL { SELECT ?s ?l WHERE { ?s ex:label ?l } } }";
+        assert!(plan_description(&store, query).contains("HashLeftJoin"));
+        assert_eq!(count_solutions(&store, query, "l"), (100, 50));
+
+        let query = "PREFIX ex: <http://example.com/> SELECT * WHERE { ?s ex:p ?o OPTIONAL { ?s ex:label ?l FILTER(STR(?l) != \"l2\") } }";
+        assert!(plan_description(&store, query).contains("HashLeftJoin"));
+        assert_eq!(count_solutions(&store, query, "l"), (100, 49));
+
+        // The filter depends on a variable of the left side
+        let query = "PREFIX ex: <http://example.com/> SELECT * WHERE { ?s ex:p ?o OPTIONAL { ?s ex:label ?l FILTER(?o != ex:o0) } }";
+        assert!(!plan_description(&store, query).contains("HashLeftJoin"));
+        assert_eq!(count_solutions(&store, query, "l"), (100, 49));
+    }
+
+    #[test]
+    fn test_bgp_cardinalities_kept_in_plan() {
+        let store = join_test_store();
+        let plan = plan_description(
+            &store,
+            "PREFIX ex: <http://example.com/> SELECT * WHERE { ?s ex:p ?o . ?s ex:label ?l }",
+        );
+        assert!(plan.contains("cardinality: Some(50)"));
+        assert!(plan.contains("cardinality: Some(100)"));
+    }
+
+    #[test]
+    fn test_custom_aggregate() {
+        #[derive(Default)]
+        struct CountAccumulator(i64);
+
+        impl CustomAccumulator for CountAccumulator {
+            fn add(&mut self, _: Option<Term>) {
+                self.0 += 1;
+            }
+
+            fn state(&self) -> Option<Term> {
+                Some(Literal::from(self.0).into())
+            }
+        }
+
+        let store = join_test_store();
+        let count = NamedNode::new_unchecked("http://example.com/count");
+        let query = Query::parse_with_custom_aggregates(
+            "SELECT (<http://example.com/count>(DISTINCT ?o) AS ?c) WHERE { ?s ?p ?o }",
+            None,
+            vec![count.clone()],
+        )
+        .unwrap();
+        assert!(store
+            .query_opt(query.clone(), QueryOptions::default())
+            .is_err());
+        if let QueryResults::Solutions(mut solutions) = store
+            .query_opt(
+                query,
+                QueryOptions::default()
+                    .with_custom_aggregate(count.clone(), || Box::new(CountAccumulator::default())),
+            )
+            .unwrap()
+        {
+            assert_eq!(
+                solutions.next().unwrap().unwrap().get("c"),
+                Some(&Literal::from(150).into())
+            );
+        } else {
+            unreachable!()
+        }
+
+        let update = Update::parse_with_custom_aggregates(
+            "INSERT { <http://example.com/s> <http://example.com/count> ?c } WHERE { { SELECT (<http://example.com/count>(?o) AS ?c) WHERE { ?s ?p ?o } } }",
+            None,
+            vec![count.clone()],
+        )
+        .unwrap();
+        store
+            .update_opt(
+                update,
+                UpdateOptions::from(
+                    QueryOptions::default()
+                        .with_custom_aggregate(count, || Box::new(CountAccumulator::default())),
+                ),
+            )
+            .unwrap();
+        assert!(store.contains(QuadRef::new(
+            NamedNodeRef::new_unchecked("http://example.com/s"),
+            NamedNodeRef::new_unchecked("http://example.com/count"),
+            &Literal::from(150),
+            GraphNameRef::DefaultGraph,
+        )));
     }
 }
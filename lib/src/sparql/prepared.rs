@@ -0,0 +1,232 @@
+//! Prepared SPARQL queries
+
+use crate::model::Term;
+use crate::sparql::algebra::Query;
+use crate::sparql::error::EvaluationError;
+use crate::sparql::eval::{CustomAggregateRegistry, CustomFunctionRegistry};
+use crate::sparql::model::Variable;
+use crate::sparql::QueryOptions;
+use std::any::Any;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) parsed once and evaluated many times
+/// with different values for some of its variables.
+///
+/// The bound variables behave as if the query WHERE clause started with a `VALUES` clause giving them their bound values.
+/// It allows to safely pass user input to a query without building the query string by concatenation.
+///
+/// The evaluation plan is built during the first evaluation and reused by the next ones against the same store
+/// if the store string dictionary allows it and if the same variables are bound.
+/// The plan is not reused if the query contains IRIs or literals that were not in the store when the plan was built
+/// or if the evaluation uses custom functions or aggregates that are not from the same [`QueryOptions`] as the one used to build the plan.
+/// The plan join order is computed from the store content at the time of the first evaluation.
+///
+/// Usage example:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{PreparedQuery, QueryOptions, QueryResults, Variable};
+///
+/// let store = MemoryStore::new();
+/// let ex = NamedNode::new("http://example.com")?;
+/// store.insert(Quad::new(ex.clone(), ex.clone(), Literal::from("foo"), None));
+///
+/// let mut query = PreparedQuery::new("SELECT ?o WHERE { ?s ?p ?o }")?;
+/// query.bind(Variable::new("s")?, ex.clone());
+/// if let QueryResults::Solutions(mut solutions) = store.query_prepared(&query, QueryOptions::default())? {
+///     assert_eq!(solutions.next().unwrap()?.get("o"), Some(&Literal::from("foo").into()));
+/// }
+///
+/// query.bind(Variable::new("s")?, NamedNode::new("http://example.com/other")?);
+/// if let QueryResults::Solutions(mut solutions) = store.query_prepared(&query, QueryOptions::default())? {
+///     assert!(solutions.next().is_none());
+/// }
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Clone)]
+pub struct PreparedQuery {
+    pub(super) query: Query,
+    pub(super) bindings: Vec<(Variable, Term)>,
+    pub(super) plans: Arc<PlanCache>,
+}
+
+impl PreparedQuery {
+    /// Parses a query to prepare it
+    pub fn new(
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<Self, EvaluationError> {
+        Ok(Self {
+            query: query.try_into().map_err(|e| e.into())?,
+            bindings: Vec::new(),
+            plans: Arc::default(),
+        })
+    }
+
+    /// The prepared query
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Binds a variable to a value, replacing its previous value if it was already bound
+    pub fn bind(&mut self, variable: Variable, value: impl Into<Term>) -> &mut Self {
+        let value = value.into();
+        if let Some((_, previous)) = self.bindings.iter_mut().find(|(v, _)| *v == variable) {
+            *previous = value;
+        } else {
+            self.bindings.push((variable, value));
+        }
+        self
+    }
+
+    /// Removes the value bound to a variable
+    pub fn unbind(&mut self, variable: &Variable) -> &mut Self {
+        self.bindings.retain(|(v, _)| v != variable);
+        self
+    }
+
+    /// Removes all the bound values
+    pub fn clear_bindings(&mut self) -> &mut Self {
+        self.bindings.clear();
+        self
+    }
+
+    /// Returns the value bound to a variable
+    pub fn binding(&self, variable: &Variable) -> Option<&Term> {
+        self.bindings
+            .iter()
+            .find_map(|(v, value)| if v == variable { Some(value) } else { None })
+    }
+}
+
+impl fmt::Debug for PreparedQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreparedQuery")
+            .field("query", &self.query)
+            .field("bindings", &self.bindings)
+            .finish()
+    }
+}
+
+impl fmt::Display for PreparedQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.query.fmt(f)
+    }
+}
+
+/// The last plan built for a prepared query
+#[derive(Default)]
+pub(super) struct PlanCache {
+    entry: Mutex<Option<PlanCacheEntry>>,
+}
+
+struct PlanCacheEntry {
+    dictionary_id: u64,
+    parameters: Vec<Variable>,
+    custom_functions: Arc<CustomFunctionRegistry>,
+    custom_aggregates: Arc<CustomAggregateRegistry>,
+    plan: Arc<dyn Any + Send + Sync>,
+}
+
+impl PlanCache {
+    /// Returns the cached plan if it has been built against the same dictionary with the same parameters and options
+    pub fn get<T: Any + Send + Sync>(
+        &self,
+        dictionary_id: u64,
+        parameters: &[Variable],
+        options: &QueryOptions,
+    ) -> Option<Arc<T>> {
+        let entry = self.entry.lock().ok()?;
+        let entry = entry.as_ref()?;
+        if entry.dictionary_id == dictionary_id
+            && entry.parameters == parameters
+            && is_same_registry(&entry.custom_functions, &options.custom_functions)
+            && is_same_registry(&entry.custom_aggregates, &options.custom_aggregates)
+        {
+            entry.plan.clone().downcast().ok()
+        } else {
+            None
+        }
+    }
+
+    pub fn set<T: Any + Send + Sync>(
+        &self,
+        dictionary_id: u64,
+        parameters: Vec<Variable>,
+        options: &QueryOptions,
+        plan: Arc<T>,
+    ) {
+        if let Ok(mut entry) = self.entry.lock() {
+            *entry = Some(PlanCacheEntry {
+                dictionary_id,
+                parameters,
+                custom_functions: options.custom_functions.clone(),
+                custom_aggregates: options.custom_aggregates.clone(),
+                plan,
+            })
+        }
+    }
+}
+
+fn is_same_registry<K, V>(a: &Arc<HashMap<K, V>>, b: &Arc<HashMap<K, V>>) -> bool {
+    Arc::ptr_eq(a, b) || (a.is_empty() && b.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{NamedNode, Quad};
+    use crate::sparql::QueryResults;
+    use crate::MemoryStore;
+
+    fn is_plan_cached(query: &PreparedQuery) -> bool {
+        query.plans.entry.lock().unwrap().is_some()
+    }
+
+    fn count_solutions(store: &MemoryStore, query: &PreparedQuery) -> usize {
+        if let QueryResults::Solutions(solutions) = store
+            .query_prepared(query, QueryOptions::default())
+            .unwrap()
+        {
+            solutions.count()
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_plan_reuse() {
+        let store = MemoryStore::new();
+        let a = NamedNode::new_unchecked("http://example.com/a");
+        let b = NamedNode::new_unchecked("http://example.com/b");
+        let p = NamedNode::new_unchecked("http://example.com/p");
+        store.insert(Quad::new(a.clone(), p.clone(), b.clone(), None));
+        let s = Variable::new_unchecked("s");
+
+        let mut query =
+            PreparedQuery::new("SELECT * WHERE { ?s <http://example.com/p> ?o }").unwrap();
+        query.bind(s.clone(), a.clone());
+        assert_eq!(count_solutions(&store, &query), 1);
+        assert!(is_plan_cached(&query));
+        query.bind(s.clone(), b);
+        assert_eq!(count_solutions(&store, &query), 0);
+        // The bound values do not need to be in the store
+        query.bind(s, NamedNode::new_unchecked("http://example.com/c"));
+        assert_eq!(count_solutions(&store, &query), 0);
+
+        // The plans with strings that are not in the store are not reused
+        let query = PreparedQuery::new("SELECT * WHERE { ?s <http://example.com/q> ?o }").unwrap();
+        assert_eq!(count_solutions(&store, &query), 0);
+        assert!(!is_plan_cached(&query));
+        store.insert(Quad::new(
+            a.clone(),
+            NamedNode::new_unchecked("http://example.com/q"),
+            a,
+            None,
+        ));
+        assert_eq!(count_solutions(&store, &query), 1);
+        assert!(is_plan_cached(&query));
+    }
+}
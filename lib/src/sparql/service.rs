@@ -4,15 +4,19 @@ use crate::sparql::algebra::Query;
 use crate::sparql::error::EvaluationError;
 use crate::sparql::http::Client;
 use crate::sparql::model::QueryResults;
-use crate::sparql::QueryResultsFormat;
+use crate::sparql::{QueryOptions, QueryResultsFormat};
+use crate::store::ReadableStore;
 use http::header::{ACCEPT, CONTENT_TYPE, USER_AGENT};
 use http::{Method, Request, StatusCode};
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Arc;
 
 /// Handler for [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) SERVICE.
 ///
 /// Should be given to [`QueryOptions`](super::QueryOptions::with_service_handler())
 /// before evaluating a SPARQL query that uses SERVICE calls.
+/// It should be `Send` and `Sync` because the query results could be consumed from other threads.
 ///
 /// ```
 /// use oxigraph::MemoryStore;
@@ -49,11 +53,128 @@ use std::error::Error;
 /// }
 /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
 /// ```
-pub trait ServiceHandler {
+pub trait ServiceHandler: Send + Sync {
     type Error: Error + Send + Sync + 'static;
 
     /// Evaluates a [`Query`] against a given service identified by a [`NamedNode`](crate::model::NamedNode).
     fn handle(&self, service_name: NamedNode, query: Query) -> Result<QueryResults, Self::Error>;
+
+    /// Evaluates a [`Query`] against a given service using the options of the query doing the SERVICE call.
+    ///
+    /// The options carry the timeout and the cancellation token of the calling query, its custom functions and aggregates, its memory limit...
+    /// By default, they are ignored and [`handle`](ServiceHandler::handle()) is called.
+    fn handle_with_options(
+        &self,
+        service_name: NamedNode,
+        query: Query,
+        options: &QueryOptions,
+    ) -> Result<QueryResults, Self::Error> {
+        let _ = options;
+        self.handle(service_name, query)
+    }
+}
+
+/// A [`ServiceHandler`] evaluating the SERVICE calls against local stores identified by IRIs.
+///
+/// The SERVICE patterns are evaluated directly against the stores without being serialized and parsed again.
+/// The SERVICE calls inside of these patterns are also handled by this handler.
+/// The patterns are evaluated with the options of the query doing the SERVICE call: its timeout, its custom functions...
+/// The calls to the other services are given to a fallback handler.
+/// By default, it is the handler used by [`QueryOptions::default()`](super::QueryOptions::default()).
+///
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{LocalServiceHandler, QueryOptions, QueryResults};
+///
+/// let store = MemoryStore::new();
+/// store.update("INSERT DATA { <http://example.com/paris> <http://example.com/country> <http://example.com/france> }")?;
+/// let reference = MemoryStore::new();
+/// reference.update("INSERT DATA { <http://example.com/france> <http://example.com/name> \"France\" }")?;
+///
+/// let service = LocalServiceHandler::new().with_store(NamedNode::new("urn:store:ref")?, reference);
+/// if let QueryResults::Solutions(mut solutions) = store.query_opt(
+///     "SELECT ?name WHERE { ?city <http://example.com/country> ?country SERVICE <urn:store:ref> { ?country <http://example.com/name> ?name } }",
+///     QueryOptions::default().with_service_handler(service)
+/// )? {
+///     assert_eq!(solutions.next().unwrap()?.get("name"), Some(&Literal::from("France").into()));
+/// }
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Clone)]
+pub struct LocalServiceHandler {
+    stores: HashMap<NamedNode, Arc<LocalStoreEvaluator>>,
+    fallback: Arc<dyn ServiceHandler<Error = EvaluationError>>,
+}
+
+type LocalStoreEvaluator =
+    dyn Fn(Query, QueryOptions) -> Result<QueryResults, EvaluationError> + Send + Sync;
+
+impl LocalServiceHandler {
+    /// Builds a handler without local stores
+    pub fn new() -> Self {
+        Self {
+            stores: HashMap::new(),
+            fallback: QueryOptions::default().service_handler,
+        }
+    }
+
+    /// Evaluates the calls to the service identified by the given IRI against the given store
+    pub fn with_store(
+        mut self,
+        service_name: NamedNode,
+        store: impl ReadableStore + Clone + Send + Sync + 'static,
+    ) -> Self {
+        self.stores.insert(
+            service_name,
+            Arc::new(move |query, options| store.query_opt(query, options)),
+        );
+        self
+    }
+
+    /// Uses a given [`ServiceHandler`] for the calls to the services that are not local stores
+    pub fn with_fallback(mut self, fallback: impl ServiceHandler + 'static) -> Self {
+        self.fallback = Arc::new(ErrorConversionServiceHandler::wrap(fallback));
+        self
+    }
+
+    /// Fails on the calls to the services that are not local stores
+    pub fn without_fallback(mut self) -> Self {
+        self.fallback = Arc::new(EmptyServiceHandler);
+        self
+    }
+}
+
+impl Default for LocalServiceHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServiceHandler for LocalServiceHandler {
+    type Error = EvaluationError;
+
+    fn handle(
+        &self,
+        service_name: NamedNode,
+        query: Query,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.handle_with_options(service_name, query, &QueryOptions::default())
+    }
+
+    fn handle_with_options(
+        &self,
+        service_name: NamedNode,
+        query: Query,
+        options: &QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        if let Some(store) = self.stores.get(&service_name) {
+            store(query, options.clone().with_service_handler(self.clone()))
+        } else {
+            self.fallback
+                .handle_with_options(service_name, query, options)
+        }
+    }
 }
 
 pub struct EmptyServiceHandler;
@@ -90,6 +211,17 @@ impl<S: ServiceHandler> ServiceHandler for ErrorConversionServiceHandler<S> {
             .handle(service_name, query)
             .map_err(EvaluationError::wrap)
     }
+
+    fn handle_with_options(
+        &self,
+        service_name: NamedNode,
+        query: Query,
+        options: &QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.handler
+            .handle_with_options(service_name, query, options)
+            .map_err(EvaluationError::wrap)
+    }
 }
 
 pub struct SimpleServiceHandler {
@@ -117,7 +249,14 @@ impl ServiceHandler for SimpleServiceHandler {
             .method(Method::POST)
             .uri(service_name.as_str())
             .header(CONTENT_TYPE, "application/sparql-query")
-            .header(ACCEPT, QueryResultsFormat::Xml.media_type())
+            .header(
+                ACCEPT,
+                format!(
+                    "{}, {};q=0.9",
+                    QueryResultsFormat::Json.media_type(),
+                    QueryResultsFormat::Xml.media_type()
+                ),
+            )
             .header(USER_AGENT, concat!("Oxigraph/", env!("CARGO_PKG_VERSION")))
             .body(Some(query.to_string().into_bytes()))
             .map_err(invalid_input_error)?;
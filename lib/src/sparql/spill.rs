@@ -0,0 +1,507 @@
+//! Storage of intermediate results in temporary files when the evaluation memory limit is exceeded
+
+use crate::sparql::error::EvaluationError;
+use crate::sparql::eval::EncodedTuplesIterator;
+use crate::sparql::plan::EncodedTuple;
+use crate::store::numeric_encoder::{EncodedTerm, StrId};
+use rand::random;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::{remove_file, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::mem::{size_of, size_of_val, take};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{env, io};
+
+/// Number of partitions in which are split the tuples that do not fit in a hash table
+const PARTITION_COUNT: usize = 16;
+
+/// Maximal number of sorted runs merged at the same time
+const MERGE_FAN_IN: usize = 64;
+
+/// Estimation of the memory used by a tuple
+pub(crate) fn tuple_memory_size<I: StrId>(tuple: &EncodedTuple<I>) -> usize {
+    size_of::<EncodedTuple<I>>() + tuple.capacity() * size_of::<Option<EncodedTerm<I>>>()
+}
+
+/// Estimation of the memory used by a hash table key
+pub(crate) fn key_memory_size<I: StrId>(key: &[Option<EncodedTerm<I>>]) -> usize {
+    size_of::<Vec<Option<EncodedTerm<I>>>>() + size_of_val(key)
+}
+
+/// A temporary file that is removed when dropped
+struct TemporaryFile {
+    path: PathBuf,
+}
+
+impl TemporaryFile {
+    fn create() -> io::Result<(Self, File)> {
+        let path = env::temp_dir().join(format!("oxigraph-{:x}.tmp", random::<u128>()));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((Self { path }, file))
+    }
+}
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.path);
+    }
+}
+
+/// Writes tuples in a temporary file
+pub(crate) struct TupleFileWriter<I: StrId> {
+    file: TemporaryFile,
+    writer: BufWriter<File>,
+    buffer: Vec<u8>,
+    len: usize,
+    phantom: PhantomData<I>,
+}
+
+impl<I: StrId> TupleFileWriter<I> {
+    pub fn new() -> io::Result<Self> {
+        let (file, writer) = TemporaryFile::create()?;
+        Ok(Self {
+            file,
+            writer: BufWriter::new(writer),
+            buffer: Vec::default(),
+            len: 0,
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn write(&mut self, tuple: &EncodedTuple<I>) -> io::Result<()> {
+        self.buffer.clear();
+        tuple.write(&mut self.buffer);
+        self.writer.write_all(&self.buffer)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Writes all the given tuples in a new temporary file and returns a reader on it
+    pub fn write_all(
+        tuples: impl IntoIterator<Item = EncodedTuple<I>>,
+    ) -> io::Result<TupleFileReader<I>> {
+        let mut writer = Self::new()?;
+        for tuple in tuples {
+            writer.write(&tuple)?;
+        }
+        writer.finish()
+    }
+
+    pub fn finish(self) -> io::Result<TupleFileReader<I>> {
+        let mut file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(TupleFileReader {
+            _file: self.file,
+            reader: BufReader::new(file),
+            remaining: self.len,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// Reads the tuples written by a [`TupleFileWriter`] and removes the file when dropped
+pub(crate) struct TupleFileReader<I: StrId> {
+    _file: TemporaryFile,
+    reader: BufReader<File>,
+    remaining: usize,
+    phantom: PhantomData<I>,
+}
+
+impl<I: StrId> Iterator for TupleFileReader<I> {
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(EncodedTuple::read(&mut self.reader).map_err(|e| {
+            self.remaining = 0;
+            e.into()
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// Splits tuples into temporary files according to the hash of a key.
+///
+/// The `level` is used to salt the hash in order to split again the tuples of a partition that is itself too big.
+pub(crate) struct TuplePartitions<I: StrId> {
+    partitions: Vec<TupleFileWriter<I>>,
+    level: u32,
+}
+
+impl<I: StrId> TuplePartitions<I> {
+    pub fn new(level: u32) -> io::Result<Self> {
+        Ok(Self {
+            partitions: (0..PARTITION_COUNT)
+                .map(|_| TupleFileWriter::new())
+                .collect::<io::Result<_>>()?,
+            level,
+        })
+    }
+
+    pub fn write(&mut self, key: &impl Hash, tuple: &EncodedTuple<I>) -> io::Result<()> {
+        let mut hasher = DefaultHasher::new();
+        self.level.hash(&mut hasher);
+        key.hash(&mut hasher);
+        let partition = usize::from(hasher.finish().to_be_bytes()[7]) % PARTITION_COUNT;
+        self.partitions[partition].write(tuple)
+    }
+
+    /// Returns readers on the non empty partitions
+    pub fn finish(self) -> io::Result<Vec<TupleFileReader<I>>> {
+        let mut readers = Vec::with_capacity(self.partitions.len());
+        for partition in self.partitions {
+            if partition.len > 0 {
+                readers.push(partition.finish()?);
+            }
+        }
+        Ok(readers)
+    }
+}
+
+/// Sorted runs of tuples written to temporary files.
+///
+/// The runs are grouped by levels: when a level contains [`MERGE_FAN_IN`] runs,
+/// they are merged into a single run of the next level in order to bound the number of open files.
+pub(crate) struct SortedRuns<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> {
+    levels: Vec<Vec<TupleFileReader<I>>>,
+    cmp: Arc<F>,
+}
+
+impl<I: StrId + 'static, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> SortedRuns<I, F> {
+    pub fn new(cmp: F) -> Self {
+        Self {
+            levels: Vec::default(),
+            cmp: Arc::new(cmp),
+        }
+    }
+
+    /// Writes a run of tuples already sorted according to the comparison function
+    pub fn push(
+        &mut self,
+        run: impl IntoIterator<Item = EncodedTuple<I>>,
+    ) -> Result<(), EvaluationError> {
+        let mut run = TupleFileWriter::write_all(run)?;
+        for level in 0.. {
+            if self.levels.len() == level {
+                self.levels.push(Vec::with_capacity(MERGE_FAN_IN));
+            }
+            self.levels[level].push(run);
+            if self.levels[level].len() < MERGE_FAN_IN {
+                break;
+            }
+            let runs = take(&mut self.levels[level])
+                .into_iter()
+                .map(|run| -> EncodedTuplesIterator<I> { Box::new(run) })
+                .collect();
+            let mut writer = TupleFileWriter::new()?;
+            for tuple in SortedRunsMerge::new(runs, &self.cmp) {
+                writer.write(&tuple?)?;
+            }
+            run = writer.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Merges the written runs with a last sorted run
+    pub fn merge(self, last: EncodedTuplesIterator<I>) -> SortedRunsMerge<I, F> {
+        // The runs of the higher levels are the oldest ones
+        let mut runs = self
+            .levels
+            .into_iter()
+            .rev()
+            .flatten()
+            .map(|run| -> EncodedTuplesIterator<I> { Box::new(run) })
+            .collect::<Vec<_>>();
+        runs.push(last);
+        SortedRunsMerge::new(runs, &self.cmp)
+    }
+}
+
+/// Merges runs of tuples sorted according to the same comparison function
+pub(crate) struct SortedRunsMerge<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> {
+    heap: BinaryHeap<MergedRun<I, F>>,
+    errors: Vec<EvaluationError>,
+}
+
+impl<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> SortedRunsMerge<I, F> {
+    fn new(runs: Vec<EncodedTuplesIterator<I>>, cmp: &Arc<F>) -> Self {
+        let mut merge = Self {
+            heap: BinaryHeap::with_capacity(runs.len()),
+            errors: Vec::default(),
+        };
+        for run in runs {
+            merge.push_run(run, cmp.clone());
+        }
+        merge
+    }
+
+    fn push_run(&mut self, mut run: EncodedTuplesIterator<I>, cmp: Arc<F>) {
+        match run.next() {
+            Some(Ok(head)) => self.heap.push(MergedRun { head, run, cmp }),
+            Some(Err(error)) => self.errors.push(error),
+            None => (),
+        }
+    }
+}
+
+impl<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> Iterator
+    for SortedRunsMerge<I, F>
+{
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        if let Some(error) = self.errors.pop() {
+            return Some(Err(error));
+        }
+        let MergedRun { head, run, cmp } = self.heap.pop()?;
+        self.push_run(run, cmp);
+        Some(Ok(head))
+    }
+}
+
+/// A run in the [`SortedRunsMerge`] heap, ordered such that the run with the smallest head is on top
+struct MergedRun<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> {
+    head: EncodedTuple<I>,
+    run: EncodedTuplesIterator<I>,
+    cmp: Arc<F>,
+}
+
+impl<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> PartialEq
+    for MergedRun<I, F>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> Eq for MergedRun<I, F> {}
+
+impl<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> PartialOrd
+    for MergedRun<I, F>
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<I: StrId, F: Fn(&EncodedTuple<I>, &EncodedTuple<I>) -> Ordering> Ord for MergedRun<I, F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap
+        (self.cmp)(&other.head, &self.head)
+    }
+}
+
+/// Removes duplicated tuples using a hash set.
+///
+/// When the set memory goes above the limit, the tuples that have not been seen yet are
+/// written into hash partitions that are deduplicated one after the other once the input is consumed.
+pub(crate) struct SpillingDeduplicate<I: StrId> {
+    input: EncodedTuplesIterator<I>,
+    already_seen: HashSet<EncodedTuple<I>>,
+    already_seen_size: usize,
+    memory_limit: usize,
+    level: u32,
+    partitions: Option<TuplePartitions<I>>,
+    pending: Vec<(TupleFileReader<I>, u32)>,
+}
+
+impl<I: StrId + 'static> SpillingDeduplicate<I> {
+    pub fn new(input: EncodedTuplesIterator<I>, memory_limit: usize) -> Self {
+        Self {
+            input,
+            already_seen: HashSet::default(),
+            already_seen_size: 0,
+            memory_limit,
+            level: 0,
+            partitions: None,
+            pending: Vec::default(),
+        }
+    }
+
+    fn spill(&mut self, tuple: &EncodedTuple<I>) -> io::Result<()> {
+        if self.partitions.is_none() {
+            self.partitions = Some(TuplePartitions::new(self.level)?);
+        }
+        if let Some(partitions) = &mut self.partitions {
+            partitions.write(tuple, tuple)?;
+        }
+        Ok(())
+    }
+}
+
+impl<I: StrId + 'static> Iterator for SpillingDeduplicate<I> {
+    type Item = Result<EncodedTuple<I>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple<I>, EvaluationError>> {
+        loop {
+            match self.input.next() {
+                Some(Ok(tuple)) => {
+                    if self.already_seen.contains(&tuple) {
+                        continue;
+                    }
+                    if self.already_seen_size > self.memory_limit {
+                        if let Err(error) = self.spill(&tuple) {
+                            return Some(Err(error.into()));
+                        }
+                        continue;
+                    }
+                    self.already_seen_size += tuple_memory_size(&tuple);
+                    self.already_seen.insert(tuple.clone());
+                    return Some(Ok(tuple));
+                }
+                Some(Err(error)) => return Some(Err(error)),
+                None => {
+                    if let Some(partitions) = self.partitions.take() {
+                        match partitions.finish() {
+                            Ok(readers) => {
+                                let level = self.level + 1;
+                                self.pending
+                                    .extend(readers.into_iter().map(|reader| (reader, level)))
+                            }
+                            Err(error) => return Some(Err(error.into())),
+                        }
+                    }
+                    let (reader, level) = self.pending.pop()?;
+                    self.input = Box::new(reader);
+                    self.level = level;
+                    self.already_seen.clear();
+                    self.already_seen_size = 0;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Literal, NamedNode, Quad};
+    use crate::sparql::{QueryOptions, QueryResults};
+    use crate::store::binary_encoder::StrHash;
+    use crate::MemoryStore;
+    use std::convert::TryFrom;
+    use std::iter::empty;
+
+    fn rows(
+        store: &MemoryStore,
+        query: &str,
+        variables: &[&str],
+        options: QueryOptions,
+    ) -> Vec<Vec<Option<String>>> {
+        if let QueryResults::Solutions(solutions) = store.query_opt(query, options).unwrap() {
+            solutions
+                .map(|solution| {
+                    let solution = solution.unwrap();
+                    variables
+                        .iter()
+                        .map(|v| solution.get(*v).map(ToString::to_string))
+                        .collect()
+                })
+                .collect()
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn memory_limit_does_not_change_results() {
+        let store = MemoryStore::new();
+        let p = NamedNode::new_unchecked("http://example.com/p");
+        for i in 0..100 {
+            store.insert(Quad::new(
+                NamedNode::new_unchecked(format!("http://example.com/s{}", i % 7)),
+                p.clone(),
+                Literal::from(i % 30),
+                None,
+            ));
+        }
+
+        let query = "SELECT ?s ?o WHERE { ?s ?p ?o } ORDER BY DESC(?o) ?s";
+        let expected = rows(&store, query, &["s", "o"], QueryOptions::default());
+        for limit in &[1, 512] {
+            let options = QueryOptions::default().with_memory_limit(*limit);
+            assert_eq!(rows(&store, query, &["s", "o"], options), expected);
+        }
+
+        for (query, variables) in &[
+            ("SELECT DISTINCT ?o WHERE { ?s ?p ?o }", &["o"][..]),
+            (
+                "SELECT ?o (COUNT(?s) AS ?c) WHERE { ?s ?p ?o } GROUP BY ?o",
+                &["o", "c"][..],
+            ),
+        ] {
+            let mut expected = rows(&store, query, variables, QueryOptions::default());
+            expected.sort();
+            assert_eq!(expected.len(), 30);
+            for limit in &[1, 512] {
+                let options = QueryOptions::default().with_memory_limit(*limit);
+                let mut actual = rows(&store, query, variables, options);
+                actual.sort();
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_runs_merge_levels() -> Result<(), EvaluationError> {
+        fn value(tuple: &EncodedTuple<StrHash>) -> i64 {
+            if let Some(EncodedTerm::IntegerLiteral(value)) = tuple.get(0) {
+                value
+            } else {
+                unreachable!()
+            }
+        }
+
+        let mut runs = SortedRuns::new(|a, b| value(a).cmp(&value(b)));
+        let count = i64::try_from(MERGE_FAN_IN * MERGE_FAN_IN + 10).unwrap();
+        for i in 0..count {
+            let mut tuple = EncodedTuple::with_capacity(1);
+            tuple.set(0, EncodedTerm::from((i * 7919) % count));
+            runs.push(vec![tuple])?;
+        }
+        // The number of open runs is bounded
+        assert_eq!(runs.levels.len(), 3);
+        assert!(runs.levels.iter().all(|level| level.len() < MERGE_FAN_IN));
+
+        let merged = runs
+            .merge(Box::new(empty()))
+            .map(|tuple| Ok(value(&tuple?)))
+            .collect::<Result<Vec<_>, EvaluationError>>()?;
+        assert_eq!(merged, (0..count).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn tuple_file_roundtrip() -> io::Result<()> {
+        let mut tuple = EncodedTuple::with_capacity(3);
+        tuple.set(
+            0,
+            EncodedTerm::NamedNode {
+                iri_id: StrHash::new("http://example.com"),
+            },
+        );
+        tuple.set(1, EncodedTerm::from(12_i64));
+        tuple.set(2, EncodedTerm::DefaultGraph);
+        let tuples = vec![tuple, EncodedTuple::with_capacity(1)];
+        let read = TupleFileWriter::write_all(tuples.clone())?
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, tuples);
+        Ok(())
+    }
+}
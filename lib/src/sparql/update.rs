@@ -5,6 +5,7 @@ use crate::sparql::algebra::{
     GraphPattern, GraphTarget, GraphUpdateOperation, NamedNodeOrVariable, QuadPattern,
     QueryDataset, TermOrVariable,
 };
+use crate::sparql::cancellation::InterruptionChecker;
 use crate::sparql::dataset::{DatasetStrId, DatasetView};
 use crate::sparql::eval::SimpleEvaluator;
 use crate::sparql::http::Client;
@@ -20,19 +21,20 @@ use http::{Method, Request, StatusCode};
 use oxiri::Iri;
 use std::collections::HashMap;
 use std::io;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub(crate) struct SimpleUpdateEvaluator<'a, R, W> {
     read: R,
     write: &'a mut W,
-    base_iri: Option<Rc<Iri<String>>>,
+    base_iri: Option<Arc<Iri<String>>>,
     options: UpdateOptions,
+    interruption_checker: Option<Arc<InterruptionChecker>>,
     client: Client,
 }
 
 impl<
         'a,
-        R: ReadableEncodedStore + Clone + 'static,
+        R: ReadableEncodedStore + Clone + Send + Sync + 'static,
         W: StrContainer<StrId = R::StrId> + WritableEncodedStore<StrId = R::StrId> + 'a,
     > SimpleUpdateEvaluator<'a, R, W>
 where
@@ -41,25 +43,36 @@ where
     pub fn new(
         read: R,
         write: &'a mut W,
-        base_iri: Option<Rc<Iri<String>>>,
+        base_iri: Option<Arc<Iri<String>>>,
         options: UpdateOptions,
     ) -> Self {
+        let interruption_checker = options.query_options.interruption_checker();
         Self {
             read,
             write,
             base_iri,
             options,
+            interruption_checker,
             client: Client::new(),
         }
     }
 
     pub fn eval_all(&mut self, updates: &[GraphUpdateOperation]) -> Result<(), EvaluationError> {
         for update in updates {
+            self.check_interruption()?;
             self.eval(update)?;
         }
         Ok(())
     }
 
+    fn check_interruption(&self) -> Result<(), EvaluationError> {
+        if let Some(checker) = &self.interruption_checker {
+            checker.check()
+        } else {
+            Ok(())
+        }
+    }
+
     fn eval(&mut self, update: &GraphUpdateOperation) -> Result<(), EvaluationError> {
         match update {
             GraphUpdateOperation::InsertData { data } => self.eval_insert_data(data),
@@ -90,6 +103,7 @@ where
     fn eval_insert_data(&mut self, data: &[Quad]) -> Result<(), EvaluationError> {
         let mut bnodes = HashMap::new();
         for quad in data {
+            self.check_interruption()?;
             if let Some(quad) = self.encode_quad_for_insertion(quad, &mut bnodes)? {
                 self.write.insert_encoded(&quad).map_err(to_eval_error)?;
             }
@@ -99,6 +113,7 @@ where
 
     fn eval_delete_data(&mut self, data: &[Quad]) -> Result<(), EvaluationError> {
         for quad in data {
+            self.check_interruption()?;
             if let Some(quad) = self.encode_quad_for_deletion(quad)? {
                 self.write.remove_encoded(&quad).map_err(to_eval_error)?;
             }
@@ -113,13 +128,26 @@ where
         using: &QueryDataset,
         algebra: &GraphPattern,
     ) -> Result<(), EvaluationError> {
-        let dataset = Rc::new(DatasetView::new(self.read.clone(), using)?);
-        let (plan, variables) = PlanBuilder::build(dataset.as_ref(), algebra)?;
-        let evaluator = SimpleEvaluator::<DatasetView<R>>::new(
+        let dataset = Arc::new(DatasetView::new(self.read.clone(), using)?);
+        let (plan, variables) = PlanBuilder::build(
+            dataset.as_ref(),
+            algebra,
+            &[],
+            self.options.query_options.custom_functions.clone(),
+            self.options.query_options.custom_aggregates.clone(),
+        )?;
+        let mut evaluator = SimpleEvaluator::<DatasetView<R>>::new(
             dataset.clone(),
             self.base_iri.clone(),
             self.options.query_options.service_handler.clone(),
-        );
+        )
+        .with_custom_functions(self.options.query_options.custom_functions.clone())
+        .with_custom_aggregates(self.options.query_options.custom_aggregates.clone())
+        .with_memory_limit(self.options.query_options.memory_limit)
+        .with_parallel_evaluation(self.options.query_options.max_threads);
+        if let Some(checker) = &self.interruption_checker {
+            evaluator = evaluator.with_interruption_checker(checker.clone());
+        }
         let mut bnodes = HashMap::new();
         for tuple in evaluator.eval_plan(&plan, EncodedTuple::with_capacity(variables.len())) {
             // We map the tuple to only get store strings
@@ -222,6 +250,7 @@ where
             format,
             to_graph_name,
             Some(from.as_str()),
+            None,
         )
         .map_err(io::Error::from)?;
         Ok(())
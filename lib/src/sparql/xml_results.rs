@@ -16,7 +16,7 @@ use std::io;
 use std::io::BufRead;
 use std::io::Write;
 use std::iter::empty;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub fn write_xml_results(results: QueryResults, sink: impl Write) -> Result<(), EvaluationError> {
     match results {
@@ -146,7 +146,7 @@ fn write_solutions(solutions: QuerySolutionIter, sink: impl Write) -> Result<(),
     Ok(())
 }
 
-pub fn read_xml_results(source: impl BufRead + 'static) -> Result<QueryResults, io::Error> {
+pub fn read_xml_results(source: impl BufRead + Send + 'static) -> Result<QueryResults, io::Error> {
     enum State {
         Start,
         Sparql,
@@ -217,7 +217,7 @@ pub fn read_xml_results(source: impl BufRead + 'static) -> Result<QueryResults,
                             mapping.insert(var.as_bytes().to_vec(), i);
                         }
                         return Ok(QueryResults::Solutions(QuerySolutionIter::new(
-                            Rc::new(variables.into_iter().map(Variable::new).collect::<Result<Vec<_>,_>>().map_err(invalid_data_error)?),
+                            Arc::new(variables.into_iter().map(Variable::new).collect::<Result<Vec<_>,_>>().map_err(invalid_data_error)?),
                             Box::new(ResultsIterator {
                                 reader,
                                 buffer: Vec::default(),
@@ -255,7 +255,7 @@ pub fn read_xml_results(source: impl BufRead + 'static) -> Result<QueryResults,
                 State::AfterHead => {
                     return if event.name() == b"results" {
                         Ok(QueryResults::Solutions(QuerySolutionIter::new(
-                            Rc::new(variables.into_iter().map(Variable::new).collect::<Result<Vec<_>,_>>().map_err(invalid_data_error)?),
+                            Arc::new(variables.into_iter().map(Variable::new).collect::<Result<Vec<_>,_>>().map_err(invalid_data_error)?),
                             Box::new(empty()),
                         )))
                     } else {
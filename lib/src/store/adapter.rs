@@ -0,0 +1,441 @@
+//! Encoding of the [`ReadableStore`] and [`WritableStore`] implementations for the SPARQL evaluator
+
+use crate::error::invalid_data_error;
+use crate::model::*;
+use crate::store::numeric_encoder::*;
+use crate::store::{ReadableEncodedStore, ReadableStore, WritableEncodedStore, WritableStore};
+use lasso::{LargeSpur, ThreadedRodeo};
+use std::io;
+use std::iter::empty;
+use std::sync::Arc;
+
+/// Exposes a [`ReadableStore`] using the encoded terms of the SPARQL evaluator.
+///
+/// The strings are interned in memory while the adapter is alive.
+#[derive(Clone)]
+pub(crate) struct EncodingAdapter<S> {
+    store: S,
+    strings: Interner,
+}
+
+impl<S: ReadableStore> EncodingAdapter<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            strings: Interner::default(),
+        }
+    }
+
+    fn decode_graph_name(
+        &self,
+        graph_name: EncodedTerm<LargeSpur>,
+    ) -> Result<GraphName, io::Error> {
+        Ok(if graph_name.is_default_graph() {
+            GraphName::DefaultGraph
+        } else {
+            self.decode_named_or_blank_node(graph_name)?.into()
+        })
+    }
+}
+
+impl<S> StrEncodingAware for EncodingAdapter<S> {
+    type Error = io::Error;
+    type StrId = LargeSpur;
+}
+
+impl<S> StrLookup for EncodingAdapter<S> {
+    fn get_str(&self, id: LargeSpur) -> Result<Option<String>, io::Error> {
+        self.strings.get_str(id)
+    }
+
+    fn get_str_id(&self, value: &str) -> Result<Option<LargeSpur>, io::Error> {
+        self.strings.get_str_id(value)
+    }
+}
+
+impl<S> StrContainer for EncodingAdapter<S> {
+    fn insert_str(&mut self, value: &str) -> Result<LargeSpur, io::Error> {
+        self.strings.insert_str(value)
+    }
+}
+
+impl<S: ReadableStore> ReadableEncodedStore for EncodingAdapter<S> {
+    type QuadsIter = Box<dyn Iterator<Item = Result<EncodedQuad<LargeSpur>, io::Error>> + Send>;
+    type GraphsIter = Box<dyn Iterator<Item = Result<EncodedTerm<LargeSpur>, io::Error>> + Send>;
+
+    fn encoded_quads_for_pattern(
+        &self,
+        subject: Option<EncodedTerm<LargeSpur>>,
+        predicate: Option<EncodedTerm<LargeSpur>>,
+        object: Option<EncodedTerm<LargeSpur>>,
+        graph_name: Option<EncodedTerm<LargeSpur>>,
+    ) -> Self::QuadsIter {
+        // The terms that could not be decoded like literals in subject position do not match any quad
+        let subject = match subject
+            .map(|t| self.decode_named_or_blank_node(t))
+            .transpose()
+        {
+            Ok(subject) => subject,
+            Err(_) => return Box::new(empty()),
+        };
+        let predicate = match predicate.map(|t| self.decode_named_node(t)).transpose() {
+            Ok(predicate) => predicate,
+            Err(_) => return Box::new(empty()),
+        };
+        let object = match object.map(|t| self.decode_term(t)).transpose() {
+            Ok(object) => object,
+            Err(_) => return Box::new(empty()),
+        };
+        let graph_name = match graph_name.map(|t| self.decode_graph_name(t)).transpose() {
+            Ok(graph_name) => graph_name,
+            Err(_) => return Box::new(empty()),
+        };
+        let mut strings = self.strings.clone();
+        Box::new(
+            self.store
+                .quads_for_pattern(
+                    subject.as_ref().map(|t| t.as_ref()),
+                    predicate.as_ref().map(|t| t.as_ref()),
+                    object.as_ref().map(|t| t.as_ref()),
+                    graph_name.as_ref().map(|t| t.as_ref()),
+                )
+                .map(move |quad| strings.encode_quad(quad.map_err(Into::into)?.as_ref())),
+        )
+    }
+
+    fn encoded_named_graphs(&self) -> Self::GraphsIter {
+        let mut strings = self.strings.clone();
+        Box::new(
+            self.store
+                .named_graphs()
+                .map(move |g| strings.encode_named_or_blank_node(g.map_err(Into::into)?.as_ref())),
+        )
+    }
+
+    fn contains_encoded_named_graph(
+        &self,
+        graph_name: EncodedTerm<LargeSpur>,
+    ) -> Result<bool, io::Error> {
+        self.store
+            .contains_named_graph(self.decode_named_or_blank_node(graph_name)?.as_ref())
+            .map_err(Into::into)
+    }
+}
+
+impl<S: WritableStore> WritableEncodedStore for EncodingAdapter<S> {
+    fn insert_encoded(&mut self, quad: &EncodedQuad<LargeSpur>) -> Result<(), io::Error> {
+        self.store
+            .insert(self.decode_quad(quad)?.as_ref())
+            .map_err(Into::into)
+    }
+
+    fn remove_encoded(&mut self, quad: &EncodedQuad<LargeSpur>) -> Result<(), io::Error> {
+        self.store
+            .remove(self.decode_quad(quad)?.as_ref())
+            .map_err(Into::into)
+    }
+
+    fn insert_encoded_named_graph(
+        &mut self,
+        graph_name: EncodedTerm<LargeSpur>,
+    ) -> Result<(), io::Error> {
+        self.store
+            .insert_named_graph(self.decode_named_or_blank_node(graph_name)?.as_ref())
+            .map_err(Into::into)
+    }
+
+    fn clear_encoded_graph(&mut self, graph_name: EncodedTerm<LargeSpur>) -> Result<(), io::Error> {
+        self.store
+            .clear_graph(self.decode_graph_name(graph_name)?.as_ref())
+            .map_err(Into::into)
+    }
+
+    fn remove_encoded_named_graph(
+        &mut self,
+        graph_name: EncodedTerm<LargeSpur>,
+    ) -> Result<(), io::Error> {
+        self.store
+            .remove_named_graph(self.decode_named_or_blank_node(graph_name)?.as_ref())
+            .map_err(Into::into)
+    }
+
+    fn clear(&mut self) -> Result<(), io::Error> {
+        WritableStore::clear(&self.store).map_err(Into::into)
+    }
+}
+
+/// The string interner shared by an adapter and its iterators
+///
+/// All the strings get an id: the ones unknown to the store are just not going to match anything.
+#[derive(Clone)]
+struct Interner {
+    strings: Arc<ThreadedRodeo<LargeSpur>>,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self {
+            strings: Arc::new(ThreadedRodeo::new()),
+        }
+    }
+}
+
+impl StrEncodingAware for Interner {
+    type Error = io::Error;
+    type StrId = LargeSpur;
+}
+
+impl StrLookup for Interner {
+    fn get_str(&self, id: LargeSpur) -> Result<Option<String>, io::Error> {
+        Ok(self.strings.try_resolve(&id).map(|e| e.to_owned()))
+    }
+
+    fn get_str_id(&self, value: &str) -> Result<Option<LargeSpur>, io::Error> {
+        Ok(Some(
+            self.strings
+                .try_get_or_intern(value)
+                .map_err(invalid_data_error)?,
+        ))
+    }
+}
+
+impl StrContainer for Interner {
+    fn insert_str(&mut self, value: &str) -> Result<LargeSpur, io::Error> {
+        Ok(self.get_str_id(value)?.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{DatasetFormat, GraphFormat};
+    use crate::sparql::QueryResults;
+    use crate::store::TransactionalStore;
+    use crate::MemoryStore;
+    use std::sync::{Arc, RwLock};
+
+    /// A naive store that could be provided by a third party crate
+    #[derive(Clone, Default)]
+    struct VecStore {
+        quads: Arc<RwLock<Vec<Quad>>>,
+    }
+
+    impl ReadableStore for VecStore {
+        type Error = io::Error;
+
+        fn quads_for_pattern(
+            &self,
+            subject: Option<NamedOrBlankNodeRef<'_>>,
+            predicate: Option<NamedNodeRef<'_>>,
+            object: Option<TermRef<'_>>,
+            graph_name: Option<GraphNameRef<'_>>,
+        ) -> Box<dyn Iterator<Item = Result<Quad, io::Error>> + Send> {
+            let quads = self
+                .quads
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|q| {
+                    subject.iter().all(|s| q.subject.as_ref() == *s)
+                        && predicate.iter().all(|p| q.predicate.as_ref() == *p)
+                        && object.iter().all(|o| q.object.as_ref() == *o)
+                        && graph_name.iter().all(|g| q.graph_name.as_ref() == *g)
+                })
+                .cloned()
+                .map(Ok)
+                .collect::<Vec<_>>();
+            Box::new(quads.into_iter())
+        }
+
+        fn named_graphs(
+            &self,
+        ) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, io::Error>> + Send> {
+            let mut graph_names = Vec::new();
+            for quad in self.quads.read().unwrap().iter() {
+                let graph_name = match &quad.graph_name {
+                    GraphName::NamedNode(g) => NamedOrBlankNode::from(g.clone()),
+                    GraphName::BlankNode(g) => NamedOrBlankNode::from(g.clone()),
+                    GraphName::DefaultGraph => continue,
+                };
+                if !graph_names.contains(&graph_name) {
+                    graph_names.push(graph_name);
+                }
+            }
+            Box::new(graph_names.into_iter().map(Ok))
+        }
+
+        fn contains_named_graph(
+            &self,
+            graph_name: NamedOrBlankNodeRef<'_>,
+        ) -> Result<bool, io::Error> {
+            Ok(self
+                .quads_for_pattern(None, None, None, Some(graph_name.into()))
+                .next()
+                .is_some())
+        }
+    }
+
+    impl WritableStore for VecStore {
+        fn insert(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+            if !self.contains(quad)? {
+                self.quads.write().unwrap().push(quad.into_owned());
+            }
+            Ok(())
+        }
+
+        fn remove(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+            self.quads.write().unwrap().retain(|q| q.as_ref() != quad);
+            Ok(())
+        }
+
+        fn insert_named_graph(&self, _: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+            Ok(())
+        }
+
+        fn remove_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+            self.clear_graph(graph_name.into())
+        }
+    }
+
+    #[test]
+    fn test_third_party_store_query() -> Result<(), io::Error> {
+        let store = VecStore::default();
+        store.load_graph(
+            &mut b"<http://example.com/s> <http://example.com/p> \"foo\" , \"bar\" .".as_ref(),
+            GraphFormat::Turtle,
+            GraphNameRef::DefaultGraph,
+            None,
+        )?;
+        assert_eq!(store.len()?, 2);
+
+        let results = store
+            .query("SELECT ?o WHERE { <http://example.com/s> ?p ?o FILTER(STRLEN(?o) > 2) } ORDER BY ?o")
+            .unwrap();
+        if let QueryResults::Solutions(solutions) = results {
+            let objects = solutions
+                .map(|s| s.unwrap().get("o").unwrap().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(objects, vec!["\"bar\"", "\"foo\""]);
+        } else {
+            panic!("SELECT query should return solutions")
+        }
+
+        // Terms unknown to the store do not match anything
+        let results = store
+            .query("ASK { <http://example.com/s> <http://example.com/other> ?o }")
+            .unwrap();
+        assert!(matches!(results, QueryResults::Boolean(false)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_third_party_store_update() -> Result<(), io::Error> {
+        let store = VecStore::default();
+        store
+            .update(
+                "INSERT DATA { GRAPH <http://example.com/g> { <http://example.com/s> <http://example.com/p> 1 } } ;
+                 INSERT { ?s <http://example.com/p> ?o2 } WHERE { GRAPH ?g { ?s ?p ?o } BIND(?o + 1 AS ?o2) }",
+            )
+            .unwrap();
+        assert_eq!(store.len()?, 2);
+        assert_eq!(
+            store.named_graphs().collect::<Result<Vec<_>, _>>()?,
+            vec![NamedNode::new_unchecked("http://example.com/g").into()]
+        );
+
+        store.update("DROP GRAPH <http://example.com/g>").unwrap();
+        assert_eq!(store.len()?, 1);
+        store.update("CLEAR ALL").unwrap();
+        assert!(store.is_empty()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generic_usage() -> Result<(), io::Error> {
+        fn copy(
+            from: &impl ReadableStore,
+            to: &impl TransactionalStore<Error = io::Error>,
+        ) -> Result<(), io::Error> {
+            to.transaction(&mut |transaction| {
+                for quad in from.quads_for_pattern(None, None, None, None) {
+                    transaction.insert(quad.map_err(Into::into)?.as_ref())?;
+                }
+                Ok(())
+            })
+        }
+
+        fn insert_and_abort(
+            store: &impl TransactionalStore<Error = io::Error>,
+            quad: QuadRef<'_>,
+        ) -> Result<(), io::Error> {
+            store.transaction(&mut |transaction| {
+                transaction.remove(quad)?;
+                Err(io::Error::new(io::ErrorKind::Interrupted, "abort"))
+            })
+        }
+
+        let ex = NamedNodeRef::new_unchecked("http://example.com");
+        let from = VecStore::default();
+        from.insert(QuadRef::new(ex, ex, ex, ex))?;
+        from.insert(QuadRef::new(ex, ex, ex, None))?;
+        let to = MemoryStore::new();
+        copy(&from, &to)?;
+        assert_eq!(to.len(), 2);
+        assert!(to.contains_named_graph(ex));
+
+        // A failing transaction is rollbacked
+        assert!(insert_and_abort(&to, QuadRef::new(ex, ex, ex, ex)).is_err());
+        assert_eq!(to.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transactional_store_load() -> Result<(), io::Error> {
+        const DATA: &str = "<http://example.com/s> <http://example.com/p> <http://example.com/o> .
+            GRAPH <http://example.com/g> { <http://example.com/s> <http://example.com/p> \"foo\" , \"bar\" }";
+
+        fn load_and_count(
+            store: &(impl TransactionalStore<Error = io::Error> + Clone + Send + Sync + 'static),
+        ) -> Result<usize, io::Error> {
+            store.transaction(&mut |transaction| {
+                transaction.load_dataset(&mut DATA.as_bytes(), DatasetFormat::TriG, None)
+            })?;
+            match store
+                .query("SELECT * WHERE { GRAPH ?g { ?s ?p ?o } }")
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+            {
+                QueryResults::Solutions(solutions) => Ok(solutions.count()),
+                _ => Ok(0),
+            }
+        }
+
+        fn load_invalid_files(store: &impl TransactionalStore) -> (bool, bool) {
+            (
+                store
+                    .load_graph_atomic(
+                        &mut b"<http://example.com/s> <http://example.com/p> 1 . <http://example.com/s>".as_ref(),
+                        GraphFormat::NTriples,
+                        GraphNameRef::DefaultGraph,
+                        None,
+                    )
+                    .is_err(),
+                store
+                    .load_dataset_atomic(
+                        &mut b"<http://example.com/s> <http://example.com/p> 2 . <http://example.com/s>".as_ref(),
+                        DatasetFormat::NQuads,
+                        None,
+                    )
+                    .is_err(),
+            )
+        }
+
+        let store = MemoryStore::new();
+        assert_eq!(load_and_count(&store)?, 2);
+        assert_eq!(store.len(), 3);
+
+        // The atomic loads write nothing if the file is invalid
+        assert_eq!(load_invalid_files(&store), (true, true));
+        assert_eq!(store.len(), 3);
+        Ok(())
+    }
+}
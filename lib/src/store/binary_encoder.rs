@@ -1,19 +1,48 @@
 use crate::error::invalid_data_error;
 use crate::model::xsd::*;
-use crate::store::numeric_encoder::StrId;
+use crate::store::numeric_encoder::{EncodedTerm, StrId};
 use crate::store::small_string::SmallString;
 use siphasher::sip128::{Hasher128, SipHasher24};
+use std::convert::TryInto;
 use std::hash::Hasher;
 use std::io;
 use std::io::{Cursor, Read};
 use std::mem::size_of;
 
-type EncodedTerm = crate::store::numeric_encoder::EncodedTerm<StrHash>;
 type EncodedQuad = crate::store::numeric_encoder::EncodedQuad<StrHash>;
 
-pub const LATEST_STORAGE_VERSION: u64 = 1;
+pub const LATEST_STORAGE_VERSION: u64 = 2;
 pub const WRITTEN_TERM_MAX_SIZE: usize = size_of::<u8>() + 2 * size_of::<StrHash>();
 
+/// The migrations applied when opening a store using an outdated storage version.
+///
+/// Each version bump of the encoding should add a migration from the previous version.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from_version: 0,
+        steps: &[MigrationStep::DeriveIndex {
+            from: StorageIndex::Gspo,
+            to: StorageIndex::Graphs,
+            derive: graph_name_key_from_gspo_key,
+        }],
+    },
+    Migration {
+        from_version: 1,
+        steps: &[MigrationStep::ComputeStatistics],
+    },
+];
+/// The key storing the progress of the running migration
+pub const MIGRATION_PROGRESS_KEY: &[u8] = b"oxmigration";
+/// The prefix of the keys used to store the rewritten entries before copying them back into their index
+pub const MIGRATION_BUFFER_PREFIX: &[u8] = b"oxmigration/";
+/// The number of entries migrated between two saves of the migration progress
+pub const MIGRATION_BATCH_SIZE: usize = 1024;
+/// The prefix of the keys storing the number of quads of the default graph and of the named graphs,
+/// in total and per predicate. They are used to estimate the cardinality of the quad patterns.
+pub const STATISTICS_PREFIX: &[u8] = b"oxstats/";
+/// The first key after the [`STATISTICS_PREFIX`] keys
+pub const STATISTICS_END: &[u8] = b"oxstats0";
+
 // Encoded term type blocks
 // 1-7: usual named nodes (except prefixes c.f. later)
 // 8-15: blank nodes
@@ -79,7 +108,19 @@ impl StrHash {
     }
 }
 
-impl StrId for StrHash {}
+impl StrId for StrHash {
+    #[inline]
+    fn write(self, sink: &mut Vec<u8>) {
+        sink.extend_from_slice(&self.to_be_bytes())
+    }
+
+    #[inline]
+    fn read(source: &mut impl Read) -> io::Result<Self> {
+        let mut buffer = [0; 16];
+        source.read_exact(&mut buffer)?;
+        Ok(Self::from_be_bytes(buffer))
+    }
+}
 
 #[derive(Clone, Copy)]
 pub enum QuadEncoding {
@@ -111,12 +152,12 @@ impl QuadEncoding {
     }
 }
 
-pub fn decode_term(buffer: &[u8]) -> Result<EncodedTerm, io::Error> {
+pub fn decode_term(buffer: &[u8]) -> Result<EncodedTerm<StrHash>, io::Error> {
     Cursor::new(&buffer).read_term()
 }
 
 pub trait TermReader {
-    fn read_term(&mut self) -> Result<EncodedTerm, io::Error>;
+    fn read_term<I: StrId>(&mut self) -> Result<EncodedTerm<I>, io::Error>;
 
     fn read_spog_quad(&mut self) -> Result<EncodedQuad, io::Error> {
         let subject = self.read_term()?;
@@ -234,17 +275,13 @@ pub trait TermReader {
 }
 
 impl<R: Read> TermReader for R {
-    fn read_term(&mut self) -> Result<EncodedTerm, io::Error> {
+    fn read_term<I: StrId>(&mut self) -> Result<EncodedTerm<I>, io::Error> {
         let mut type_buffer = [0];
         self.read_exact(&mut type_buffer)?;
         match type_buffer[0] {
-            TYPE_NAMED_NODE_ID => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::NamedNode {
-                    iri_id: StrHash::from_be_bytes(buffer),
-                })
-            }
+            TYPE_NAMED_NODE_ID => Ok(EncodedTerm::NamedNode {
+                iri_id: I::read(self)?,
+            }),
             TYPE_NUMERICAL_BLANK_NODE_ID => {
                 let mut buffer = [0; 16];
                 self.read_exact(&mut buffer)?;
@@ -259,13 +296,9 @@ impl<R: Read> TermReader for R {
                     SmallString::from_be_bytes(buffer).map_err(invalid_data_error)?,
                 ))
             }
-            TYPE_BIG_BLANK_NODE_ID => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::BigBlankNode {
-                    id_id: StrHash::from_be_bytes(buffer),
-                })
-            }
+            TYPE_BIG_BLANK_NODE_ID => Ok(EncodedTerm::BigBlankNode {
+                id_id: I::read(self)?,
+            }),
             TYPE_SMALL_SMALL_LANG_STRING_LITERAL => {
                 let mut language_buffer = [0; 16];
                 self.read_exact(&mut language_buffer)?;
@@ -278,54 +311,44 @@ impl<R: Read> TermReader for R {
                 })
             }
             TYPE_SMALL_BIG_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
+                let language_id = I::read(self)?;
                 let mut value_buffer = [0; 16];
                 self.read_exact(&mut value_buffer)?;
                 Ok(EncodedTerm::SmallBigLangStringLiteral {
                     value: SmallString::from_be_bytes(value_buffer).map_err(invalid_data_error)?,
-                    language_id: StrHash::from_be_bytes(language_buffer),
+                    language_id,
                 })
             }
             TYPE_BIG_SMALL_LANG_STRING_LITERAL => {
                 let mut language_buffer = [0; 16];
                 self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
                 Ok(EncodedTerm::BigSmallLangStringLiteral {
-                    value_id: StrHash::from_be_bytes(value_buffer),
+                    value_id: I::read(self)?,
                     language: SmallString::from_be_bytes(language_buffer)
                         .map_err(invalid_data_error)?,
                 })
             }
             TYPE_BIG_BIG_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
+                let language_id = I::read(self)?;
                 Ok(EncodedTerm::BigBigLangStringLiteral {
-                    value_id: StrHash::from_be_bytes(value_buffer),
-                    language_id: StrHash::from_be_bytes(language_buffer),
+                    value_id: I::read(self)?,
+                    language_id,
                 })
             }
             TYPE_SMALL_TYPED_LITERAL => {
-                let mut datatype_buffer = [0; 16];
-                self.read_exact(&mut datatype_buffer)?;
+                let datatype_id = I::read(self)?;
                 let mut value_buffer = [0; 16];
                 self.read_exact(&mut value_buffer)?;
                 Ok(EncodedTerm::SmallTypedLiteral {
-                    datatype_id: StrHash::from_be_bytes(datatype_buffer),
+                    datatype_id,
                     value: SmallString::from_be_bytes(value_buffer).map_err(invalid_data_error)?,
                 })
             }
             TYPE_BIG_TYPED_LITERAL => {
-                let mut datatype_buffer = [0; 16];
-                self.read_exact(&mut datatype_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
+                let datatype_id = I::read(self)?;
                 Ok(EncodedTerm::BigTypedLiteral {
-                    datatype_id: StrHash::from_be_bytes(datatype_buffer),
-                    value_id: StrHash::from_be_bytes(value_buffer),
+                    datatype_id,
+                    value_id: I::read(self)?,
                 })
             }
             TYPE_SMALL_STRING_LITERAL => {
@@ -335,13 +358,9 @@ impl<R: Read> TermReader for R {
                     SmallString::from_be_bytes(buffer).map_err(invalid_data_error)?,
                 ))
             }
-            TYPE_BIG_STRING_LITERAL => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::BigStringLiteral {
-                    value_id: StrHash::from_be_bytes(buffer),
-                })
-            }
+            TYPE_BIG_STRING_LITERAL => Ok(EncodedTerm::BigStringLiteral {
+                value_id: I::read(self)?,
+            }),
             TYPE_BOOLEAN_LITERAL_TRUE => Ok(EncodedTerm::BooleanLiteral(true)),
             TYPE_BOOLEAN_LITERAL_FALSE => Ok(EncodedTerm::BooleanLiteral(false)),
             TYPE_FLOAT_LITERAL => {
@@ -496,20 +515,24 @@ pub fn write_osp_quad(sink: &mut Vec<u8>, quad: &EncodedQuad) {
     write_term(sink, quad.predicate);
 }
 
-pub fn encode_term(t: EncodedTerm) -> Vec<u8> {
+pub fn encode_term(t: EncodedTerm<StrHash>) -> Vec<u8> {
     let mut vec = Vec::with_capacity(WRITTEN_TERM_MAX_SIZE);
     write_term(&mut vec, t);
     vec
 }
 
-pub fn encode_term_pair(t1: EncodedTerm, t2: EncodedTerm) -> Vec<u8> {
+pub fn encode_term_pair(t1: EncodedTerm<StrHash>, t2: EncodedTerm<StrHash>) -> Vec<u8> {
     let mut vec = Vec::with_capacity(2 * WRITTEN_TERM_MAX_SIZE);
     write_term(&mut vec, t1);
     write_term(&mut vec, t2);
     vec
 }
 
-pub fn encode_term_triple(t1: EncodedTerm, t2: EncodedTerm, t3: EncodedTerm) -> Vec<u8> {
+pub fn encode_term_triple(
+    t1: EncodedTerm<StrHash>,
+    t2: EncodedTerm<StrHash>,
+    t3: EncodedTerm<StrHash>,
+) -> Vec<u8> {
     let mut vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
     write_term(&mut vec, t1);
     write_term(&mut vec, t2);
@@ -518,10 +541,10 @@ pub fn encode_term_triple(t1: EncodedTerm, t2: EncodedTerm, t3: EncodedTerm) ->
 }
 
 pub fn encode_term_quad(
-    t1: EncodedTerm,
-    t2: EncodedTerm,
-    t3: EncodedTerm,
-    t4: EncodedTerm,
+    t1: EncodedTerm<StrHash>,
+    t2: EncodedTerm<StrHash>,
+    t3: EncodedTerm<StrHash>,
+    t4: EncodedTerm<StrHash>,
 ) -> Vec<u8> {
     let mut vec = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
     write_term(&mut vec, t1);
@@ -531,12 +554,12 @@ pub fn encode_term_quad(
     vec
 }
 
-pub fn write_term(sink: &mut Vec<u8>, term: EncodedTerm) {
+pub fn write_term<I: StrId>(sink: &mut Vec<u8>, term: EncodedTerm<I>) {
     match term {
         EncodedTerm::DefaultGraph => (),
         EncodedTerm::NamedNode { iri_id } => {
             sink.push(TYPE_NAMED_NODE_ID);
-            sink.extend_from_slice(&iri_id.to_be_bytes());
+            iri_id.write(sink);
         }
         EncodedTerm::NumericalBlankNode { id } => {
             sink.push(TYPE_NUMERICAL_BLANK_NODE_ID);
@@ -548,7 +571,7 @@ pub fn write_term(sink: &mut Vec<u8>, term: EncodedTerm) {
         }
         EncodedTerm::BigBlankNode { id_id } => {
             sink.push(TYPE_BIG_BLANK_NODE_ID);
-            sink.extend_from_slice(&id_id.to_be_bytes());
+            id_id.write(sink);
         }
         EncodedTerm::SmallStringLiteral(value) => {
             sink.push(TYPE_SMALL_STRING_LITERAL);
@@ -556,7 +579,7 @@ pub fn write_term(sink: &mut Vec<u8>, term: EncodedTerm) {
         }
         EncodedTerm::BigStringLiteral { value_id } => {
             sink.push(TYPE_BIG_STRING_LITERAL);
-            sink.extend_from_slice(&value_id.to_be_bytes());
+            value_id.write(sink);
         }
         EncodedTerm::SmallSmallLangStringLiteral { value, language } => {
             sink.push(TYPE_SMALL_SMALL_LANG_STRING_LITERAL);
@@ -565,25 +588,25 @@ pub fn write_term(sink: &mut Vec<u8>, term: EncodedTerm) {
         }
         EncodedTerm::SmallBigLangStringLiteral { value, language_id } => {
             sink.push(TYPE_SMALL_BIG_LANG_STRING_LITERAL);
-            sink.extend_from_slice(&language_id.to_be_bytes());
+            language_id.write(sink);
             sink.extend_from_slice(&value.to_be_bytes());
         }
         EncodedTerm::BigSmallLangStringLiteral { value_id, language } => {
             sink.push(TYPE_BIG_SMALL_LANG_STRING_LITERAL);
             sink.extend_from_slice(&language.to_be_bytes());
-            sink.extend_from_slice(&value_id.to_be_bytes());
+            value_id.write(sink);
         }
         EncodedTerm::BigBigLangStringLiteral {
             value_id,
             language_id,
         } => {
             sink.push(TYPE_BIG_BIG_LANG_STRING_LITERAL);
-            sink.extend_from_slice(&language_id.to_be_bytes());
-            sink.extend_from_slice(&value_id.to_be_bytes());
+            language_id.write(sink);
+            value_id.write(sink);
         }
         EncodedTerm::SmallTypedLiteral { value, datatype_id } => {
             sink.push(TYPE_SMALL_TYPED_LITERAL);
-            sink.extend_from_slice(&datatype_id.to_be_bytes());
+            datatype_id.write(sink);
             sink.extend_from_slice(&value.to_be_bytes());
         }
         EncodedTerm::BigTypedLiteral {
@@ -591,8 +614,8 @@ pub fn write_term(sink: &mut Vec<u8>, term: EncodedTerm) {
             datatype_id,
         } => {
             sink.push(TYPE_BIG_TYPED_LITERAL);
-            sink.extend_from_slice(&datatype_id.to_be_bytes());
-            sink.extend_from_slice(&value_id.to_be_bytes());
+            datatype_id.write(sink);
+            value_id.write(sink);
         }
         EncodedTerm::BooleanLiteral(true) => sink.push(TYPE_BOOLEAN_LITERAL_TRUE),
         EncodedTerm::BooleanLiteral(false) => sink.push(TYPE_BOOLEAN_LITERAL_FALSE),
@@ -659,6 +682,189 @@ pub fn write_term(sink: &mut Vec<u8>, term: EncodedTerm) {
     }
 }
 
+/// The indexes of the stores, i.e. the RocksDB column families or the Sled trees
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum StorageIndex {
+    Id2Str,
+    Spog,
+    Posg,
+    Ospg,
+    Gspo,
+    Gpos,
+    Gosp,
+    Dspo,
+    Dpos,
+    Dosp,
+    Graphs,
+}
+
+impl StorageIndex {
+    pub const fn name(self) -> &'static str {
+        match self {
+            StorageIndex::Id2Str => "id2str",
+            StorageIndex::Spog => "spog",
+            StorageIndex::Posg => "posg",
+            StorageIndex::Ospg => "ospg",
+            StorageIndex::Gspo => "gspo",
+            StorageIndex::Gpos => "gpos",
+            StorageIndex::Gosp => "gosp",
+            StorageIndex::Dspo => "dspo",
+            StorageIndex::Dpos => "dpos",
+            StorageIndex::Dosp => "dosp",
+            StorageIndex::Graphs => "graphs",
+        }
+    }
+}
+
+/// A migration of the storage from a version to the next one
+pub struct Migration {
+    /// The version to migrate from. The migration leads to `from_version + 1`
+    pub from_version: u64,
+    /// The steps of the migration, applied one after the other
+    pub steps: &'static [MigrationStep],
+}
+
+/// A step of a [`Migration`].
+///
+/// The steps are applied by batches of [`MIGRATION_BATCH_SIZE`] entries and could be resumed after an interruption.
+pub enum MigrationStep {
+    /// Replaces each key of the index by the one returned by `rewrite`, keeping the values.
+    ///
+    /// The rewritten entries are first written in the default column family or tree
+    /// using the [`MIGRATION_BUFFER_PREFIX`] prefix, then the index is cleared and they are copied back into it.
+    #[allow(dead_code)] // Not used by the current migrations
+    RewriteIndex {
+        index: StorageIndex,
+        rewrite: fn(&[u8]) -> Result<Vec<u8>, io::Error>,
+    },
+    /// Inserts into the index `to` the key returned by `derive` for each key of the index `from`, with an empty value
+    DeriveIndex {
+        from: StorageIndex,
+        to: StorageIndex,
+        derive: fn(&[u8]) -> Result<Vec<u8>, io::Error>,
+    },
+    /// Computes again the statistics stored with the [`STATISTICS_PREFIX`] prefix from the quad indexes.
+    ///
+    /// It is started again from the beginning if it is interrupted.
+    ComputeStatistics,
+}
+
+/// The phases of a [`MigrationStep`]
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum MigrationPhase {
+    /// The entries of the index are read
+    Read,
+    /// The rewritten entries are copied back into their index
+    CopyBack,
+}
+
+/// The progress of a migration, saved in the store to be able to resume it
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct MigrationProgress {
+    /// The version the migration starts from
+    pub version: u64,
+    /// The position of the current step in [`Migration::steps`]
+    pub step: usize,
+    pub phase: MigrationPhase,
+    /// The last key processed during the current phase
+    pub last_key: Option<Vec<u8>>,
+}
+
+impl MigrationProgress {
+    pub fn new(version: u64) -> Self {
+        Self {
+            version,
+            step: 0,
+            phase: MigrationPhase::Read,
+            last_key: None,
+        }
+    }
+
+    /// Moves to the beginning of the next step
+    pub fn next_step(&mut self) {
+        self.step += 1;
+        self.phase = MigrationPhase::Read;
+        self.last_key = None;
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(18 + self.last_key.as_ref().map_or(0, Vec::len));
+        buffer.extend_from_slice(&self.version.to_be_bytes());
+        buffer.extend_from_slice(&(self.step as u64).to_be_bytes());
+        buffer.push(match self.phase {
+            MigrationPhase::Read => 0,
+            MigrationPhase::CopyBack => 1,
+        });
+        if let Some(last_key) = &self.last_key {
+            buffer.push(1);
+            buffer.extend_from_slice(last_key);
+        } else {
+            buffer.push(0);
+        }
+        buffer
+    }
+
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self, io::Error> {
+        if buffer.len() < 18 {
+            return Err(invalid_data_error("The migration progress is too short"));
+        }
+        let mut version = [0; 8];
+        version.copy_from_slice(&buffer[0..8]);
+        let mut step = [0; 8];
+        step.copy_from_slice(&buffer[8..16]);
+        Ok(Self {
+            version: u64::from_be_bytes(version),
+            step: u64::from_be_bytes(step)
+                .try_into()
+                .map_err(invalid_data_error)?,
+            phase: match buffer[16] {
+                0 => MigrationPhase::Read,
+                1 => MigrationPhase::CopyBack,
+                _ => return Err(invalid_data_error("Invalid migration phase")),
+            },
+            last_key: match buffer[17] {
+                0 => None,
+                1 => Some(buffer[18..].to_vec()),
+                _ => return Err(invalid_data_error("Invalid migration last key")),
+            },
+        })
+    }
+}
+
+/// Returns the key of the statistic counting the quads of the default graph or of the named graphs,
+/// only the ones with the given predicate if it is set.
+pub fn statistics_key(default_graph: bool, predicate: Option<EncodedTerm<StrHash>>) -> Vec<u8> {
+    let mut key = Vec::with_capacity(STATISTICS_PREFIX.len() + 1 + WRITTEN_TERM_MAX_SIZE);
+    key.extend_from_slice(STATISTICS_PREFIX);
+    key.push(if default_graph { 0 } else { 1 });
+    if let Some(predicate) = predicate {
+        write_term(&mut key, predicate);
+    }
+    key
+}
+
+/// Returns the keys of the statistics counting the given quad
+pub fn quad_statistics_keys(quad: &EncodedQuad) -> [Vec<u8>; 2] {
+    let default_graph = quad.graph_name.is_default_graph();
+    [
+        statistics_key(default_graph, None),
+        statistics_key(default_graph, Some(quad.predicate)),
+    ]
+}
+
+pub fn encode_statistic(count: i64) -> [u8; 8] {
+    count.to_be_bytes()
+}
+
+pub fn decode_statistic(value: &[u8]) -> Option<i64> {
+    Some(i64::from_be_bytes(value.try_into().ok()?))
+}
+
+/// Returns the key of the graph name of a GSPO index key in the graphs index
+fn graph_name_key_from_gspo_key(key: &[u8]) -> Result<Vec<u8>, io::Error> {
+    Ok(encode_term(QuadEncoding::GSPO.decode(key)?.graph_name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -773,4 +979,25 @@ mod tests {
             assert_eq!(encoded, Cursor::new(&buffer).read_term().unwrap());
         }
     }
+
+    #[test]
+    fn test_migration_progress_encoding() {
+        let mut progress = MigrationProgress::new(1);
+        assert_eq!(
+            progress,
+            MigrationProgress::from_bytes(&progress.to_bytes()).unwrap()
+        );
+        progress.phase = MigrationPhase::CopyBack;
+        progress.last_key = Some(vec![1, 2, 3]);
+        assert_eq!(
+            progress,
+            MigrationProgress::from_bytes(&progress.to_bytes()).unwrap()
+        );
+        progress.next_step();
+        assert_eq!(
+            progress,
+            MigrationProgress::from_bytes(&progress.to_bytes()).unwrap()
+        );
+        assert!(MigrationProgress::from_bytes(&[1, 2]).is_err());
+    }
 }
@@ -1,25 +1,29 @@
 //! In-memory store.
 
-use crate::error::{invalid_input_error, UnwrapInfallible};
-use crate::io::{DatasetFormat, DatasetParser, GraphFormat, GraphParser};
+use crate::error::{invalid_data_error, invalid_input_error, UnwrapInfallible};
+use crate::io::read::SyntaxError;
+use crate::io::{
+    DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser, GraphSerializer,
+};
 use crate::model::*;
 use crate::sparql::{
-    evaluate_query, evaluate_update, EvaluationError, Query, QueryOptions, QueryResults, Update,
-    UpdateOptions,
+    evaluate_prepared_query, evaluate_query, evaluate_update, explain_query, EvaluationError,
+    PreparedQuery, Query, QueryExplanation, QueryOptions, QueryResults, Update, UpdateOptions,
 };
 use crate::store::numeric_encoder::{
     Decoder, ReadEncoder, StrContainer, StrEncodingAware, StrId, StrLookup, WriteEncoder,
 };
 use crate::store::{
-    dump_dataset, dump_graph, get_encoded_quad_pattern, load_dataset, load_graph,
-    ReadableEncodedStore, WritableEncodedStore,
+    dump_dataset, dump_graph, get_encoded_quad_pattern, load_dataset, load_dataset_with_parser,
+    load_graph, load_graph_with_parser, DictionaryId, ReadableEncodedStore, ReadableStore,
+    StoreTransaction, TransactionalStore, WritableEncodedStore, WritableStore,
 };
-use lasso::{LargeSpur, ThreadedRodeo};
+use lasso::{Key, LargeSpur, ThreadedRodeo};
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
-use std::convert::{Infallible, TryInto};
+use std::convert::{Infallible, TryFrom, TryInto};
 use std::hash::{Hash, Hasher};
-use std::io::{BufRead, Write};
+use std::io::{BufRead, Read, Write};
 use std::iter::FromIterator;
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::vec::IntoIter;
@@ -56,6 +60,7 @@ use std::{fmt, io};
 pub struct MemoryStore {
     indexes: Arc<RwLock<MemoryStoreIndexes>>,
     strings: Arc<ThreadedRodeo<LargeSpur>>,
+    dictionary_id: DictionaryId,
 }
 
 type TripleMap<T> = HashMap<T, HashMap<T, HashSet<T>>>;
@@ -88,6 +93,7 @@ impl MemoryStore {
         Self {
             indexes: Arc::new(RwLock::default()),
             strings: Arc::new(ThreadedRodeo::new()),
+            dictionary_id: DictionaryId::new(),
         }
     }
 
@@ -129,6 +135,42 @@ impl MemoryStore {
         evaluate_query(self.clone(), query, options)
     }
 
+    /// Executes a [`PreparedQuery`] with some options.
+    ///
+    /// The query evaluation plan is reused between the evaluations against this store if possible.
+    /// See [`PreparedQuery`] for a usage example.
+    pub fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        evaluate_prepared_query(self.clone(), query, options)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options
+    /// and returns an explanation of its evaluation.
+    ///
+    /// If `with_stats` is not set, the query is not evaluated: only its evaluation plan is returned.
+    /// If `with_stats` is set, the query results are returned with the explanation
+    /// that also contains the number of rows returned by each operation and the time spent evaluating it.
+    /// They are collected while the returned results are consumed.
+    ///
+    /// See [`QueryExplanation`] for a usage example.
+    pub fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        explain_query(self.clone(), query, options, with_stats)
+    }
+
     /// Retrieves quads with a filter on each quad component
     ///
     /// Usage example:
@@ -336,7 +378,65 @@ impl MemoryStore {
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
         let mut store = self;
-        load_graph(&mut store, reader, format, to_graph_name.into(), base_iri)?;
+        load_graph(
+            &mut store,
+            reader,
+            format,
+            to_graph_name.into(),
+            base_iri,
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Loads a graph file (i.e. triples) into the store in lenient mode: the invalid statements are skipped and their errors are given to `on_error`.
+    ///
+    /// Only the line based format [N-Triples](https://www.w3.org/TR/n-triples/) is able to recover from a syntax error.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = MemoryStore::new();
+    ///
+    /// // insertion
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> .\n<http://example.com> <http://example.com> \"foo .\n";
+    /// let mut errors = Vec::new();
+    /// store.load_graph_lenient(file.as_ref(), GraphFormat::NTriples, &GraphName::DefaultGraph, None, |e| errors.push(e))?;
+    ///
+    /// // we inspect the store contents and the errors
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, None)));
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].line(), Some(2));
+    ///
+    /// // the other formats are not supported
+    /// let error = store.load_graph_lenient(file.as_ref(), GraphFormat::Turtle, &GraphName::DefaultGraph, None, |_| ()).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    pub fn load_graph_lenient<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+        mut on_error: impl FnMut(SyntaxError),
+    ) -> Result<(), io::Error> {
+        let mut store = self;
+        load_graph(
+            &mut store,
+            reader,
+            format,
+            to_graph_name.into(),
+            base_iri,
+            Some(&mut on_error),
+        )?;
         Ok(())
     }
 
@@ -373,7 +473,138 @@ impl MemoryStore {
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
         let mut store = self;
-        load_dataset(&mut store, reader, format, base_iri)?;
+        load_dataset(&mut store, reader, format, base_iri, None)?;
+        Ok(())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store in lenient mode: the invalid statements are skipped and their errors are given to `on_error`.
+    ///
+    /// Only the line based format [N-Quads](https://www.w3.org/TR/n-quads/) is able to recover from a syntax error.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = MemoryStore::new();
+    ///
+    /// // insertion
+    /// let file = b"<http://example.com> <http://example.com> \"foo\"@ <http://example.com> .\n<http://example.com> <http://example.com> <http://example.com> <http://example.com> .";
+    /// let mut errors = Vec::new();
+    /// store.load_dataset_lenient(file.as_ref(), DatasetFormat::NQuads, None, |e| errors.push(e))?;
+    ///
+    /// // we inspect the store contents and the errors
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex)));
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].line(), Some(1));
+    ///
+    /// // the other formats are not supported
+    /// let error = store.load_dataset_lenient(file.as_ref(), DatasetFormat::TriG, None, |_| ()).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    pub fn load_dataset_lenient(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+        mut on_error: impl FnMut(SyntaxError),
+    ) -> Result<(), io::Error> {
+        let mut store = self;
+        load_dataset(&mut store, reader, format, base_iri, Some(&mut on_error))?;
+        Ok(())
+    }
+
+    /// Loads a graph file (i.e. triples) into the store using the options of a [`GraphParser`].
+    ///
+    /// It allows to provide a [JSON-LD context loader](GraphParser::with_json_ld_context_loader()).
+    /// If the parser is in [lenient mode](GraphParser::with_lenient_mode()), only available for [N-Triples](https://www.w3.org/TR/n-triples/), the syntax errors it is able to recover from are given to its callback.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::io::{GraphFormat, GraphParser};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = MemoryStore::new();
+    ///
+    /// // insertion
+    /// let file = br#"{"@context": "http://example.com/context.jsonld", "@id": "s", "p": {"@id": "o"}}"#;
+    /// let parser = GraphParser::from_format(GraphFormat::JsonLd)
+    ///     .with_base_iri("http://example.com/")?
+    ///     .with_json_ld_context_loader(|_: &str| Ok(r#"{"@context": {"@vocab": "http://example.com/"}}"#.to_owned()));
+    /// store.load_graph_with_parser(file.as_ref(), &parser, &GraphName::DefaultGraph)?;
+    ///
+    /// // we inspect the store contents
+    /// let s = NamedNodeRef::new("http://example.com/s")?;
+    /// let p = NamedNodeRef::new("http://example.com/p")?;
+    /// let o = NamedNodeRef::new("http://example.com/o")?;
+    /// assert!(store.contains(QuadRef::new(s, p, o, None)));
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Warning: This functions saves the triples during the parsing.
+    /// If the parsing fails in the middle of the file, the triples read before stay in the store.
+    /// Use a (memory greedy) [transaction](MemoryStore::transaction()) if you do not want that.
+    ///
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    pub fn load_graph_with_parser<'a>(
+        &self,
+        reader: impl BufRead,
+        parser: &GraphParser,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        let mut store = self;
+        load_graph_with_parser(&mut store, reader, parser, to_graph_name.into(), None)?;
+        Ok(())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store using the options of a [`DatasetParser`].
+    ///
+    /// It allows to provide a [JSON-LD context loader](DatasetParser::with_json_ld_context_loader()).
+    /// If the parser is in [lenient mode](DatasetParser::with_lenient_mode()), only available for [N-Quads](https://www.w3.org/TR/n-quads/), the syntax errors it is able to recover from are given to its callback.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::io::{DatasetFormat, DatasetParser};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = MemoryStore::new();
+    ///
+    /// // insertion
+    /// let file = br#"{"@context": "http://example.com/context.jsonld", "@id": "g", "@graph": {"@id": "s", "p": {"@id": "o"}}}"#;
+    /// let parser = DatasetParser::from_format(DatasetFormat::JsonLd)
+    ///     .with_base_iri("http://example.com/")?
+    ///     .with_json_ld_context_loader(|_: &str| Ok(r#"{"@context": {"@vocab": "http://example.com/"}}"#.to_owned()));
+    /// store.load_dataset_with_parser(file.as_ref(), &parser)?;
+    ///
+    /// // we inspect the store contents
+    /// let s = NamedNodeRef::new("http://example.com/s")?;
+    /// let p = NamedNodeRef::new("http://example.com/p")?;
+    /// let o = NamedNodeRef::new("http://example.com/o")?;
+    /// let g = NamedNodeRef::new("http://example.com/g")?;
+    /// assert!(store.contains(QuadRef::new(s, p, o, g)));
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Warning: This functions saves the quads during the parsing.
+    /// If the parsing fails in the middle of the file, the quads read before stay in the store.
+    /// Use a (memory greedy) [transaction](MemoryStore::transaction()) if you do not want that.
+    ///
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    pub fn load_dataset_with_parser(
+        &self,
+        reader: impl BufRead,
+        parser: &DatasetParser,
+    ) -> Result<(), io::Error> {
+        let mut store = self;
+        load_dataset_with_parser(&mut store, reader, parser, None)?;
         Ok(())
     }
 
@@ -426,12 +657,45 @@ impl MemoryStore {
         writer: impl Write,
         format: GraphFormat,
         from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.dump_graph_with_serializer(
+            writer,
+            &GraphSerializer::from_format(format),
+            from_graph_name,
+        )
+    }
+
+    /// Dumps a store graph into a file using the options of a [`GraphSerializer`].
+    ///
+    /// It allows to set the prefixes and the base IRI used by the Turtle serializer or the JSON-LD context.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::MemoryStore;
+    /// use oxigraph::io::{GraphFormat, GraphSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com/s")?;
+    /// let store = MemoryStore::new();
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph));
+    ///
+    /// let mut buffer = Vec::new();
+    /// let serializer = GraphSerializer::from_format(GraphFormat::Turtle).with_prefix("ex", "http://example.com/")?;
+    /// store.dump_graph_with_serializer(&mut buffer, &serializer, GraphNameRef::DefaultGraph)?;
+    /// assert_eq!(buffer, b"@prefix ex: <http://example.com/> .\n\nex:s ex:s ex:s .\n");
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn dump_graph_with_serializer<'a>(
+        &self,
+        writer: impl Write,
+        serializer: &GraphSerializer,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
     ) -> Result<(), io::Error> {
         dump_graph(
             self.quads_for_pattern(None, None, None, Some(from_graph_name.into()))
                 .map(|q| Ok(q.into())),
             writer,
-            format,
+            serializer,
         )
     }
 
@@ -453,7 +717,20 @@ impl MemoryStore {
     /// # std::io::Result::Ok(())
     /// ```
     pub fn dump_dataset(&self, writer: impl Write, format: DatasetFormat) -> Result<(), io::Error> {
-        dump_dataset(self.iter().map(Ok), writer, format)
+        self.dump_dataset_with_serializer(writer, &DatasetSerializer::from_format(format))
+    }
+
+    /// Dumps the store into a file using the options of a [`DatasetSerializer`].
+    ///
+    /// It allows to set the prefixes and the base IRI used by the TriG serializer or the JSON-LD context.
+    ///
+    /// See [`dump_graph_with_serializer`](MemoryStore::dump_graph_with_serializer()) for a usage example.
+    pub fn dump_dataset_with_serializer(
+        &self,
+        writer: impl Write,
+        serializer: &DatasetSerializer,
+    ) -> Result<(), io::Error> {
+        dump_dataset(self.iter().map(Ok), writer, serializer)
     }
 
     /// Returns all the store named graphs
@@ -1019,6 +1296,58 @@ impl<'a> ReadableEncodedStore for MemoryStore {
     fn contains_encoded_named_graph(&self, graph_name: EncodedTerm) -> Result<bool, Infallible> {
         Ok(self.indexes().gspo.contains_key(&graph_name))
     }
+
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        subject: Option<EncodedTerm>,
+        predicate: Option<EncodedTerm>,
+        object: Option<EncodedTerm>,
+        graph_name: Option<EncodedTerm>,
+    ) -> Option<u64> {
+        let indexes = self.indexes();
+        Some(match graph_name {
+            Some(graph_name) if graph_name.is_default_graph() => index_cardinality(
+                &indexes.default_spo,
+                &indexes.default_pos,
+                &indexes.default_osp,
+                subject,
+                predicate,
+                object,
+            ),
+            Some(graph_name) => {
+                if let (Some(spo), Some(pos), Some(osp)) = (
+                    indexes.gspo.get(&graph_name),
+                    indexes.gpos.get(&graph_name),
+                    indexes.gosp.get(&graph_name),
+                ) {
+                    index_cardinality(spo, pos, osp, subject, predicate, object)
+                } else {
+                    0
+                }
+            }
+            None => index_cardinality(
+                &indexes.default_spo,
+                &indexes.default_pos,
+                &indexes.default_osp,
+                subject,
+                predicate,
+                object,
+            )
+            .saturating_add(index_cardinality(
+                &indexes.spog,
+                &indexes.posg,
+                &indexes.ospg,
+                subject,
+                predicate,
+                object,
+            )),
+        })
+    }
+
+    fn dictionary_id(&self) -> Option<u64> {
+        // The strings are never removed from the dictionary
+        Some(self.dictionary_id.get())
+    }
 }
 
 impl<'a> WritableEncodedStore for &'a MemoryStore {
@@ -1273,6 +1602,83 @@ fn remove_from_quad_map<T: Eq + Hash>(quad_map: &mut QuadMap<T>, e1: &T, e2: &T,
     }
 }
 
+/// Number of entries of each index level read to estimate the cardinality of a pattern
+const CARDINALITY_ESTIMATION_SAMPLE_SIZE: usize = 32;
+
+/// Estimates the number of quads matching a triple pattern using the three indexes of a graph (or of all the graphs).
+fn index_cardinality<V: IndexLevel>(
+    spo: &HashMap<EncodedTerm, HashMap<EncodedTerm, V>>,
+    pos: &HashMap<EncodedTerm, HashMap<EncodedTerm, V>>,
+    osp: &HashMap<EncodedTerm, HashMap<EncodedTerm, V>>,
+    subject: Option<EncodedTerm>,
+    predicate: Option<EncodedTerm>,
+    object: Option<EncodedTerm>,
+) -> u64 {
+    match (subject, predicate, object) {
+        (Some(subject), Some(predicate), Some(object)) => spo
+            .get(&subject)
+            .and_then(|po| po.get(&predicate))
+            .map_or(0, |o| o.estimated_len_for(&object)),
+        (Some(subject), Some(predicate), None) => spo
+            .get(&subject)
+            .and_then(|po| po.get(&predicate))
+            .map_or(0, V::estimated_len),
+        (Some(subject), None, Some(object)) => osp
+            .get(&object)
+            .and_then(|sp| sp.get(&subject))
+            .map_or(0, V::estimated_len),
+        (Some(subject), None, None) => spo.estimated_len_for(&subject),
+        (None, Some(predicate), Some(object)) => pos
+            .get(&predicate)
+            .and_then(|os| os.get(&object))
+            .map_or(0, V::estimated_len),
+        (None, Some(predicate), None) => pos.estimated_len_for(&predicate),
+        (None, None, Some(object)) => osp.estimated_len_for(&object),
+        (None, None, None) => spo.estimated_len(),
+    }
+}
+
+/// A level of the nested maps used as indexes
+trait IndexLevel {
+    /// Estimates the number of quads in this level by extrapolating from its first entries
+    fn estimated_len(&self) -> u64;
+
+    /// Estimates the number of quads under a given key of this level
+    fn estimated_len_for(&self, key: &EncodedTerm) -> u64;
+}
+
+impl IndexLevel for HashSet<EncodedTerm> {
+    fn estimated_len(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn estimated_len_for(&self, key: &EncodedTerm) -> u64 {
+        if self.contains(key) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl<V: IndexLevel> IndexLevel for HashMap<EncodedTerm, V> {
+    fn estimated_len(&self) -> u64 {
+        let (sampled, sum) = self
+            .values()
+            .take(CARDINALITY_ESTIMATION_SAMPLE_SIZE)
+            .fold((0, 0), |(sampled, sum): (u64, u64), v| {
+                (sampled + 1, sum.saturating_add(v.estimated_len()))
+            });
+        sum.saturating_mul(self.len() as u64)
+            .checked_div(sampled)
+            .unwrap_or(0)
+    }
+
+    fn estimated_len_for(&self, key: &EncodedTerm) -> u64 {
+        self.get(key).map_or(0, V::estimated_len)
+    }
+}
+
 fn option_set_flatten<T: Clone>(i: Option<&HashSet<T>>) -> impl Iterator<Item = T> + '_ {
     i.into_iter().flat_map(|s| s.iter().cloned())
 }
@@ -1443,6 +1849,185 @@ impl MemoryTransaction {
     }
 }
 
+impl ReadableStore for MemoryStore {
+    type Error = io::Error;
+
+    fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Box<dyn Iterator<Item = Result<Quad, io::Error>> + Send> {
+        Box::new(
+            MemoryStore::quads_for_pattern(self, subject, predicate, object, graph_name).map(Ok),
+        )
+    }
+
+    fn contains(&self, quad: QuadRef<'_>) -> Result<bool, io::Error> {
+        Ok(MemoryStore::contains(self, quad))
+    }
+
+    fn len(&self) -> Result<usize, io::Error> {
+        Ok(MemoryStore::len(self))
+    }
+
+    fn is_empty(&self) -> Result<bool, io::Error> {
+        Ok(MemoryStore::is_empty(self))
+    }
+
+    fn named_graphs(&self) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, io::Error>> + Send> {
+        Box::new(MemoryStore::named_graphs(self).map(Ok))
+    }
+
+    fn contains_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<bool, io::Error> {
+        Ok(MemoryStore::contains_named_graph(self, graph_name))
+    }
+
+    fn dump_graph(
+        &self,
+        writer: &mut dyn Write,
+        format: GraphFormat,
+        from_graph_name: GraphNameRef<'_>,
+    ) -> Result<(), io::Error> {
+        MemoryStore::dump_graph(self, writer, format, from_graph_name)
+    }
+
+    fn dump_dataset(&self, writer: &mut dyn Write, format: DatasetFormat) -> Result<(), io::Error> {
+        MemoryStore::dump_dataset(self, writer, format)
+    }
+
+    fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        MemoryStore::query_opt(self, query, options)
+    }
+
+    fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        MemoryStore::explain_query_opt(self, query, options, with_stats)
+    }
+
+    fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        MemoryStore::query_prepared(self, query, options)
+    }
+}
+
+impl WritableStore for MemoryStore {
+    fn insert(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        MemoryStore::insert(self, quad);
+        Ok(())
+    }
+
+    fn remove(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        MemoryStore::remove(self, quad);
+        Ok(())
+    }
+
+    fn load_graph(
+        &self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        MemoryStore::load_graph(self, reader, format, to_graph_name, base_iri)
+    }
+
+    fn load_dataset(
+        &self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        MemoryStore::load_dataset(self, reader, format, base_iri)
+    }
+
+    fn insert_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+        MemoryStore::insert_named_graph(self, graph_name.into_owned());
+        Ok(())
+    }
+
+    fn clear_graph(&self, graph_name: GraphNameRef<'_>) -> Result<(), io::Error> {
+        MemoryStore::clear_graph(self, graph_name);
+        Ok(())
+    }
+
+    fn remove_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+        MemoryStore::remove_named_graph(self, graph_name);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        MemoryStore::clear(self);
+        Ok(())
+    }
+
+    fn update_opt(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+        options: UpdateOptions,
+    ) -> Result<(), EvaluationError> {
+        MemoryStore::update_opt(self, update, options)
+    }
+}
+
+impl TransactionalStore for MemoryStore {
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&mut dyn StoreTransaction) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error> {
+        MemoryStore::transaction(self, |transaction| f(transaction))
+    }
+}
+
+impl StoreTransaction for MemoryTransaction {
+    fn insert(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        MemoryTransaction::insert(self, quad.into_owned());
+        Ok(())
+    }
+
+    fn remove(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        MemoryTransaction::remove(self, quad.into_owned());
+        Ok(())
+    }
+
+    fn load_graph(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        MemoryTransaction::load_graph(self, reader, format, to_graph_name, base_iri)
+    }
+
+    fn load_dataset(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        MemoryTransaction::load_dataset(self, reader, format, base_iri)
+    }
+}
+
 impl PartialEq for MemoryStore {
     fn eq(&self, other: &Self) -> bool {
         self.indexes().spog == other.indexes().spog
@@ -1580,7 +2165,22 @@ impl Iterator for MemoryGraphNameIter {
     }
 }
 
-impl StrId for LargeSpur {}
+impl StrId for LargeSpur {
+    #[inline]
+    fn write(self, sink: &mut Vec<u8>) {
+        sink.extend_from_slice(&(self.into_usize() as u64).to_be_bytes())
+    }
+
+    #[inline]
+    fn read(source: &mut impl Read) -> io::Result<Self> {
+        let mut buffer = [0; 8];
+        source.read_exact(&mut buffer)?;
+        usize::try_from(u64::from_be_bytes(buffer))
+            .ok()
+            .and_then(Self::try_from_usize)
+            .ok_or_else(|| invalid_data_error("Invalid string id"))
+    }
+}
 
 // Isomorphism implementation
 
@@ -1,7 +1,8 @@
 //! RDF [RDF dataset](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-dataset) storage implementations.
 
-#[cfg(any(feature = "rocksdb", feature = "sled"))]
-mod binary_encoder;
+mod adapter;
+#[cfg_attr(not(any(feature = "rocksdb", feature = "sled")), allow(dead_code))]
+pub(crate) mod binary_encoder;
 pub mod memory;
 pub(crate) mod numeric_encoder;
 #[cfg(feature = "rocksdb")]
@@ -19,22 +20,35 @@ pub use crate::store::rocksdb::RocksDbStore;
 pub use crate::store::sled::SledStore;
 
 use crate::error::invalid_input_error;
-use crate::io::{DatasetFormat, DatasetSerializer, GraphFormat, GraphSerializer};
+use crate::io::jsonld::{json_ld_triple, read_json_ld};
+use crate::io::read::{
+    handle_syntax_error, lenient_mode_not_supported, syntax_error, LineStarts, SyntaxError,
+};
+use crate::io::{
+    DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser, GraphSerializer,
+};
 use crate::model::*;
+use crate::sparql::{
+    evaluate_prepared_query, evaluate_query, evaluate_update, explain_query, EvaluationError,
+    PreparedQuery, Query, QueryExplanation, QueryOptions, QueryResults, Update, UpdateOptions,
+};
+use crate::store::adapter::EncodingAdapter;
 use crate::store::numeric_encoder::*;
-use oxiri::Iri;
 use rio_api::parser::{QuadsParser, TriplesParser};
 use rio_turtle::{NQuadsParser, NTriplesParser, TriGParser, TurtleError, TurtleParser};
 use rio_xml::{RdfXmlError, RdfXmlParser};
 use std::collections::HashMap;
-use std::convert::Infallible;
+use std::convert::{Infallible, TryInto};
+use std::error::Error;
 use std::io;
 use std::io::{BufRead, Write};
 use std::iter::Iterator;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 pub(crate) trait ReadableEncodedStore: StrLookup {
-    type QuadsIter: Iterator<Item = Result<EncodedQuad<Self::StrId>, Self::Error>> + 'static;
-    type GraphsIter: Iterator<Item = Result<EncodedTerm<Self::StrId>, Self::Error>> + 'static;
+    type QuadsIter: Iterator<Item = Result<EncodedQuad<Self::StrId>, Self::Error>> + Send + 'static;
+    type GraphsIter: Iterator<Item = Result<EncodedTerm<Self::StrId>, Self::Error>> + Send + 'static;
 
     fn encoded_quads_for_pattern(
         &self,
@@ -50,6 +64,68 @@ pub(crate) trait ReadableEncodedStore: StrLookup {
         &self,
         graph_name: EncodedTerm<Self::StrId>,
     ) -> Result<bool, Self::Error>;
+
+    /// Estimates the number of quads returned by [`encoded_quads_for_pattern`](ReadableEncodedStore::encoded_quads_for_pattern()).
+    ///
+    /// It is used by the query planner to order joins. `None` means that the store is not able to provide an estimate.
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        _subject: Option<EncodedTerm<Self::StrId>>,
+        _predicate: Option<EncodedTerm<Self::StrId>>,
+        _object: Option<EncodedTerm<Self::StrId>>,
+        _graph_name: Option<EncodedTerm<Self::StrId>>,
+    ) -> Option<u64> {
+        None
+    }
+
+    /// Returns the current identifier of the store string dictionary.
+    ///
+    /// It is used to reuse query plans between evaluations.
+    /// The identifier should change each time an id returned by [`get_str_id`](StrLookup::get_str_id()) might stop being valid.
+    /// `None` means that the ids are not stable and the plans should never be reused.
+    fn dictionary_id(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl<T: ReadableEncodedStore> ReadableEncodedStore for &T {
+    type QuadsIter = T::QuadsIter;
+    type GraphsIter = T::GraphsIter;
+
+    fn encoded_quads_for_pattern(
+        &self,
+        subject: Option<EncodedTerm<Self::StrId>>,
+        predicate: Option<EncodedTerm<Self::StrId>>,
+        object: Option<EncodedTerm<Self::StrId>>,
+        graph_name: Option<EncodedTerm<Self::StrId>>,
+    ) -> Self::QuadsIter {
+        (*self).encoded_quads_for_pattern(subject, predicate, object, graph_name)
+    }
+
+    fn encoded_named_graphs(&self) -> Self::GraphsIter {
+        (*self).encoded_named_graphs()
+    }
+
+    fn contains_encoded_named_graph(
+        &self,
+        graph_name: EncodedTerm<Self::StrId>,
+    ) -> Result<bool, Self::Error> {
+        (*self).contains_encoded_named_graph(graph_name)
+    }
+
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        subject: Option<EncodedTerm<Self::StrId>>,
+        predicate: Option<EncodedTerm<Self::StrId>>,
+        object: Option<EncodedTerm<Self::StrId>>,
+        graph_name: Option<EncodedTerm<Self::StrId>>,
+    ) -> Option<u64> {
+        (*self).encoded_quads_for_pattern_cardinality(subject, predicate, object, graph_name)
+    }
+
+    fn dictionary_id(&self) -> Option<u64> {
+        (*self).dictionary_id()
+    }
 }
 
 pub(crate) trait WritableEncodedStore: StrEncodingAware {
@@ -75,27 +151,529 @@ pub(crate) trait WritableEncodedStore: StrEncodingAware {
     fn clear(&mut self) -> Result<(), Self::Error>;
 }
 
+/// A read access to an [RDF dataset](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-dataset) storage.
+///
+/// It is implemented by [`MemoryStore`], [`SledStore`](sled::SledStore) and [`RocksDbStore`](rocksdb::RocksDbStore)
+/// and allows to write code that is generic over the storage backend.
+///
+/// The trait methods have the same names and behaviors as the inherent methods of these stores.
+/// The inherent methods take precedence when the method is called on a concrete store type
+/// so the trait methods are meant to be used from code that is generic over the store.
+///
+/// The trait could also be implemented by other storage systems.
+/// Only [`quads_for_pattern`](ReadableStore::quads_for_pattern()), [`named_graphs`](ReadableStore::named_graphs())
+/// and [`contains_named_graph`](ReadableStore::contains_named_graph()) are required, the SPARQL evaluator
+/// is then available using [`query`](ReadableStore::query()).
+///
+/// Usage example:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{EvaluationError, QueryResults};
+/// use oxigraph::store::WritableStore;
+///
+/// fn insert_and_count_subjects(
+///     store: &(impl WritableStore + Clone + Send + Sync + 'static),
+///     quad: QuadRef<'_>,
+/// ) -> Result<usize, EvaluationError> {
+///     store.insert(quad).map_err(Into::<std::io::Error>::into)?;
+///     if let QueryResults::Solutions(solutions) = store.query("SELECT DISTINCT ?s WHERE { ?s ?p ?o }")? {
+///         Ok(solutions.count())
+///     } else {
+///         Ok(0)
+///     }
+/// }
+///
+/// let store = MemoryStore::new();
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// assert_eq!(insert_and_count_subjects(&store, QuadRef::new(ex, ex, ex, None))?, 1);
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+pub trait ReadableStore {
+    /// The error returned by the storage operations.
+    ///
+    /// The operations that also read or write files like [`dump_graph`](ReadableStore::dump_graph())
+    /// or [`load_graph`](WritableStore::load_graph()) convert it to an [`io::Error`].
+    /// [`MemoryStore`], [`SledStore`](sled::SledStore) and [`RocksDbStore`](rocksdb::RocksDbStore) use [`io::Error`].
+    type Error: Error + Into<io::Error> + Send + Sync + 'static;
+
+    /// Retrieves quads with a filter on each quad component
+    ///
+    /// A `None` graph name matches the quads of all the graphs, including the default graph.
+    fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Box<dyn Iterator<Item = Result<Quad, Self::Error>> + Send>;
+
+    /// Checks if this store contains a given quad
+    fn contains(&self, quad: QuadRef<'_>) -> Result<bool, Self::Error> {
+        Ok(self
+            .quads_for_pattern(
+                Some(quad.subject),
+                Some(quad.predicate),
+                Some(quad.object),
+                Some(quad.graph_name),
+            )
+            .next()
+            .transpose()?
+            .is_some())
+    }
+
+    /// Returns the number of quads in the store
+    fn len(&self) -> Result<usize, Self::Error> {
+        self.quads_for_pattern(None, None, None, None)
+            .try_fold(0, |count, quad| quad.map(|_| count + 1))
+    }
+
+    /// Returns if the store is empty
+    fn is_empty(&self) -> Result<bool, Self::Error> {
+        Ok(self
+            .quads_for_pattern(None, None, None, None)
+            .next()
+            .transpose()?
+            .is_none())
+    }
+
+    /// Returns all the store named graphs
+    fn named_graphs(
+        &self,
+    ) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, Self::Error>> + Send>;
+
+    /// Checks if the store contains a given graph
+    fn contains_named_graph(
+        &self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<bool, Self::Error>;
+
+    /// Dumps a store graph into a file.
+    fn dump_graph(
+        &self,
+        writer: &mut dyn Write,
+        format: GraphFormat,
+        from_graph_name: GraphNameRef<'_>,
+    ) -> Result<(), io::Error> {
+        dump_graph(
+            self.quads_for_pattern(None, None, None, Some(from_graph_name))
+                .map(|q| Ok(q.map_err(Into::into)?.into())),
+            writer,
+            &GraphSerializer::from_format(format),
+        )
+    }
+
+    /// Dumps the store into a file.
+    fn dump_dataset(&self, writer: &mut dyn Write, format: DatasetFormat) -> Result<(), io::Error> {
+        dump_dataset(
+            self.quads_for_pattern(None, None, None, None)
+                .map(|q| q.map_err(Into::into)),
+            writer,
+            &DatasetSerializer::from_format(format),
+        )
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
+    fn query(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, EvaluationError>
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        self.query_opt(query, QueryOptions::default())
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    ///
+    /// The default implementation evaluates the query against [`quads_for_pattern`](ReadableStore::quads_for_pattern()).
+    fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError>
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        evaluate_query(EncodingAdapter::new(self.clone()), query, options)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options
+    /// and returns an explanation of its evaluation.
+    ///
+    /// See [`QueryExplanation`] for more details.
+    fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    >
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        explain_query(
+            EncodingAdapter::new(self.clone()),
+            query,
+            options,
+            with_stats,
+        )
+    }
+
+    /// Executes a [`PreparedQuery`] with some options.
+    ///
+    /// The default implementation evaluates the query against [`quads_for_pattern`](ReadableStore::quads_for_pattern())
+    /// and never reuses the query evaluation plan.
+    fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError>
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        evaluate_prepared_query(EncodingAdapter::new(self.clone()), query, options)
+    }
+}
+
+/// A write access to an [RDF dataset](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-dataset) storage.
+///
+/// It is implemented by [`MemoryStore`], [`SledStore`](sled::SledStore) and [`RocksDbStore`](rocksdb::RocksDbStore).
+///
+/// The writes are not done in a transaction: the operations on many quads like
+/// [`load_graph`](WritableStore::load_graph()) or [`update`](WritableStore::update()) might be only partially applied if they fail.
+/// See [`TransactionalStore`] for ACID writes and atomic file loading.
+pub trait WritableStore: ReadableStore {
+    /// Adds a quad to this store
+    fn insert(&self, quad: QuadRef<'_>) -> Result<(), Self::Error>;
+
+    /// Removes a quad from this store
+    fn remove(&self, quad: QuadRef<'_>) -> Result<(), Self::Error>;
+
+    /// Loads a graph file (i.e. triples) into the store
+    ///
+    /// The loading is not atomic: if the parsing fails in the middle of the file, the triples read before might stay in the store.
+    /// Use [`TransactionalStore::load_graph_atomic`] if you do not want that.
+    /// The default implementation inserts the triples one by one using [`insert`](WritableStore::insert()).
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    fn load_graph(
+        &self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        let mut parser = GraphParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(invalid_input_error)?;
+        }
+        for triple in parser.read_triples(reader)? {
+            self.insert(triple?.as_ref().in_graph(to_graph_name))
+                .map_err(Into::into)?;
+        }
+        Ok(())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store.
+    ///
+    /// The loading is not atomic: if the parsing fails in the middle of the file, the quads read before might stay in the store.
+    /// Use [`TransactionalStore::load_dataset_atomic`] if you do not want that.
+    /// The default implementation inserts the quads one by one using [`insert`](WritableStore::insert()).
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    fn load_dataset(
+        &self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        let mut parser = DatasetParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(invalid_input_error)?;
+        }
+        for quad in parser.read_quads(reader)? {
+            self.insert(quad?.as_ref()).map_err(Into::into)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a graph into this store
+    fn insert_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), Self::Error>;
+
+    /// Clears a graph from this store.
+    fn clear_graph(&self, graph_name: GraphNameRef<'_>) -> Result<(), Self::Error> {
+        let quads = self
+            .quads_for_pattern(None, None, None, Some(graph_name))
+            .collect::<Result<Vec<_>, _>>()?;
+        for quad in quads {
+            self.remove(quad.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Removes a graph from this store.
+    fn remove_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), Self::Error>;
+
+    /// Clears the store.
+    fn clear(&self) -> Result<(), Self::Error> {
+        let graph_names = self.named_graphs().collect::<Result<Vec<_>, _>>()?;
+        for graph_name in graph_names {
+            self.remove_named_graph(graph_name.as_ref())?;
+        }
+        self.clear_graph(GraphNameRef::DefaultGraph)
+    }
+
+    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/).
+    fn update(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+    ) -> Result<(), EvaluationError>
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        self.update_opt(update, UpdateOptions::default())
+    }
+
+    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/) with some options.
+    ///
+    /// The default implementation evaluates the update using [`quads_for_pattern`](ReadableStore::quads_for_pattern()),
+    /// [`insert`](WritableStore::insert()) and [`remove`](WritableStore::remove()).
+    fn update_opt(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+        options: UpdateOptions,
+    ) -> Result<(), EvaluationError>
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        let mut adapter = EncodingAdapter::new(self.clone());
+        evaluate_update(
+            adapter.clone(),
+            &mut adapter,
+            update.try_into().map_err(|e| e.into())?,
+            options,
+        )
+    }
+}
+
+/// A store supporting ACID transactions.
+///
+/// It is implemented by [`MemoryStore`], [`SledStore`](sled::SledStore) and [`RocksDbStore`](rocksdb::RocksDbStore).
+///
+/// The transaction is executed if the given closure returns `Ok`.
+/// The transaction is rollbacked if the closure returns `Err`.
+/// The closure might be called multiple times if the storage needs to retry the transaction after a conflict.
+///
+/// Usage example:
+/// ```
+/// use oxigraph::MemoryStore;
+/// use oxigraph::model::*;
+/// use oxigraph::store::TransactionalStore;
+///
+/// let store = MemoryStore::new();
+///
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// let quad = QuadRef::new(ex, ex, ex, ex);
+///
+/// fn insert_in_transaction(store: &impl TransactionalStore, quad: QuadRef<'_>) -> std::io::Result<bool> {
+///     store.transaction(&mut |transaction| transaction.insert(quad))?;
+///     store.contains(quad).map_err(Into::into)
+/// }
+///
+/// assert!(insert_in_transaction(&store, quad)?);
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+///
+/// The errors of the transactions are [`io::Error`]s because they mix the storage errors with the file parsing errors of
+/// [`StoreTransaction::load_graph`] and [`StoreTransaction::load_dataset`].
+pub trait TransactionalStore: WritableStore {
+    /// Executes an ACID transaction.
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&mut dyn StoreTransaction) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error>;
+
+    /// Loads a graph file (i.e. triples) into the store atomically.
+    ///
+    /// If the parsing fails in the middle of the file, nothing is written.
+    /// The default implementation parses the whole file in main memory before writing it in a [transaction](TransactionalStore::transaction()).
+    /// Do not use it for big files.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    fn load_graph_atomic(
+        &self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        let mut parser = GraphParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(invalid_input_error)?;
+        }
+        let triples = parser
+            .read_triples(reader)?
+            .collect::<Result<Vec<_>, _>>()?;
+        self.transaction(&mut |transaction| {
+            for triple in &triples {
+                transaction.insert(triple.as_ref().in_graph(to_graph_name))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store atomically.
+    ///
+    /// If the parsing fails in the middle of the file, nothing is written.
+    /// The default implementation parses the whole file in main memory before writing it in a [transaction](TransactionalStore::transaction()).
+    /// Do not use it for big files.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    fn load_dataset_atomic(
+        &self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        let mut parser = DatasetParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(invalid_input_error)?;
+        }
+        let quads = parser.read_quads(reader)?.collect::<Result<Vec<_>, _>>()?;
+        self.transaction(&mut |transaction| {
+            for quad in &quads {
+                transaction.insert(quad.as_ref())?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The operations available during a [`TransactionalStore`] transaction.
+pub trait StoreTransaction {
+    /// Adds a quad to this store during the transaction.
+    fn insert(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error>;
+
+    /// Removes a quad from this store during the transaction.
+    fn remove(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error>;
+
+    /// Loads a graph file (i.e. triples) into the store during the transaction.
+    fn load_graph(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error>;
+
+    /// Loads a dataset file (i.e. quads) into the store during the transaction.
+    fn load_dataset(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error>;
+}
+
+/// Loads a graph file into the store
+///
+/// If `on_syntax_error` is set, the syntax errors the parser is able to recover from are given to it instead of stopping the loading.
+/// An error is returned if the format is not able to recover from syntax errors.
 pub(crate) fn load_graph<S: WritableEncodedStore + StrContainer>(
     store: &mut S,
     reader: impl BufRead,
     format: GraphFormat,
     to_graph_name: GraphNameRef<'_>,
     base_iri: Option<&str>,
+    on_syntax_error: Option<&mut dyn FnMut(SyntaxError)>,
 ) -> Result<(), StoreOrParseError<S::Error>> {
-    let base_iri = if let Some(base_iri) = base_iri {
-        Some(Iri::parse(base_iri.into()).map_err(invalid_input_error)?)
-    } else {
-        None
+    let mut parser = GraphParser::from_format(format);
+    if let Some(base_iri) = base_iri {
+        parser = parser
+            .with_base_iri(base_iri)
+            .map_err(invalid_input_error)?;
+    }
+    load_graph_with_parser(store, reader, &parser, to_graph_name, on_syntax_error)
+}
+
+/// Loads a graph file into the store using the options of `parser`
+///
+/// If `on_syntax_error` is not set, the parser lenient mode callback is used if there is one.
+pub(crate) fn load_graph_with_parser<S: WritableEncodedStore + StrContainer>(
+    store: &mut S,
+    reader: impl BufRead,
+    parser: &GraphParser,
+    to_graph_name: GraphNameRef<'_>,
+    on_syntax_error: Option<&mut dyn FnMut(SyntaxError)>,
+) -> Result<(), StoreOrParseError<S::Error>> {
+    let mut parser_on_syntax_error = parser
+        .on_syntax_error
+        .clone()
+        .map(|on_syntax_error| move |e| on_syntax_error(e));
+    let on_syntax_error: Option<&mut dyn FnMut(SyntaxError)> = match on_syntax_error {
+        Some(on_syntax_error) => Some(on_syntax_error),
+        None => match &mut parser_on_syntax_error {
+            Some(on_syntax_error) => Some(on_syntax_error),
+            None => None,
+        },
     };
-    match format {
-        GraphFormat::NTriples => {
-            load_from_triple_parser(store, NTriplesParser::new(reader), to_graph_name)
-        }
-        GraphFormat::Turtle => {
-            load_from_triple_parser(store, TurtleParser::new(reader, base_iri), to_graph_name)
-        }
-        GraphFormat::RdfXml => {
-            load_from_triple_parser(store, RdfXmlParser::new(reader, base_iri), to_graph_name)
+    if on_syntax_error.is_some() && parser.format != GraphFormat::NTriples {
+        return Err(lenient_mode_not_supported(parser.format.media_type()).into());
+    }
+    let base_iri = parser.base_iri.clone();
+    let line_starts = LineStarts::new();
+    let reader = line_starts.track(reader);
+    match parser.format {
+        GraphFormat::NTriples => load_from_triple_parser(
+            store,
+            NTriplesParser::new(reader),
+            to_graph_name,
+            &line_starts,
+            on_syntax_error,
+        ),
+        GraphFormat::Turtle => load_from_triple_parser(
+            store,
+            TurtleParser::new(reader, base_iri),
+            to_graph_name,
+            &line_starts,
+            None,
+        ),
+        GraphFormat::RdfXml => load_from_triple_parser(
+            store,
+            RdfXmlParser::new(reader, base_iri),
+            to_graph_name,
+            &line_starts,
+            None,
+        ),
+        GraphFormat::JsonLd => {
+            for quad in read_json_ld(reader, base_iri, parser.json_ld_context_loader())? {
+                let triple = json_ld_triple(quad)?;
+                let quad = store
+                    .encode_quad(triple.as_ref().in_graph(to_graph_name))
+                    .map_err(StoreOrParseError::Store)?;
+                store
+                    .insert_encoded(&quad)
+                    .map_err(StoreOrParseError::Store)?;
+            }
+            Ok(())
         }
     }
 }
@@ -104,6 +682,8 @@ fn load_from_triple_parser<S: WritableEncodedStore + StrContainer, P: TriplesPar
     store: &mut S,
     mut parser: P,
     to_graph_name: GraphNameRef<'_>,
+    line_starts: &LineStarts,
+    mut on_syntax_error: Option<&mut dyn FnMut(SyntaxError)>,
 ) -> Result<(), StoreOrParseError<S::Error>>
 where
     StoreOrParseError<S::Error>: From<P::Error>,
@@ -112,77 +692,239 @@ where
     let to_graph_name = store
         .encode_graph_name(to_graph_name)
         .map_err(StoreOrParseError::Store)?;
-    parser.parse_all(&mut move |t| {
-        let quad = store
-            .encode_rio_triple_in_graph(t, to_graph_name, &mut bnode_map)
-            .map_err(StoreOrParseError::Store)?;
-        store
-            .insert_encoded(&quad)
-            .map_err(StoreOrParseError::Store)?;
-        Ok(())
-    })
+    while !parser.is_end() {
+        let result = parser.parse_step(&mut |t| {
+            let quad = store
+                .encode_rio_triple_in_graph(t, to_graph_name, &mut bnode_map)
+                .map_err(StoreOrParseError::Store)?;
+            store
+                .insert_encoded(&quad)
+                .map_err(StoreOrParseError::Store)?;
+            Ok(())
+        });
+        handle_load_error(result, line_starts, &mut on_syntax_error)?;
+    }
+    Ok(())
 }
 
 fn dump_graph(
     triples: impl Iterator<Item = Result<Triple, io::Error>>,
     writer: impl Write,
-    format: GraphFormat,
+    serializer: &GraphSerializer,
 ) -> Result<(), io::Error> {
-    let mut writer = GraphSerializer::from_format(format).triple_writer(writer)?;
+    let mut writer = serializer.triple_writer(writer)?;
     for triple in triples {
         writer.write(&triple?)?;
     }
     writer.finish()
 }
 
+/// Loads a dataset file into the store
+///
+/// If `on_syntax_error` is set, the syntax errors the parser is able to recover from are given to it instead of stopping the loading.
+/// An error is returned if the format is not able to recover from syntax errors.
 fn load_dataset<S: WritableEncodedStore + StrContainer>(
     store: &mut S,
     reader: impl BufRead,
     format: DatasetFormat,
     base_iri: Option<&str>,
+    on_syntax_error: Option<&mut dyn FnMut(SyntaxError)>,
 ) -> Result<(), StoreOrParseError<S::Error>> {
-    let base_iri = if let Some(base_iri) = base_iri {
-        Some(Iri::parse(base_iri.into()).map_err(invalid_input_error)?)
-    } else {
-        None
+    let mut parser = DatasetParser::from_format(format);
+    if let Some(base_iri) = base_iri {
+        parser = parser
+            .with_base_iri(base_iri)
+            .map_err(invalid_input_error)?;
+    }
+    load_dataset_with_parser(store, reader, &parser, on_syntax_error)
+}
+
+/// Loads a dataset file into the store using the options of `parser`
+///
+/// If `on_syntax_error` is not set, the parser lenient mode callback is used if there is one.
+fn load_dataset_with_parser<S: WritableEncodedStore + StrContainer>(
+    store: &mut S,
+    reader: impl BufRead,
+    parser: &DatasetParser,
+    on_syntax_error: Option<&mut dyn FnMut(SyntaxError)>,
+) -> Result<(), StoreOrParseError<S::Error>> {
+    let mut parser_on_syntax_error = parser
+        .on_syntax_error
+        .clone()
+        .map(|on_syntax_error| move |e| on_syntax_error(e));
+    let on_syntax_error: Option<&mut dyn FnMut(SyntaxError)> = match on_syntax_error {
+        Some(on_syntax_error) => Some(on_syntax_error),
+        None => match &mut parser_on_syntax_error {
+            Some(on_syntax_error) => Some(on_syntax_error),
+            None => None,
+        },
     };
-    match format {
-        DatasetFormat::NQuads => load_from_quad_parser(store, NQuadsParser::new(reader)),
-        DatasetFormat::TriG => load_from_quad_parser(store, TriGParser::new(reader, base_iri)),
+    if on_syntax_error.is_some() && parser.format != DatasetFormat::NQuads {
+        return Err(lenient_mode_not_supported(parser.format.media_type()).into());
+    }
+    let base_iri = parser.base_iri.clone();
+    let line_starts = LineStarts::new();
+    let reader = line_starts.track(reader);
+    match parser.format {
+        DatasetFormat::NQuads => load_from_quad_parser(
+            store,
+            NQuadsParser::new(reader),
+            &line_starts,
+            on_syntax_error,
+        ),
+        DatasetFormat::TriG => {
+            load_from_quad_parser(store, TriGParser::new(reader, base_iri), &line_starts, None)
+        }
+        DatasetFormat::JsonLd => {
+            for quad in read_json_ld(reader, base_iri, parser.json_ld_context_loader())? {
+                let quad = store
+                    .encode_quad(quad.as_ref())
+                    .map_err(StoreOrParseError::Store)?;
+                store
+                    .insert_encoded(&quad)
+                    .map_err(StoreOrParseError::Store)?;
+            }
+            Ok(())
+        }
     }
 }
 
 fn load_from_quad_parser<S: WritableEncodedStore + StrContainer, P: QuadsParser>(
     store: &mut S,
     mut parser: P,
+    line_starts: &LineStarts,
+    mut on_syntax_error: Option<&mut dyn FnMut(SyntaxError)>,
 ) -> Result<(), StoreOrParseError<S::Error>>
 where
     StoreOrParseError<S::Error>: From<P::Error>,
 {
     let mut bnode_map = HashMap::default();
-    parser.parse_all(&mut move |q| {
-        let quad = store
-            .encode_rio_quad(q, &mut bnode_map)
-            .map_err(StoreOrParseError::Store)?;
-        store
-            .insert_encoded(&quad)
-            .map_err(StoreOrParseError::Store)?;
-        Ok(())
-    })
+    while !parser.is_end() {
+        let result = parser.parse_step(&mut |q| {
+            let quad = store
+                .encode_rio_quad(q, &mut bnode_map)
+                .map_err(StoreOrParseError::Store)?;
+            store
+                .insert_encoded(&quad)
+                .map_err(StoreOrParseError::Store)?;
+            Ok(())
+        });
+        handle_load_error(result, line_starts, &mut on_syntax_error)?;
+    }
+    Ok(())
+}
+
+/// Fills the position of the syntax errors and gives them to `on_syntax_error` if it is set
+fn handle_load_error<S>(
+    result: Result<(), StoreOrParseError<S>>,
+    line_starts: &LineStarts,
+    on_syntax_error: &mut Option<&mut dyn FnMut(SyntaxError)>,
+) -> Result<(), StoreOrParseError<S>> {
+    match result {
+        Err(StoreOrParseError::Parse(error)) => {
+            let error = line_starts.locate(error);
+            if let Some(on_syntax_error) = on_syntax_error {
+                handle_syntax_error(error, *on_syntax_error).map_err(StoreOrParseError::Parse)
+            } else {
+                Err(StoreOrParseError::Parse(error))
+            }
+        }
+        result => result,
+    }
 }
 
 fn dump_dataset(
     quads: impl Iterator<Item = Result<Quad, io::Error>>,
     writer: impl Write,
-    format: DatasetFormat,
+    serializer: &DatasetSerializer,
 ) -> Result<(), io::Error> {
-    let mut writer = DatasetSerializer::from_format(format).quad_writer(writer)?;
+    let mut writer = serializer.quad_writer(writer)?;
     for quad in quads {
         writer.write(&quad?)?;
     }
     writer.finish()
 }
 
+/// Maximal number of quads read to estimate the cardinality of a pattern with a bound subject, object or named graph
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+const CARDINALITY_PROBE_LIMIT: usize = 100;
+
+/// Estimates a pattern cardinality using the numbers of quads maintained by the store.
+///
+/// `count` returns the number of quads in the default graph (if its first argument is `true`) or in the named graphs,
+/// only the ones using the given predicate if it is set.
+/// Bound subjects, objects and named graphs are usually selective and are not covered by the statistics:
+/// the quads returned by `quads` are counted up to [`CARDINALITY_PROBE_LIMIT`] and the statistics are only used if there are more.
+///
+/// The estimate is only exact for the patterns matching at most [`CARDINALITY_PROBE_LIMIT`] quads.
+/// Above it, the number of quads using the predicate (or all the quads if it is not bound) is returned:
+/// it is an upper bound that might be orders of magnitude too high for patterns binding a frequent object and a predicate used by many other quads.
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+fn estimate_cardinality_from_statistics<I: StrId, T, E, Q: Iterator<Item = Result<T, E>>>(
+    subject: Option<EncodedTerm<I>>,
+    predicate: Option<EncodedTerm<I>>,
+    object: Option<EncodedTerm<I>>,
+    graph_name: Option<EncodedTerm<I>>,
+    quads: impl FnOnce() -> Q,
+    count: impl Fn(bool, Option<EncodedTerm<I>>) -> Result<u64, E>,
+) -> Option<u64> {
+    let (in_default_graph, in_named_graphs) = match graph_name {
+        Some(graph_name) => (
+            graph_name.is_default_graph(),
+            !graph_name.is_default_graph(),
+        ),
+        None => (true, true),
+    };
+    let mut probed = 0;
+    if subject.is_some() || object.is_some() || (graph_name.is_some() && in_named_graphs) {
+        for quad in quads().take(CARDINALITY_PROBE_LIMIT + 1) {
+            quad.ok()?;
+            probed += 1;
+        }
+        if probed <= CARDINALITY_PROBE_LIMIT {
+            return Some(probed as u64);
+        }
+    }
+    let mut estimate = 0_u64;
+    if in_default_graph {
+        estimate = estimate.saturating_add(count(true, predicate).ok()?);
+    }
+    if in_named_graphs {
+        estimate = estimate.saturating_add(count(false, predicate).ok()?);
+    }
+    // The statistics might be slightly off if there were concurrent writes
+    Some(estimate.max(probed as u64))
+}
+
+/// The identifier of a store string dictionary returned by [`ReadableEncodedStore::dictionary_id`]
+///
+/// It is shared between the clones of a store and is unique among all the stores opened by the process.
+#[derive(Clone)]
+pub(crate) struct DictionaryId(Arc<AtomicU64>);
+
+static NEXT_DICTIONARY_ID: AtomicU64 = AtomicU64::new(0);
+
+impl DictionaryId {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU64::new(
+            NEXT_DICTIONARY_ID.fetch_add(1, Ordering::Relaxed),
+        )))
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Changes the identifier, to be called after some strings have been removed from the dictionary
+    #[cfg_attr(not(any(feature = "rocksdb", feature = "sled")), allow(dead_code))]
+    pub fn renew(&self) {
+        self.0.store(
+            NEXT_DICTIONARY_ID.fetch_add(1, Ordering::Relaxed),
+            Ordering::Release,
+        )
+    }
+}
+
 pub(crate) enum StoreOrParseError<S> {
     Store(S),
     Parse(io::Error),
@@ -190,13 +932,13 @@ pub(crate) enum StoreOrParseError<S> {
 
 impl<S> From<TurtleError> for StoreOrParseError<S> {
     fn from(error: TurtleError) -> Self {
-        Self::Parse(error.into())
+        Self::Parse(syntax_error(error))
     }
 }
 
 impl<S> From<RdfXmlError> for StoreOrParseError<S> {
     fn from(error: RdfXmlError) -> Self {
-        Self::Parse(error.into())
+        Self::Parse(syntax_error(error))
     }
 }
 
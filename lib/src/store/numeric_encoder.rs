@@ -13,9 +13,16 @@ use std::error::Error;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::io::Read;
 use std::{fmt, io, str};
 
-pub trait StrId: Eq + Debug + Copy + Hash {}
+pub trait StrId: Eq + Debug + Copy + Hash + Send + Sync + 'static {
+    /// Writes the id in a binary form readable by [`read`](StrId::read())
+    fn write(self, sink: &mut Vec<u8>);
+
+    /// Reads an id written using [`write`](StrId::write())
+    fn read(source: &mut impl Read) -> io::Result<Self>;
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum EncodedTerm<I: StrId> {
@@ -445,6 +452,34 @@ impl<I: StrId> EncodedTerm<I> {
             Self::DayTimeDurationLiteral(value) => EncodedTerm::DayTimeDurationLiteral(value),
         })
     }
+
+    /// Calls `callback` with each string id used by the term
+    #[cfg_attr(not(any(feature = "rocksdb", feature = "sled")), allow(dead_code))]
+    pub fn on_each_id(&self, mut callback: impl FnMut(I)) {
+        match self {
+            Self::NamedNode { iri_id } => callback(*iri_id),
+            Self::BigBlankNode { id_id } => callback(*id_id),
+            Self::BigStringLiteral { value_id } => callback(*value_id),
+            Self::SmallBigLangStringLiteral { language_id, .. } => callback(*language_id),
+            Self::BigSmallLangStringLiteral { value_id, .. } => callback(*value_id),
+            Self::BigBigLangStringLiteral {
+                value_id,
+                language_id,
+            } => {
+                callback(*value_id);
+                callback(*language_id);
+            }
+            Self::SmallTypedLiteral { datatype_id, .. } => callback(*datatype_id),
+            Self::BigTypedLiteral {
+                value_id,
+                datatype_id,
+            } => {
+                callback(*value_id);
+                callback(*datatype_id);
+            }
+            _ => (),
+        }
+    }
 }
 
 impl<I: StrId> From<bool> for EncodedTerm<I> {
@@ -571,6 +606,16 @@ pub(crate) trait StrLookup: StrEncodingAware {
     fn get_str_id(&self, value: &str) -> Result<Option<Self::StrId>, Self::Error>;
 }
 
+impl<T: StrLookup> StrLookup for &T {
+    fn get_str(&self, id: Self::StrId) -> Result<Option<String>, Self::Error> {
+        (*self).get_str(id)
+    }
+
+    fn get_str_id(&self, value: &str) -> Result<Option<Self::StrId>, Self::Error> {
+        (*self).get_str_id(value)
+    }
+}
+
 pub(crate) trait StrContainer: StrEncodingAware {
     fn insert_str(&mut self, value: &str) -> Result<Self::StrId, Self::Error>;
 }
@@ -1,29 +1,44 @@
 //! Store based on the [RocksDB](https://rocksdb.org/) key-value database.
 
-use crate::error::invalid_data_error;
-use crate::io::{DatasetFormat, GraphFormat};
+use crate::error::{invalid_data_error, invalid_input_error, UnwrapInfallible};
+use crate::io::read::{syntax_error, LineStarts, SyntaxError};
+use crate::io::{
+    DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser, GraphSerializer,
+};
 use crate::model::*;
 use crate::sparql::{
-    evaluate_query, evaluate_update, EvaluationError, Query, QueryOptions, QueryResults, Update,
-    UpdateOptions,
+    evaluate_prepared_query, evaluate_query, evaluate_update, explain_query, EvaluationError,
+    PreparedQuery, Query, QueryExplanation, QueryOptions, QueryResults, Update, UpdateOptions,
 };
 use crate::store::binary_encoder::*;
 use crate::store::numeric_encoder::{
     Decoder, ReadEncoder, StrContainer, StrEncodingAware, StrLookup, WriteEncoder,
 };
 use crate::store::{
-    dump_dataset, dump_graph, get_encoded_quad_pattern, load_dataset, load_graph,
-    ReadableEncodedStore, WritableEncodedStore,
+    dump_dataset, dump_graph, estimate_cardinality_from_statistics, get_encoded_quad_pattern,
+    load_dataset, load_dataset_with_parser, load_graph, load_graph_with_parser, DictionaryId,
+    ReadableEncodedStore, ReadableStore, StoreTransaction, TransactionalStore,
+    WritableEncodedStore, WritableStore,
 };
+use rand::random;
+use rio_api::model as rio;
+use rio_api::parser::{QuadsParser, TriplesParser};
+use rio_turtle::{NQuadsParser, NTriplesParser, TurtleError};
 use rocksdb::*;
-use std::collections::HashMap;
-use std::convert::TryInto;
+use siphasher::sip128::{Hasher128, SipHasher24};
+use std::collections::{HashMap, HashSet};
+use std::convert::{Infallible, TryFrom, TryInto};
+use std::fs::{create_dir, remove_dir_all};
+use std::hash::Hasher;
 use std::io;
 use std::io::{BufRead, Write};
 use std::iter::{once, Once};
-use std::mem::{take, transmute};
-use std::path::Path;
-use std::sync::Arc;
+use std::mem::{size_of, take, transmute};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, JoinHandle};
 use std::{fmt, str};
 
 /// Store based on the [RocksDB](https://rocksdb.org/) key-value database.
@@ -62,22 +77,28 @@ use std::{fmt, str};
 #[derive(Clone)]
 pub struct RocksDbStore {
     db: Arc<DB>,
+    snapshot: Option<Arc<StaticSnapshot>>,
+    dictionary_id: DictionaryId,
+    write_lock: Arc<RwLock<()>>,
+    /// Held while reading the statistics and writing a batch updating them
+    statistics_lock: Arc<Mutex<()>>,
+    is_secondary: bool,
 }
 
 type EncodedTerm = crate::store::numeric_encoder::EncodedTerm<StrHash>;
 type EncodedQuad = crate::store::numeric_encoder::EncodedQuad<StrHash>;
 
-const ID2STR_CF: &str = "id2str";
-const SPOG_CF: &str = "spog";
-const POSG_CF: &str = "posg";
-const OSPG_CF: &str = "ospg";
-const GSPO_CF: &str = "gspo";
-const GPOS_CF: &str = "gpos";
-const GOSP_CF: &str = "gosp";
-const DSPO_CF: &str = "dspo";
-const DPOS_CF: &str = "dpos";
-const DOSP_CF: &str = "dosp";
-const GRAPHS_CF: &str = "graphs";
+const ID2STR_CF: &str = StorageIndex::Id2Str.name();
+const SPOG_CF: &str = StorageIndex::Spog.name();
+const POSG_CF: &str = StorageIndex::Posg.name();
+const OSPG_CF: &str = StorageIndex::Ospg.name();
+const GSPO_CF: &str = StorageIndex::Gspo.name();
+const GPOS_CF: &str = StorageIndex::Gpos.name();
+const GOSP_CF: &str = StorageIndex::Gosp.name();
+const DSPO_CF: &str = StorageIndex::Dspo.name();
+const DPOS_CF: &str = StorageIndex::Dpos.name();
+const DOSP_CF: &str = StorageIndex::Dosp.name();
+const GRAPHS_CF: &str = StorageIndex::Graphs.name();
 
 const COLUMN_FAMILIES: [&str; 11] = [
     ID2STR_CF, SPOG_CF, POSG_CF, OSPG_CF, GSPO_CF, GPOS_CF, GOSP_CF, DSPO_CF, DPOS_CF, DOSP_CF,
@@ -86,6 +107,17 @@ const COLUMN_FAMILIES: [&str; 11] = [
 
 const MAX_TRANSACTION_SIZE: usize = 1024;
 
+/// The quad indexes column families in the order of [`RocksDbBulkLoader`] buffers
+const QUAD_INDEX_CFS: [&str; 9] = [
+    SPOG_CF, POSG_CF, OSPG_CF, GSPO_CF, GPOS_CF, GOSP_CF, DSPO_CF, DPOS_CF, DOSP_CF,
+];
+/// Size in bytes of the chunks of lines of N-Triples and N-Quads files parsed in parallel by the bulk loader
+const BULK_LOAD_CHUNK_SIZE: usize = 1024 * 1024;
+/// Number of quads parsed by the bulk loader from the files in the other formats before being sent to the encoding threads
+const BULK_LOAD_BATCH_SIZE: usize = 10_000;
+/// Default number of threads used by the bulk loader to parse and encode the quads
+const DEFAULT_BULK_LOAD_THREADS: usize = 4;
+
 impl RocksDbStore {
     /// Opens a [`RocksDbStore`]()
     pub fn open(path: impl AsRef<Path>) -> Result<Self, io::Error> {
@@ -94,37 +126,152 @@ impl RocksDbStore {
         options.create_missing_column_families(true);
         options.set_compaction_style(DBCompactionStyle::Universal);
 
-        let this = Self {
-            db: Arc::new(DB::open_cf(&options, path, &COLUMN_FAMILIES).map_err(map_err)?),
-        };
+        let this = Self::new(
+            DB::open_cf(&options, path, COLUMN_FAMILIES).map_err(map_err)?,
+            false,
+        );
+        let version = this.migrate(MIGRATIONS)?;
+        check_version(version)?;
+        Ok(this)
+    }
 
-        let mut version = this.ensure_version()?;
-        if version == 0 {
-            // We migrate to v1
-            let mut transaction = this.auto_batch_writer();
-            for quad in this.encoded_quads_for_pattern(None, None, None, None) {
-                let quad = quad?;
-                if !quad.graph_name.is_default_graph() {
-                    transaction.insert_encoded_named_graph(quad.graph_name)?;
-                }
-            }
-            transaction.apply()?;
-            version = 1;
-            this.set_version(version)?;
-            this.flush()?;
+    /// Opens a read-only [`RocksDbStore`]().
+    ///
+    /// Many processes could open the same database in read-only mode.
+    /// The store content is the one of the database when it is opened, the later writes of an other process are not visible.
+    /// Use [`RocksDbStore::open_secondary`] to see them.
+    /// All the write operations fail.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// {
+    ///     let store = RocksDbStore::open("example.db")?;
+    ///     store.insert(QuadRef::new(ex, ex, ex, None))?;
+    /// }
+    ///
+    /// let store = RocksDbStore::open_read_only("example.db")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, None))?);
+    /// assert!(store.insert(QuadRef::new(ex, ex, ex, ex)).is_err());
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let this = Self::new(
+            DB::open_cf_for_read_only(&Options::default(), path, COLUMN_FAMILIES, false)
+                .map_err(map_err)?,
+            false,
+        );
+        this.check_version_without_migration()?;
+        Ok(this)
+    }
+
+    /// Opens a [`RocksDbStore`]() as a secondary instance of the database at `primary_path` opened by an other process using [`RocksDbStore::open`].
+    ///
+    /// Many secondary instances could be opened at the same time, each of them needs its own directory `secondary_path` to store its logs.
+    /// The store content is the one of the primary when the secondary is opened.
+    /// It is updated with the primary writes only when [`RocksDbStore::catch_up_with_primary`] is called.
+    /// All the write operations fail.
+    ///
+    /// RocksDB does not support snapshots on secondary instances: the reads are done directly on the database,
+    /// and [`RocksDbStore::snapshot`] returns a view that is updated by [`RocksDbStore::catch_up_with_primary`].
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let primary = RocksDbStore::open("example.db")?;
+    /// let secondary = RocksDbStore::open_secondary("example.db", "example_secondary.db")?;
+    ///
+    /// primary.insert(QuadRef::new(ex, ex, ex, None))?;
+    /// assert!(secondary.is_empty());
+    /// secondary.catch_up_with_primary()?;
+    /// assert!(secondary.contains(QuadRef::new(ex, ex, ex, None))?);
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # remove_dir_all("example_secondary.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn open_secondary(
+        primary_path: impl AsRef<Path>,
+        secondary_path: impl AsRef<Path>,
+    ) -> Result<Self, io::Error> {
+        let mut options = Options::default();
+        options.set_max_open_files(-1); // Required by the secondary instances
+        let this = Self::new(
+            DB::open_cf_as_secondary(
+                &options,
+                primary_path.as_ref(),
+                secondary_path.as_ref(),
+                COLUMN_FAMILIES,
+            )
+            .map_err(map_err)?,
+            true,
+        );
+        this.check_version_without_migration()?;
+        Ok(this)
+    }
+
+    /// Updates a store opened using [`RocksDbStore::open_secondary`] with the latest writes of the primary.
+    ///
+    /// The iterators already created are not updated.
+    /// It fails if the store has not been opened as a secondary instance.
+    ///
+    /// See [`RocksDbStore::open_secondary`] for a usage example.
+    pub fn catch_up_with_primary(&self) -> Result<(), io::Error> {
+        let sequence_number = self.db.latest_sequence_number();
+        self.db.try_catch_up_with_primary().map_err(map_err)?;
+        if self.db.latest_sequence_number() != sequence_number {
+            // The primary might have removed some strings
+            self.dictionary_id.renew();
         }
+        Ok(())
+    }
 
-        match version {
-            _ if version < LATEST_STORAGE_VERSION => Err(invalid_data_error(format!(
-                "The RocksDB database is using the outdated encoding version {}. Automated migration is not supported, please dump the store dataset using a compatible Oxigraph version and load it again using the current version",
-                version
-            ))),
-            LATEST_STORAGE_VERSION => Ok(this),
-            _ => Err(invalid_data_error(format!(
-                "The RocksDB database is using the too recent version {}. Upgrade to the latest Oxigraph version to load this database",
+    fn new(db: DB, is_secondary: bool) -> Self {
+        Self {
+            db: Arc::new(db),
+            snapshot: None,
+            dictionary_id: DictionaryId::new(),
+            write_lock: Arc::default(),
+            statistics_lock: Arc::default(),
+            is_secondary,
+        }
+    }
+
+    fn check_version_without_migration(&self) -> Result<(), io::Error> {
+        if self
+            .db
+            .get(MIGRATION_PROGRESS_KEY)
+            .map_err(map_err)?
+            .is_some()
+        {
+            return Err(invalid_data_error(
+                "The RocksDB database is being migrated. Open it once using RocksDbStore::open to finish the migration",
+            ));
+        }
+        let version = if let Some(version) = self.db.get("oxversion").map_err(map_err)? {
+            u64::from_be_bytes(version.as_slice().try_into().map_err(invalid_data_error)?)
+        } else {
+            LATEST_STORAGE_VERSION
+        };
+        if MIGRATIONS.iter().any(|m| m.from_version == version) {
+            return Err(invalid_data_error(format!(
+                "The RocksDB database is using the outdated encoding version {}. Open it once using RocksDbStore::open to migrate it to the current version",
                 version
-            )))
+            )));
         }
+        check_version(version)
     }
 
     fn ensure_version(&self) -> Result<u64, io::Error> {
@@ -153,6 +300,189 @@ impl RocksDbStore {
         Ok(())
     }
 
+    /// Applies the migrations starting from the current storage version and returns the reached version.
+    ///
+    /// The migration progress is saved regularly so an interrupted migration is resumed when the store is opened again.
+    fn migrate(&self, migrations: &[Migration]) -> Result<u64, io::Error> {
+        let mut version = self.ensure_version()?;
+        let mut progress =
+            if let Some(progress) = self.db.get(MIGRATION_PROGRESS_KEY).map_err(map_err)? {
+                MigrationProgress::from_bytes(&progress)?
+            } else {
+                MigrationProgress::new(version)
+            };
+        if progress.version != version {
+            return Err(invalid_data_error(format!(
+                "The RocksDB database contains the progress of a migration from version {} but is using the version {}",
+                progress.version, version
+            )));
+        }
+        while let Some(migration) = migrations.iter().find(|m| m.from_version == version) {
+            while let Some(step) = migration.steps.get(progress.step) {
+                self.apply_migration_step(step, &mut progress)?;
+                progress.next_step();
+                self.db
+                    .put(MIGRATION_PROGRESS_KEY, progress.to_bytes())
+                    .map_err(map_err)?;
+            }
+            version += 1;
+            let mut batch = WriteBatch::default();
+            batch.put("oxversion", version.to_be_bytes());
+            batch.delete(MIGRATION_PROGRESS_KEY);
+            self.db.write(batch).map_err(map_err)?;
+            self.flush()?;
+            progress = MigrationProgress::new(version);
+        }
+        Ok(version)
+    }
+
+    fn apply_migration_step(
+        &self,
+        step: &MigrationStep,
+        progress: &mut MigrationProgress,
+    ) -> Result<(), io::Error> {
+        match step {
+            MigrationStep::RewriteIndex { index, rewrite } => {
+                let cf = get_cf(&self.db, index.name());
+                if progress.phase == MigrationPhase::Read {
+                    self.migrate_batches(cf, progress, |key, value, batch| {
+                        let mut buffer_key = MIGRATION_BUFFER_PREFIX.to_vec();
+                        buffer_key.extend_from_slice(&rewrite(key)?);
+                        batch.put(buffer_key, value);
+                        Ok(())
+                    })?;
+                    progress.phase = MigrationPhase::CopyBack;
+                    progress.last_key = None;
+                    let mut batch = WriteBatch::default();
+                    clear_cf(&mut batch, cf);
+                    batch.put(MIGRATION_PROGRESS_KEY, progress.to_bytes());
+                    self.db.write(batch).map_err(map_err)?;
+                }
+
+                // The copied entries are removed from the buffer so there is no need to track the progress
+                let mut batch = WriteBatch::default();
+                let mut iter = self.db.raw_iterator();
+                iter.seek(MIGRATION_BUFFER_PREFIX);
+                while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                    if !key.starts_with(MIGRATION_BUFFER_PREFIX) {
+                        break;
+                    }
+                    batch.put_cf(cf, &key[MIGRATION_BUFFER_PREFIX.len()..], value);
+                    batch.delete(key);
+                    if batch.len() >= 2 * MIGRATION_BATCH_SIZE {
+                        self.db.write(take(&mut batch)).map_err(map_err)?;
+                    }
+                    iter.next();
+                }
+                iter.status().map_err(map_err)?;
+                self.db.write(batch).map_err(map_err)
+            }
+            MigrationStep::DeriveIndex { from, to, derive } => {
+                let to = get_cf(&self.db, to.name());
+                self.migrate_batches(get_cf(&self.db, from.name()), progress, |key, _, batch| {
+                    batch.put_cf(to, derive(key)?, []);
+                    Ok(())
+                })
+            }
+            MigrationStep::ComputeStatistics => self.compute_statistics(),
+        }
+    }
+
+    /// Replaces the statistics by the ones computed from the DPOS and POSG indexes
+    fn compute_statistics(&self) -> Result<(), io::Error> {
+        let _lock = self.statistics_lock();
+        let mut statistics = HashMap::<_, i64>::new();
+        for (cf, encoding) in &[
+            (self.dpos_cf(), QuadEncoding::DPOS),
+            (self.posg_cf(), QuadEncoding::POSG),
+        ] {
+            let mut iter = self.db.raw_iterator_cf(cf);
+            iter.seek_to_first();
+            while let Some(key) = iter.key() {
+                for statistic_key in &quad_statistics_keys(&encoding.decode(key)?) {
+                    *statistics.entry(statistic_key.clone()).or_default() += 1;
+                }
+                iter.next();
+            }
+            iter.status().map_err(map_err)?;
+        }
+        let mut batch = WriteBatch::default();
+        batch.delete_range(STATISTICS_PREFIX, STATISTICS_END);
+        for (key, count) in statistics {
+            batch.put(key, encode_statistic(count));
+        }
+        self.db.write(batch).map_err(map_err)
+    }
+
+    /// Returns the current value of a statistic, ignoring the snapshot
+    fn statistic(&self, key: &[u8]) -> Result<i64, io::Error> {
+        Ok(if let Some(value) = self.db.get(key).map_err(map_err)? {
+            decode_statistic(&value)
+                .ok_or_else(|| invalid_data_error("Invalid quad count in the statistics"))?
+        } else {
+            0
+        })
+    }
+
+    /// Returns the number of quads in the default graph or in the named graphs, only the ones with the given predicate if it is set.
+    fn quad_count(
+        &self,
+        default_graph: bool,
+        predicate: Option<EncodedTerm>,
+    ) -> Result<u64, io::Error> {
+        let key = statistics_key(default_graph, predicate);
+        let value = if let Some(snapshot) = &self.snapshot {
+            snapshot.snapshot.get(key)
+        } else {
+            self.db.get(key)
+        };
+        Ok(if let Some(value) = value.map_err(map_err)? {
+            // The count might be negative if there were concurrent writes
+            u64::try_from(
+                decode_statistic(&value)
+                    .ok_or_else(|| invalid_data_error("Invalid quad count in the statistics"))?,
+            )
+            .unwrap_or(0)
+        } else {
+            0
+        })
+    }
+
+    /// Calls `f` on each entry of the column family `cf` after the last key of `progress` and writes the filled batches.
+    ///
+    /// The progress is saved with each batch.
+    fn migrate_batches(
+        &self,
+        cf: &ColumnFamily,
+        progress: &mut MigrationProgress,
+        mut f: impl FnMut(&[u8], &[u8], &mut WriteBatch) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error> {
+        let mut iter = self.db.raw_iterator_cf(cf);
+        if let Some(last_key) = &progress.last_key {
+            iter.seek(last_key);
+            if iter.key() == Some(last_key) {
+                iter.next();
+            }
+        } else {
+            iter.seek_to_first();
+        }
+        let mut batch = WriteBatch::default();
+        let mut batch_size = 0;
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            f(key, value, &mut batch)?;
+            batch_size += 1;
+            if batch_size >= MIGRATION_BATCH_SIZE {
+                progress.last_key = Some(key.to_vec());
+                batch.put(MIGRATION_PROGRESS_KEY, progress.to_bytes());
+                self.db.write(take(&mut batch)).map_err(map_err)?;
+                batch_size = 0;
+            }
+            iter.next();
+        }
+        iter.status().map_err(map_err)?;
+        self.db.write(batch).map_err(map_err)
+    }
+
     /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
     ///
     /// See [`MemoryStore`](super::memory::MemoryStore::query()) for a usage example.
@@ -164,16 +494,52 @@ impl RocksDbStore {
     }
 
     /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    ///
+    /// The query is evaluated against a snapshot of the store taken when this method is called:
+    /// the writes done during the evaluation are not visible to it.
     pub fn query_opt(
         &self,
         query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
         options: QueryOptions,
     ) -> Result<QueryResults, EvaluationError> {
-        evaluate_query(self.clone(), query, options)
+        evaluate_query(self.with_snapshot(), query, options)
+    }
+
+    /// Executes a [`PreparedQuery`] with some options.
+    ///
+    /// The query evaluation plan is reused between the evaluations against this store if possible.
+    /// See [`PreparedQuery`] for a usage example.
+    pub fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        evaluate_prepared_query(self.with_snapshot(), query, options)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options
+    /// and returns an explanation of its evaluation.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::explain_query_opt()) for more details.
+    pub fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        explain_query(self.with_snapshot(), query, options, with_stats)
     }
 
     /// Retrieves quads with a filter on each quad component
     ///
+    /// The quads are read from a snapshot of the store taken when this method is called.
+    ///
     /// See [`MemoryStore`](super::memory::MemoryStore::quads_for_pattern()) for a usage example.
     pub fn quads_for_pattern(
         &self,
@@ -182,11 +548,12 @@ impl RocksDbStore {
         object: Option<TermRef<'_>>,
         graph_name: Option<GraphNameRef<'_>>,
     ) -> RocksDbQuadIter {
+        let store = self.with_snapshot();
         RocksDbQuadIter {
-            inner: match get_encoded_quad_pattern(self, subject, predicate, object, graph_name) {
+            inner: match get_encoded_quad_pattern(&store, subject, predicate, object, graph_name) {
                 Ok(Some((subject, predicate, object, graph_name))) => QuadIterInner::Quads {
-                    iter: self.encoded_quads_for_pattern(subject, predicate, object, graph_name),
-                    store: self.clone(),
+                    iter: store.encoded_quads_for_pattern(subject, predicate, object, graph_name),
+                    store,
                 },
                 Ok(None) => QuadIterInner::Empty,
                 Err(error) => QuadIterInner::Error(once(error)),
@@ -212,36 +579,85 @@ impl RocksDbStore {
     ///
     /// Warning: this function executes a full scan
     pub fn len(&self) -> usize {
-        let default = self
-            .db
-            .full_iterator_cf(self.dspo_cf(), IteratorMode::Start)
-            .count();
-        let named = self
-            .db
-            .full_iterator_cf(self.gspo_cf(), IteratorMode::Start)
-            .count();
-        default + named
+        let mut count = 0;
+        for cf in &[self.dspo_cf(), self.gspo_cf()] {
+            let mut iter = self.db_iter(cf);
+            iter.iter.seek_to_first();
+            while iter.key().is_some() {
+                count += 1;
+                iter.next();
+            }
+        }
+        count
     }
 
     /// Returns if the store is empty
     pub fn is_empty(&self) -> bool {
-        let default = self
-            .db
-            .full_iterator_cf(self.dspo_cf(), IteratorMode::Start)
-            .next()
-            .is_none();
-        let named = self
-            .db
-            .full_iterator_cf(self.gspo_cf(), IteratorMode::Start)
-            .next()
-            .is_none();
-        default && named
+        [self.dspo_cf(), self.gspo_cf()].iter().all(|cf| {
+            let mut iter = self.db_iter(cf);
+            iter.iter.seek_to_first();
+            iter.key().is_none()
+        })
+    }
+
+    /// Returns a read-only view of the store as it is when this method is called.
+    ///
+    /// All the reads and queries done using the returned [`RocksDbSnapshot`] are consistent with each other,
+    /// the writes done after the snapshot creation are not visible to them.
+    ///
+    /// The snapshot relies on [RocksDB snapshots](https://github.com/facebook/rocksdb/wiki/Snapshot):
+    /// while it is alive, RocksDB keeps the old versions of the modified entries and can't free their space.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let store = RocksDbStore::open("example.db")?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, None))?;
+    ///
+    /// let snapshot = store.snapshot();
+    /// store.remove(QuadRef::new(ex, ex, ex, None))?;
+    /// assert!(store.is_empty());
+    /// assert_eq!(snapshot.len(), 1);
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn snapshot(&self) -> RocksDbSnapshot {
+        RocksDbSnapshot {
+            store: self.with_snapshot(),
+        }
+    }
+
+    /// Returns a store reading from a snapshot of the database.
+    ///
+    /// Returns a copy of `self` if it is already reading from a snapshot
+    /// or if it is a secondary instance: RocksDB does not support snapshots on them
+    /// and their content only changes when catching up with the primary.
+    fn with_snapshot(&self) -> Self {
+        if self.snapshot.is_some() || self.is_secondary {
+            return self.clone();
+        }
+        Self {
+            db: self.db.clone(),
+            snapshot: Some(Arc::new(StaticSnapshot::new(self.db.clone()))),
+            dictionary_id: self.dictionary_id.clone(),
+            write_lock: self.write_lock.clone(),
+            statistics_lock: self.statistics_lock.clone(),
+            is_secondary: false,
+        }
     }
 
     /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/).
     ///
     /// The store does not track the existence of empty named graphs.
-    /// This method has no ACID guarantees.
+    ///
+    /// The update is applied atomically: if it fails, the store is not modified, and readers never see a partially applied update.
+    /// The changes are staged in main memory before being written.
     ///
     /// See [`MemoryStore`](super::memory::MemoryStore::update()) for a usage example.
     pub fn update(
@@ -252,19 +668,23 @@ impl RocksDbStore {
     }
 
     /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/) with some options.
+    ///
+    /// The update is applied atomically like with [`update`](RocksDbStore::update()).
     pub fn update_opt(
         &self,
         update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
         options: UpdateOptions,
     ) -> Result<(), EvaluationError> {
-        let mut writer = self.auto_batch_writer();
+        // The update might insert quads using strings read from the store, we make sure they are not removed in the meantime
+        let _lock = self.write_lock();
+        let mut staging = StagingArea::new(self);
         evaluate_update(
-            self.clone(),
-            &mut writer,
+            staging.clone(),
+            &mut staging,
             update.try_into().map_err(|e| e.into())?,
             options,
         )?;
-        Ok(writer.apply()?)
+        Ok(staging.apply()?)
     }
 
     /// Executes an ACID transaction.
@@ -284,15 +704,17 @@ impl RocksDbStore {
             batch: WriteBatch::default(),
             buffer: Vec::new(),
             new_strings: HashMap::new(),
+            statistics: StatisticsChanges::default(),
         };
         f(&mut transaction)?;
+        let _lock = self.write_lock();
         Ok(transaction.apply()?)
     }
 
     /// Loads a graph file (i.e. triples) into the store
     ///
     /// Warning: This functions saves the triples in batch. If the parsing fails in the middle of the file,
-    /// only a part of it may be written. Use a (memory greedy) [transaction](RocksDbStore::transaction()) if you do not want that.
+    /// only a part of it may be written. Use the (memory greedy) [`load_graph_atomic`](RocksDbStore::load_graph_atomic()) if you do not want that.
     ///
     /// See [`MemoryStore`](super::memory::MemoryStore::load_graph()) for a usage example.
     ///
@@ -313,14 +735,64 @@ impl RocksDbStore {
             format,
             to_graph_name.into(),
             base_iri,
+            None,
         )?;
-        Ok(transaction.apply()?)
+        transaction.apply()
+    }
+
+    /// Loads a graph file (i.e. triples) into the store atomically.
+    ///
+    /// If the parsing fails in the middle of the file, nothing is written, and readers never see a partially loaded file.
+    /// The triples are stored in main memory before being written. Do not use for big files.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_graph_atomic<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        self.transaction(|transaction| {
+            transaction.load_graph(reader, format, to_graph_name, base_iri)
+        })
+    }
+
+    /// Loads a graph file (i.e. triples) into the store in lenient mode: the invalid statements are skipped and their errors are given to `on_error`.
+    ///
+    /// Only the line based format [N-Triples](https://www.w3.org/TR/n-triples/) is able to recover from a syntax error.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_graph_lenient()) for a usage example.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_graph_lenient<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+        mut on_error: impl FnMut(SyntaxError),
+    ) -> Result<(), io::Error> {
+        let mut transaction = self.auto_batch_writer();
+        load_graph(
+            &mut transaction,
+            reader,
+            format,
+            to_graph_name.into(),
+            base_iri,
+            Some(&mut on_error),
+        )?;
+        transaction.apply()
     }
 
     /// Loads a dataset file (i.e. quads) into the store.
     ///
     /// Warning: This functions saves the quads in batch. If the parsing fails in the middle of the file,
-    /// only a part of it may be written. Use a (memory greedy) [transaction](RocksDbStore::transaction()) if you do not want that.
+    /// only a part of it may be written. Use the (memory greedy) [`load_dataset_atomic`](RocksDbStore::load_dataset_atomic()) if you do not want that.
     ///
     /// See [`MemoryStore`](super::memory::MemoryStore::load_dataset()) for a usage example.
     ///
@@ -334,8 +806,135 @@ impl RocksDbStore {
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
         let mut transaction = self.auto_batch_writer();
-        load_dataset(&mut transaction, reader, format, base_iri)?;
-        Ok(transaction.apply()?)
+        load_dataset(&mut transaction, reader, format, base_iri, None)?;
+        transaction.apply()
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store atomically.
+    ///
+    /// If the parsing fails in the middle of the file, nothing is written, and readers never see a partially loaded file.
+    /// The quads are stored in main memory before being written. Do not use for big files.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_dataset_atomic(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        self.transaction(|transaction| transaction.load_dataset(reader, format, base_iri))
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store in lenient mode: the invalid statements are skipped and their errors are given to `on_error`.
+    ///
+    /// Only the line based format [N-Quads](https://www.w3.org/TR/n-quads/) is able to recover from a syntax error.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_dataset_lenient()) for a usage example.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_dataset_lenient(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+        mut on_error: impl FnMut(SyntaxError),
+    ) -> Result<(), io::Error> {
+        let mut transaction = self.auto_batch_writer();
+        load_dataset(
+            &mut transaction,
+            reader,
+            format,
+            base_iri,
+            Some(&mut on_error),
+        )?;
+        transaction.apply()
+    }
+
+    /// Loads a graph file (i.e. triples) into the store using the options of a [`GraphParser`].
+    ///
+    /// It allows to provide a [JSON-LD context loader](GraphParser::with_json_ld_context_loader()).
+    /// If the parser is in [lenient mode](GraphParser::with_lenient_mode()), only available for [N-Triples](https://www.w3.org/TR/n-triples/), the syntax errors it is able to recover from are given to its callback.
+    ///
+    /// Warning: This functions saves the triples in batch like [`load_graph`](RocksDbStore::load_graph()).
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_graph_with_parser()) for a usage example.
+    ///
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_graph_with_parser<'a>(
+        &self,
+        reader: impl BufRead,
+        parser: &GraphParser,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        let mut transaction = self.auto_batch_writer();
+        load_graph_with_parser(&mut transaction, reader, parser, to_graph_name.into(), None)?;
+        transaction.apply()
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store using the options of a [`DatasetParser`].
+    ///
+    /// It allows to provide a [JSON-LD context loader](DatasetParser::with_json_ld_context_loader()).
+    /// If the parser is in [lenient mode](DatasetParser::with_lenient_mode()), only available for [N-Quads](https://www.w3.org/TR/n-quads/), the syntax errors it is able to recover from are given to its callback.
+    ///
+    /// Warning: This functions saves the quads in batch like [`load_dataset`](RocksDbStore::load_dataset()).
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_dataset_with_parser()) for a usage example.
+    ///
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_dataset_with_parser(
+        &self,
+        reader: impl BufRead,
+        parser: &DatasetParser,
+    ) -> Result<(), io::Error> {
+        let mut transaction = self.auto_batch_writer();
+        load_dataset_with_parser(&mut transaction, reader, parser, None)?;
+        transaction.apply()
+    }
+
+    /// Creates a [`RocksDbBulkLoader`] allowing to load big files into the store much faster than [`RocksDbStore::load_dataset`].
+    ///
+    /// It is designed for the initial import of data into an empty store.
+    /// It requires an exclusive access to the store: the loading fails with an [`InvalidInput`](std::io::ErrorKind::InvalidInput) error if clones of this store, snapshots or iterators on it are still alive.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let mut store = RocksDbStore::open("example.db")?;
+    ///
+    /// // bulk loading
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> <http://example.com> .";
+    /// store
+    ///     .bulk_loader()
+    ///     .with_num_threads(2)
+    ///     .with_max_memory_size_in_megabytes(512)
+    ///     .on_progress(|count| println!("{} quads loaded", count))
+    ///     .load_dataset(file.as_ref(), DatasetFormat::NQuads, None)?;
+    ///
+    /// // we inspect the store contents
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn bulk_loader(&mut self) -> RocksDbBulkLoader<'_> {
+        RocksDbBulkLoader {
+            store: self,
+            num_threads: DEFAULT_BULK_LOAD_THREADS,
+            max_memory_size: 1024 * 1024 * 1024,
+            on_progress: None,
+        }
     }
 
     /// Adds a quad to this store.
@@ -360,35 +959,75 @@ impl RocksDbStore {
     }
 
     /// Dumps a store graph into a file.
-    ///    
+    ///
+    /// The graph is read from a snapshot of the store taken when this method is called.
+    ///
     /// See [`MemoryStore`](super::memory::MemoryStore::dump_graph()) for a usage example.
     pub fn dump_graph<'a>(
         &self,
         writer: impl Write,
         format: GraphFormat,
         from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.dump_graph_with_serializer(
+            writer,
+            &GraphSerializer::from_format(format),
+            from_graph_name,
+        )
+    }
+
+    /// Dumps a store graph into a file using the options of a [`GraphSerializer`].
+    ///
+    /// It allows to set the prefixes and the base IRI used by the Turtle serializer or the JSON-LD context.
+    /// The graph is read from a snapshot of the store taken when this method is called.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::dump_graph_with_serializer()) for a usage example.
+    pub fn dump_graph_with_serializer<'a>(
+        &self,
+        writer: impl Write,
+        serializer: &GraphSerializer,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
     ) -> Result<(), io::Error> {
         dump_graph(
-            self.quads_for_pattern(None, None, None, Some(from_graph_name.into()))
+            self.with_snapshot()
+                .quads_for_pattern(None, None, None, Some(from_graph_name.into()))
                 .map(|q| Ok(q?.into())),
             writer,
-            format,
+            serializer,
         )
     }
 
     /// Dumps the store into a file.
-    ///    
+    ///
+    /// The dataset is read from a snapshot of the store taken when this method is called.
+    ///
     /// See [`MemoryStore`](super::memory::MemoryStore::dump_dataset()) for a usage example.
     pub fn dump_dataset(&self, writer: impl Write, syntax: DatasetFormat) -> Result<(), io::Error> {
-        dump_dataset(self.iter(), writer, syntax)
+        self.dump_dataset_with_serializer(writer, &DatasetSerializer::from_format(syntax))
+    }
+
+    /// Dumps the store into a file using the options of a [`DatasetSerializer`].
+    ///
+    /// It allows to set the prefixes and the base IRI used by the TriG serializer or the JSON-LD context.
+    /// The dataset is read from a snapshot of the store taken when this method is called.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::dump_graph_with_serializer()) for a usage example.
+    pub fn dump_dataset_with_serializer(
+        &self,
+        writer: impl Write,
+        serializer: &DatasetSerializer,
+    ) -> Result<(), io::Error> {
+        dump_dataset(self.with_snapshot().iter(), writer, serializer)
     }
 
     /// Returns all the store named graphs
     ///
+    /// The graph names are read from a snapshot of the store taken when this method is called.
+    ///
     /// See [`MemoryStore`](super::memory::MemoryStore::named_graphs()) for a usage example.
     pub fn named_graphs(&self) -> impl Iterator<Item = Result<NamedOrBlankNode, io::Error>> {
-        let this = self.clone();
-        self.encoded_named_graphs()
+        let this = self.with_snapshot();
+        this.encoded_named_graphs()
             .map(move |g| Ok(this.decode_named_or_blank_node(g?)?))
     }
 
@@ -460,9 +1099,167 @@ impl RocksDbStore {
         transaction.apply()
     }
 
-    fn id2str_cf(&self) -> &ColumnFamily {
-        get_cf(&self.db, ID2STR_CF)
-    }
+    /// Removes from the store string dictionary the IRIs, blank node identifiers and literals not used anymore by any quad or named graph.
+    ///
+    /// Removing quads does not remove the strings they use from the dictionary, this method allows to reclaim their space.
+    /// It returns the number of removed strings.
+    ///
+    /// The reads and SPARQL queries are not blocked during the collection but the writes wait for its end.
+    /// The identifiers of all the used strings are kept in main memory during the collection.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let store = RocksDbStore::open("example.db")?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, LiteralRef::new_simple_literal("a long enough string"), None);
+    /// store.insert(quad)?;
+    /// store.remove(quad)?;
+    ///
+    /// assert_eq!(store.remove_unused_strings()?, 2);
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[allow(clippy::expect_used)]
+    pub fn remove_unused_strings(&self) -> Result<usize, io::Error> {
+        let _lock = self
+            .write_lock
+            .write()
+            .expect("the RocksDB write lock has been poisoned because of a panic");
+        let snapshot = self.with_snapshot();
+
+        let mut used = HashSet::new();
+        for quad in snapshot.encoded_quads_for_pattern(None, None, None, None) {
+            let quad = quad?;
+            for term in &[quad.subject, quad.predicate, quad.object, quad.graph_name] {
+                term.on_each_id(|id| {
+                    used.insert(id);
+                });
+            }
+        }
+        for graph_name in snapshot.encoded_named_graphs() {
+            graph_name?.on_each_id(|id| {
+                used.insert(id);
+            });
+        }
+
+        let mut batch = WriteBatch::default();
+        let mut count = 0;
+        let mut iter = snapshot.db_iter(self.id2str_cf());
+        iter.iter.seek_to_first();
+        while let Some(key) = iter.key() {
+            let id = StrHash::from_be_bytes(key.try_into().map_err(invalid_data_error)?);
+            if !used.contains(&id) {
+                batch.delete_cf(self.id2str_cf(), key);
+                count += 1;
+                if batch.len() > MAX_TRANSACTION_SIZE {
+                    self.db.write(take(&mut batch)).map_err(map_err)?;
+                }
+            }
+            iter.next();
+        }
+        iter.status()?;
+        self.db.write(batch).map_err(map_err)?;
+        if count > 0 {
+            // The removed strings ids are not valid anymore
+            self.dictionary_id.renew();
+        }
+        Ok(count)
+    }
+
+    /// Creates a consistent copy of the store in the directory `path` that could be opened using [`RocksDbStore::open`].
+    ///
+    /// It uses a [RocksDB checkpoint](https://github.com/facebook/rocksdb/wiki/Checkpoints):
+    /// the data files are hard-linked if `path` is on the same file system as the store and copied otherwise.
+    /// The store could be read and written during the backup.
+    /// The directory `path` must not exist yet.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let store = RocksDbStore::open("example.db")?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, None))?;
+    ///
+    /// store.backup("example_backup.db")?;
+    /// assert!(RocksDbStore::open("example_backup.db")?.contains(QuadRef::new(ex, ex, ex, None))?);
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # remove_dir_all("example_backup.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn backup(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        checkpoint::Checkpoint::new(&self.db)
+            .map_err(map_err)?
+            .create_checkpoint(path)
+            .map_err(map_err)
+    }
+
+    /// Adds a new backup of the store to the backup directory `backup_dir`.
+    ///
+    /// It uses the [RocksDB backup engine](https://github.com/facebook/rocksdb/wiki/How-to-backup-RocksDB):
+    /// the backups are incremental, the data files already saved by a previous backup in the same directory are not copied again.
+    /// The store could be read and written during the backup.
+    /// The latest backup is restored using [`RocksDbStore::restore_backup`].
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::RocksDbStore;
+    /// use oxigraph::model::*;
+    /// # use std::fs::remove_dir_all;
+    ///
+    /// # {
+    /// let store = RocksDbStore::open("example.db")?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, None))?;
+    /// store.incremental_backup("example_backups")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.incremental_backup("example_backups")?;
+    ///
+    /// RocksDbStore::restore_backup("example_backups", "example_restored.db")?;
+    /// assert_eq!(RocksDbStore::open("example_restored.db")?.len(), 2);
+    /// # };
+    /// # remove_dir_all("example.db")?;
+    /// # remove_dir_all("example_backups")?;
+    /// # remove_dir_all("example_restored.db")?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn incremental_backup(&self, backup_dir: impl AsRef<Path>) -> Result<(), io::Error> {
+        backup::BackupEngine::open(&backup::BackupEngineOptions::default(), backup_dir)
+            .map_err(map_err)?
+            .create_new_backup_flush(&self.db, true)
+            .map_err(map_err)
+    }
+
+    /// Restores in the directory `path` the latest backup written in `backup_dir` by [`RocksDbStore::incremental_backup`].
+    ///
+    /// The restored store could then be opened using [`RocksDbStore::open`].
+    /// The existing content of `path` is overwritten, so no store should be opened on it during the restoration.
+    ///
+    /// See [`RocksDbStore::incremental_backup`] for a usage example.
+    pub fn restore_backup(
+        backup_dir: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), io::Error> {
+        let path = path.as_ref();
+        backup::BackupEngine::open(&backup::BackupEngineOptions::default(), backup_dir)
+            .map_err(map_err)?
+            .restore_from_latest_backup(path, path, &backup::RestoreOptions::default())
+            .map_err(map_err)
+    }
+
+    fn id2str_cf(&self) -> &ColumnFamily {
+        get_cf(&self.db, ID2STR_CF)
+    }
 
     fn spog_cf(&self) -> &ColumnFamily {
         get_cf(&self.db, SPOG_CF)
@@ -508,25 +1305,39 @@ impl RocksDbStore {
             store: self,
             batch: WriteBatch::default(),
             buffer: Vec::default(),
+            statistics: StatisticsChanges::default(),
         }
     }
 
+    /// Returns the lock to hold while writing.
+    ///
+    /// It is taken exclusively by [`remove_unused_strings`](RocksDbStore::remove_unused_strings()).
+    /// It is enough to hold it while writing a batch if the batch inserts the strings of all the quads it inserts.
+    #[allow(clippy::expect_used)]
+    fn write_lock(&self) -> RwLockReadGuard<'_, ()> {
+        self.write_lock
+            .read()
+            .expect("the RocksDB write lock has been poisoned because of a panic")
+    }
+
+    /// Returns the lock to hold while reading the statistics and writing their updated values.
+    ///
+    /// The statistics store the absolute numbers of quads so that read-only and secondary instances are able to read them.
+    #[allow(clippy::expect_used)]
+    fn statistics_lock(&self) -> MutexGuard<'_, ()> {
+        self.statistics_lock
+            .lock()
+            .expect("the RocksDB statistics lock has been poisoned because of a panic")
+    }
+
     fn contains_encoded(&self, quad: &EncodedQuad) -> Result<bool, io::Error> {
         let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut buffer, quad);
-            Ok(self
-                .db
-                .get_pinned_cf(self.dspo_cf(), &buffer)
-                .map_err(map_err)?
-                .is_some())
+            self.db_contains(self.dspo_cf(), &buffer)
         } else {
             write_gspo_quad(&mut buffer, quad);
-            Ok(self
-                .db
-                .get_pinned_cf(self.gspo_cf(), &buffer)
-                .map_err(map_err)?
-                .is_some())
+            self.db_contains(self.gspo_cf(), &buffer)
         }
     }
 
@@ -754,236 +1565,736 @@ impl RocksDbStore {
     #[allow(unsafe_code)]
     fn db_iter(&self, cf: &ColumnFamily) -> StaticDBRowIterator {
         // Valid because it's the same database so db can't be dropped before iter
-        unsafe { StaticDBRowIterator::new(self.db.raw_iterator_cf(cf), self.db.clone()) }
-    }
-}
-
-impl fmt::Display for RocksDbStore {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for t in self.iter() {
-            writeln!(f, "{}", t.map_err(|_| fmt::Error)?)?;
+        // and the snapshot is kept alive by the iterator
+        unsafe {
+            if let Some(snapshot) = &self.snapshot {
+                StaticDBRowIterator::new(
+                    snapshot.snapshot.raw_iterator_cf(cf),
+                    Some(snapshot.clone()),
+                    self.db.clone(),
+                )
+            } else {
+                StaticDBRowIterator::new(self.db.raw_iterator_cf(cf), None, self.db.clone())
+            }
         }
-        Ok(())
     }
-}
-
-impl StrEncodingAware for RocksDbStore {
-    type Error = io::Error;
-    type StrId = StrHash;
-}
 
-impl StrLookup for RocksDbStore {
-    fn get_str(&self, id: StrHash) -> Result<Option<String>, io::Error> {
-        self.db
-            .get_cf(self.id2str_cf(), &id.to_be_bytes())
-            .map_err(map_err)?
-            .map(String::from_utf8)
-            .transpose()
-            .map_err(invalid_data_error)
+    fn db_get(&self, cf: &ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        let value = if let Some(snapshot) = &self.snapshot {
+            snapshot.snapshot.get_cf(cf, key)
+        } else {
+            self.db.get_cf(cf, key)
+        };
+        value.map_err(map_err)
     }
 
-    fn get_str_id(&self, value: &str) -> Result<Option<StrHash>, io::Error> {
-        let id = StrHash::new(value);
-        Ok(
-            if self
-                .db
-                .get_cf(self.id2str_cf(), &id.to_be_bytes())
+    fn db_contains(&self, cf: &ColumnFamily, key: &[u8]) -> Result<bool, io::Error> {
+        Ok(if let Some(snapshot) = &self.snapshot {
+            snapshot
+                .snapshot
+                .get_cf(cf, key)
                 .map_err(map_err)?
                 .is_some()
-            {
-                Some(id)
-            } else {
-                None
-            },
-        )
+        } else {
+            self.db.get_pinned_cf(cf, key).map_err(map_err)?.is_some()
+        })
     }
 }
 
-impl ReadableEncodedStore for RocksDbStore {
-    type QuadsIter = DecodingIndexesIterator;
-    type GraphsIter = DecodingGraphIterator;
+impl ReadableStore for RocksDbStore {
+    type Error = io::Error;
 
-    fn encoded_quads_for_pattern(
+    fn quads_for_pattern(
         &self,
-        subject: Option<EncodedTerm>,
-        predicate: Option<EncodedTerm>,
-        object: Option<EncodedTerm>,
-        graph_name: Option<EncodedTerm>,
-    ) -> DecodingIndexesIterator {
-        match subject {
-            Some(subject) => match predicate {
-                Some(predicate) => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => self.quads_for_subject_predicate_object_graph(
-                            subject, predicate, object, graph_name,
-                        ),
-                        None => self.quads_for_subject_predicate_object(subject, predicate, object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => {
-                            self.quads_for_subject_predicate_graph(subject, predicate, graph_name)
-                        }
-                        None => self.quads_for_subject_predicate(subject, predicate),
-                    },
-                },
-                None => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => {
-                            self.quads_for_subject_object_graph(subject, object, graph_name)
-                        }
-                        None => self.quads_for_subject_object(subject, object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => self.quads_for_subject_graph(subject, graph_name),
-                        None => self.quads_for_subject(subject),
-                    },
-                },
-            },
-            None => match predicate {
-                Some(predicate) => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => {
-                            self.quads_for_predicate_object_graph(predicate, object, graph_name)
-                        }
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Box<dyn Iterator<Item = Result<Quad, io::Error>> + Send> {
+        Box::new(RocksDbStore::quads_for_pattern(
+            self, subject, predicate, object, graph_name,
+        ))
+    }
 
-                        None => self.quads_for_predicate_object(predicate, object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => self.quads_for_predicate_graph(predicate, graph_name),
-                        None => self.quads_for_predicate(predicate),
-                    },
-                },
-                None => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => self.quads_for_object_graph(object, graph_name),
-                        None => self.quads_for_object(object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => self.quads_for_graph(graph_name),
-                        None => self.quads(),
-                    },
-                },
-            },
-        }
+    fn contains(&self, quad: QuadRef<'_>) -> Result<bool, io::Error> {
+        RocksDbStore::contains(self, quad)
     }
 
-    fn encoded_named_graphs(&self) -> DecodingGraphIterator {
-        let mut iter = self.db_iter(self.graphs_cf());
-        iter.iter.seek_to_first();
-        DecodingGraphIterator { iter }
+    fn len(&self) -> Result<usize, io::Error> {
+        Ok(RocksDbStore::len(self))
     }
 
-    fn contains_encoded_named_graph(&self, graph_name: EncodedTerm) -> Result<bool, io::Error> {
-        Ok(self
-            .db
-            .get_cf(self.graphs_cf(), &encode_term(graph_name))
-            .map_err(map_err)?
-            .is_some())
+    fn is_empty(&self) -> Result<bool, io::Error> {
+        Ok(RocksDbStore::is_empty(self))
     }
-}
 
-struct AutoBatchWriter<'a> {
-    store: &'a RocksDbStore,
-    batch: WriteBatch,
-    buffer: Vec<u8>,
-}
+    fn named_graphs(&self) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, io::Error>> + Send> {
+        Box::new(RocksDbStore::named_graphs(self))
+    }
 
-impl AutoBatchWriter<'_> {
-    fn apply(self) -> Result<(), io::Error> {
-        self.store.db.write(self.batch).map_err(map_err)
+    fn contains_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<bool, io::Error> {
+        RocksDbStore::contains_named_graph(self, graph_name)
     }
 
-    fn apply_if_big(&mut self) -> Result<(), io::Error> {
-        if self.batch.len() > MAX_TRANSACTION_SIZE {
-            self.store
-                .db
-                .write(take(&mut self.batch))
-                .map_err(map_err)?;
-        }
-        Ok(())
+    fn dump_graph(
+        &self,
+        writer: &mut dyn Write,
+        format: GraphFormat,
+        from_graph_name: GraphNameRef<'_>,
+    ) -> Result<(), io::Error> {
+        RocksDbStore::dump_graph(self, writer, format, from_graph_name)
     }
 
-    fn clear_cf(&mut self, cf: &ColumnFamily) {
-        self.batch.delete_range_cf(
-            cf,
-            [
-                u8::MIN,
-                u8::MIN,
-                u8::MIN,
-                u8::MIN,
-                u8::MIN,
-                u8::MIN,
-                u8::MIN,
-                u8::MIN,
-            ],
-            [
-                u8::MAX,
-                u8::MAX,
-                u8::MAX,
-                u8::MAX,
-                u8::MAX,
-                u8::MAX,
-                u8::MAX,
-                u8::MAX,
-            ],
-        )
+    fn dump_dataset(&self, writer: &mut dyn Write, format: DatasetFormat) -> Result<(), io::Error> {
+        RocksDbStore::dump_dataset(self, writer, format)
     }
-}
 
-impl StrEncodingAware for AutoBatchWriter<'_> {
-    type Error = io::Error;
-    type StrId = StrHash;
-}
+    fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        RocksDbStore::query_opt(self, query, options)
+    }
 
-impl StrContainer for AutoBatchWriter<'_> {
-    fn insert_str(&mut self, value: &str) -> Result<StrHash, io::Error> {
-        let key = StrHash::new(value);
-        self.batch
-            .put_cf(self.store.id2str_cf(), &key.to_be_bytes(), value);
-        Ok(key)
+    fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        RocksDbStore::explain_query_opt(self, query, options, with_stats)
+    }
+
+    fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        RocksDbStore::query_prepared(self, query, options)
     }
 }
 
-impl WritableEncodedStore for AutoBatchWriter<'_> {
-    fn insert_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
-        if quad.graph_name.is_default_graph() {
-            write_spo_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.dspo_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+impl WritableStore for RocksDbStore {
+    fn insert(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        RocksDbStore::insert(self, quad)
+    }
 
-            write_pos_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.dpos_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn remove(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        RocksDbStore::remove(self, quad)
+    }
 
-            write_osp_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.dosp_cf(), &self.buffer, &[]);
-            self.buffer.clear();
-        } else {
-            write_spog_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.spog_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn load_graph(
+        &self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        RocksDbStore::load_graph(self, reader, format, to_graph_name, base_iri)
+    }
 
-            write_posg_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.posg_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn load_dataset(
+        &self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        RocksDbStore::load_dataset(self, reader, format, base_iri)
+    }
 
-            write_ospg_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.ospg_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn insert_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+        RocksDbStore::insert_named_graph(self, graph_name)
+    }
 
-            write_gspo_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.gspo_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn clear_graph(&self, graph_name: GraphNameRef<'_>) -> Result<(), io::Error> {
+        RocksDbStore::clear_graph(self, graph_name)
+    }
 
-            write_gpos_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.gpos_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn remove_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+        RocksDbStore::remove_named_graph(self, graph_name)
+    }
 
-            write_gosp_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.gosp_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+    fn clear(&self) -> Result<(), io::Error> {
+        RocksDbStore::clear(self)
+    }
 
-            write_term(&mut self.buffer, quad.graph_name);
+    fn update_opt(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+        options: UpdateOptions,
+    ) -> Result<(), EvaluationError> {
+        RocksDbStore::update_opt(self, update, options)
+    }
+}
+
+impl TransactionalStore for RocksDbStore {
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&mut dyn StoreTransaction) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error> {
+        RocksDbStore::transaction(self, |transaction| f(transaction))
+    }
+
+    fn load_graph_atomic(
+        &self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        RocksDbStore::load_graph_atomic(self, reader, format, to_graph_name, base_iri)
+    }
+
+    fn load_dataset_atomic(
+        &self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        RocksDbStore::load_dataset_atomic(self, reader, format, base_iri)
+    }
+}
+
+impl fmt::Display for RocksDbStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in self.iter() {
+            writeln!(f, "{}", t.map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// A read-only view of a [`RocksDbStore`] at a given point in time.
+///
+/// It is created using [`RocksDbStore::snapshot`].
+#[derive(Clone)]
+pub struct RocksDbSnapshot {
+    store: RocksDbStore,
+}
+
+impl RocksDbSnapshot {
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
+    pub fn query(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query(query)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    pub fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_opt(query, options)
+    }
+
+    /// Executes a [`PreparedQuery`] with some options.
+    pub fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_prepared(query, options)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options
+    /// and returns an explanation of its evaluation.
+    pub fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        self.store.explain_query_opt(query, options, with_stats)
+    }
+
+    /// Retrieves quads with a filter on each quad component
+    pub fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> RocksDbQuadIter {
+        self.store
+            .quads_for_pattern(subject, predicate, object, graph_name)
+    }
+
+    /// Returns all the quads contained in the snapshot
+    pub fn iter(&self) -> RocksDbQuadIter {
+        self.store.iter()
+    }
+
+    /// Checks if this snapshot contains a given quad
+    pub fn contains<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, io::Error> {
+        self.store.contains(quad)
+    }
+
+    /// Returns the number of quads in the snapshot
+    ///
+    /// Warning: this function executes a full scan
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns if the snapshot is empty
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Dumps a graph into a file.
+    pub fn dump_graph<'a>(
+        &self,
+        writer: impl Write,
+        format: GraphFormat,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.store.dump_graph(writer, format, from_graph_name)
+    }
+
+    /// Dumps a graph into a file using the options of a [`GraphSerializer`].
+    pub fn dump_graph_with_serializer<'a>(
+        &self,
+        writer: impl Write,
+        serializer: &GraphSerializer,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.store
+            .dump_graph_with_serializer(writer, serializer, from_graph_name)
+    }
+
+    /// Dumps the snapshot into a file.
+    pub fn dump_dataset(&self, writer: impl Write, format: DatasetFormat) -> Result<(), io::Error> {
+        self.store.dump_dataset(writer, format)
+    }
+
+    /// Dumps the snapshot into a file using the options of a [`DatasetSerializer`].
+    pub fn dump_dataset_with_serializer(
+        &self,
+        writer: impl Write,
+        serializer: &DatasetSerializer,
+    ) -> Result<(), io::Error> {
+        self.store.dump_dataset_with_serializer(writer, serializer)
+    }
+
+    /// Returns all the named graphs of the snapshot
+    pub fn named_graphs(&self) -> impl Iterator<Item = Result<NamedOrBlankNode, io::Error>> {
+        self.store.named_graphs()
+    }
+
+    /// Checks if the snapshot contains a given graph
+    pub fn contains_named_graph<'a>(
+        &self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<bool, io::Error> {
+        self.store.contains_named_graph(graph_name)
+    }
+}
+
+impl ReadableStore for RocksDbSnapshot {
+    type Error = io::Error;
+
+    fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Box<dyn Iterator<Item = Result<Quad, io::Error>> + Send> {
+        ReadableStore::quads_for_pattern(&self.store, subject, predicate, object, graph_name)
+    }
+
+    fn contains(&self, quad: QuadRef<'_>) -> Result<bool, io::Error> {
+        ReadableStore::contains(&self.store, quad)
+    }
+
+    fn len(&self) -> Result<usize, io::Error> {
+        ReadableStore::len(&self.store)
+    }
+
+    fn is_empty(&self) -> Result<bool, io::Error> {
+        ReadableStore::is_empty(&self.store)
+    }
+
+    fn named_graphs(&self) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, io::Error>> + Send> {
+        ReadableStore::named_graphs(&self.store)
+    }
+
+    fn contains_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<bool, io::Error> {
+        ReadableStore::contains_named_graph(&self.store, graph_name)
+    }
+
+    fn dump_graph(
+        &self,
+        writer: &mut dyn Write,
+        format: GraphFormat,
+        from_graph_name: GraphNameRef<'_>,
+    ) -> Result<(), io::Error> {
+        ReadableStore::dump_graph(&self.store, writer, format, from_graph_name)
+    }
+
+    fn dump_dataset(&self, writer: &mut dyn Write, format: DatasetFormat) -> Result<(), io::Error> {
+        ReadableStore::dump_dataset(&self.store, writer, format)
+    }
+
+    fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_opt(query, options)
+    }
+
+    fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        self.store.explain_query_opt(query, options, with_stats)
+    }
+
+    fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_prepared(query, options)
+    }
+}
+
+impl fmt::Display for RocksDbSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.store.fmt(f)
+    }
+}
+
+impl StrEncodingAware for RocksDbStore {
+    type Error = io::Error;
+    type StrId = StrHash;
+}
+
+impl StrLookup for RocksDbStore {
+    fn get_str(&self, id: StrHash) -> Result<Option<String>, io::Error> {
+        self.db_get(self.id2str_cf(), &id.to_be_bytes())?
+            .map(String::from_utf8)
+            .transpose()
+            .map_err(invalid_data_error)
+    }
+
+    fn get_str_id(&self, value: &str) -> Result<Option<StrHash>, io::Error> {
+        let id = StrHash::new(value);
+        Ok(if self.db_contains(self.id2str_cf(), &id.to_be_bytes())? {
+            Some(id)
+        } else {
+            None
+        })
+    }
+}
+
+impl ReadableEncodedStore for RocksDbStore {
+    type QuadsIter = DecodingIndexesIterator;
+    type GraphsIter = DecodingGraphIterator;
+
+    fn encoded_quads_for_pattern(
+        &self,
+        subject: Option<EncodedTerm>,
+        predicate: Option<EncodedTerm>,
+        object: Option<EncodedTerm>,
+        graph_name: Option<EncodedTerm>,
+    ) -> DecodingIndexesIterator {
+        match subject {
+            Some(subject) => match predicate {
+                Some(predicate) => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => self.quads_for_subject_predicate_object_graph(
+                            subject, predicate, object, graph_name,
+                        ),
+                        None => self.quads_for_subject_predicate_object(subject, predicate, object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => {
+                            self.quads_for_subject_predicate_graph(subject, predicate, graph_name)
+                        }
+                        None => self.quads_for_subject_predicate(subject, predicate),
+                    },
+                },
+                None => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => {
+                            self.quads_for_subject_object_graph(subject, object, graph_name)
+                        }
+                        None => self.quads_for_subject_object(subject, object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => self.quads_for_subject_graph(subject, graph_name),
+                        None => self.quads_for_subject(subject),
+                    },
+                },
+            },
+            None => match predicate {
+                Some(predicate) => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => {
+                            self.quads_for_predicate_object_graph(predicate, object, graph_name)
+                        }
+
+                        None => self.quads_for_predicate_object(predicate, object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => self.quads_for_predicate_graph(predicate, graph_name),
+                        None => self.quads_for_predicate(predicate),
+                    },
+                },
+                None => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => self.quads_for_object_graph(object, graph_name),
+                        None => self.quads_for_object(object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => self.quads_for_graph(graph_name),
+                        None => self.quads(),
+                    },
+                },
+            },
+        }
+    }
+
+    fn encoded_named_graphs(&self) -> DecodingGraphIterator {
+        let mut iter = self.db_iter(self.graphs_cf());
+        iter.iter.seek_to_first();
+        DecodingGraphIterator { iter }
+    }
+
+    fn contains_encoded_named_graph(&self, graph_name: EncodedTerm) -> Result<bool, io::Error> {
+        self.db_contains(self.graphs_cf(), &encode_term(graph_name))
+    }
+
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        subject: Option<EncodedTerm>,
+        predicate: Option<EncodedTerm>,
+        object: Option<EncodedTerm>,
+        graph_name: Option<EncodedTerm>,
+    ) -> Option<u64> {
+        estimate_cardinality_from_statistics(
+            subject,
+            predicate,
+            object,
+            graph_name,
+            || self.encoded_quads_for_pattern(subject, predicate, object, graph_name),
+            |default_graph, predicate| self.quad_count(default_graph, predicate),
+        )
+    }
+
+    fn dictionary_id(&self) -> Option<u64> {
+        Some(self.dictionary_id.get())
+    }
+}
+
+struct AutoBatchWriter<'a> {
+    store: &'a RocksDbStore,
+    batch: WriteBatch,
+    buffer: Vec<u8>,
+    statistics: StatisticsChanges,
+}
+
+impl AutoBatchWriter<'_> {
+    fn apply(mut self) -> Result<(), io::Error> {
+        self.write_batch()
+    }
+
+    fn apply_if_big(&mut self) -> Result<(), io::Error> {
+        if self.batch.len() > MAX_TRANSACTION_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), io::Error> {
+        let _lock = self.store.write_lock();
+        self.statistics.write(self.store, take(&mut self.batch))
+    }
+
+    fn clear_cf(&mut self, cf: &ColumnFamily) {
+        clear_cf(&mut self.batch, cf)
+    }
+}
+
+/// The changes of the statistics done by a [`WriteBatch`].
+///
+/// The written quads are only looked up in the database when the batch is written, while holding the statistics lock,
+/// so that the quads inserted or removed concurrently by other writers are not counted twice.
+#[derive(Default)]
+struct StatisticsChanges {
+    /// If each written quad is in the database after the batch
+    written: HashMap<EncodedQuad, bool>,
+    /// Should the database quads be ignored
+    cleared: bool,
+    /// Should the database default graph quads be ignored
+    default_graph_cleared: bool,
+}
+
+impl StatisticsChanges {
+    fn insert(&mut self, quad: &EncodedQuad) {
+        self.written.insert(*quad, true);
+    }
+
+    fn remove(&mut self, quad: &EncodedQuad) {
+        self.written.insert(*quad, false);
+    }
+
+    /// Should be called when the default graph indexes are cleared by the batch
+    fn clear_default_graph(&mut self, batch: &mut WriteBatch) {
+        self.default_graph_cleared = true;
+        self.written
+            .retain(|quad, _| !quad.graph_name.is_default_graph());
+        batch.delete_range(statistics_key(true, None), statistics_key(false, None));
+    }
+
+    /// Should be called when all the quad indexes are cleared by the batch
+    fn clear(&mut self, batch: &mut WriteBatch) {
+        self.cleared = true;
+        self.written.clear();
+        batch.delete_range(STATISTICS_PREFIX, STATISTICS_END);
+    }
+
+    /// Adds the updated statistics to the batch, writes it and resets the changes
+    ///
+    /// The caller should hold the store write lock.
+    fn write(&mut self, store: &RocksDbStore, mut batch: WriteBatch) -> Result<(), io::Error> {
+        let changes = take(self);
+        let default_graph_prefix = statistics_key(true, None);
+        let _lock = store.statistics_lock();
+        let mut deltas = HashMap::<Vec<u8>, i64>::new();
+        for (quad, contained) in &changes.written {
+            let was_contained = !(changes.cleared
+                || changes.default_graph_cleared && quad.graph_name.is_default_graph())
+                && store.contains_encoded(quad)?;
+            if *contained != was_contained {
+                for key in quad_statistics_keys(quad) {
+                    *deltas.entry(key).or_default() += if *contained { 1 } else { -1 };
+                }
+            }
+        }
+        for (key, delta) in deltas {
+            if delta != 0 {
+                let count = if changes.cleared
+                    || (changes.default_graph_cleared && key.starts_with(&default_graph_prefix))
+                {
+                    0
+                } else {
+                    store.statistic(&key)?
+                };
+                batch.put(key, encode_statistic(count + delta));
+            }
+        }
+        store.db.write(batch).map_err(map_err)
+    }
+}
+
+fn clear_cf(batch: &mut WriteBatch, cf: &ColumnFamily) {
+    batch.delete_range_cf(
+        cf,
+        [
+            u8::MIN,
+            u8::MIN,
+            u8::MIN,
+            u8::MIN,
+            u8::MIN,
+            u8::MIN,
+            u8::MIN,
+            u8::MIN,
+        ],
+        [
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+        ],
+    )
+}
+
+impl StrEncodingAware for AutoBatchWriter<'_> {
+    type Error = io::Error;
+    type StrId = StrHash;
+}
+
+impl StrContainer for AutoBatchWriter<'_> {
+    fn insert_str(&mut self, value: &str) -> Result<StrHash, io::Error> {
+        let key = StrHash::new(value);
+        self.batch
+            .put_cf(self.store.id2str_cf(), &key.to_be_bytes(), value);
+        Ok(key)
+    }
+}
+
+impl WritableEncodedStore for AutoBatchWriter<'_> {
+    fn insert_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
+        self.statistics.insert(quad);
+        if quad.graph_name.is_default_graph() {
+            write_spo_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.dspo_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_pos_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.dpos_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_osp_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.dosp_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+        } else {
+            write_spog_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.spog_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_posg_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.posg_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_ospg_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.ospg_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_gspo_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.gspo_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_gpos_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.gpos_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_gosp_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.gosp_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_term(&mut self.buffer, quad.graph_name);
             self.batch.put_cf(self.store.graphs_cf(), &self.buffer, &[]);
             self.buffer.clear();
         }
@@ -992,6 +2303,7 @@ impl WritableEncodedStore for AutoBatchWriter<'_> {
     }
 
     fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
+        self.statistics.remove(quad);
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut self.buffer, quad);
             self.batch.delete_cf(self.store.dspo_cf(), &self.buffer);
@@ -1009,71 +2321,390 @@ impl WritableEncodedStore for AutoBatchWriter<'_> {
             self.batch.delete_cf(self.store.spog_cf(), &self.buffer);
             self.buffer.clear();
 
-            write_posg_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.posg_cf(), &self.buffer);
-            self.buffer.clear();
+            write_posg_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.posg_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_ospg_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.ospg_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_gspo_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.gspo_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_gpos_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.gpos_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_gosp_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.gosp_cf(), &self.buffer);
+            self.buffer.clear();
+        }
+
+        self.apply_if_big()
+    }
+
+    fn insert_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
+        self.batch
+            .put_cf(self.store.graphs_cf(), &encode_term(graph_name), &[]);
+        self.apply_if_big()
+    }
+
+    fn clear_encoded_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
+        if graph_name.is_default_graph() {
+            self.clear_cf(self.store.dspo_cf());
+            self.clear_cf(self.store.dpos_cf());
+            self.clear_cf(self.store.dosp_cf());
+            self.statistics.clear_default_graph(&mut self.batch);
+        } else {
+            for quad in self.store.quads_for_graph(graph_name) {
+                self.remove_encoded(&quad?)?;
+            }
+        }
+        self.apply_if_big()
+    }
+
+    fn remove_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
+        for quad in self.store.quads_for_graph(graph_name) {
+            self.remove_encoded(&quad?)?;
+        }
+        self.batch
+            .delete_cf(self.store.graphs_cf(), &encode_term(graph_name));
+        self.apply_if_big()
+    }
+
+    fn clear(&mut self) -> Result<(), io::Error> {
+        self.clear_cf(self.store.spog_cf());
+        self.clear_cf(self.store.posg_cf());
+        self.clear_cf(self.store.ospg_cf());
+        self.clear_cf(self.store.gspo_cf());
+        self.clear_cf(self.store.gpos_cf());
+        self.clear_cf(self.store.gosp_cf());
+        self.clear_cf(self.store.dspo_cf());
+        self.clear_cf(self.store.dpos_cf());
+        self.clear_cf(self.store.dosp_cf());
+        self.clear_cf(self.store.graphs_cf());
+        self.clear_cf(self.store.id2str_cf());
+        self.statistics.clear(&mut self.batch);
+        // The strings ids are not valid anymore after the deletion
+        self.write_batch()?;
+        self.store.dictionary_id.renew();
+        Ok(())
+    }
+}
+
+/// Stages the changes of a SPARQL update in memory before writing them atomically.
+///
+/// The reads see the database content with the staged changes applied.
+#[derive(Clone)]
+struct StagingArea {
+    store: RocksDbStore,
+    changes: Arc<RwLock<StagedChanges>>,
+}
+
+#[derive(Default)]
+struct StagedChanges {
+    /// Should the database quads and named graphs be ignored
+    cleared: bool,
+    /// Should the database default graph quads be ignored
+    default_graph_cleared: bool,
+    inserted: HashSet<EncodedQuad>,
+    removed: HashSet<EncodedQuad>,
+    inserted_graphs: HashSet<EncodedTerm>,
+    removed_graphs: HashSet<EncodedTerm>,
+    strings: HashMap<StrHash, String>,
+}
+
+impl StagedChanges {
+    /// Returns if a quad read from the database should be ignored
+    fn hides(&self, quad: &EncodedQuad) -> bool {
+        self.cleared
+            || (self.default_graph_cleared && quad.graph_name.is_default_graph())
+            || self.removed.contains(quad)
+    }
+}
+
+impl StagingArea {
+    fn new(store: &RocksDbStore) -> Self {
+        Self {
+            store: store.clone(),
+            changes: Arc::default(),
+        }
+    }
+
+    #[allow(clippy::expect_used)]
+    fn changes(&self) -> RwLockReadGuard<'_, StagedChanges> {
+        self.changes
+            .read()
+            .expect("the RocksDB staging area mutex has been poisoned because of a panic")
+    }
+
+    #[allow(clippy::expect_used)]
+    fn changes_mut(&self) -> RwLockWriteGuard<'_, StagedChanges> {
+        self.changes
+            .write()
+            .expect("the RocksDB staging area mutex has been poisoned because of a panic")
+    }
+
+    /// Writes the staged changes in a single atomic write
+    fn apply(self) -> Result<(), io::Error> {
+        let changes = self.changes();
+        let mut transaction = RocksDbTransaction {
+            store: &self.store,
+            batch: WriteBatch::default(),
+            buffer: Vec::new(),
+            new_strings: HashMap::new(),
+            statistics: StatisticsChanges::default(),
+        };
+        if changes.cleared {
+            for cf in &[
+                SPOG_CF, POSG_CF, OSPG_CF, GSPO_CF, GPOS_CF, GOSP_CF, DSPO_CF, DPOS_CF, DOSP_CF,
+                GRAPHS_CF,
+            ] {
+                clear_cf(&mut transaction.batch, get_cf(&self.store.db, cf));
+            }
+            transaction.statistics.clear(&mut transaction.batch);
+        } else if changes.default_graph_cleared {
+            for cf in &[DSPO_CF, DPOS_CF, DOSP_CF] {
+                clear_cf(&mut transaction.batch, get_cf(&self.store.db, cf));
+            }
+            transaction
+                .statistics
+                .clear_default_graph(&mut transaction.batch);
+        }
+        for (id, value) in &changes.strings {
+            transaction
+                .batch
+                .put_cf(self.store.id2str_cf(), id.to_be_bytes(), value);
+        }
+        for quad in &changes.removed {
+            transaction.remove_encoded(quad)?;
+        }
+        for graph_name in &changes.removed_graphs {
+            transaction
+                .batch
+                .delete_cf(self.store.graphs_cf(), encode_term(*graph_name));
+        }
+        for quad in &changes.inserted {
+            transaction.insert_encoded(quad)?;
+        }
+        for graph_name in &changes.inserted_graphs {
+            transaction.insert_encoded_named_graph(*graph_name)?;
+        }
+        transaction.apply()
+    }
+}
+
+impl StrEncodingAware for StagingArea {
+    type Error = io::Error;
+    type StrId = StrHash;
+}
+
+impl StrLookup for StagingArea {
+    fn get_str(&self, id: StrHash) -> Result<Option<String>, io::Error> {
+        if let Some(value) = self.changes().strings.get(&id) {
+            Ok(Some(value.clone()))
+        } else {
+            self.store.get_str(id)
+        }
+    }
+
+    fn get_str_id(&self, value: &str) -> Result<Option<StrHash>, io::Error> {
+        let id = StrHash::new(value);
+        if self.changes().strings.contains_key(&id) {
+            Ok(Some(id))
+        } else {
+            self.store.get_str_id(value)
+        }
+    }
+}
+
+impl ReadableEncodedStore for StagingArea {
+    type QuadsIter = Box<dyn Iterator<Item = Result<EncodedQuad, io::Error>> + Send>;
+    type GraphsIter = Box<dyn Iterator<Item = Result<EncodedTerm, io::Error>> + Send>;
+
+    fn encoded_quads_for_pattern(
+        &self,
+        subject: Option<EncodedTerm>,
+        predicate: Option<EncodedTerm>,
+        object: Option<EncodedTerm>,
+        graph_name: Option<EncodedTerm>,
+    ) -> Self::QuadsIter {
+        let inserted = self
+            .changes()
+            .inserted
+            .iter()
+            .filter(|quad| {
+                subject.iter().all(|s| *s == quad.subject)
+                    && predicate.iter().all(|p| *p == quad.predicate)
+                    && object.iter().all(|o| *o == quad.object)
+                    && graph_name.iter().all(|g| *g == quad.graph_name)
+            })
+            .copied()
+            .collect::<HashSet<_>>();
+        let changes = self.changes.clone();
+        let returned_inserted = inserted.clone();
+        Box::new(
+            self.store
+                .encoded_quads_for_pattern(subject, predicate, object, graph_name)
+                .filter(move |quad| {
+                    match quad {
+                    #[allow(clippy::expect_used)]
+                    Ok(quad) => !returned_inserted.contains(quad) && !changes
+                        .read()
+                        .expect(
+                            "the RocksDB staging area mutex has been poisoned because of a panic",
+                        )
+                        .hides(quad),
+                    Err(_) => true,
+                }
+                })
+                .chain(inserted.into_iter().map(Ok)),
+        )
+    }
+
+    fn encoded_named_graphs(&self) -> Self::GraphsIter {
+        let inserted = self.changes().inserted_graphs.clone();
+        let changes = self.changes.clone();
+        let returned_inserted = inserted.clone();
+        Box::new(
+            self.store
+                .encoded_named_graphs()
+                .filter(move |graph_name| match graph_name {
+                    #[allow(clippy::expect_used)]
+                    Ok(graph_name) => {
+                        let changes = changes.read().expect(
+                            "the RocksDB staging area mutex has been poisoned because of a panic",
+                        );
+                        !changes.cleared
+                            && !changes.removed_graphs.contains(graph_name)
+                            && !returned_inserted.contains(graph_name)
+                    }
+                    Err(_) => true,
+                })
+                .chain(inserted.into_iter().map(Ok)),
+        )
+    }
 
-            write_ospg_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.ospg_cf(), &self.buffer);
-            self.buffer.clear();
+    fn contains_encoded_named_graph(&self, graph_name: EncodedTerm) -> Result<bool, io::Error> {
+        let changes = self.changes();
+        if changes.inserted_graphs.contains(&graph_name) {
+            Ok(true)
+        } else if changes.cleared || changes.removed_graphs.contains(&graph_name) {
+            Ok(false)
+        } else {
+            self.store.contains_encoded_named_graph(graph_name)
+        }
+    }
 
-            write_gspo_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.gspo_cf(), &self.buffer);
-            self.buffer.clear();
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        subject: Option<EncodedTerm>,
+        predicate: Option<EncodedTerm>,
+        object: Option<EncodedTerm>,
+        graph_name: Option<EncodedTerm>,
+    ) -> Option<u64> {
+        // The removed quads are ignored, it is only an estimate
+        let changes = self.changes();
+        let inserted = changes
+            .inserted
+            .iter()
+            .filter(|quad| {
+                subject.iter().all(|s| *s == quad.subject)
+                    && predicate.iter().all(|p| *p == quad.predicate)
+                    && object.iter().all(|o| *o == quad.object)
+                    && graph_name.iter().all(|g| *g == quad.graph_name)
+            })
+            .count() as u64;
+        let stored = if changes.cleared
+            || (changes.default_graph_cleared
+                && matches!(graph_name, Some(g) if g.is_default_graph()))
+        {
+            0
+        } else {
+            self.store
+                .encoded_quads_for_pattern_cardinality(subject, predicate, object, graph_name)?
+        };
+        Some(stored.saturating_add(inserted))
+    }
+}
 
-            write_gpos_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.gpos_cf(), &self.buffer);
-            self.buffer.clear();
+impl StrContainer for StagingArea {
+    fn insert_str(&mut self, value: &str) -> Result<StrHash, io::Error> {
+        let key = StrHash::new(value);
+        self.changes_mut()
+            .strings
+            .entry(key)
+            .or_insert_with(|| value.to_owned());
+        Ok(key)
+    }
+}
 
-            write_gosp_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.gosp_cf(), &self.buffer);
-            self.buffer.clear();
+impl WritableEncodedStore for StagingArea {
+    fn insert_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
+        let mut changes = self.changes_mut();
+        changes.removed.remove(quad);
+        changes.inserted.insert(*quad);
+        if !quad.graph_name.is_default_graph() {
+            changes.removed_graphs.remove(&quad.graph_name);
+            changes.inserted_graphs.insert(quad.graph_name);
         }
+        Ok(())
+    }
 
-        self.apply_if_big()
+    fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
+        let mut changes = self.changes_mut();
+        changes.inserted.remove(quad);
+        changes.removed.insert(*quad);
+        Ok(())
     }
 
     fn insert_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
-        self.batch
-            .put_cf(self.store.graphs_cf(), &encode_term(graph_name), &[]);
-        self.apply_if_big()
+        let mut changes = self.changes_mut();
+        changes.removed_graphs.remove(&graph_name);
+        changes.inserted_graphs.insert(graph_name);
+        Ok(())
     }
 
     fn clear_encoded_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
         if graph_name.is_default_graph() {
-            self.clear_cf(self.store.dspo_cf());
-            self.clear_cf(self.store.dpos_cf());
-            self.clear_cf(self.store.dosp_cf());
+            let mut changes = self.changes_mut();
+            changes.default_graph_cleared = true;
+            changes
+                .inserted
+                .retain(|quad| !quad.graph_name.is_default_graph());
+            changes
+                .removed
+                .retain(|quad| !quad.graph_name.is_default_graph());
+            Ok(())
         } else {
-            for quad in self.store.quads_for_graph(graph_name) {
-                self.remove_encoded(&quad?)?;
+            let quads = self
+                .encoded_quads_for_pattern(None, None, None, Some(graph_name))
+                .collect::<Result<Vec<_>, _>>()?;
+            for quad in quads {
+                self.remove_encoded(&quad)?;
             }
+            Ok(())
         }
-        self.apply_if_big()
     }
 
     fn remove_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
-        for quad in self.store.quads_for_graph(graph_name) {
-            self.remove_encoded(&quad?)?;
-        }
-        self.batch
-            .delete_cf(self.store.graphs_cf(), &encode_term(graph_name));
-        self.apply_if_big()
+        self.clear_encoded_graph(graph_name)?;
+        let mut changes = self.changes_mut();
+        changes.inserted_graphs.remove(&graph_name);
+        changes.removed_graphs.insert(graph_name);
+        Ok(())
     }
 
     fn clear(&mut self) -> Result<(), io::Error> {
-        self.clear_cf(self.store.spog_cf());
-        self.clear_cf(self.store.posg_cf());
-        self.clear_cf(self.store.ospg_cf());
-        self.clear_cf(self.store.gspo_cf());
-        self.clear_cf(self.store.gpos_cf());
-        self.clear_cf(self.store.gosp_cf());
-        self.clear_cf(self.store.dspo_cf());
-        self.clear_cf(self.store.dpos_cf());
-        self.clear_cf(self.store.dosp_cf());
-        self.clear_cf(self.store.graphs_cf());
-        self.clear_cf(self.store.id2str_cf());
-        self.apply_if_big()
+        let mut changes = self.changes_mut();
+        changes.cleared = true;
+        changes.inserted.clear();
+        changes.removed.clear();
+        changes.inserted_graphs.clear();
+        changes.removed_graphs.clear();
+        Ok(())
     }
 }
 
@@ -1083,6 +2714,7 @@ pub struct RocksDbTransaction<'a> {
     batch: WriteBatch,
     buffer: Vec<u8>,
     new_strings: HashMap<StrHash, String>,
+    statistics: StatisticsChanges,
 }
 
 impl RocksDbTransaction<'_> {
@@ -1107,7 +2739,7 @@ impl RocksDbTransaction<'_> {
         to_graph_name: impl Into<GraphNameRef<'a>>,
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
-        load_graph(self, reader, syntax, to_graph_name.into(), base_iri)?;
+        load_graph(self, reader, syntax, to_graph_name.into(), base_iri, None)?;
         Ok(())
     }
 
@@ -1131,7 +2763,7 @@ impl RocksDbTransaction<'_> {
         format: DatasetFormat,
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
-        load_dataset(self, reader, format, base_iri)?;
+        load_dataset(self, reader, format, base_iri, None)?;
         Ok(())
     }
 
@@ -1151,8 +2783,38 @@ impl RocksDbTransaction<'_> {
         }
     }
 
-    fn apply(self) -> Result<(), io::Error> {
-        self.store.db.write(self.batch).map_err(map_err)
+    /// The caller should hold the store write lock
+    fn apply(mut self) -> Result<(), io::Error> {
+        self.statistics.write(self.store, self.batch)
+    }
+}
+
+impl StoreTransaction for RocksDbTransaction<'_> {
+    fn insert(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        RocksDbTransaction::insert(self, quad)
+    }
+
+    fn remove(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        RocksDbTransaction::remove(self, quad)
+    }
+
+    fn load_graph(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        RocksDbTransaction::load_graph(self, reader, format, to_graph_name, base_iri)
+    }
+
+    fn load_dataset(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        RocksDbTransaction::load_dataset(self, reader, format, base_iri)
     }
 }
 
@@ -1201,6 +2863,7 @@ impl StrContainer for RocksDbTransaction<'_> {
 
 impl WritableEncodedStore for RocksDbTransaction<'_> {
     fn insert_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
+        self.statistics.insert(quad);
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut self.buffer, quad);
             self.batch.put_cf(self.store.dspo_cf(), &self.buffer, &[]);
@@ -1218,101 +2881,638 @@ impl WritableEncodedStore for RocksDbTransaction<'_> {
             self.batch.put_cf(self.store.spog_cf(), &self.buffer, &[]);
             self.buffer.clear();
 
-            write_posg_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.posg_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+            write_posg_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.posg_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_ospg_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.ospg_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_gspo_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.gspo_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_gpos_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.gpos_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_gosp_quad(&mut self.buffer, quad);
+            self.batch.put_cf(self.store.gosp_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+
+            write_term(&mut self.buffer, quad.graph_name);
+            self.batch.put_cf(self.store.graphs_cf(), &self.buffer, &[]);
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+
+    fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
+        self.statistics.remove(quad);
+        if quad.graph_name.is_default_graph() {
+            write_spo_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.dspo_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_pos_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.dpos_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_osp_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.dosp_cf(), &self.buffer);
+            self.buffer.clear();
+        } else {
+            write_spog_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.spog_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_posg_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.posg_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_ospg_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.ospg_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_gspo_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.gspo_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_gpos_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.gpos_cf(), &self.buffer);
+            self.buffer.clear();
+
+            write_gosp_quad(&mut self.buffer, quad);
+            self.batch.delete_cf(self.store.gosp_cf(), &self.buffer);
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+
+    fn insert_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
+        self.batch
+            .put_cf(self.store.graphs_cf(), &encode_term(graph_name), &[]);
+        Ok(())
+    }
+
+    fn clear_encoded_graph(&mut self, _: EncodedTerm) -> Result<(), io::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "CLEAR is not implemented in RocksDB transactions",
+        ))
+    }
+
+    fn remove_encoded_named_graph(&mut self, _: EncodedTerm) -> Result<(), io::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "DROP is not implemented in RocksDB transactions",
+        ))
+    }
+
+    fn clear(&mut self) -> Result<(), Self::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "CLEAR ALL is not implemented in RocksDB transactions",
+        ))
+    }
+}
+
+/// A bulk loader for [`RocksDbStore`] created using [`RocksDbStore::bulk_loader`].
+///
+/// The quads are parsed and encoded by many threads into sorted [SST files](https://github.com/facebook/rocksdb/wiki/Creating-and-Ingesting-SST-files)
+/// that are written next to the store files and then ingested directly by RocksDB, without going through the usual write path.
+/// The lines of [N-Triples](https://www.w3.org/TR/n-triples/) and [N-Quads](https://www.w3.org/TR/n-quads/) files are parsed in parallel,
+/// the files in the other formats are parsed by the calling thread and encoded in parallel.
+///
+/// Nothing is written into the store if the loading fails, for example because of a syntax error.
+/// The SST files of the different indexes are not ingested atomically.
+/// That is why the loader borrows the store mutably and fails if another handle on the same database (a clone of the store, a snapshot or an iterator) is alive:
+/// no reader could see the loaded data in some indexes and not in others.
+///
+/// See [`RocksDbStore::bulk_loader`] for a usage example.
+#[must_use]
+pub struct RocksDbBulkLoader<'a> {
+    store: &'a mut RocksDbStore,
+    num_threads: usize,
+    max_memory_size: usize,
+    on_progress: Option<Box<dyn Fn(u64)>>,
+}
+
+impl RocksDbBulkLoader<'_> {
+    /// Sets the number of threads used to parse and encode the quads.
+    ///
+    /// The default is 4.
+    pub fn with_num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = num_threads.max(1);
+        self
+    }
+
+    /// Sets the approximate maximal amount of memory used to buffer the encoded quads before writing them into SST files.
+    ///
+    /// It is shared between the threads. The default is 1024MB.
+    /// A bigger budget means less and bigger SST files that are faster to query once ingested.
+    pub fn with_max_memory_size_in_megabytes(mut self, max_memory_size: usize) -> Self {
+        self.max_memory_size = max_memory_size.saturating_mul(1024 * 1024);
+        self
+    }
+
+    /// Sets a callback called regularly with the number of quads already parsed and encoded.
+    ///
+    /// It is called a last time with the total number of loaded quads once they have all been ingested into the store.
+    pub fn on_progress(mut self, callback: impl Fn(u64) + 'static) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Loads a graph file (i.e. triples) into the store.
+    ///
+    /// It fails with an [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error on the first syntax error.
+    ///
+    /// See [`RocksDbStore::load_graph`] for the description of the parameters.
+    pub fn load_graph<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        let to_graph_name = to_graph_name.into().into_owned();
+        if format == GraphFormat::NTriples {
+            return self.load(|send| {
+                send_lines(reader, send, |lines| BulkLoadJob::NTriples {
+                    lines,
+                    to_graph_name: to_graph_name.clone(),
+                })
+            });
+        }
+        let mut parser = GraphParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(invalid_input_error)?;
+        }
+        self.load(|send| {
+            send_quads(
+                parser
+                    .read_triples(reader)?
+                    .map(|t| Ok(t?.in_graph(to_graph_name.clone()))),
+                send,
+            )
+        })
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store.
+    ///
+    /// It fails with an [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error on the first syntax error.
+    ///
+    /// See [`RocksDbStore::load_dataset`] for the description of the parameters.
+    pub fn load_dataset(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        if format == DatasetFormat::NQuads {
+            return self.load(|send| send_lines(reader, send, BulkLoadJob::NQuads));
+        }
+        let mut parser = DatasetParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(invalid_input_error)?;
+        }
+        self.load(|send| send_quads(parser.read_quads(reader)?, send))
+    }
+
+    /// Sends the jobs created by `read` to the encoding threads and then ingests the SST files they have written
+    fn load(
+        &self,
+        read: impl FnOnce(&mut dyn FnMut(BulkLoadJob) -> Result<(), io::Error>) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error> {
+        // The loader holds the only mutable borrow of this handle so no new handle could be created during the loading
+        if Arc::strong_count(&self.store.db) > 1 {
+            return Err(invalid_input_error(
+                "The bulk loader requires an exclusive access to the store: the clones of the store, the snapshots and the iterators on it should be dropped first",
+            ));
+        }
+        let directory = TemporaryDirectory::create(self.store.db.path())?;
+        let loaded = Arc::new(AtomicU64::new(0));
+        let (sender, receiver) = sync_channel(self.num_threads);
+        let receiver = Arc::new(Mutex::new(receiver));
+        // The blank node identifiers of the chunks of the same file should be mapped to the same blank nodes
+        let blank_node_hasher = SipHasher24::new_with_keys(random(), random());
+        let mut workers = Vec::with_capacity(self.num_threads);
+        for _ in 0..self.num_threads {
+            let worker = BulkLoadWorker {
+                directory: directory.path.clone(),
+                max_memory_size: self.max_memory_size / self.num_threads,
+                blank_node_hasher,
+                loaded: loaded.clone(),
+                strings: HashMap::default(),
+                quads: Default::default(),
+                graphs: HashSet::default(),
+                buffer: Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE),
+                memory_size: 0,
+                files: Vec::new(),
+            };
+            let receiver = receiver.clone();
+            workers.push(thread::Builder::new().spawn(move || worker.run(&receiver))?);
+        }
+
+        let read_result = read(&mut |job| {
+            sender.send(job).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "The bulk loading threads have stopped unexpectedly",
+                )
+            })?;
+            if let Some(on_progress) = &self.on_progress {
+                on_progress(loaded.load(Ordering::Relaxed));
+            }
+            Ok(())
+        });
+        drop(sender);
+        let files = join_workers(workers)?;
+        read_result?;
+
+        // The strings are ingested first so the ingested quads are always decodable
+        let mut options = IngestExternalFileOptions::default();
+        options.set_move_files(true);
+        for cf in &COLUMN_FAMILIES {
+            let paths = files
+                .iter()
+                .filter(|(file_cf, _)| file_cf == cf)
+                .map(|(_, path)| path)
+                .collect::<Vec<_>>();
+            if !paths.is_empty() {
+                self.store
+                    .db
+                    .ingest_external_file_cf_opts(get_cf(&self.store.db, cf), &options, paths)
+                    .map_err(map_err)?;
+            }
+        }
+        // The loaded quads might already be in the store, it is simpler to count again all the quads
+        self.store.compute_statistics()?;
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(loaded.load(Ordering::Relaxed));
+        }
+        Ok(())
+    }
+}
+
+/// Work given to the [`RocksDbBulkLoader`] encoding threads
+enum BulkLoadJob {
+    NTriples {
+        lines: BulkLoadLines,
+        to_graph_name: GraphName,
+    },
+    NQuads(BulkLoadLines),
+    Quads(Vec<Quad>),
+}
 
-            write_ospg_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.ospg_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+/// Complete lines of an N-Triples or N-Quads file
+struct BulkLoadLines {
+    data: Vec<u8>,
+    /// The number of lines before them in the file
+    line_offset: u64,
+    /// Their position in bytes in the file
+    byte_offset: u64,
+}
 
-            write_gspo_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.gspo_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+/// Splits an N-Triples or N-Quads file into chunks of lines
+fn send_lines(
+    mut reader: impl BufRead,
+    send: &mut dyn FnMut(BulkLoadJob) -> Result<(), io::Error>,
+    job: impl Fn(BulkLoadLines) -> BulkLoadJob,
+) -> Result<(), io::Error> {
+    let mut line_offset = 0;
+    let mut byte_offset = 0;
+    loop {
+        let mut data = Vec::with_capacity(BULK_LOAD_CHUNK_SIZE + 1024);
+        let mut line_count = 0;
+        while data.len() < BULK_LOAD_CHUNK_SIZE && reader.read_until(b'\n', &mut data)? > 0 {
+            line_count += 1;
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let len = data.len() as u64;
+        send(job(BulkLoadLines {
+            data,
+            line_offset,
+            byte_offset,
+        }))?;
+        line_offset += line_count;
+        byte_offset += len;
+    }
+}
 
-            write_gpos_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.gpos_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+/// Splits already parsed quads into batches
+fn send_quads(
+    quads: impl Iterator<Item = Result<Quad, io::Error>>,
+    send: &mut dyn FnMut(BulkLoadJob) -> Result<(), io::Error>,
+) -> Result<(), io::Error> {
+    let mut batch = Vec::with_capacity(BULK_LOAD_BATCH_SIZE);
+    for quad in quads {
+        batch.push(quad?);
+        if batch.len() >= BULK_LOAD_BATCH_SIZE {
+            send(BulkLoadJob::Quads(take(&mut batch)))?;
+        }
+    }
+    if !batch.is_empty() {
+        send(BulkLoadJob::Quads(batch))?;
+    }
+    Ok(())
+}
 
-            write_gosp_quad(&mut self.buffer, quad);
-            self.batch.put_cf(self.store.gosp_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+/// Waits for the end of the encoding threads and returns the SST files they have written
+fn join_workers(
+    workers: Vec<JoinHandle<Result<Vec<(&'static str, PathBuf)>, io::Error>>>,
+) -> Result<Vec<(&'static str, PathBuf)>, io::Error> {
+    let mut files = Vec::new();
+    let mut error = None;
+    for worker in workers {
+        match worker.join() {
+            Ok(Ok(worker_files)) => files.extend(worker_files),
+            Ok(Err(e)) => error = error.or(Some(e)),
+            Err(_) => {
+                error = error.or_else(|| {
+                    Some(io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "A bulk loading thread has panicked",
+                    ))
+                })
+            }
+        }
+    }
+    if let Some(error) = error {
+        Err(error)
+    } else {
+        Ok(files)
+    }
+}
 
-            write_term(&mut self.buffer, quad.graph_name);
-            self.batch.put_cf(self.store.graphs_cf(), &self.buffer, &[]);
-            self.buffer.clear();
+/// Encodes quads and writes them into sorted SST files each time its memory budget is exceeded
+struct BulkLoadWorker {
+    directory: PathBuf,
+    max_memory_size: usize,
+    blank_node_hasher: SipHasher24,
+    loaded: Arc<AtomicU64>,
+    strings: HashMap<StrHash, String>,
+    /// The keys of the quad indexes in the order of [`QUAD_INDEX_CFS`]
+    quads: [Vec<Vec<u8>>; 9],
+    graphs: HashSet<EncodedTerm>,
+    buffer: Vec<u8>,
+    memory_size: usize,
+    files: Vec<(&'static str, PathBuf)>,
+}
+
+impl BulkLoadWorker {
+    #[allow(clippy::expect_used)]
+    fn run(
+        mut self,
+        receiver: &Mutex<Receiver<BulkLoadJob>>,
+    ) -> Result<Vec<(&'static str, PathBuf)>, io::Error> {
+        loop {
+            let job = receiver
+                .lock()
+                .expect("the bulk loader job queue has been poisoned because of a panic")
+                .recv();
+            match job {
+                Ok(job) => self.process(job)?,
+                Err(_) => break, // No more jobs
+            }
+            if self.memory_size > self.max_memory_size {
+                self.flush()?;
+            }
         }
+        self.flush()?;
+        Ok(self.files)
+    }
 
+    fn process(&mut self, job: BulkLoadJob) -> Result<(), io::Error> {
+        let mut count = 0;
+        match job {
+            BulkLoadJob::NTriples {
+                lines,
+                to_graph_name,
+            } => {
+                let to_graph_name = self
+                    .encode_graph_name(to_graph_name.as_ref())
+                    .unwrap_infallible();
+                let line_starts = LineStarts::starting_at(lines.line_offset, lines.byte_offset);
+                let mut parser = NTriplesParser::new(line_starts.track(lines.data.as_slice()));
+                let mut bnodes_map = HashMap::new();
+                while !parser.is_end() {
+                    parser
+                        .parse_step(&mut |t| -> Result<(), TurtleError> {
+                            self.map_blank_nodes(
+                                &[Some(t.subject.into()), Some(t.object)],
+                                &mut bnodes_map,
+                            );
+                            let quad = self
+                                .encode_rio_triple_in_graph(t, to_graph_name, &mut bnodes_map)
+                                .unwrap_infallible();
+                            self.insert_encoded(&quad);
+                            count += 1;
+                            Ok(())
+                        })
+                        .map_err(|e| line_starts.locate(syntax_error(e)))?;
+                }
+            }
+            BulkLoadJob::NQuads(lines) => {
+                let line_starts = LineStarts::starting_at(lines.line_offset, lines.byte_offset);
+                let mut parser = NQuadsParser::new(line_starts.track(lines.data.as_slice()));
+                let mut bnodes_map = HashMap::new();
+                while !parser.is_end() {
+                    parser
+                        .parse_step(&mut |q| -> Result<(), TurtleError> {
+                            self.map_blank_nodes(
+                                &[
+                                    Some(q.subject.into()),
+                                    Some(q.object),
+                                    q.graph_name.map(rio::Term::from),
+                                ],
+                                &mut bnodes_map,
+                            );
+                            let quad = self.encode_rio_quad(q, &mut bnodes_map).unwrap_infallible();
+                            self.insert_encoded(&quad);
+                            count += 1;
+                            Ok(())
+                        })
+                        .map_err(|e| line_starts.locate(syntax_error(e)))?;
+                }
+            }
+            BulkLoadJob::Quads(quads) => {
+                for quad in quads {
+                    let quad = self.encode_quad(quad.as_ref()).unwrap_infallible();
+                    self.insert_encoded(&quad);
+                    count += 1;
+                }
+            }
+        }
+        self.loaded.fetch_add(count, Ordering::Relaxed);
         Ok(())
     }
 
-    fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
-        if quad.graph_name.is_default_graph() {
-            write_spo_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.dspo_cf(), &self.buffer);
-            self.buffer.clear();
-
-            write_pos_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.dpos_cf(), &self.buffer);
-            self.buffer.clear();
+    /// Derives the blank nodes ids from their identifiers in the file so all the encoding threads agree on them
+    fn map_blank_nodes(
+        &self,
+        terms: &[Option<rio::Term<'_>>],
+        bnodes_map: &mut HashMap<String, u128>,
+    ) {
+        for term in terms {
+            if let Some(rio::Term::BlankNode(blank_node)) = term {
+                if !bnodes_map.contains_key(blank_node.id) {
+                    let mut hasher = self.blank_node_hasher;
+                    hasher.write(blank_node.id.as_bytes());
+                    bnodes_map.insert(blank_node.id.to_owned(), hasher.finish128().into());
+                }
+            }
+        }
+    }
 
-            write_osp_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.dosp_cf(), &self.buffer);
-            self.buffer.clear();
+    fn insert_encoded(&mut self, quad: &EncodedQuad) {
+        if quad.graph_name.is_default_graph() {
+            self.insert_key(6, write_spo_quad, quad);
+            self.insert_key(7, write_pos_quad, quad);
+            self.insert_key(8, write_osp_quad, quad);
         } else {
-            write_spog_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.spog_cf(), &self.buffer);
-            self.buffer.clear();
-
-            write_posg_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.posg_cf(), &self.buffer);
-            self.buffer.clear();
-
-            write_ospg_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.ospg_cf(), &self.buffer);
-            self.buffer.clear();
+            self.insert_key(0, write_spog_quad, quad);
+            self.insert_key(1, write_posg_quad, quad);
+            self.insert_key(2, write_ospg_quad, quad);
+            self.insert_key(3, write_gspo_quad, quad);
+            self.insert_key(4, write_gpos_quad, quad);
+            self.insert_key(5, write_gosp_quad, quad);
+            if self.graphs.insert(quad.graph_name) {
+                self.memory_size += size_of::<EncodedTerm>();
+            }
+        }
+    }
 
-            write_gspo_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.gspo_cf(), &self.buffer);
-            self.buffer.clear();
+    fn insert_key(
+        &mut self,
+        index: usize,
+        write: fn(&mut Vec<u8>, &EncodedQuad),
+        quad: &EncodedQuad,
+    ) {
+        write(&mut self.buffer, quad);
+        self.memory_size += size_of::<Vec<u8>>() + self.buffer.len();
+        self.quads[index].push(self.buffer.clone());
+        self.buffer.clear();
+    }
 
-            write_gpos_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.gpos_cf(), &self.buffer);
-            self.buffer.clear();
+    /// Writes the buffered entries into new SST files, one per column family
+    fn flush(&mut self) -> Result<(), io::Error> {
+        let mut strings = take(&mut self.strings)
+            .into_iter()
+            .map(|(id, value)| (id.to_be_bytes(), value))
+            .collect::<Vec<_>>();
+        strings.sort_unstable_by_key(|(key, _)| *key);
+        self.write_sst_file(
+            ID2STR_CF,
+            strings
+                .iter()
+                .map(|(key, value)| (key.as_ref(), value.as_bytes())),
+        )?;
 
-            write_gosp_quad(&mut self.buffer, quad);
-            self.batch.delete_cf(self.store.gosp_cf(), &self.buffer);
-            self.buffer.clear();
+        let mut quads = take(&mut self.quads);
+        for (cf, keys) in QUAD_INDEX_CFS.iter().zip(quads.iter_mut()) {
+            keys.sort_unstable();
+            keys.dedup();
+            self.write_sst_file(cf, keys.iter().map(|key| (key.as_slice(), [].as_ref())))?;
         }
 
+        let mut graphs = take(&mut self.graphs)
+            .into_iter()
+            .map(|graph_name| {
+                let mut key = Vec::with_capacity(WRITTEN_TERM_MAX_SIZE);
+                write_term(&mut key, graph_name);
+                key
+            })
+            .collect::<Vec<_>>();
+        graphs.sort_unstable();
+        self.write_sst_file(
+            GRAPHS_CF,
+            graphs.iter().map(|key| (key.as_slice(), [].as_ref())),
+        )?;
+
+        self.memory_size = 0;
         Ok(())
     }
 
-    fn insert_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
-        self.batch
-            .put_cf(self.store.graphs_cf(), &encode_term(graph_name), &[]);
+    /// Writes entries sorted by key into a new SST file if there are some
+    fn write_sst_file<'b>(
+        &mut self,
+        cf: &'static str,
+        entries: impl Iterator<Item = (&'b [u8], &'b [u8])>,
+    ) -> Result<(), io::Error> {
+        let mut entries = entries.peekable();
+        if entries.peek().is_none() {
+            return Ok(()); // RocksDB does not allow empty SST files
+        }
+        let options = Options::default();
+        let mut writer = SstFileWriter::create(&options);
+        let path = self.directory.join(format!("{:x}.sst", random::<u128>()));
+        writer.open(&path).map_err(map_err)?;
+        for (key, value) in entries {
+            writer.put(key, value).map_err(map_err)?;
+        }
+        writer.finish().map_err(map_err)?;
+        self.files.push((cf, path));
         Ok(())
     }
+}
 
-    fn clear_encoded_graph(&mut self, _: EncodedTerm) -> Result<(), io::Error> {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            "CLEAR is not implemented in RocksDB transactions",
-        ))
+impl StrEncodingAware for BulkLoadWorker {
+    type Error = Infallible;
+    type StrId = StrHash;
+}
+
+impl StrContainer for BulkLoadWorker {
+    fn insert_str(&mut self, value: &str) -> Result<StrHash, Infallible> {
+        let key = StrHash::new(value);
+        if !self.strings.contains_key(&key) {
+            self.memory_size += size_of::<(StrHash, String)>() + value.len();
+            self.strings.insert(key, value.to_owned());
+        }
+        Ok(key)
     }
+}
 
-    fn remove_encoded_named_graph(&mut self, _: EncodedTerm) -> Result<(), io::Error> {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            "DROP is not implemented in RocksDB transactions",
-        ))
+/// A directory inside of the store directory that is removed when dropped
+struct TemporaryDirectory {
+    path: PathBuf,
+}
+
+impl TemporaryDirectory {
+    fn create(parent: &Path) -> Result<Self, io::Error> {
+        let path = parent.join(format!("bulk-load-{:x}", random::<u128>()));
+        create_dir(&path)?;
+        Ok(Self { path })
     }
+}
 
-    fn clear(&mut self) -> Result<(), Self::Error> {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            "CLEAR ALL is not implemented in RocksDB transactions",
-        ))
+impl Drop for TemporaryDirectory {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.path);
+    }
+}
+
+fn check_version(version: u64) -> Result<(), io::Error> {
+    match version {
+        _ if version < LATEST_STORAGE_VERSION => Err(invalid_data_error(format!(
+            "The RocksDB database is using the outdated encoding version {}. No automated migration is available from it, please dump the store dataset using a compatible Oxigraph version and load it again using the current version",
+            version
+        ))),
+        LATEST_STORAGE_VERSION => Ok(()),
+        _ => Err(invalid_data_error(format!(
+            "The RocksDB database is using the too recent version {}. Upgrade to the latest Oxigraph version to load this database",
+            version
+        )))
     }
 }
 
@@ -1324,20 +3524,28 @@ fn get_cf<'a>(db: &'a DB, name: &str) -> &'a ColumnFamily {
 
 struct StaticDBRowIterator {
     iter: DBRawIterator<'static>,
+    _snapshot: Option<Arc<StaticSnapshot>>, // needed to ensure that the snapshot still lives while iter is used
     _db: Arc<DB>, // needed to ensure that DB still lives while iter is used
+    error_returned: bool,
 }
 
 impl StaticDBRowIterator {
     /// Creates a static iterator from a non static one by keeping a ARC reference to the database
-    /// Caller must ensure that the iterator belongs to the same database
+    /// Caller must ensure that the iterator belongs to the same database and to the given snapshot if any
     ///
     /// This unsafe method is required to get static iterators and ease the usage of the library
     /// and make streaming Python bindings possible
     #[allow(unsafe_code)]
-    unsafe fn new(iter: DBRawIterator<'_>, db: Arc<DB>) -> Self {
+    unsafe fn new(
+        iter: DBRawIterator<'_>,
+        snapshot: Option<Arc<StaticSnapshot>>,
+        db: Arc<DB>,
+    ) -> Self {
         Self {
             iter: transmute(iter),
+            _snapshot: snapshot,
             _db: db,
+            error_returned: false,
         }
     }
 
@@ -1348,6 +3556,36 @@ impl StaticDBRowIterator {
     fn next(&mut self) {
         self.iter.next()
     }
+
+    fn status(&self) -> Result<(), io::Error> {
+        self.iter.status().map_err(map_err)
+    }
+
+    /// Returns the error that has stopped the iteration if there is one and it has not been returned yet
+    fn take_error(&mut self) -> Option<io::Error> {
+        if self.error_returned {
+            return None;
+        }
+        let error = self.status().err();
+        self.error_returned = error.is_some();
+        error
+    }
+}
+
+struct StaticSnapshot {
+    snapshot: Snapshot<'static>,
+    _db: Arc<DB>, // needed to ensure that DB still lives while the snapshot is used
+}
+
+impl StaticSnapshot {
+    #[allow(unsafe_code)]
+    fn new(db: Arc<DB>) -> Self {
+        Self {
+            // Valid because the database can't be dropped before the snapshot
+            snapshot: unsafe { transmute::<Snapshot<'_>, Snapshot<'static>>(db.snapshot()) },
+            _db: db,
+        }
+    }
 }
 
 pub(crate) struct DecodingIndexesIterator {
@@ -1404,7 +3642,7 @@ impl Iterator for DecodingIndexIterator {
                 None
             }
         } else {
-            None
+            self.iter.take_error().map(Err)
         }
     }
 }
@@ -1455,7 +3693,7 @@ impl Iterator for DecodingGraphIterator {
             self.iter.next();
             Some(result)
         } else {
-            None
+            self.iter.take_error().map(Err)
         }
     }
 }
@@ -1663,3 +3901,338 @@ fn store() -> Result<(), io::Error> {
     remove_dir_all(&repo_path)?;
     Ok(())
 }
+
+#[test]
+fn migration() -> Result<(), io::Error> {
+    use crate::model::*;
+    use rand::random;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static REWRITE_CALLS: AtomicUsize = AtomicUsize::new(0);
+    const QUAD_COUNT: usize = 3000;
+    const INTERRUPTION: usize = 1500;
+
+    /// Fixes the DOSP index keys written in the SPO order and fails once in the middle
+    fn rewrite(key: &[u8]) -> Result<Vec<u8>, io::Error> {
+        if REWRITE_CALLS.fetch_add(1, Ordering::Relaxed) == INTERRUPTION {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interruption"));
+        }
+        let mut buffer = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
+        write_osp_quad(&mut buffer, &QuadEncoding::DSPO.decode(key)?);
+        Ok(buffer)
+    }
+    const STEPS: &[MigrationStep] = &[MigrationStep::RewriteIndex {
+        index: StorageIndex::Dosp,
+        rewrite,
+    }];
+    let migrations = [Migration {
+        from_version: LATEST_STORAGE_VERSION,
+        steps: STEPS,
+    }];
+
+    let mut repo_path = temp_dir();
+    repo_path.push(random::<u128>().to_string());
+
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        let ex = NamedNodeRef::new_unchecked("http://example.com");
+        for i in 0..QUAD_COUNT {
+            store.insert(QuadRef::new(ex, ex, &Literal::from(i as u64), None))?;
+        }
+        let mut batch = WriteBatch::default();
+        clear_cf(&mut batch, store.dosp_cf());
+        let mut iter = store.db_iter(store.dspo_cf());
+        iter.iter.seek_to_first();
+        while let Some(key) = iter.key() {
+            batch.put_cf(store.dosp_cf(), key, []);
+            iter.next();
+        }
+        iter.status()?;
+        store.db.write(batch).map_err(map_err)?;
+
+        // The migration is interrupted and then resumed from the last saved progress
+        assert!(store.migrate(&migrations).is_err());
+        assert_eq!(store.migrate(&migrations)?, LATEST_STORAGE_VERSION + 1);
+        assert_eq!(
+            REWRITE_CALLS.load(Ordering::Relaxed),
+            INTERRUPTION + 1 + QUAD_COUNT - MIGRATION_BATCH_SIZE
+        );
+        assert!(store
+            .db
+            .get(MIGRATION_PROGRESS_KEY)
+            .map_err(map_err)?
+            .is_none());
+        for i in 0..QUAD_COUNT {
+            let object = Literal::from(i as u64);
+            assert_eq!(
+                store
+                    .quads_for_pattern(
+                        None,
+                        None,
+                        Some(object.as_ref().into()),
+                        Some(GraphNameRef::DefaultGraph)
+                    )
+                    .collect::<Result<Vec<_>, _>>()?,
+                vec![Quad::new(ex, ex, object, None)]
+            );
+        }
+    }
+
+    remove_dir_all(&repo_path)?;
+    Ok(())
+}
+
+#[test]
+fn statistics() -> Result<(), io::Error> {
+    use crate::model::*;
+    use rand::random;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+
+    fn statistics(store: &RocksDbStore) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        let mut statistics = Vec::new();
+        let mut iter = store.db.raw_iterator();
+        iter.seek(STATISTICS_PREFIX);
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            if !key.starts_with(STATISTICS_PREFIX) {
+                break;
+            }
+            if decode_statistic(value) != Some(0) {
+                statistics.push((key.to_vec(), value.to_vec()));
+            }
+            iter.next();
+        }
+        iter.status().map_err(map_err)?;
+        Ok(statistics)
+    }
+
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        let ex = NamedNodeRef::new_unchecked("http://example.com");
+        let ex2 = NamedNodeRef::new_unchecked("http://example.com/2");
+        let cardinality = |predicate, graph_name| {
+            store.encoded_quads_for_pattern_cardinality(None, predicate, None, graph_name)
+        };
+        store.insert(QuadRef::new(ex, ex, ex, None))?;
+        let encoded_ex = store.get_encoded_term(ex.into())?.unwrap();
+        store.insert(QuadRef::new(ex, ex, ex, None))?;
+        store.insert(QuadRef::new(ex, ex, ex2, None))?;
+        store.insert(QuadRef::new(ex, ex2, ex, ex))?;
+        store.transaction(|transaction| {
+            transaction.insert(QuadRef::new(ex, ex, ex, ex))?;
+            transaction.insert(QuadRef::new(ex2, ex, ex, ex))?;
+            transaction.insert(QuadRef::new(ex2, ex, ex, ex))?;
+            transaction.remove(QuadRef::new(ex2, ex, ex, ex))?;
+            transaction.remove(QuadRef::new(ex2, ex2, ex2, ex2))?;
+            Result::<_, io::Error>::Ok(())
+        })?;
+        store.remove(QuadRef::new(ex, ex, ex2, None))?;
+        store.remove(QuadRef::new(ex, ex, ex2, None))?;
+        assert_eq!(cardinality(None, None), Some(3));
+        assert_eq!(cardinality(Some(encoded_ex), None), Some(2));
+        assert_eq!(
+            cardinality(Some(encoded_ex), Some(EncodedTerm::DefaultGraph)),
+            Some(1)
+        );
+        assert_eq!(cardinality(None, Some(encoded_ex)), Some(2));
+        store
+            .update(
+                "INSERT DATA { <http://example.com/2> <http://example.com> <http://example.com> }",
+            )
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        assert_eq!(cardinality(Some(encoded_ex), None), Some(3));
+
+        // The statistics are computed again from the indexes
+        let before = statistics(&store)?;
+        store.compute_statistics()?;
+        assert_eq!(statistics(&store)?, before);
+
+        store.clear_graph(GraphNameRef::DefaultGraph)?;
+        assert_eq!(cardinality(None, None), Some(2));
+        store.clear()?;
+        assert_eq!(cardinality(None, None), Some(0));
+    }
+    remove_dir_all(&repo_path)?;
+    Ok(())
+}
+
+#[test]
+fn cardinality_estimates() -> Result<(), io::Error> {
+    use crate::model::*;
+    use rand::random;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        let rdf_type =
+            NamedNodeRef::new_unchecked("http://www.w3.org/1999/02/22-rdf-syntax-ns#type");
+        let class = NamedNodeRef::new_unchecked("http://example.com/Class");
+        let rare_class = NamedNodeRef::new_unchecked("http://example.com/RareClass");
+        let age = NamedNodeRef::new_unchecked("http://example.com/age");
+        let knows = NamedNodeRef::new_unchecked("http://example.com/knows");
+        let likes = NamedNodeRef::new_unchecked("http://example.com/likes");
+        let popular = NamedNodeRef::new_unchecked("http://example.com/popular");
+        let s0 = NamedNode::new_unchecked("http://example.com/s0");
+        for i in 0..1000 {
+            let s = NamedNode::new_unchecked(format!("http://example.com/s{}", i));
+            let o = NamedNode::new_unchecked(format!("http://example.com/o{}", i));
+            // All the subjects share the same class
+            store.insert(QuadRef::new(&s, rdf_type, class, None))?;
+            // The ages are inline literals
+            store.insert(QuadRef::new(&s, age, &Literal::from(i), None))?;
+            // The objects are evenly distributed hashed IRIs
+            store.insert(QuadRef::new(&s, knows, &o, None))?;
+            // A quarter of the subjects like the same object
+            if i % 4 == 0 {
+                store.insert(QuadRef::new(&s, likes, popular, None))?;
+            } else {
+                store.insert(QuadRef::new(&s, likes, &o, None))?;
+            }
+        }
+        store.insert(QuadRef::new(&s0, rdf_type, rare_class, None))?;
+        let encode = |term: TermRef<'_>| -> Result<EncodedTerm, io::Error> {
+            Ok(store.get_encoded_term(term)?.unwrap())
+        };
+        let rdf_type = encode(rdf_type.into())?;
+        let class = encode(class.into())?;
+        let rare_class = encode(rare_class.into())?;
+        let age = encode(age.into())?;
+        let forty_two = encode(Literal::from(42).as_ref().into())?;
+        let knows = encode(knows.into())?;
+        let likes = encode(likes.into())?;
+        let popular = encode(popular.into())?;
+        let o1 = encode(NamedNodeRef::new_unchecked("http://example.com/o1").into())?;
+        let s0 = encode(s0.as_ref().into())?;
+        let cardinality = |subject, predicate, object| {
+            store.encoded_quads_for_pattern_cardinality(subject, predicate, object, None)
+        };
+        assert_eq!(cardinality(None, None, None), Some(4001));
+        assert_eq!(cardinality(None, Some(rdf_type), None), Some(1001));
+        assert_eq!(cardinality(None, Some(rdf_type), Some(class)), Some(1001));
+        assert_eq!(cardinality(None, Some(rdf_type), Some(rare_class)), Some(1));
+        assert_eq!(cardinality(None, Some(age), None), Some(1000));
+        assert_eq!(cardinality(None, Some(age), Some(forty_two)), Some(1));
+        assert_eq!(cardinality(None, Some(knows), None), Some(1000));
+        assert_eq!(cardinality(Some(s0), Some(knows), None), Some(1));
+        assert_eq!(cardinality(None, Some(likes), Some(o1)), Some(1));
+        // A frequent object is estimated using the predicate count, an upper bound of the exact cardinality
+        let likes_popular = cardinality(None, Some(likes), Some(popular)).unwrap();
+        assert!((250..=1000).contains(&likes_popular));
+
+        // The read-only instances read the same statistics
+        let read_only = RocksDbStore::open_read_only(&repo_path)?;
+        assert_eq!(
+            read_only.encoded_quads_for_pattern_cardinality(None, Some(rdf_type), None, None),
+            Some(1001)
+        );
+    }
+    remove_dir_all(&repo_path)?;
+    Ok(())
+}
+
+#[test]
+fn bulk_load_statistics() -> Result<(), io::Error> {
+    use crate::io::DatasetFormat;
+    use crate::model::*;
+    use rand::random;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let mut store = RocksDbStore::open(&repo_path)?;
+        let ex = NamedNodeRef::new_unchecked("http://example.com");
+        let ex2 = NamedNodeRef::new_unchecked("http://example.com/2");
+        store.insert(QuadRef::new(ex, ex, ex, None))?;
+        store.insert(QuadRef::new(ex, ex2, ex, ex))?;
+
+        // The loaded quads partially overlap the existing ones and are loaded twice
+        let file = "<http://example.com> <http://example.com> <http://example.com> .\n\
+                    <http://example.com> <http://example.com> <http://example.com/2> .\n\
+                    <http://example.com> <http://example.com/2> <http://example.com> <http://example.com> .\n\
+                    <http://example.com> <http://example.com> <http://example.com> <http://example.com/2> .\n";
+        for _ in 0..2 {
+            store
+                .bulk_loader()
+                .load_dataset(file.as_bytes(), DatasetFormat::NQuads, None)?;
+        }
+
+        let encoded_ex = store.get_encoded_term(ex.into())?.unwrap();
+        let encoded_ex2 = store.get_encoded_term(ex2.into())?.unwrap();
+        let cardinality = |predicate, graph_name| {
+            store.encoded_quads_for_pattern_cardinality(None, predicate, None, graph_name)
+        };
+        assert_eq!(cardinality(None, None), Some(4));
+        assert_eq!(cardinality(Some(encoded_ex), None), Some(3));
+        assert_eq!(cardinality(Some(encoded_ex2), None), Some(1));
+        assert_eq!(
+            cardinality(Some(encoded_ex), Some(EncodedTerm::DefaultGraph)),
+            Some(2)
+        );
+        assert_eq!(cardinality(None, Some(encoded_ex)), Some(1));
+        assert_eq!(cardinality(None, Some(encoded_ex2)), Some(1));
+    }
+    remove_dir_all(&repo_path)?;
+    Ok(())
+}
+
+#[test]
+fn statistics_with_concurrent_writers() -> Result<(), io::Error> {
+    use crate::model::*;
+    use rand::random;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        let p = NamedNodeRef::new_unchecked("http://example.com/p");
+        // All the threads insert and then remove the same quads
+        let threads = (0..4)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let s = NamedNode::new_unchecked(format!("http://example.com/s{}", i));
+                        store.insert(QuadRef::new(&s, p, p, None))?;
+                    }
+                    Result::<_, io::Error>::Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        let encoded_p = store.get_encoded_term(p.into())?.unwrap();
+        assert_eq!(
+            store.encoded_quads_for_pattern_cardinality(None, Some(encoded_p), None, None),
+            Some(100)
+        );
+        let threads = (0..4)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let s = NamedNode::new_unchecked(format!("http://example.com/s{}", i));
+                        store.remove(QuadRef::new(&s, p, p, None))?;
+                    }
+                    Result::<_, io::Error>::Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        assert_eq!(
+            store.encoded_quads_for_pattern_cardinality(None, Some(encoded_p), None, None),
+            Some(0)
+        );
+    }
+    remove_dir_all(&repo_path)?;
+    Ok(())
+}
@@ -1,30 +1,40 @@
 //! Store based on the [Sled](https://sled.rs/) key-value database.
 
 use crate::error::invalid_data_error;
-use crate::io::{DatasetFormat, GraphFormat};
+use crate::io::read::SyntaxError;
+use crate::io::{
+    DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser, GraphSerializer,
+};
 use crate::model::*;
 use crate::sparql::{
-    evaluate_query, evaluate_update, EvaluationError, Query, QueryOptions, QueryResults, Update,
-    UpdateOptions,
+    evaluate_prepared_query, evaluate_query, evaluate_update, explain_query, EvaluationError,
+    PreparedQuery, Query, QueryExplanation, QueryOptions, QueryResults, Update, UpdateOptions,
 };
 use crate::store::binary_encoder::*;
 use crate::store::numeric_encoder::{
     Decoder, ReadEncoder, StrContainer, StrEncodingAware, StrLookup, WriteEncoder,
 };
 use crate::store::{
-    dump_dataset, dump_graph, get_encoded_quad_pattern, load_dataset, load_graph,
-    ReadableEncodedStore, StoreOrParseError, WritableEncodedStore,
+    dump_dataset, dump_graph, estimate_cardinality_from_statistics, get_encoded_quad_pattern,
+    load_dataset, load_dataset_with_parser, load_graph, load_graph_with_parser, DictionaryId,
+    ReadableEncodedStore, ReadableStore, StoreOrParseError, StoreTransaction, TransactionalStore,
+    WritableEncodedStore, WritableStore,
 };
 use sled::transaction::{
     ConflictableTransactionError, TransactionError, Transactional, TransactionalTree,
     UnabortableTransactionError,
 };
-use sled::{Config, Db, Iter, Tree};
-use std::convert::TryInto;
+use sled::{Batch, Config, Db, IVec, Iter, Tree};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
 use std::error::Error;
 use std::io::{BufRead, Write};
 use std::iter::{once, Once};
+use std::mem::take;
+use std::ops::Bound;
 use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 use std::{fmt, io, str};
 
 /// Store based on the [Sled](https://sled.rs/) key-value database.
@@ -76,6 +86,10 @@ pub struct SledStore {
     dpos: Tree,
     dosp: Tree,
     graphs: Tree,
+    snapshots: Arc<SnapshotRegistry>,
+    snapshot: Option<Arc<SnapshotOverlay>>,
+    dictionary_id: DictionaryId,
+    write_lock: Arc<RwLock<()>>,
 }
 
 type EncodedTerm = crate::store::numeric_encoder::EncodedTerm<StrHash>;
@@ -98,37 +112,27 @@ impl SledStore {
         let db = config.open()?;
         let this = Self {
             default: db.clone(),
-            id2str: db.open_tree("id2str")?,
-            spog: db.open_tree("spog")?,
-            posg: db.open_tree("posg")?,
-            ospg: db.open_tree("ospg")?,
-            gspo: db.open_tree("gspo")?,
-            gpos: db.open_tree("gpos")?,
-            gosp: db.open_tree("gosp")?,
-            dspo: db.open_tree("dspo")?,
-            dpos: db.open_tree("dpos")?,
-            dosp: db.open_tree("dosp")?,
-            graphs: db.open_tree("graphs")?,
+            id2str: db.open_tree(StorageIndex::Id2Str.name())?,
+            spog: db.open_tree(StorageIndex::Spog.name())?,
+            posg: db.open_tree(StorageIndex::Posg.name())?,
+            ospg: db.open_tree(StorageIndex::Ospg.name())?,
+            gspo: db.open_tree(StorageIndex::Gspo.name())?,
+            gpos: db.open_tree(StorageIndex::Gpos.name())?,
+            gosp: db.open_tree(StorageIndex::Gosp.name())?,
+            dspo: db.open_tree(StorageIndex::Dspo.name())?,
+            dpos: db.open_tree(StorageIndex::Dpos.name())?,
+            dosp: db.open_tree(StorageIndex::Dosp.name())?,
+            graphs: db.open_tree(StorageIndex::Graphs.name())?,
+            snapshots: Arc::default(),
+            snapshot: None,
+            dictionary_id: DictionaryId::new(),
+            write_lock: Arc::default(),
         };
 
-        let mut version = this.ensure_version()?;
-        if version == 0 {
-            // We migrate to v1
-            for quad in this.encoded_quads_for_pattern(None, None, None, None) {
-                let mut this_mut = &this;
-                let quad = quad?;
-                if !quad.graph_name.is_default_graph() {
-                    this_mut.insert_encoded_named_graph(quad.graph_name)?;
-                }
-            }
-            version = 1;
-            this.set_version(version)?;
-            this.graphs.flush()?;
-        }
-
+        let version = this.migrate(MIGRATIONS)?;
         match version {
             _ if version < LATEST_STORAGE_VERSION => Err(invalid_data_error(format!(
-                "The Sled database is using the outdated encoding version {}. Automated migration is not supported, please dump the store dataset using a compatible Oxigraph version and load it again using the current version",
+                "The Sled database is using the outdated encoding version {}. No automated migration is available from it, please dump the store dataset using a compatible Oxigraph version and load it again using the current version",
                 version
             ))),
             LATEST_STORAGE_VERSION => Ok(this),
@@ -155,6 +159,219 @@ impl SledStore {
         Ok(())
     }
 
+    /// Applies the migrations starting from the current storage version and returns the reached version.
+    ///
+    /// The migration progress is saved regularly so an interrupted migration is resumed when the store is opened again.
+    fn migrate(&self, migrations: &[Migration]) -> Result<u64, io::Error> {
+        let mut version = self.ensure_version()?;
+        let mut progress = if let Some(progress) = self.default.get(MIGRATION_PROGRESS_KEY)? {
+            MigrationProgress::from_bytes(&progress)?
+        } else {
+            MigrationProgress::new(version)
+        };
+        if progress.version != version {
+            return Err(invalid_data_error(format!(
+                "The Sled database contains the progress of a migration from version {} but is using the version {}",
+                progress.version, version
+            )));
+        }
+        while let Some(migration) = migrations.iter().find(|m| m.from_version == version) {
+            while let Some(step) = migration.steps.get(progress.step) {
+                self.apply_migration_step(step, &mut progress)?;
+                progress.next_step();
+                self.default
+                    .insert(MIGRATION_PROGRESS_KEY, progress.to_bytes())?;
+            }
+            version += 1;
+            let mut batch = Batch::default();
+            batch.insert("oxversion", &version.to_be_bytes());
+            batch.remove(MIGRATION_PROGRESS_KEY);
+            self.default.apply_batch(batch)?;
+            self.default.flush()?;
+            progress = MigrationProgress::new(version);
+        }
+        Ok(version)
+    }
+
+    fn apply_migration_step(
+        &self,
+        step: &MigrationStep,
+        progress: &mut MigrationProgress,
+    ) -> Result<(), io::Error> {
+        match step {
+            MigrationStep::RewriteIndex { index, rewrite } => {
+                let tree = self.tree(*index);
+                if progress.phase == MigrationPhase::Read {
+                    self.migrate_batches(tree, &self.default, progress, |key, value, batch| {
+                        let mut buffer_key = MIGRATION_BUFFER_PREFIX.to_vec();
+                        buffer_key.extend_from_slice(&rewrite(key)?);
+                        batch.insert(buffer_key, value);
+                        Ok(())
+                    })?;
+                    tree.clear()?;
+                    progress.phase = MigrationPhase::CopyBack;
+                    progress.last_key = None;
+                    self.default
+                        .insert(MIGRATION_PROGRESS_KEY, progress.to_bytes())?;
+                }
+
+                // The copied entries are removed from the buffer so there is no need to track the progress
+                let mut index_batch = Batch::default();
+                let mut buffer_batch = Batch::default();
+                let mut batch_size = 0;
+                for entry in self.default.scan_prefix(MIGRATION_BUFFER_PREFIX) {
+                    let (key, value) = entry?;
+                    index_batch.insert(&key[MIGRATION_BUFFER_PREFIX.len()..], value);
+                    buffer_batch.remove(key);
+                    batch_size += 1;
+                    if batch_size >= MIGRATION_BATCH_SIZE {
+                        tree.apply_batch(take(&mut index_batch))?;
+                        self.default.apply_batch(take(&mut buffer_batch))?;
+                        batch_size = 0;
+                    }
+                }
+                tree.apply_batch(index_batch)?;
+                self.default.apply_batch(buffer_batch)?;
+                tree.flush()?;
+                Ok(())
+            }
+            MigrationStep::DeriveIndex { from, to, derive } => {
+                let to = self.tree(*to);
+                self.migrate_batches(self.tree(*from), to, progress, |key, _, batch| {
+                    batch.insert(derive(key)?, &[]);
+                    Ok(())
+                })?;
+                to.flush()?;
+                Ok(())
+            }
+            MigrationStep::ComputeStatistics => {
+                self.compute_statistics()?;
+                self.default.flush()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Replaces the statistics by the ones computed from the DPOS and POSG indexes
+    fn compute_statistics(&self) -> Result<(), io::Error> {
+        let mut statistics = HashMap::<_, i64>::new();
+        for (tree, encoding) in &[
+            (&self.dpos, QuadEncoding::DPOS),
+            (&self.posg, QuadEncoding::POSG),
+        ] {
+            for key in tree.iter().keys() {
+                for statistic_key in &quad_statistics_keys(&encoding.decode(&key?)?) {
+                    *statistics.entry(statistic_key.clone()).or_default() += 1;
+                }
+            }
+        }
+        let mut batch = Batch::default();
+        for key in self.default.range(STATISTICS_PREFIX..STATISTICS_END).keys() {
+            batch.remove(key?);
+        }
+        for (key, count) in statistics {
+            batch.insert(key, &encode_statistic(count));
+        }
+        self.default.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Adds `delta` to the statistics counting the quad
+    fn update_statistics(&self, quad: &EncodedQuad, delta: i64) -> Result<(), io::Error> {
+        for key in &quad_statistics_keys(quad) {
+            self.update_statistic(key, delta)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `delta` to a statistic
+    fn update_statistic(&self, key: &[u8], delta: i64) -> Result<(), io::Error> {
+        self.default.fetch_and_update(key, |count| {
+            let count = count.and_then(decode_statistic).unwrap_or(0);
+            Some(encode_statistic(count + delta).to_vec())
+        })?;
+        Ok(())
+    }
+
+    /// Removes the statistics in the given range of keys
+    fn clear_statistics(&self, from: &[u8], to: &[u8]) -> Result<(), io::Error> {
+        for key in self.default.range(from..to).keys() {
+            self.default.remove(key?)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of quads in the default graph or in the named graphs, only the ones with the given predicate if it is set.
+    fn quad_count(
+        &self,
+        default_graph: bool,
+        predicate: Option<EncodedTerm>,
+    ) -> Result<u64, io::Error> {
+        Ok(
+            if let Some(value) = self.default.get(statistics_key(default_graph, predicate))? {
+                // The count might be negative if there were concurrent writes
+                u64::try_from(
+                    decode_statistic(&value).ok_or_else(|| {
+                        invalid_data_error("Invalid quad count in the statistics")
+                    })?,
+                )
+                .unwrap_or(0)
+            } else {
+                0
+            },
+        )
+    }
+
+    /// Calls `f` on each entry of `from` after the last key of `progress` and applies the filled batches to `to`.
+    ///
+    /// The progress is saved after each applied batch.
+    /// `f` should be idempotent because the entries processed after the last save are processed again if the migration is resumed.
+    fn migrate_batches(
+        &self,
+        from: &Tree,
+        to: &Tree,
+        progress: &mut MigrationProgress,
+        mut f: impl FnMut(&[u8], &[u8], &mut Batch) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error> {
+        let iter = if let Some(last_key) = &progress.last_key {
+            from.range::<&[u8], _>((Bound::Excluded(last_key.as_slice()), Bound::Unbounded))
+        } else {
+            from.iter()
+        };
+        let mut batch = Batch::default();
+        let mut batch_size = 0;
+        for entry in iter {
+            let (key, value) = entry?;
+            f(&key, &value, &mut batch)?;
+            batch_size += 1;
+            if batch_size >= MIGRATION_BATCH_SIZE {
+                to.apply_batch(take(&mut batch))?;
+                batch_size = 0;
+                progress.last_key = Some(key.to_vec());
+                self.default
+                    .insert(MIGRATION_PROGRESS_KEY, progress.to_bytes())?;
+            }
+        }
+        to.apply_batch(batch)?;
+        Ok(())
+    }
+
+    fn tree(&self, index: StorageIndex) -> &Tree {
+        match index {
+            StorageIndex::Id2Str => &self.id2str,
+            StorageIndex::Spog => &self.spog,
+            StorageIndex::Posg => &self.posg,
+            StorageIndex::Ospg => &self.ospg,
+            StorageIndex::Gspo => &self.gspo,
+            StorageIndex::Gpos => &self.gpos,
+            StorageIndex::Gosp => &self.gosp,
+            StorageIndex::Dspo => &self.dspo,
+            StorageIndex::Dpos => &self.dpos,
+            StorageIndex::Dosp => &self.dosp,
+            StorageIndex::Graphs => &self.graphs,
+        }
+    }
+
     /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
     ///
     /// Usage example:
@@ -183,12 +400,46 @@ impl SledStore {
     }
 
     /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    ///
+    /// The query reads the current content of the store: the writes done during the evaluation might be partially visible to it.
+    /// Use a [snapshot](SledStore::snapshot()) to evaluate queries against a consistent view of the store.
     pub fn query_opt(
         &self,
         query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
         options: QueryOptions,
     ) -> Result<QueryResults, EvaluationError> {
-        evaluate_query(self.clone(), query, options)
+        evaluate_query(self.for_reading(), query, options)
+    }
+
+    /// Executes a [`PreparedQuery`] with some options.
+    ///
+    /// The query evaluation plan is reused between the evaluations against this store if possible.
+    /// See [`PreparedQuery`] for a usage example.
+    pub fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        evaluate_prepared_query(self.for_reading(), query, options)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options
+    /// and returns an explanation of its evaluation.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::explain_query_opt()) for more details.
+    pub fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        explain_query(self.for_reading(), query, options, with_stats)
     }
 
     /// Retrieves quads with a filter on each quad component
@@ -217,11 +468,12 @@ impl SledStore {
         object: Option<TermRef<'_>>,
         graph_name: Option<GraphNameRef<'_>>,
     ) -> SledQuadIter {
+        let store = self.for_reading();
         SledQuadIter {
-            inner: match get_encoded_quad_pattern(self, subject, predicate, object, graph_name) {
+            inner: match get_encoded_quad_pattern(&store, subject, predicate, object, graph_name) {
                 Ok(Some((subject, predicate, object, graph_name))) => QuadIterInner::Quads {
-                    iter: self.encoded_quads_for_pattern(subject, predicate, object, graph_name),
-                    store: self.clone(),
+                    iter: store.encoded_quads_for_pattern(subject, predicate, object, graph_name),
+                    store,
                 },
                 Ok(None) => QuadIterInner::Empty,
                 Err(error) => QuadIterInner::Error(once(error)),
@@ -247,12 +499,67 @@ impl SledStore {
     ///
     /// Warning: this function executes a full scan
     pub fn len(&self) -> usize {
-        self.gspo.len() + self.dspo.len()
+        if self.index_snapshot().is_some() {
+            self.tree_keys(&self.gspo, Vec::default()).count()
+                + self.tree_keys(&self.dspo, Vec::default()).count()
+        } else {
+            self.gspo.len() + self.dspo.len()
+        }
     }
 
     /// Returns if the store is empty
     pub fn is_empty(&self) -> bool {
-        self.gspo.is_empty() && self.dspo.is_empty()
+        if self.index_snapshot().is_some() {
+            self.tree_keys(&self.gspo, Vec::default()).next().is_none()
+                && self.tree_keys(&self.dspo, Vec::default()).next().is_none()
+        } else {
+            self.gspo.is_empty() && self.dspo.is_empty()
+        }
+    }
+
+    /// Returns a read-only view of the store as it is when this method is called.
+    ///
+    /// All the reads and queries done using the returned [`SledSnapshot`] are consistent with each other,
+    /// the writes done after the snapshot creation are not visible to them.
+    ///
+    /// Sled does not provide snapshots so they are emulated:
+    /// while the snapshot is alive, each write reads the previous values of the entries it modifies and keeps them in main memory
+    /// until the snapshot is dropped, without any size limit. The snapshots should be dropped as soon as they are not needed anymore.
+    /// Clearing the default graph or the store is not recorded: the snapshot reads fail after it with a [`NotFound`](std::io::ErrorKind::NotFound) error.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::SledStore;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = SledStore::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, None))?;
+    ///
+    /// let snapshot = store.snapshot();
+    /// store.remove(QuadRef::new(ex, ex, ex, None))?;
+    /// assert!(store.is_empty());
+    /// assert_eq!(snapshot.len(), 1);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn snapshot(&self) -> SledSnapshot {
+        let mut store = self.clone();
+        if store.index_snapshot().is_none() {
+            store.snapshot = Some(self.snapshots.create(false));
+        }
+        SledSnapshot { store }
+    }
+
+    /// Returns a store reading the current content of the database that is still able to decode the strings removed by
+    /// [`remove_unused_strings`](SledStore::remove_unused_strings()) after this call.
+    ///
+    /// Returns a copy of `self` if it is already reading from a snapshot.
+    fn for_reading(&self) -> Self {
+        let mut store = self.clone();
+        if store.snapshot.is_none() {
+            store.snapshot = Some(self.snapshots.create(true));
+        }
+        store
     }
 
     /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/).
@@ -288,6 +595,7 @@ impl SledStore {
         update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
         options: UpdateOptions,
     ) -> Result<(), EvaluationError> {
+        let _lock = self.write_lock();
         evaluate_update(
             self.clone(),
             &mut &*self,
@@ -327,7 +635,16 @@ impl SledStore {
         &self,
         f: impl Fn(SledTransaction<'_>) -> Result<T, SledConflictableTransactionError<E>>,
     ) -> Result<T, SledTransactionError<E>> {
-        Ok((
+        // The lock should be taken before the Sled transaction start to avoid deadlocks with the strings removal
+        let _lock = self.write_lock();
+        // The keys written by the transaction are recorded into the snapshots just before the commit
+        // and no snapshot is created until the commit is done.
+        // The previous values are the ones returned by the transactional writes:
+        // reading the trees directly during the transaction would deadlock.
+        let written_keys = RefCell::new(Vec::new());
+        let statistics = RefCell::new(HashMap::new());
+        let snapshots_writer = RefCell::new(None);
+        let result = (
             &self.id2str,
             &self.spog,
             &self.posg,
@@ -341,8 +658,11 @@ impl SledStore {
             &self.graphs,
         )
             .transaction(
-                move |(id2str, spog, posg, ospg, gspo, gpos, gosp, dspo, dpos, dosp, graphs)| {
-                    Ok(f(SledTransaction {
+                |(id2str, spog, posg, ospg, gspo, gpos, gosp, dspo, dpos, dosp, graphs)| {
+                    snapshots_writer.replace(None); // We release the lock taken by a previous attempt
+                    written_keys.borrow_mut().clear();
+                    statistics.borrow_mut().clear();
+                    let result = f(SledTransaction {
                         id2str,
                         spog,
                         posg,
@@ -354,9 +674,28 @@ impl SledStore {
                         dpos,
                         dosp,
                         graphs,
-                    })?)
+                        store: self,
+                        written_keys: &written_keys,
+                        statistics: &statistics,
+                    })?;
+                    let writer = self.snapshots.writer();
+                    for (tree, key, old_value) in written_keys.borrow().iter() {
+                        writer.record(tree, key, old_value);
+                    }
+                    snapshots_writer.replace(Some(writer));
+                    Ok(result)
                 },
-            )?)
+            );
+        drop(snapshots_writer);
+        let result = result?;
+        // The statistics are not part of the transaction so that the concurrent transactions do not conflict on them
+        for (key, delta) in statistics.into_inner() {
+            if delta != 0 {
+                self.update_statistic(&key, delta)
+                    .map_err(SledTransactionError::Storage)?;
+            }
+        }
+        Ok(result)
     }
 
     /// Loads a graph file (i.e. triples) into the store
@@ -394,8 +733,46 @@ impl SledStore {
         to_graph_name: impl Into<GraphNameRef<'a>>,
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
         let mut this = self;
-        load_graph(&mut this, reader, format, to_graph_name.into(), base_iri)?;
+        load_graph(
+            &mut this,
+            reader,
+            format,
+            to_graph_name.into(),
+            base_iri,
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Loads a graph file (i.e. triples) into the store in lenient mode: the invalid statements are skipped and their errors are given to `on_error`.
+    ///
+    /// Only the line based format [N-Triples](https://www.w3.org/TR/n-triples/) is able to recover from a syntax error.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_graph_lenient()) for a usage example.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_graph_lenient<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+        mut on_error: impl FnMut(SyntaxError),
+    ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
+        let mut this = self;
+        load_graph(
+            &mut this,
+            reader,
+            format,
+            to_graph_name.into(),
+            base_iri,
+            Some(&mut on_error),
+        )?;
         Ok(())
     }
 
@@ -433,8 +810,76 @@ impl SledStore {
         format: DatasetFormat,
         base_iri: Option<&str>,
     ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
         let mut this = self;
-        load_dataset(&mut this, reader, format, base_iri)?;
+        load_dataset(&mut this, reader, format, base_iri, None)?;
+        Ok(())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store in lenient mode: the invalid statements are skipped and their errors are given to `on_error`.
+    ///
+    /// Only the line based format [N-Quads](https://www.w3.org/TR/n-quads/) is able to recover from a syntax error.
+    /// An error with the [`InvalidInput`](std::io::ErrorKind::InvalidInput) kind is returned for the other formats.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_dataset_lenient()) for a usage example.
+    ///
+    /// Errors related to parameter validation like the base IRI use the [`InvalidInput`](std::io::ErrorKind::InvalidInput) error kind.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_dataset_lenient(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+        mut on_error: impl FnMut(SyntaxError),
+    ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
+        let mut this = self;
+        load_dataset(&mut this, reader, format, base_iri, Some(&mut on_error))?;
+        Ok(())
+    }
+
+    /// Loads a graph file (i.e. triples) into the store using the options of a [`GraphParser`].
+    ///
+    /// It allows to provide a [JSON-LD context loader](GraphParser::with_json_ld_context_loader()).
+    /// If the parser is in [lenient mode](GraphParser::with_lenient_mode()), only available for [N-Triples](https://www.w3.org/TR/n-triples/), the syntax errors it is able to recover from are given to its callback.
+    ///
+    /// Warning: This functions saves the triples in a not atomic way like [`load_graph`](SledStore::load_graph()).
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_graph_with_parser()) for a usage example.
+    ///
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_graph_with_parser<'a>(
+        &self,
+        reader: impl BufRead,
+        parser: &GraphParser,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
+        let mut this = self;
+        load_graph_with_parser(&mut this, reader, parser, to_graph_name.into(), None)?;
+        Ok(())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store using the options of a [`DatasetParser`].
+    ///
+    /// It allows to provide a [JSON-LD context loader](DatasetParser::with_json_ld_context_loader()).
+    /// If the parser is in [lenient mode](DatasetParser::with_lenient_mode()), only available for [N-Quads](https://www.w3.org/TR/n-quads/), the syntax errors it is able to recover from are given to its callback.
+    ///
+    /// Warning: This functions saves the quads in a not atomic way like [`load_dataset`](SledStore::load_dataset()).
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::load_dataset_with_parser()) for a usage example.
+    ///
+    /// Errors related to a bad syntax in the loaded file use the [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error kinds.
+    /// Errors related to data loading into the store use the other error kinds.
+    pub fn load_dataset_with_parser(
+        &self,
+        reader: impl BufRead,
+        parser: &DatasetParser,
+    ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
+        let mut this = self;
+        load_dataset_with_parser(&mut this, reader, parser, None)?;
         Ok(())
     }
 
@@ -444,6 +889,7 @@ impl SledStore {
     /// It might leave the store in a bad state if a crash happens during the insertion.
     /// Use a (memory greedy) [transaction](SledStore::transaction()) if you do not want that.
     pub fn insert<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
         let mut this = self;
         let quad = this.encode_quad(quad.into())?;
         this.insert_encoded(&quad)
@@ -486,12 +932,30 @@ impl SledStore {
         writer: impl Write,
         format: GraphFormat,
         from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.dump_graph_with_serializer(
+            writer,
+            &GraphSerializer::from_format(format),
+            from_graph_name,
+        )
+    }
+
+    /// Dumps a store graph into a file using the options of a [`GraphSerializer`].
+    ///
+    /// It allows to set the prefixes and the base IRI used by the Turtle serializer or the JSON-LD context.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::dump_graph_with_serializer()) for a usage example.
+    pub fn dump_graph_with_serializer<'a>(
+        &self,
+        writer: impl Write,
+        serializer: &GraphSerializer,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
     ) -> Result<(), io::Error> {
         dump_graph(
             self.quads_for_pattern(None, None, None, Some(from_graph_name.into()))
                 .map(|q| Ok(q?.into())),
             writer,
-            format,
+            serializer,
         )
     }
 
@@ -512,7 +976,20 @@ impl SledStore {
     /// # std::io::Result::Ok(())
     /// ```
     pub fn dump_dataset(&self, writer: impl Write, format: DatasetFormat) -> Result<(), io::Error> {
-        dump_dataset(self.iter(), writer, format)
+        self.dump_dataset_with_serializer(writer, &DatasetSerializer::from_format(format))
+    }
+
+    /// Dumps the store into a file using the options of a [`DatasetSerializer`].
+    ///
+    /// It allows to set the prefixes and the base IRI used by the TriG serializer or the JSON-LD context.
+    ///
+    /// See [`MemoryStore`](super::memory::MemoryStore::dump_graph_with_serializer()) for a usage example.
+    pub fn dump_dataset_with_serializer(
+        &self,
+        writer: impl Write,
+        serializer: &DatasetSerializer,
+    ) -> Result<(), io::Error> {
+        dump_dataset(self.iter(), writer, serializer)
     }
 
     /// Returns all the store named graphs
@@ -530,9 +1007,10 @@ impl SledStore {
     /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
     /// ```
     pub fn named_graphs(&self) -> SledGraphNameIter {
+        let store = self.for_reading();
         SledGraphNameIter {
-            iter: self.encoded_named_graphs(),
-            store: self.clone(),
+            iter: store.encoded_named_graphs(),
+            store,
         }
     }
 
@@ -577,6 +1055,7 @@ impl SledStore {
         &self,
         graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
     ) -> Result<(), io::Error> {
+        let _lock = self.write_lock();
         let mut this = self;
         let graph_name = this.encode_named_or_blank_node(graph_name.into())?;
         this.insert_encoded_named_graph(graph_name)
@@ -664,14 +1143,87 @@ impl SledStore {
         (&mut this).clear()
     }
 
+    /// Removes from the store string dictionary the IRIs, blank node identifiers and literals not used anymore by any quad or named graph.
+    ///
+    /// Removing quads does not remove the strings they use from the dictionary, this method allows to reclaim their space.
+    /// It returns the number of removed strings.
+    ///
+    /// The reads and SPARQL queries are not blocked during the collection but the writes inserting quads or named graphs wait for its end.
+    /// The queries and iterators running during the collection keep the strings it removes in main memory to still decode their quads.
+    /// The identifiers of all the used strings are kept in main memory during the collection.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::SledStore;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = SledStore::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, LiteralRef::new_simple_literal("a long enough string"), None);
+    /// store.insert(quad)?;
+    /// store.remove(quad)?;
+    ///
+    /// assert_eq!(store.remove_unused_strings()?, 2);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[allow(clippy::expect_used)]
+    pub fn remove_unused_strings(&self) -> Result<usize, io::Error> {
+        let _lock = self
+            .write_lock
+            .write()
+            .expect("the Sled write lock has been poisoned because of a panic");
+
+        let mut used = HashSet::new();
+        for quad in self.encoded_quads_for_pattern(None, None, None, None) {
+            let quad = quad?;
+            for term in &[quad.subject, quad.predicate, quad.object, quad.graph_name] {
+                term.on_each_id(|id| {
+                    used.insert(id);
+                });
+            }
+        }
+        for graph_name in self.encoded_named_graphs() {
+            graph_name?.on_each_id(|id| {
+                used.insert(id);
+            });
+        }
+
+        let mut count = 0;
+        for key in self.id2str.iter().keys() {
+            let key = key?;
+            let id = StrHash::from_be_bytes(key.as_ref().try_into().map_err(invalid_data_error)?);
+            if !used.contains(&id) {
+                let snapshots = self.snapshots.writer();
+                snapshots.before_string_removal(&self.id2str, &key)?;
+                self.id2str.remove(&key)?;
+                count += 1;
+            }
+        }
+        if count > 0 {
+            // The removed strings ids are not valid anymore
+            self.dictionary_id.renew();
+        }
+        Ok(count)
+    }
+
+    /// Returns the lock to hold while inserting quads or named graphs.
+    ///
+    /// It is taken exclusively by [`remove_unused_strings`](SledStore::remove_unused_strings()).
+    #[allow(clippy::expect_used)]
+    fn write_lock(&self) -> RwLockReadGuard<'_, ()> {
+        self.write_lock
+            .read()
+            .expect("the Sled write lock has been poisoned because of a panic")
+    }
+
     fn contains_encoded(&self, quad: &EncodedQuad) -> Result<bool, io::Error> {
         let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut buffer, quad);
-            Ok(self.dspo.contains_key(buffer)?)
+            Ok(self.tree_get(&self.dspo, &buffer)?.is_some())
         } else {
             write_gspo_quad(&mut buffer, quad);
-            Ok(self.gspo.contains_key(buffer)?)
+            Ok(self.tree_get(&self.gspo, &buffer)?.is_some())
         }
     }
     fn quads(&self) -> DecodingQuadsIterator {
@@ -844,61 +1396,481 @@ impl SledStore {
         })
     }
 
-    fn spog_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.spog, prefix, QuadEncoding::SPOG)
+    fn spog_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.spog, prefix, QuadEncoding::SPOG)
+    }
+
+    fn posg_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.posg, prefix, QuadEncoding::POSG)
+    }
+
+    fn ospg_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.ospg, prefix, QuadEncoding::OSPG)
+    }
+
+    fn gspo_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.gspo, prefix, QuadEncoding::GSPO)
+    }
+
+    fn gpos_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.gpos, prefix, QuadEncoding::GPOS)
+    }
+
+    fn gosp_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.gosp, prefix, QuadEncoding::GOSP)
+    }
+
+    fn dspo_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.dspo, prefix, QuadEncoding::DSPO)
+    }
+
+    fn dpos_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.dpos, prefix, QuadEncoding::DPOS)
+    }
+
+    fn dosp_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
+        self.inner_quads(&self.dosp, prefix, QuadEncoding::DOSP)
+    }
+
+    fn inner_quads(
+        &self,
+        tree: &Tree,
+        prefix: Vec<u8>,
+        encoding: QuadEncoding,
+    ) -> DecodingQuadIterator {
+        DecodingQuadIterator {
+            iter: self.tree_keys(tree, prefix),
+            encoding,
+        }
+    }
+
+    /// Returns the snapshot to read the indexes from if the store is reading from an explicit snapshot
+    fn index_snapshot(&self) -> Option<&Arc<SnapshotOverlay>> {
+        self.snapshot
+            .as_ref()
+            .filter(|snapshot| !snapshot.strings_only)
+    }
+
+    fn tree_get(&self, tree: &Tree, key: &[u8]) -> Result<Option<IVec>, io::Error> {
+        // The tree must be read before the snapshot because the keys are recorded in the snapshot before being written
+        let value = tree.get(key)?;
+        if let Some(snapshot) = self.index_snapshot() {
+            if let Some(value) = snapshot.get(tree, key)? {
+                return Ok(value);
+            }
+        }
+        Ok(value)
+    }
+
+    fn tree_keys(&self, tree: &Tree, prefix: Vec<u8>) -> TreeKeyIterator {
+        TreeKeyIterator {
+            iter: tree.scan_prefix(&prefix),
+            snapshot: self
+                .index_snapshot()
+                .map(|snapshot| (snapshot.clone(), tree.name())),
+            prefix,
+            last: None,
+            next_from_tree: None,
+            failed: false,
+        }
+    }
+
+    /// Reads a string from the dictionary
+    ///
+    /// The value of a string id never changes: if the string is still in the dictionary, it is also the one it had when the snapshot has been created.
+    fn id2str_get(&self, key: &[u8]) -> Result<Option<IVec>, io::Error> {
+        if let Some(value) = self.id2str.get(key)? {
+            return Ok(Some(value));
+        }
+        if let Some(snapshot) = &self.snapshot {
+            if let Some(value) = snapshot.get(&self.id2str, key)? {
+                return Ok(value);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl ReadableStore for SledStore {
+    type Error = io::Error;
+
+    fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Box<dyn Iterator<Item = Result<Quad, io::Error>> + Send> {
+        Box::new(SledStore::quads_for_pattern(
+            self, subject, predicate, object, graph_name,
+        ))
+    }
+
+    fn contains(&self, quad: QuadRef<'_>) -> Result<bool, io::Error> {
+        SledStore::contains(self, quad)
+    }
+
+    fn len(&self) -> Result<usize, io::Error> {
+        Ok(SledStore::len(self))
+    }
+
+    fn is_empty(&self) -> Result<bool, io::Error> {
+        Ok(SledStore::is_empty(self))
+    }
+
+    fn named_graphs(&self) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, io::Error>> + Send> {
+        Box::new(SledStore::named_graphs(self))
+    }
+
+    fn contains_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<bool, io::Error> {
+        SledStore::contains_named_graph(self, graph_name)
+    }
+
+    fn dump_graph(
+        &self,
+        writer: &mut dyn Write,
+        format: GraphFormat,
+        from_graph_name: GraphNameRef<'_>,
+    ) -> Result<(), io::Error> {
+        SledStore::dump_graph(self, writer, format, from_graph_name)
+    }
+
+    fn dump_dataset(&self, writer: &mut dyn Write, format: DatasetFormat) -> Result<(), io::Error> {
+        SledStore::dump_dataset(self, writer, format)
+    }
+
+    fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        SledStore::query_opt(self, query, options)
+    }
+
+    fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        SledStore::explain_query_opt(self, query, options, with_stats)
+    }
+
+    fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        SledStore::query_prepared(self, query, options)
+    }
+}
+
+impl WritableStore for SledStore {
+    fn insert(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        SledStore::insert(self, quad)
+    }
+
+    fn remove(&self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        SledStore::remove(self, quad)
+    }
+
+    fn load_graph(
+        &self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        SledStore::load_graph(self, reader, format, to_graph_name, base_iri)
+    }
+
+    fn load_dataset(
+        &self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        SledStore::load_dataset(self, reader, format, base_iri)
+    }
+
+    fn insert_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+        SledStore::insert_named_graph(self, graph_name)
+    }
+
+    fn clear_graph(&self, graph_name: GraphNameRef<'_>) -> Result<(), io::Error> {
+        SledStore::clear_graph(self, graph_name)
+    }
+
+    fn remove_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<(), io::Error> {
+        SledStore::remove_named_graph(self, graph_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        SledStore::clear(self)
+    }
+
+    fn update_opt(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+        options: UpdateOptions,
+    ) -> Result<(), EvaluationError> {
+        SledStore::update_opt(self, update, options)
+    }
+}
+
+impl TransactionalStore for SledStore {
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&mut dyn StoreTransaction) -> Result<(), io::Error>,
+    ) -> Result<(), io::Error> {
+        // Sled might call the closure again after a conflict
+        let f = RefCell::new(f);
+        Ok(SledStore::transaction(self, |mut transaction| {
+            (f.borrow_mut())(&mut transaction).map_err(|e| {
+                if let Some(SledUnabortableTransactionError::Conflict) = e
+                    .get_ref()
+                    .and_then(|e| e.downcast_ref::<SledUnabortableTransactionError>())
+                {
+                    SledConflictableTransactionError::Conflict
+                } else {
+                    SledConflictableTransactionError::Abort(e)
+                }
+            })
+        })?)
+    }
+}
+
+impl fmt::Display for SledStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in self.iter() {
+            writeln!(f, "{}", t.map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// A read-only view of a [`SledStore`] at a given point in time.
+///
+/// It is created using [`SledStore::snapshot`].
+#[derive(Clone)]
+pub struct SledSnapshot {
+    store: SledStore,
+}
+
+impl SledSnapshot {
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
+    pub fn query(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query(query)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    pub fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_opt(query, options)
+    }
+
+    /// Executes a [`PreparedQuery`] with some options.
+    pub fn query_prepared(
+        &self,
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_prepared(query, options)
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options
+    /// and returns an explanation of its evaluation.
+    pub fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        self.store.explain_query_opt(query, options, with_stats)
+    }
+
+    /// Retrieves quads with a filter on each quad component
+    pub fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> SledQuadIter {
+        self.store
+            .quads_for_pattern(subject, predicate, object, graph_name)
+    }
+
+    /// Returns all the quads contained in the snapshot
+    pub fn iter(&self) -> SledQuadIter {
+        self.store.iter()
+    }
+
+    /// Checks if this snapshot contains a given quad
+    pub fn contains<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, io::Error> {
+        self.store.contains(quad)
+    }
+
+    /// Returns the number of quads in the snapshot
+    ///
+    /// Warning: this function executes a full scan
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns if the snapshot is empty
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Dumps a graph into a file.
+    pub fn dump_graph<'a>(
+        &self,
+        writer: impl Write,
+        format: GraphFormat,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.store.dump_graph(writer, format, from_graph_name)
+    }
+
+    /// Dumps a graph into a file using the options of a [`GraphSerializer`].
+    pub fn dump_graph_with_serializer<'a>(
+        &self,
+        writer: impl Write,
+        serializer: &GraphSerializer,
+        from_graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), io::Error> {
+        self.store
+            .dump_graph_with_serializer(writer, serializer, from_graph_name)
+    }
+
+    /// Dumps the snapshot into a file.
+    pub fn dump_dataset(&self, writer: impl Write, format: DatasetFormat) -> Result<(), io::Error> {
+        self.store.dump_dataset(writer, format)
+    }
+
+    /// Dumps the snapshot into a file using the options of a [`DatasetSerializer`].
+    pub fn dump_dataset_with_serializer(
+        &self,
+        writer: impl Write,
+        serializer: &DatasetSerializer,
+    ) -> Result<(), io::Error> {
+        self.store.dump_dataset_with_serializer(writer, serializer)
+    }
+
+    /// Returns all the named graphs of the snapshot
+    pub fn named_graphs(&self) -> SledGraphNameIter {
+        self.store.named_graphs()
+    }
+
+    /// Checks if the snapshot contains a given graph
+    pub fn contains_named_graph<'a>(
+        &self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<bool, io::Error> {
+        self.store.contains_named_graph(graph_name)
+    }
+}
+
+impl ReadableStore for SledSnapshot {
+    type Error = io::Error;
+
+    fn quads_for_pattern(
+        &self,
+        subject: Option<NamedOrBlankNodeRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Box<dyn Iterator<Item = Result<Quad, io::Error>> + Send> {
+        ReadableStore::quads_for_pattern(&self.store, subject, predicate, object, graph_name)
+    }
+
+    fn contains(&self, quad: QuadRef<'_>) -> Result<bool, io::Error> {
+        ReadableStore::contains(&self.store, quad)
     }
 
-    fn posg_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.posg, prefix, QuadEncoding::POSG)
+    fn len(&self) -> Result<usize, io::Error> {
+        ReadableStore::len(&self.store)
     }
 
-    fn ospg_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.ospg, prefix, QuadEncoding::OSPG)
+    fn is_empty(&self) -> Result<bool, io::Error> {
+        ReadableStore::is_empty(&self.store)
     }
 
-    fn gspo_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.gspo, prefix, QuadEncoding::GSPO)
+    fn named_graphs(&self) -> Box<dyn Iterator<Item = Result<NamedOrBlankNode, io::Error>> + Send> {
+        ReadableStore::named_graphs(&self.store)
     }
 
-    fn gpos_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.gpos, prefix, QuadEncoding::GPOS)
+    fn contains_named_graph(&self, graph_name: NamedOrBlankNodeRef<'_>) -> Result<bool, io::Error> {
+        ReadableStore::contains_named_graph(&self.store, graph_name)
     }
 
-    fn gosp_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.gosp, prefix, QuadEncoding::GOSP)
+    fn dump_graph(
+        &self,
+        writer: &mut dyn Write,
+        format: GraphFormat,
+        from_graph_name: GraphNameRef<'_>,
+    ) -> Result<(), io::Error> {
+        ReadableStore::dump_graph(&self.store, writer, format, from_graph_name)
     }
 
-    fn dspo_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.dspo, prefix, QuadEncoding::DSPO)
+    fn dump_dataset(&self, writer: &mut dyn Write, format: DatasetFormat) -> Result<(), io::Error> {
+        ReadableStore::dump_dataset(&self.store, writer, format)
     }
 
-    fn dpos_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.dpos, prefix, QuadEncoding::DPOS)
+    fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_opt(query, options)
     }
 
-    fn dosp_quads(&self, prefix: Vec<u8>) -> DecodingQuadIterator {
-        self.inner_quads(&self.dosp, prefix, QuadEncoding::DOSP)
+    fn explain_query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+        with_stats: bool,
+    ) -> Result<
+        (
+            Option<Result<QueryResults, EvaluationError>>,
+            QueryExplanation,
+        ),
+        EvaluationError,
+    > {
+        self.store.explain_query_opt(query, options, with_stats)
     }
 
-    fn inner_quads(
+    fn query_prepared(
         &self,
-        tree: &Tree,
-        prefix: impl AsRef<[u8]>,
-        encoding: QuadEncoding,
-    ) -> DecodingQuadIterator {
-        DecodingQuadIterator {
-            iter: tree.scan_prefix(prefix),
-            encoding,
-        }
+        query: &PreparedQuery,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store.query_prepared(query, options)
     }
 }
 
-impl fmt::Display for SledStore {
+impl fmt::Display for SledSnapshot {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for t in self.iter() {
-            writeln!(f, "{}", t.map_err(|_| fmt::Error)?)?;
-        }
-        Ok(())
+        self.store.fmt(f)
     }
 }
 
@@ -909,8 +1881,7 @@ impl StrEncodingAware for SledStore {
 
 impl StrLookup for SledStore {
     fn get_str(&self, id: StrHash) -> Result<Option<String>, io::Error> {
-        self.id2str
-            .get(id.to_be_bytes())?
+        self.id2str_get(&id.to_be_bytes())?
             .map(|v| String::from_utf8(v.to_vec()))
             .transpose()
             .map_err(invalid_data_error)
@@ -918,7 +1889,7 @@ impl StrLookup for SledStore {
 
     fn get_str_id(&self, value: &str) -> Result<Option<StrHash>, io::Error> {
         let id = StrHash::new(value);
-        Ok(if self.id2str.contains_key(&id.to_be_bytes())? {
+        Ok(if self.id2str_get(&id.to_be_bytes())?.is_some() {
             Some(id)
         } else {
             None
@@ -995,12 +1966,35 @@ impl ReadableEncodedStore for SledStore {
 
     fn encoded_named_graphs(&self) -> DecodingGraphIterator {
         DecodingGraphIterator {
-            iter: self.graphs.iter(),
+            iter: self.tree_keys(&self.graphs, Vec::default()),
         }
     }
 
     fn contains_encoded_named_graph(&self, graph_name: EncodedTerm) -> Result<bool, io::Error> {
-        Ok(self.graphs.contains_key(&encode_term(graph_name))?)
+        Ok(self
+            .tree_get(&self.graphs, &encode_term(graph_name))?
+            .is_some())
+    }
+
+    fn encoded_quads_for_pattern_cardinality(
+        &self,
+        subject: Option<EncodedTerm>,
+        predicate: Option<EncodedTerm>,
+        object: Option<EncodedTerm>,
+        graph_name: Option<EncodedTerm>,
+    ) -> Option<u64> {
+        estimate_cardinality_from_statistics(
+            subject,
+            predicate,
+            object,
+            graph_name,
+            || self.encoded_quads_for_pattern(subject, predicate, object, graph_name),
+            |default_graph, predicate| self.quad_count(default_graph, predicate),
+        )
+    }
+
+    fn dictionary_id(&self) -> Option<u64> {
+        Some(self.dictionary_id.get())
     }
 }
 
@@ -1015,45 +2009,60 @@ impl<'a> StrContainer for &'a SledStore {
 impl<'a> WritableEncodedStore for &'a SledStore {
     fn insert_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
         let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE + 1);
+        let snapshots = self.snapshots.writer();
 
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut buffer, quad);
-            self.dspo.insert(buffer.as_slice(), &[])?;
+            snapshots.before_write(&self.dspo, &buffer)?;
+            if self.dspo.insert(buffer.as_slice(), &[])?.is_none() {
+                self.update_statistics(quad, 1)?;
+            }
             buffer.clear();
 
             write_pos_quad(&mut buffer, quad);
+            snapshots.before_write(&self.dpos, &buffer)?;
             self.dpos.insert(buffer.as_slice(), &[])?;
             buffer.clear();
 
             write_osp_quad(&mut buffer, quad);
+            snapshots.before_write(&self.dosp, &buffer)?;
             self.dosp.insert(buffer.as_slice(), &[])?;
             buffer.clear();
         } else {
             write_spog_quad(&mut buffer, quad);
-            self.spog.insert(buffer.as_slice(), &[])?;
+            snapshots.before_write(&self.spog, &buffer)?;
+            if self.spog.insert(buffer.as_slice(), &[])?.is_none() {
+                self.update_statistics(quad, 1)?;
+            }
             buffer.clear();
 
             write_posg_quad(&mut buffer, quad);
+            snapshots.before_write(&self.posg, &buffer)?;
             self.posg.insert(buffer.as_slice(), &[])?;
             buffer.clear();
 
             write_ospg_quad(&mut buffer, quad);
+            snapshots.before_write(&self.ospg, &buffer)?;
             self.ospg.insert(buffer.as_slice(), &[])?;
             buffer.clear();
 
             write_gspo_quad(&mut buffer, quad);
+            snapshots.before_write(&self.gspo, &buffer)?;
             self.gspo.insert(buffer.as_slice(), &[])?;
             buffer.clear();
 
             write_gpos_quad(&mut buffer, quad);
+            snapshots.before_write(&self.gpos, &buffer)?;
             self.gpos.insert(buffer.as_slice(), &[])?;
             buffer.clear();
 
             write_gosp_quad(&mut buffer, quad);
+            snapshots.before_write(&self.gosp, &buffer)?;
             self.gosp.insert(buffer.as_slice(), &[])?;
             buffer.clear();
 
             write_term(&mut buffer, quad.graph_name);
+            snapshots.before_write(&self.graphs, &buffer)?;
             self.graphs.insert(&buffer, &[])?;
             buffer.clear();
         }
@@ -1063,41 +2072,55 @@ impl<'a> WritableEncodedStore for &'a SledStore {
 
     fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<(), io::Error> {
         let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE + 1);
+        let snapshots = self.snapshots.writer();
 
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut buffer, quad);
-            self.dspo.remove(buffer.as_slice())?;
+            snapshots.before_write(&self.dspo, &buffer)?;
+            if self.dspo.remove(buffer.as_slice())?.is_some() {
+                self.update_statistics(quad, -1)?;
+            }
             buffer.clear();
 
             write_pos_quad(&mut buffer, quad);
+            snapshots.before_write(&self.dpos, &buffer)?;
             self.dpos.remove(buffer.as_slice())?;
             buffer.clear();
 
             write_osp_quad(&mut buffer, quad);
+            snapshots.before_write(&self.dosp, &buffer)?;
             self.dosp.remove(buffer.as_slice())?;
             buffer.clear();
         } else {
             write_spog_quad(&mut buffer, quad);
-            self.spog.remove(buffer.as_slice())?;
+            snapshots.before_write(&self.spog, &buffer)?;
+            if self.spog.remove(buffer.as_slice())?.is_some() {
+                self.update_statistics(quad, -1)?;
+            }
             buffer.clear();
 
             write_posg_quad(&mut buffer, quad);
+            snapshots.before_write(&self.posg, &buffer)?;
             self.posg.remove(buffer.as_slice())?;
             buffer.clear();
 
             write_ospg_quad(&mut buffer, quad);
+            snapshots.before_write(&self.ospg, &buffer)?;
             self.ospg.remove(buffer.as_slice())?;
             buffer.clear();
 
             write_gspo_quad(&mut buffer, quad);
+            snapshots.before_write(&self.gspo, &buffer)?;
             self.gspo.remove(buffer.as_slice())?;
             buffer.clear();
 
             write_gpos_quad(&mut buffer, quad);
+            snapshots.before_write(&self.gpos, &buffer)?;
             self.gpos.remove(buffer.as_slice())?;
             buffer.clear();
 
             write_gosp_quad(&mut buffer, quad);
+            snapshots.before_write(&self.gosp, &buffer)?;
             self.gosp.remove(buffer.as_slice())?;
             buffer.clear();
         }
@@ -1106,15 +2129,21 @@ impl<'a> WritableEncodedStore for &'a SledStore {
     }
 
     fn insert_encoded_named_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
-        self.graphs.insert(&encode_term(graph_name), &[])?;
+        let key = encode_term(graph_name);
+        let snapshots = self.snapshots.writer();
+        snapshots.before_write(&self.graphs, &key)?;
+        self.graphs.insert(key, &[])?;
         Ok(())
     }
 
     fn clear_encoded_graph(&mut self, graph_name: EncodedTerm) -> Result<(), io::Error> {
         if graph_name.is_default_graph() {
-            self.dspo.clear()?;
-            self.dpos.clear()?;
-            self.dosp.clear()?;
+            let snapshots = self.snapshots.writer();
+            for tree in &[&self.dspo, &self.dpos, &self.dosp] {
+                snapshots.before_clear(tree);
+                tree.clear()?;
+            }
+            self.clear_statistics(&statistics_key(true, None), &statistics_key(false, None))?;
         } else {
             for quad in self.quads_for_graph(graph_name) {
                 self.remove_encoded(&quad?)?;
@@ -1127,22 +2156,34 @@ impl<'a> WritableEncodedStore for &'a SledStore {
         for quad in self.quads_for_graph(graph_name) {
             self.remove_encoded(&quad?)?;
         }
-        self.graphs.remove(&encode_term(graph_name))?;
+        let key = encode_term(graph_name);
+        let snapshots = self.snapshots.writer();
+        snapshots.before_write(&self.graphs, &key)?;
+        self.graphs.remove(key)?;
         Ok(())
     }
 
     fn clear(&mut self) -> Result<(), io::Error> {
-        self.dspo.clear()?;
-        self.dpos.clear()?;
-        self.dosp.clear()?;
-        self.gspo.clear()?;
-        self.gpos.clear()?;
-        self.gosp.clear()?;
-        self.spog.clear()?;
-        self.posg.clear()?;
-        self.ospg.clear()?;
-        self.graphs.clear()?;
+        let snapshots = self.snapshots.writer();
+        for tree in &[
+            &self.dspo,
+            &self.dpos,
+            &self.dosp,
+            &self.gspo,
+            &self.gpos,
+            &self.gosp,
+            &self.spog,
+            &self.posg,
+            &self.ospg,
+            &self.graphs,
+        ] {
+            snapshots.before_clear(tree);
+            tree.clear()?;
+        }
+        snapshots.before_dictionary_clear(&self.id2str);
         self.id2str.clear()?;
+        self.clear_statistics(STATISTICS_PREFIX, STATISTICS_END)?;
+        self.dictionary_id.renew();
         Ok(())
     }
 }
@@ -1160,6 +2201,10 @@ pub struct SledTransaction<'a> {
     dpos: &'a TransactionalTree,
     dosp: &'a TransactionalTree,
     graphs: &'a TransactionalTree,
+    store: &'a SledStore,
+    written_keys: &'a RefCell<Vec<(Tree, Vec<u8>, Option<IVec>)>>,
+    /// The changes of the statistics, written after the transaction commit to avoid conflicts
+    statistics: &'a RefCell<HashMap<Vec<u8>, i64>>,
 }
 
 impl SledTransaction<'_> {
@@ -1206,7 +2251,14 @@ impl SledTransaction<'_> {
         base_iri: Option<&str>,
     ) -> Result<(), SledUnabortableTransactionError> {
         let mut this = self;
-        load_graph(&mut this, reader, format, to_graph_name.into(), base_iri)?;
+        load_graph(
+            &mut this,
+            reader,
+            format,
+            to_graph_name.into(),
+            base_iri,
+            None,
+        )?;
         Ok(())
     }
 
@@ -1252,7 +2304,7 @@ impl SledTransaction<'_> {
         base_iri: Option<&str>,
     ) -> Result<(), SledUnabortableTransactionError> {
         let mut this = self;
-        load_dataset(&mut this, reader, format, base_iri)?;
+        load_dataset(&mut this, reader, format, base_iri, None)?;
         Ok(())
     }
 
@@ -1278,6 +2330,56 @@ impl SledTransaction<'_> {
             Ok(())
         }
     }
+
+    fn add_written_key(&self, tree: &Tree, key: &[u8], old_value: Option<IVec>) {
+        self.written_keys
+            .borrow_mut()
+            .push((tree.clone(), key.to_vec(), old_value));
+    }
+
+    fn update_statistics(&self, quad: &EncodedQuad, delta: i64) {
+        let mut statistics = self.statistics.borrow_mut();
+        for key in quad_statistics_keys(quad) {
+            *statistics.entry(key).or_default() += delta;
+        }
+    }
+}
+
+impl StoreTransaction for SledTransaction<'_> {
+    fn insert(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        Ok(SledTransaction::insert(self, quad)?)
+    }
+
+    fn remove(&mut self, quad: QuadRef<'_>) -> Result<(), io::Error> {
+        Ok(SledTransaction::remove(self, quad)?)
+    }
+
+    fn load_graph(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: GraphFormat,
+        to_graph_name: GraphNameRef<'_>,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        Ok(SledTransaction::load_graph(
+            self,
+            reader,
+            format,
+            to_graph_name,
+            base_iri,
+        )?)
+    }
+
+    fn load_dataset(
+        &mut self,
+        reader: &mut dyn BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), io::Error> {
+        Ok(SledTransaction::load_dataset(
+            self, reader, format, base_iri,
+        )?)
+    }
 }
 
 impl<'a> StrEncodingAware for &'a SledTransaction<'a> {
@@ -1307,7 +2409,8 @@ impl<'a> StrLookup for &'a SledTransaction<'a> {
 impl<'a> StrContainer for &'a SledTransaction<'a> {
     fn insert_str(&mut self, value: &str) -> Result<StrHash, SledUnabortableTransactionError> {
         let key = StrHash::new(value);
-        self.id2str.insert(key.to_be_bytes().as_ref(), value)?;
+        let old = self.id2str.insert(key.to_be_bytes().as_ref(), value)?;
+        self.add_written_key(&self.store.id2str, &key.to_be_bytes(), old);
         Ok(key)
     }
 }
@@ -1321,43 +2424,59 @@ impl<'a> WritableEncodedStore for &'a SledTransaction<'a> {
 
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut buffer, quad);
-            self.dspo.insert(buffer.as_slice(), &[])?;
+            let old = self.dspo.insert(buffer.as_slice(), &[])?;
+            if old.is_none() {
+                self.update_statistics(quad, 1);
+            }
+            self.add_written_key(&self.store.dspo, &buffer, old);
             buffer.clear();
 
             write_pos_quad(&mut buffer, quad);
-            self.dpos.insert(buffer.as_slice(), &[])?;
+            let old = self.dpos.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.dpos, &buffer, old);
             buffer.clear();
 
             write_osp_quad(&mut buffer, quad);
-            self.dosp.insert(buffer.as_slice(), &[])?;
+            let old = self.dosp.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.dosp, &buffer, old);
             buffer.clear();
         } else {
             write_spog_quad(&mut buffer, quad);
-            self.spog.insert(buffer.as_slice(), &[])?;
+            let old = self.spog.insert(buffer.as_slice(), &[])?;
+            if old.is_none() {
+                self.update_statistics(quad, 1);
+            }
+            self.add_written_key(&self.store.spog, &buffer, old);
             buffer.clear();
 
             write_posg_quad(&mut buffer, quad);
-            self.posg.insert(buffer.as_slice(), &[])?;
+            let old = self.posg.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.posg, &buffer, old);
             buffer.clear();
 
             write_ospg_quad(&mut buffer, quad);
-            self.ospg.insert(buffer.as_slice(), &[])?;
+            let old = self.ospg.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.ospg, &buffer, old);
             buffer.clear();
 
             write_gspo_quad(&mut buffer, quad);
-            self.gspo.insert(buffer.as_slice(), &[])?;
+            let old = self.gspo.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.gspo, &buffer, old);
             buffer.clear();
 
             write_gpos_quad(&mut buffer, quad);
-            self.gpos.insert(buffer.as_slice(), &[])?;
+            let old = self.gpos.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.gpos, &buffer, old);
             buffer.clear();
 
             write_gosp_quad(&mut buffer, quad);
-            self.gosp.insert(buffer.as_slice(), &[])?;
+            let old = self.gosp.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.gosp, &buffer, old);
             buffer.clear();
 
             write_term(&mut buffer, quad.graph_name);
-            self.graphs.insert(buffer.as_slice(), &[])?;
+            let old = self.graphs.insert(buffer.as_slice(), &[])?;
+            self.add_written_key(&self.store.graphs, &buffer, old);
             buffer.clear();
         }
 
@@ -1372,39 +2491,54 @@ impl<'a> WritableEncodedStore for &'a SledTransaction<'a> {
 
         if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut buffer, quad);
-            self.dspo.remove(buffer.as_slice())?;
+            let old = self.dspo.remove(buffer.as_slice())?;
+            if old.is_some() {
+                self.update_statistics(quad, -1);
+            }
+            self.add_written_key(&self.store.dspo, &buffer, old);
             buffer.clear();
 
             write_pos_quad(&mut buffer, quad);
-            self.dpos.remove(buffer.as_slice())?;
+            let old = self.dpos.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.dpos, &buffer, old);
             buffer.clear();
 
             write_osp_quad(&mut buffer, quad);
-            self.dosp.remove(buffer.as_slice())?;
+            let old = self.dosp.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.dosp, &buffer, old);
             buffer.clear();
         } else {
             write_spog_quad(&mut buffer, quad);
-            self.spog.remove(buffer.as_slice())?;
+            let old = self.spog.remove(buffer.as_slice())?;
+            if old.is_some() {
+                self.update_statistics(quad, -1);
+            }
+            self.add_written_key(&self.store.spog, &buffer, old);
             buffer.clear();
 
             write_posg_quad(&mut buffer, quad);
-            self.posg.remove(buffer.as_slice())?;
+            let old = self.posg.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.posg, &buffer, old);
             buffer.clear();
 
             write_ospg_quad(&mut buffer, quad);
-            self.ospg.remove(buffer.as_slice())?;
+            let old = self.ospg.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.ospg, &buffer, old);
             buffer.clear();
 
             write_gspo_quad(&mut buffer, quad);
-            self.gspo.remove(buffer.as_slice())?;
+            let old = self.gspo.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.gspo, &buffer, old);
             buffer.clear();
 
             write_gpos_quad(&mut buffer, quad);
-            self.gpos.remove(buffer.as_slice())?;
+            let old = self.gpos.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.gpos, &buffer, old);
             buffer.clear();
 
             write_gosp_quad(&mut buffer, quad);
-            self.gosp.remove(buffer.as_slice())?;
+            let old = self.gosp.remove(buffer.as_slice())?;
+            self.add_written_key(&self.store.gosp, &buffer, old);
             buffer.clear();
         }
 
@@ -1415,7 +2549,9 @@ impl<'a> WritableEncodedStore for &'a SledTransaction<'a> {
         &mut self,
         graph_name: EncodedTerm,
     ) -> Result<(), SledUnabortableTransactionError> {
-        self.graphs.insert(encode_term(graph_name), &[])?;
+        let key = encode_term(graph_name);
+        let old = self.graphs.insert(key.as_slice(), &[])?;
+        self.add_written_key(&self.store.graphs, &key, old);
         Ok(())
     }
 
@@ -1520,6 +2656,18 @@ impl Error for SledUnabortableTransactionError {
     }
 }
 
+impl From<SledUnabortableTransactionError> for io::Error {
+    fn from(e: SledUnabortableTransactionError) -> Self {
+        match e {
+            SledUnabortableTransactionError::Storage(e) => e,
+            SledUnabortableTransactionError::Conflict => io::Error::new(
+                io::ErrorKind::Interrupted,
+                SledUnabortableTransactionError::Conflict,
+            ),
+        }
+    }
+}
+
 impl From<SledUnabortableTransactionError> for EvaluationError {
     fn from(e: SledUnabortableTransactionError) -> Self {
         match e {
@@ -1635,7 +2783,7 @@ impl Iterator for DecodingQuadsIterator {
 }
 
 pub(crate) struct DecodingQuadIterator {
-    iter: Iter,
+    iter: TreeKeyIterator,
     encoding: QuadEncoding,
 }
 
@@ -1644,14 +2792,14 @@ impl Iterator for DecodingQuadIterator {
 
     fn next(&mut self) -> Option<Result<EncodedQuad, io::Error>> {
         Some(match self.iter.next()? {
-            Ok((encoded, _)) => self.encoding.decode(&encoded),
-            Err(error) => Err(error.into()),
+            Ok(encoded) => self.encoding.decode(&encoded),
+            Err(error) => Err(error),
         })
     }
 }
 
 pub(crate) struct DecodingGraphIterator {
-    iter: Iter,
+    iter: TreeKeyIterator,
 }
 
 impl Iterator for DecodingGraphIterator {
@@ -1659,12 +2807,287 @@ impl Iterator for DecodingGraphIterator {
 
     fn next(&mut self) -> Option<Result<EncodedTerm, io::Error>> {
         Some(match self.iter.next()? {
-            Ok((encoded, _)) => decode_term(&encoded),
-            Err(error) => Err(error.into()),
+            Ok(encoded) => decode_term(&encoded),
+            Err(error) => Err(error),
         })
     }
 }
 
+/// Iterates on the keys of a tree starting with a given prefix.
+///
+/// If a snapshot is given, the keys written since the snapshot creation are returned as they were when it has been created.
+/// An error is returned if the tree is cleared after the snapshot creation.
+struct TreeKeyIterator {
+    iter: Iter,
+    snapshot: Option<(Arc<SnapshotOverlay>, IVec)>,
+    prefix: Vec<u8>,
+    last: Option<IVec>,
+    next_from_tree: Option<IVec>,
+    failed: bool,
+}
+
+impl Iterator for TreeKeyIterator {
+    type Item = Result<IVec, io::Error>;
+
+    fn next(&mut self) -> Option<Result<IVec, io::Error>> {
+        if self.failed {
+            return None;
+        }
+        let (snapshot, tree_name) = if let Some(snapshot) = &self.snapshot {
+            snapshot
+        } else {
+            return Some(match self.iter.next()? {
+                Ok((key, _)) => Ok(key),
+                Err(error) => Err(error.into()),
+            });
+        };
+
+        // The next key of the tree that has not been written since the snapshot creation
+        let from_tree = loop {
+            if self.next_from_tree.is_none() {
+                match self.iter.next() {
+                    Some(Ok((key, _))) => self.next_from_tree = Some(key),
+                    Some(Err(error)) => return Some(Err(error.into())),
+                    None => break None,
+                }
+            }
+            if let Some(key) = &self.next_from_tree {
+                if snapshot.is_written(tree_name, key) {
+                    self.next_from_tree = None;
+                } else {
+                    break Some(key.clone());
+                }
+            }
+        };
+
+        // The next key written since the snapshot creation that was there when the snapshot has been created
+        let start = match &self.last {
+            Some(last) => Bound::Excluded(last.as_ref()),
+            None => Bound::Included(self.prefix.as_slice()),
+        };
+        let from_snapshot = snapshot.next_present_key(tree_name, start, &self.prefix);
+
+        let key = match (from_tree, from_snapshot) {
+            (Some(from_tree), Some(from_snapshot)) if from_snapshot < from_tree => {
+                Some(from_snapshot)
+            }
+            (Some(from_tree), _) => {
+                self.next_from_tree = None;
+                Some(from_tree)
+            }
+            (None, Some(from_snapshot)) => Some(from_snapshot),
+            (None, None) => None,
+        };
+        // The clear is recorded before being done so the keys read before the check are the ones of the snapshot
+        if snapshot.is_cleared(tree_name) {
+            self.failed = true;
+            return Some(Err(snapshot_cleared_error()));
+        }
+        let key = key?;
+        self.last = Some(key.clone());
+        Some(Ok(key))
+    }
+}
+
+/// The snapshots of a [`SledStore`] that are still alive.
+///
+/// Sled does not provide snapshots so they are emulated:
+/// before writing a key, the writers record its current value into all the snapshots that are alive.
+/// The queries and the iterators only record the strings removed from the dictionary to still be able to decode the quads they have read.
+#[derive(Default)]
+struct SnapshotRegistry {
+    snapshots: RwLock<Vec<Weak<SnapshotOverlay>>>,
+}
+
+impl SnapshotRegistry {
+    /// Creates a new snapshot, only recording the strings removed from the dictionary if `strings_only` is set
+    ///
+    /// It waits for the running writes to finish to not see them partially.
+    #[allow(clippy::expect_used)]
+    fn create(&self, strings_only: bool) -> Arc<SnapshotOverlay> {
+        let snapshot = Arc::new(SnapshotOverlay {
+            strings_only,
+            trees: RwLock::default(),
+            cleared: RwLock::default(),
+        });
+        let mut snapshots = self
+            .snapshots
+            .write()
+            .expect("the Sled snapshot registry mutex has been poisoned because of a panic");
+        snapshots.retain(|snapshot| snapshot.strong_count() > 0);
+        snapshots.push(Arc::downgrade(&snapshot));
+        snapshot
+    }
+
+    /// Returns the snapshots to record the written keys into
+    ///
+    /// No snapshot is created while the returned writer is alive.
+    #[allow(clippy::expect_used)]
+    fn writer(&self) -> SnapshotWriter<'_> {
+        let lock = self
+            .snapshots
+            .read()
+            .expect("the Sled snapshot registry mutex has been poisoned because of a panic");
+        SnapshotWriter {
+            snapshots: lock.iter().filter_map(Weak::upgrade).collect(),
+            _lock: lock,
+        }
+    }
+}
+
+struct SnapshotWriter<'a> {
+    snapshots: Vec<Arc<SnapshotOverlay>>,
+    _lock: RwLockReadGuard<'a, Vec<Weak<SnapshotOverlay>>>,
+}
+
+impl SnapshotWriter<'_> {
+    /// To call before writing a key of an index
+    fn before_write(&self, tree: &Tree, key: &[u8]) -> Result<(), io::Error> {
+        if self.snapshots.iter().any(|snapshot| !snapshot.strings_only) {
+            self.record(tree, key, &tree.get(key)?);
+        }
+        Ok(())
+    }
+
+    /// Records the value a key of an index had before being written
+    fn record(&self, tree: &Tree, key: &[u8], old_value: &Option<IVec>) {
+        for snapshot in &self.snapshots {
+            if !snapshot.strings_only {
+                snapshot.record(tree, key, old_value);
+            }
+        }
+    }
+
+    /// To call before removing a string from the dictionary
+    fn before_string_removal(&self, tree: &Tree, key: &[u8]) -> Result<(), io::Error> {
+        if !self.snapshots.is_empty() {
+            let old_value = tree.get(key)?;
+            for snapshot in &self.snapshots {
+                snapshot.record(tree, key, &old_value);
+            }
+        }
+        Ok(())
+    }
+
+    /// To call before clearing an index
+    fn before_clear(&self, tree: &Tree) {
+        for snapshot in &self.snapshots {
+            if !snapshot.strings_only {
+                snapshot.record_clear(tree);
+            }
+        }
+    }
+
+    /// To call before clearing the string dictionary
+    fn before_dictionary_clear(&self, tree: &Tree) {
+        for snapshot in &self.snapshots {
+            snapshot.record_clear(tree);
+        }
+    }
+}
+
+/// The values that the keys written since the snapshot creation had when the snapshot has been created.
+///
+/// The values are indexed by tree name then by key. `None` is used for the keys that were not in the tree.
+/// The cleared trees are only recorded by name: their content is not readable from the snapshot anymore.
+struct SnapshotOverlay {
+    /// If only the strings removed from the dictionary are recorded
+    strings_only: bool,
+    trees: RwLock<HashMap<IVec, BTreeMap<IVec, Option<IVec>>>>,
+    /// The names of the trees cleared since the snapshot creation
+    cleared: RwLock<HashSet<IVec>>,
+}
+
+impl SnapshotOverlay {
+    #[allow(clippy::expect_used)]
+    fn trees(&self) -> RwLockReadGuard<'_, HashMap<IVec, BTreeMap<IVec, Option<IVec>>>> {
+        self.trees
+            .read()
+            .expect("the Sled snapshot mutex has been poisoned because of a panic")
+    }
+
+    #[allow(clippy::expect_used)]
+    fn trees_mut(&self) -> RwLockWriteGuard<'_, HashMap<IVec, BTreeMap<IVec, Option<IVec>>>> {
+        self.trees
+            .write()
+            .expect("the Sled snapshot mutex has been poisoned because of a panic")
+    }
+
+    /// Records the value of a key before its first write since the snapshot creation
+    fn record(&self, tree: &Tree, key: &[u8], old_value: &Option<IVec>) {
+        let mut trees = self.trees_mut();
+        let values = trees.entry(tree.name()).or_default();
+        if !values.contains_key(key) {
+            values.insert(key.into(), old_value.clone());
+        }
+    }
+
+    /// Records that a tree is cleared
+    #[allow(clippy::expect_used)]
+    fn record_clear(&self, tree: &Tree) {
+        self.cleared
+            .write()
+            .expect("the Sled snapshot mutex has been poisoned because of a panic")
+            .insert(tree.name());
+    }
+
+    #[allow(clippy::expect_used)]
+    fn is_cleared(&self, tree_name: &IVec) -> bool {
+        self.cleared
+            .read()
+            .expect("the Sled snapshot mutex has been poisoned because of a panic")
+            .contains(tree_name)
+    }
+
+    /// Returns the value of the key when the snapshot has been created if it has been written since
+    ///
+    /// Fails if the value is not known because the tree has been cleared since the snapshot creation.
+    fn get(&self, tree: &Tree, key: &[u8]) -> Result<Option<Option<IVec>>, io::Error> {
+        let tree_name = tree.name();
+        if let Some(value) = self
+            .trees()
+            .get(&tree_name)
+            .and_then(|values| values.get(key))
+        {
+            return Ok(Some(value.clone()));
+        }
+        if self.is_cleared(&tree_name) {
+            return Err(snapshot_cleared_error());
+        }
+        Ok(None)
+    }
+
+    fn is_written(&self, tree_name: &IVec, key: &[u8]) -> bool {
+        if let Some(values) = self.trees().get(tree_name) {
+            values.contains_key(key)
+        } else {
+            false
+        }
+    }
+
+    /// Returns the first key after `start` starting with `prefix`, written since the snapshot creation and that was in the tree when the snapshot has been created
+    fn next_present_key(
+        &self,
+        tree_name: &IVec,
+        start: Bound<&[u8]>,
+        prefix: &[u8],
+    ) -> Option<IVec> {
+        self.trees()
+            .get(tree_name)?
+            .range::<[u8], _>((start, Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .find(|(_, value)| value.is_some())
+            .map(|(key, _)| key.clone())
+    }
+}
+
+fn snapshot_cleared_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "The Sled snapshot is not readable anymore because the store has been cleared after its creation",
+    )
+}
+
 /// An iterator returning the quads contained in a [`SledStore`].
 pub struct SledQuadIter {
     inner: QuadIterInner,
@@ -1913,3 +3336,208 @@ fn store() -> Result<(), io::Error> {
 
     Ok(())
 }
+
+#[test]
+fn migration() -> Result<(), io::Error> {
+    use crate::model::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static REWRITE_CALLS: AtomicUsize = AtomicUsize::new(0);
+    const QUAD_COUNT: usize = 3000;
+    const INTERRUPTION: usize = 1500;
+
+    /// Fixes the DOSP index keys written in the SPO order and fails once in the middle
+    fn rewrite(key: &[u8]) -> Result<Vec<u8>, io::Error> {
+        if REWRITE_CALLS.fetch_add(1, Ordering::Relaxed) == INTERRUPTION {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interruption"));
+        }
+        let mut buffer = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
+        write_osp_quad(&mut buffer, &QuadEncoding::DSPO.decode(key)?);
+        Ok(buffer)
+    }
+    const STEPS: &[MigrationStep] = &[MigrationStep::RewriteIndex {
+        index: StorageIndex::Dosp,
+        rewrite,
+    }];
+    let migrations = [Migration {
+        from_version: LATEST_STORAGE_VERSION,
+        steps: STEPS,
+    }];
+
+    let store = SledStore::new()?;
+    let ex = NamedNodeRef::new_unchecked("http://example.com");
+    for i in 0..QUAD_COUNT {
+        store.insert(QuadRef::new(ex, ex, &Literal::from(i as u64), None))?;
+    }
+    store.dosp.clear()?;
+    for key in store.dspo.iter().keys() {
+        store.dosp.insert(key?, &[])?;
+    }
+
+    // The migration is interrupted and then resumed from the last saved progress
+    assert!(store.migrate(&migrations).is_err());
+    assert_eq!(store.migrate(&migrations)?, LATEST_STORAGE_VERSION + 1);
+    assert_eq!(
+        REWRITE_CALLS.load(Ordering::Relaxed),
+        INTERRUPTION + 1 + QUAD_COUNT - MIGRATION_BATCH_SIZE
+    );
+    assert!(store.default.get(MIGRATION_PROGRESS_KEY)?.is_none());
+    assert_eq!(
+        store.default.scan_prefix(MIGRATION_BUFFER_PREFIX).count(),
+        0
+    );
+    assert_eq!(store.dosp.len(), QUAD_COUNT);
+    for i in 0..QUAD_COUNT {
+        let object = Literal::from(i as u64);
+        assert_eq!(
+            store
+                .quads_for_pattern(
+                    None,
+                    None,
+                    Some(object.as_ref().into()),
+                    Some(GraphNameRef::DefaultGraph)
+                )
+                .collect::<Result<Vec<_>, _>>()?,
+            vec![Quad::new(ex, ex, object, None)]
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn statistics() -> Result<(), io::Error> {
+    use crate::model::*;
+
+    let store = SledStore::new()?;
+    let ex = NamedNodeRef::new_unchecked("http://example.com");
+    let ex2 = NamedNodeRef::new_unchecked("http://example.com/2");
+    let encoded_ex = (&store).encode_term(ex.into())?;
+    let cardinality = |predicate, graph_name| {
+        store.encoded_quads_for_pattern_cardinality(None, predicate, None, graph_name)
+    };
+    store.insert(QuadRef::new(ex, ex, ex, None))?;
+    store.insert(QuadRef::new(ex, ex, ex, None))?;
+    store.insert(QuadRef::new(ex, ex, ex2, None))?;
+    store.insert(QuadRef::new(ex, ex2, ex, ex))?;
+    store.transaction::<_, io::Error>(|transaction| {
+        transaction.insert(QuadRef::new(ex, ex, ex, ex))?;
+        transaction.insert(QuadRef::new(ex2, ex, ex, ex))?;
+        transaction.remove(QuadRef::new(ex2, ex, ex, ex))?;
+        transaction.remove(QuadRef::new(ex2, ex2, ex2, ex2))?;
+        Ok(())
+    })?;
+    store.remove(QuadRef::new(ex, ex, ex2, None))?;
+    store.remove(QuadRef::new(ex, ex, ex2, None))?;
+    assert_eq!(cardinality(None, None), Some(3));
+    assert_eq!(cardinality(Some(encoded_ex), None), Some(2));
+    assert_eq!(
+        cardinality(Some(encoded_ex), Some(EncodedTerm::DefaultGraph)),
+        Some(1)
+    );
+    assert_eq!(cardinality(None, Some(encoded_ex)), Some(2));
+
+    // The statistics are computed again from the indexes
+    let before = store
+        .default
+        .range(STATISTICS_PREFIX..STATISTICS_END)
+        .collect::<Result<Vec<_>, _>>()?;
+    store.compute_statistics()?;
+    assert_eq!(
+        store
+            .default
+            .range(STATISTICS_PREFIX..STATISTICS_END)
+            .collect::<Result<Vec<_>, _>>()?,
+        before
+    );
+
+    store.clear_graph(GraphNameRef::DefaultGraph)?;
+    assert_eq!(cardinality(None, None), Some(2));
+    store.clear()?;
+    assert_eq!(cardinality(None, None), Some(0));
+    Ok(())
+}
+
+#[test]
+fn cardinality_estimates() -> Result<(), io::Error> {
+    use crate::model::*;
+
+    let store = SledStore::new()?;
+    let rdf_type = NamedNodeRef::new_unchecked("http://www.w3.org/1999/02/22-rdf-syntax-ns#type");
+    let class = NamedNodeRef::new_unchecked("http://example.com/Class");
+    let rare_class = NamedNodeRef::new_unchecked("http://example.com/RareClass");
+    let age = NamedNodeRef::new_unchecked("http://example.com/age");
+    let knows = NamedNodeRef::new_unchecked("http://example.com/knows");
+    let likes = NamedNodeRef::new_unchecked("http://example.com/likes");
+    let popular = NamedNodeRef::new_unchecked("http://example.com/popular");
+    let s0 = NamedNode::new_unchecked("http://example.com/s0");
+    for i in 0..1000 {
+        let s = NamedNode::new_unchecked(format!("http://example.com/s{}", i));
+        let o = NamedNode::new_unchecked(format!("http://example.com/o{}", i));
+        // All the subjects share the same class
+        store.insert(QuadRef::new(&s, rdf_type, class, None))?;
+        // The ages are inline literals
+        store.insert(QuadRef::new(&s, age, &Literal::from(i), None))?;
+        // The objects are evenly distributed hashed IRIs
+        store.insert(QuadRef::new(&s, knows, &o, None))?;
+        // A quarter of the subjects like the same object
+        if i % 4 == 0 {
+            store.insert(QuadRef::new(&s, likes, popular, None))?;
+        } else {
+            store.insert(QuadRef::new(&s, likes, &o, None))?;
+        }
+    }
+    store.insert(QuadRef::new(&s0, rdf_type, rare_class, None))?;
+    let encode = |term: TermRef<'_>| (&store).encode_term(term);
+    let rdf_type = encode(rdf_type.into())?;
+    let class = encode(class.into())?;
+    let rare_class = encode(rare_class.into())?;
+    let age = encode(age.into())?;
+    let forty_two = encode(Literal::from(42).as_ref().into())?;
+    let knows = encode(knows.into())?;
+    let likes = encode(likes.into())?;
+    let popular = encode(popular.into())?;
+    let o1 = encode(NamedNodeRef::new_unchecked("http://example.com/o1").into())?;
+    let s0 = encode(s0.as_ref().into())?;
+    let cardinality = |subject, predicate, object| {
+        store.encoded_quads_for_pattern_cardinality(subject, predicate, object, None)
+    };
+    assert_eq!(cardinality(None, None, None), Some(4001));
+    assert_eq!(cardinality(None, Some(rdf_type), None), Some(1001));
+    assert_eq!(cardinality(None, Some(rdf_type), Some(class)), Some(1001));
+    assert_eq!(cardinality(None, Some(rdf_type), Some(rare_class)), Some(1));
+    assert_eq!(cardinality(None, Some(age), None), Some(1000));
+    assert_eq!(cardinality(None, Some(age), Some(forty_two)), Some(1));
+    assert_eq!(cardinality(None, Some(knows), None), Some(1000));
+    assert_eq!(cardinality(Some(s0), Some(knows), None), Some(1));
+    assert_eq!(cardinality(None, Some(likes), Some(o1)), Some(1));
+    // A frequent object is estimated using the predicate count, an upper bound of the exact cardinality
+    let likes_popular = cardinality(None, Some(likes), Some(popular)).unwrap();
+    assert!((250..=1000).contains(&likes_popular));
+    Ok(())
+}
+
+#[test]
+fn snapshot_overlays() -> Result<(), io::Error> {
+    use crate::model::*;
+
+    let store = SledStore::new()?;
+    let ex = NamedNodeRef::new_unchecked("http://example.com");
+    let reading = store.for_reading();
+    let snapshot = store.snapshot();
+    store.insert(QuadRef::new(ex, ex, ex, None))?;
+
+    // Only the explicit snapshots record the written keys
+    assert!(reading.snapshot.as_ref().unwrap().trees().is_empty());
+    assert!(!snapshot.store.snapshot.as_ref().unwrap().trees().is_empty());
+    drop(snapshot);
+
+    // The clears are recorded without copying the trees
+    let snapshot = store.snapshot();
+    store.clear()?;
+    let overlay = snapshot.store.snapshot.as_ref().unwrap();
+    assert!(overlay.trees().is_empty());
+    assert!(overlay.is_cleared(&store.dspo.name()));
+    assert!(snapshot.iter().next().unwrap().is_err());
+    assert!(reading.is_empty());
+    Ok(())
+}
@@ -1,8 +1,18 @@
+use oxigraph::io::read::SyntaxError;
+use oxigraph::io::{DatasetFormat, GraphFormat};
 use oxigraph::model::vocab::{rdf, xsd};
 use oxigraph::model::*;
+use oxigraph::sparql::QueryResults;
 use oxigraph::RocksDbStore;
+use rand::random;
+use std::collections::HashSet;
+use std::env::temp_dir;
+use std::fs::{read_dir, remove_dir_all};
 use std::io;
+use std::path::Path;
 use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 fn quads(graph_name: impl Into<GraphNameRef<'static>>) -> Vec<QuadRef<'static>> {
     let graph_name = graph_name.into();
@@ -79,6 +89,483 @@ fn test_backward_compatibility() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_atomic_update() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        let ex = NamedNodeRef::new_unchecked("http://example.com");
+        store.insert(QuadRef::new(ex, ex, ex, ex))?;
+
+        // A failing update does not modify the store, even if it is big
+        let mut update = "INSERT DATA {".to_owned();
+        for i in 0..1500 {
+            update += &format!(" <http://example.com/s{}> <http://example.com> {} .", i, i);
+        }
+        update += " } ; CREATE GRAPH <http://example.com>";
+        assert!(store.update(update.as_str()).is_err());
+        assert_eq!(store.len(), 1);
+
+        // The operations of an update see the changes of the previous operations
+        store
+            .update(
+                "INSERT DATA { <http://example.com/s> <http://example.com/p> 1 } ;
+                DELETE { ?s ?p ?o } INSERT { ?s ?p 2 } WHERE { ?s ?p ?o FILTER(?o = 1) } ;
+                DROP GRAPH <http://example.com> ;
+                INSERT DATA { GRAPH <http://example.com/g> { <http://example.com/s> <http://example.com/p> 3 } } ;
+                CLEAR GRAPH <http://example.com/g>",
+            )
+            .unwrap();
+        let s = NamedNodeRef::new_unchecked("http://example.com/s");
+        let p = NamedNodeRef::new_unchecked("http://example.com/p");
+        assert_eq!(
+            store
+                .quads_for_pattern(None, None, None, None)
+                .collect::<io::Result<Vec<_>>>()?,
+            vec![Quad::new(s, p, Literal::from(2), GraphName::DefaultGraph)]
+        );
+        assert!(!store.contains_named_graph(ex)?);
+
+        // An invalid file is not partially loaded
+        assert!(store
+            .load_graph_atomic(
+                b"<http://example.com/s> <http://example.com/p> 4 .\n<http://example.com/s> <http://example.com/p> ."
+                    .as_ref(),
+                GraphFormat::Turtle,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .is_err());
+        assert_eq!(store.len(), 1);
+    }
+    remove_dir_all(&repo_path)
+}
+
+#[test]
+fn test_snapshot() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        for q in quads(GraphNameRef::DefaultGraph) {
+            store.insert(q)?;
+        }
+        let snapshot = store.snapshot();
+
+        // Writes done after the snapshot creation are not visible
+        let ex = NamedNodeRef::new_unchecked("http://example.com");
+        store.insert(QuadRef::new(ex, ex, ex, ex))?;
+        store.clear_graph(GraphNameRef::DefaultGraph)?;
+        assert_eq!(store.len(), 1);
+        assert_eq!(snapshot.len(), quads(GraphNameRef::DefaultGraph).len());
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(snapshot.contains(q)?);
+        }
+        assert!(!snapshot.contains(QuadRef::new(ex, ex, ex, ex))?);
+        assert!(!snapshot.contains_named_graph(ex)?);
+
+        // The snapshot stays the same after the store is cleared
+        store.clear()?;
+        assert!(store.is_empty());
+        assert_eq!(
+            snapshot.iter().count(),
+            quads(GraphNameRef::DefaultGraph).len()
+        );
+    }
+    remove_dir_all(&repo_path)
+}
+
+#[test]
+fn test_lenient_mode_not_supported() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        let file = b"<http://example.com> <http://example.com> <http://example.com> .";
+        let error = store
+            .load_graph_lenient(
+                file.as_ref(),
+                GraphFormat::Turtle,
+                GraphNameRef::DefaultGraph,
+                None,
+                |_| (),
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let error = store
+            .load_dataset_lenient(file.as_ref(), DatasetFormat::TriG, None, |_| ())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(store.is_empty());
+    }
+    remove_dir_all(&repo_path)
+}
+
+#[test]
+fn test_remove_unused_strings() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        for q in quads(GraphNameRef::DefaultGraph) {
+            store.insert(q)?;
+        }
+        assert_eq!(store.remove_unused_strings()?, 0);
+
+        let ex = NamedNodeRef::new_unchecked("http://example.com/graph");
+        let quad = QuadRef::new(
+            ex,
+            ex,
+            LiteralRef::new_simple_literal("a literal to remove"),
+            ex,
+        );
+        store.insert(quad)?;
+        let snapshot = store.snapshot();
+        store.remove(quad)?;
+        store.remove_named_graph(ex)?;
+        assert_eq!(store.remove_unused_strings()?, 2);
+        assert_eq!(store.remove_unused_strings()?, 0);
+
+        // The used strings are kept
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(store.contains(q)?);
+        }
+        assert_eq!(
+            store.iter().collect::<io::Result<Vec<_>>>()?.len(),
+            quads(GraphNameRef::DefaultGraph).len()
+        );
+
+        // The snapshots still see the removed strings
+        assert!(snapshot.contains(quad)?);
+        assert!(snapshot
+            .iter()
+            .collect::<io::Result<Vec<_>>>()?
+            .contains(&quad.into_owned()));
+
+        // The strings are added back if needed
+        store.insert(quad)?;
+        assert!(store.contains(quad)?);
+    }
+    remove_dir_all(&repo_path)
+}
+
+#[test]
+fn test_backup() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    let checkpoint_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    let backup_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    let restore_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    let ex = NamedNodeRef::new_unchecked("http://example.com");
+    {
+        let store = RocksDbStore::open(&repo_path)?;
+        for q in quads(GraphNameRef::DefaultGraph) {
+            store.insert(q)?;
+        }
+        store.backup(&checkpoint_path)?;
+        store.incremental_backup(&backup_path)?;
+        store.insert(QuadRef::new(ex, ex, ex, ex))?;
+        store.incremental_backup(&backup_path)?;
+        store.clear()?;
+
+        // The checkpoint is not modified by the later writes
+        let checkpoint = RocksDbStore::open(&checkpoint_path)?;
+        assert_eq!(checkpoint.len(), quads(GraphNameRef::DefaultGraph).len());
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(checkpoint.contains(q)?);
+        }
+        assert!(!checkpoint.contains_named_graph(ex)?);
+    }
+    {
+        // The latest backup is restored
+        RocksDbStore::restore_backup(&backup_path, &restore_path)?;
+        let restored = RocksDbStore::open(&restore_path)?;
+        assert_eq!(restored.len(), quads(GraphNameRef::DefaultGraph).len() + 1);
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(restored.contains(q)?);
+        }
+        assert!(restored.contains(QuadRef::new(ex, ex, ex, ex))?);
+    }
+    remove_dir_all(&repo_path)?;
+    remove_dir_all(&checkpoint_path)?;
+    remove_dir_all(&backup_path)?;
+    remove_dir_all(&restore_path)
+}
+
+#[test]
+fn test_read_only_and_secondary() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    let secondary_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    let ex = NamedNodeRef::new_unchecked("http://example.com");
+    {
+        let primary = RocksDbStore::open(&repo_path)?;
+        for q in quads(GraphNameRef::DefaultGraph) {
+            primary.insert(q)?;
+        }
+        let read_only = RocksDbStore::open_read_only(&repo_path)?;
+        let secondary = RocksDbStore::open_secondary(&repo_path, &secondary_path)?;
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(read_only.contains(q)?);
+            assert!(secondary.contains(q)?);
+        }
+        assert!(read_only.insert(QuadRef::new(ex, ex, ex, ex)).is_err());
+        assert!(secondary.insert(QuadRef::new(ex, ex, ex, ex)).is_err());
+        assert!(read_only.catch_up_with_primary().is_err());
+
+        // The secondary sees the primary writes only after catching up
+        primary.insert(QuadRef::new(ex, ex, ex, ex))?;
+        primary.remove(quads(GraphNameRef::DefaultGraph)[0])?;
+        assert!(!secondary.contains(QuadRef::new(ex, ex, ex, ex))?);
+        secondary.catch_up_with_primary()?;
+        assert!(secondary.contains(QuadRef::new(ex, ex, ex, ex))?);
+        assert!(!secondary.contains(quads(GraphNameRef::DefaultGraph)[0])?);
+        assert!(secondary.contains_named_graph(ex)?);
+        assert_eq!(secondary.len(), primary.len());
+        assert_eq!(
+            secondary.iter().collect::<Result<HashSet<_>, _>>()?,
+            primary.iter().collect::<Result<HashSet<_>, _>>()?
+        );
+        assert_eq!(
+            secondary
+                .quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph))
+                .count(),
+            quads(GraphNameRef::DefaultGraph).len() - 1
+        );
+        if let QueryResults::Solutions(solutions) = secondary
+            .query("SELECT ?s WHERE { { ?s ?p ?o } UNION { GRAPH ?g { ?s ?p ?o } } }")
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        {
+            assert_eq!(solutions.count(), primary.len());
+        } else {
+            panic!("SELECT query results expected")
+        }
+        assert!(secondary
+            .snapshot()
+            .contains(QuadRef::new(ex, ex, ex, ex))?);
+    }
+    remove_dir_all(&repo_path)?;
+    remove_dir_all(&secondary_path)
+}
+
+#[test]
+fn test_bulk_load() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let mut store = RocksDbStore::open(&repo_path)?;
+
+        // Many chunks of lines and many SST files, with blank nodes shared between the chunks
+        let mut file = String::new();
+        for i in 0..100_000 {
+            file += &format!(
+                "_:s <http://example.com/p> \"{}\" <http://example.com/g{}> .\n",
+                i,
+                i % 3
+            );
+        }
+        file += "<http://example.com/s> <http://example.com/p> _:s .\n";
+        let progress = Arc::new(AtomicU64::new(0));
+        let progress_sink = progress.clone();
+        store
+            .bulk_loader()
+            .with_num_threads(4)
+            .with_max_memory_size_in_megabytes(1)
+            .on_progress(move |count| progress_sink.store(count, Ordering::Relaxed))
+            .load_dataset(file.as_bytes(), DatasetFormat::NQuads, None)?;
+        assert_eq!(store.len(), 100_001);
+        assert_eq!(progress.load(Ordering::Relaxed), 100_001);
+        assert_eq!(store.named_graphs().count(), 3);
+        let s = NamedNodeRef::new_unchecked("http://example.com/s");
+        let p = NamedNodeRef::new_unchecked("http://example.com/p");
+        let blank_nodes = store
+            .quads_for_pattern(None, None, None, None)
+            .map(|q| {
+                let q = q?;
+                Ok(if q.subject == s.into() {
+                    q.object
+                } else {
+                    q.subject.into()
+                })
+            })
+            .collect::<io::Result<HashSet<_>>>()?;
+        assert_eq!(blank_nodes.len(), 1);
+
+        // The other formats
+        store.bulk_loader().load_graph(
+            b"<s> <p> <o> .".as_ref(),
+            GraphFormat::Turtle,
+            GraphNameRef::DefaultGraph,
+            Some("http://example.com/"),
+        )?;
+        let o = NamedNodeRef::new_unchecked("http://example.com/o");
+        assert!(store.contains(QuadRef::new(s, p, o, GraphNameRef::DefaultGraph))?);
+
+        // Nothing is written on syntax errors that are located in the file
+        file += "<http://example.com/s> <http://example.com/p> \"foo .\n";
+        let error = store
+            .bulk_loader()
+            .with_max_memory_size_in_megabytes(1)
+            .load_graph(
+                file.replace(" <http://example.com/g0> .", " .")
+                    .replace(" <http://example.com/g1> .", " .")
+                    .replace(" <http://example.com/g2> .", " .")
+                    .as_bytes(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .unwrap_err();
+        let error = error
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<SyntaxError>()
+            .unwrap();
+        assert_eq!(error.line(), Some(100_002));
+        assert_eq!(error.column(), Some(53));
+        assert_eq!(store.len(), 100_002);
+    }
+    remove_dir_all(&repo_path)
+}
+
+#[test]
+fn test_bulk_load_into_non_empty_store() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let mut store = RocksDbStore::open(&repo_path)?;
+        for q in quads(GraphNameRef::DefaultGraph) {
+            store.insert(q)?;
+        }
+        let initial_len = store.len();
+
+        let mut file = String::new();
+        for i in 0..100_000 {
+            file += &format!(
+                "<http://example.com/s{}> <http://example.com/p> \"{}\" .\n",
+                i % 100,
+                i
+            );
+        }
+
+        // A syntax error in the middle of the file after some SST files have been written
+        let mut invalid_file = file.clone();
+        invalid_file.insert_str(
+            invalid_file.len() / 2,
+            "<http://example.com/s> <http://example.com/p> \"foo .\n",
+        );
+        let error = store
+            .bulk_loader()
+            .with_num_threads(2)
+            .with_max_memory_size_in_megabytes(1)
+            .load_graph(
+                invalid_file.as_bytes(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(store.len(), initial_len);
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(store.contains(q)?);
+        }
+        assert_no_bulk_load_directory(&repo_path)?;
+
+        // The loaded quads are added to the existing ones
+        store
+            .bulk_loader()
+            .with_num_threads(2)
+            .with_max_memory_size_in_megabytes(1)
+            .load_graph(
+                file.as_bytes(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )?;
+        assert_eq!(store.len(), initial_len + 100_000);
+        assert_no_bulk_load_directory(&repo_path)?;
+
+        // Loading again the same quads does not duplicate them
+        store
+            .bulk_loader()
+            .with_max_memory_size_in_megabytes(1)
+            .load_graph(
+                file.as_bytes(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )?;
+        store.bulk_loader().load_graph(
+            b"<http://example.com/s0> <http://example.com/p> \"0\" , \"foo\" .".as_ref(),
+            GraphFormat::Turtle,
+            GraphNameRef::DefaultGraph,
+            None,
+        )?;
+        assert_eq!(store.len(), initial_len + 100_001);
+        for q in quads(GraphNameRef::DefaultGraph) {
+            assert!(store.contains(q)?);
+        }
+        let p = NamedNodeRef::new_unchecked("http://example.com/p");
+        assert_eq!(
+            store.quads_for_pattern(None, Some(p), None, None).count(),
+            100_001
+        );
+        assert_no_bulk_load_directory(&repo_path)?;
+    }
+    remove_dir_all(&repo_path)
+}
+
+#[test]
+fn test_bulk_load_requires_exclusive_access() -> io::Result<()> {
+    let repo_path = temp_dir().join(format!("oxigraph-test-{}", random::<u128>()));
+    {
+        let mut store = RocksDbStore::open(&repo_path)?;
+        let file = b"<http://example.com/s> <http://example.com/p> <http://example.com/o> .";
+
+        let clone = store.clone();
+        let error = store
+            .bulk_loader()
+            .load_graph(
+                file.as_ref(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        drop(clone);
+
+        let iter = store.quads_for_pattern(None, None, None, None);
+        assert!(store
+            .bulk_loader()
+            .load_graph(
+                file.as_ref(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .is_err());
+        drop(iter);
+        assert!(store.is_empty());
+        assert_no_bulk_load_directory(&repo_path)?;
+
+        store.bulk_loader().load_graph(
+            file.as_ref(),
+            GraphFormat::NTriples,
+            GraphNameRef::DefaultGraph,
+            None,
+        )?;
+        assert_eq!(store.len(), 1);
+    }
+    remove_dir_all(&repo_path)
+}
+
+fn assert_no_bulk_load_directory(repo_path: &Path) -> io::Result<()> {
+    for entry in read_dir(repo_path)? {
+        let name = entry?.file_name();
+        assert!(
+            !name.to_string_lossy().starts_with("bulk-load-"),
+            "The temporary directory {:?} has not been removed",
+            name
+        );
+    }
+    Ok(())
+}
+
 fn reset_dir(dir: &str) -> io::Result<()> {
     assert!(Command::new("git")
         .args(&["clean", "-fX", dir])
@@ -1,11 +1,20 @@
-use oxigraph::io::{DatasetFormat, GraphFormat};
+use oxigraph::io::read::SyntaxError;
+use oxigraph::io::{DatasetFormat, GraphFormat, GraphParser};
 use oxigraph::model::vocab::{rdf, xsd};
 use oxigraph::model::*;
+use oxigraph::sparql::{
+    DescribeStrategy, EvaluationError, LocalServiceHandler, PreparedQuery, Query, QueryOptions,
+    QueryResults, ServiceHandler, UpdateOptions, Variable,
+};
 use oxigraph::store::sled::SledConflictableTransactionError;
-use oxigraph::SledStore;
+use oxigraph::{MemoryStore, SledStore};
 use std::io;
 use std::io::Cursor;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 const DATA: &str = r#"
 @prefix schema: <http://schema.org/> .
@@ -95,6 +104,159 @@ fn test_load_dataset() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_load_json_ld_graph_with_parser() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let parser = GraphParser::from_format(GraphFormat::JsonLd)
+        .with_base_iri("http://example.com/")
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        .with_json_ld_context_loader(|iri: &str| {
+            assert_eq!(iri, "http://example.com/context.jsonld");
+            Ok(r#"{"@context": {"@vocab": "http://example.com/"}}"#.to_owned())
+        });
+    let ex = NamedNodeRef::new_unchecked("http://example.com/");
+    store.load_graph_with_parser(
+        Cursor::new(r#"{"@context": "context.jsonld", "@id": "s", "p": {"@id": "o"}}"#),
+        &parser,
+        ex,
+    )?;
+    assert!(store.contains(QuadRef::new(
+        NamedNodeRef::new_unchecked("http://example.com/s"),
+        NamedNodeRef::new_unchecked("http://example.com/p"),
+        NamedNodeRef::new_unchecked("http://example.com/o"),
+        ex,
+    ))?);
+
+    // Named graphs are not allowed in graph formats
+    assert_eq!(
+        store
+            .load_graph(
+                Cursor::new(r#"{"@id": "http://example.com/g", "@graph": {"@id": "http://example.com/s", "http://example.com/p": {"@id": "http://example.com/o"}}}"#),
+                GraphFormat::JsonLd,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidData
+    );
+
+    let mut buffer = Vec::new();
+    store.dump_graph(&mut buffer, GraphFormat::JsonLd, ex)?;
+    assert_eq!(
+        buffer,
+        br#"[{"@id":"http://example.com/s","http://example.com/p":[{"@id":"http://example.com/o"}]}]"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_load_graph_lenient() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let mut errors = Vec::new();
+    store.load_graph_lenient(
+        Cursor::new(
+            "<http://example.com/s> <http://example.com/p> \"foo\" .\n<http://example.com/s> <http://example.com/p> \"bar .\n<http://example.com/s> <http://example.com/p> \"baz\" .\n",
+        ),
+        GraphFormat::NTriples,
+        GraphNameRef::DefaultGraph,
+        None,
+        |e| errors.push(e),
+    )?;
+    assert_eq!(store.len(), 2);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line(), Some(2));
+    assert_eq!(errors[0].byte_offset(), Some(106));
+    Ok(())
+}
+
+#[test]
+fn test_load_dataset_lenient() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let mut errors = Vec::new();
+    store.load_dataset_lenient(
+        Cursor::new(
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/g>\n<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n",
+        ),
+        DatasetFormat::NQuads,
+        None,
+        |e| errors.push(e),
+    )?;
+    assert_eq!(store.len(), 1);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line(), Some(1));
+    Ok(())
+}
+
+#[test]
+fn test_lenient_mode_not_supported() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let error = store
+        .load_graph_lenient(
+            Cursor::new(DATA),
+            GraphFormat::Turtle,
+            GraphNameRef::DefaultGraph,
+            None,
+            |_| (),
+        )
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    let error = store
+        .load_dataset_lenient(Cursor::new(DATA), DatasetFormat::TriG, None, |_| ())
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    assert!(store.is_empty());
+    assert!(GraphParser::from_format(GraphFormat::RdfXml)
+        .with_lenient_mode(|_| ())
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_load_graph_lenient_error_position_in_big_file() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let line = "<http://example.com/s> <http://example.com/p> \"foo\" .\n";
+    let mut file = line.repeat(10_000);
+    let error_offset = file.len();
+    file.push_str("<http://example.com/s> <http://example.com/p> \"bar .\n");
+    file.push_str(&line.repeat(10_000));
+    let mut errors = Vec::new();
+    store.load_graph_lenient(
+        Cursor::new(file),
+        GraphFormat::NTriples,
+        GraphNameRef::DefaultGraph,
+        None,
+        |e| errors.push(e),
+    )?;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line(), Some(10_001));
+    assert_eq!(
+        errors[0].byte_offset(),
+        Some(error_offset as u64 + errors[0].column().unwrap() - 1)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_load_graph_syntax_error_position() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let error = store
+        .load_graph(
+            Cursor::new("@prefix ex: <http://example.com/> .\nex:s ex:p ex:o ;\n    ex:p foo:o ."),
+            GraphFormat::Turtle,
+            GraphNameRef::DefaultGraph,
+            None,
+        )
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let error = error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<SyntaxError>())
+        .unwrap();
+    assert_eq!(error.line(), Some(3));
+    Ok(())
+}
+
 #[test]
 fn test_dump_graph() -> io::Result<()> {
     let store = SledStore::new()?;
@@ -153,6 +315,628 @@ fn test_transaction_load_dataset() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_update_with_custom_function() -> Result<(), EvaluationError> {
+    let store = SledStore::new()?;
+    store.load_graph(
+        Cursor::new(DATA),
+        GraphFormat::Turtle,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    store.update_opt(
+        "INSERT { ?s <http://example.com/upperName> ?upper } WHERE { ?s <http://schema.org/name> ?name BIND(<http://example.com/upper>(?name) AS ?upper) }",
+        UpdateOptions::from(QueryOptions::default().with_custom_function(
+            NamedNode::new_unchecked("http://example.com/upper"),
+            |args| {
+                if let [Term::Literal(literal)] = args {
+                    Some(Literal::new_simple_literal(literal.value().to_uppercase()).into())
+                } else {
+                    None
+                }
+            },
+        )),
+    )?;
+    assert!(store.contains(QuadRef::new(
+        NamedNodeRef::new_unchecked("http://www.wikidata.org/entity/Q90"),
+        NamedNodeRef::new_unchecked("http://example.com/upperName"),
+        LiteralRef::new_simple_literal("PARIS"),
+        GraphNameRef::DefaultGraph,
+    ))?);
+    Ok(())
+}
+
+#[test]
+fn test_query_results_in_other_thread() -> Result<(), EvaluationError> {
+    let store = SledStore::new()?;
+    store.load_graph(
+        Cursor::new(DATA),
+        GraphFormat::Turtle,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    let results = store.query("SELECT ?s WHERE { ?s ?p ?o }")?;
+    let count = thread::spawn(move || {
+        if let QueryResults::Solutions(solutions) = results {
+            solutions.count()
+        } else {
+            0
+        }
+    })
+    .join()
+    .unwrap();
+    assert_eq!(count, NUMBER_OF_TRIPLES);
+    Ok(())
+}
+
+#[test]
+fn test_parallel_evaluation() -> Result<(), EvaluationError> {
+    fn solutions(
+        store: &SledStore,
+        query: &str,
+        options: QueryOptions,
+    ) -> Result<Vec<Vec<Option<Term>>>, EvaluationError> {
+        if let QueryResults::Solutions(solutions) = store.query_opt(query, options)? {
+            solutions
+                .map(|s| Ok(s?.values().map(|t| t.cloned()).collect()))
+                .collect::<Result<_, EvaluationError>>()
+        } else {
+            unreachable!()
+        }
+    }
+
+    let store = SledStore::new()?;
+    store.load_graph(
+        Cursor::new(DATA),
+        GraphFormat::Turtle,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    for query in &[
+        "SELECT * WHERE { { ?s ?p ?o } UNION { ?o ?p ?s } UNION { ?s a ?o } }",
+        "SELECT * WHERE { { SELECT ?s ?name WHERE { ?s <http://schema.org/name> ?name } } { SELECT ?s ?country WHERE { ?s <http://schema.org/country> ?country } } }",
+        "SELECT * WHERE { ?s ?p ?o OPTIONAL { SELECT ?s ?name WHERE { ?s <http://schema.org/name> ?name } } }",
+        "SELECT * WHERE { ?s ?p ?o OPTIONAL { { ?s <http://schema.org/name> ?name } UNION { ?s <http://schema.org/country> ?name } } }",
+        "SELECT * WHERE { ?s ?p ?o FILTER EXISTS { { ?s <http://schema.org/name> ?name } UNION { ?s a ?name } } }",
+    ] {
+        assert_eq!(
+            solutions(&store, query, QueryOptions::default().with_parallel_evaluation(4))?,
+            solutions(&store, query, QueryOptions::default())?
+        );
+    }
+
+    // More UNION branches than threads allowed, some results not read
+    let query = format!(
+        "SELECT * WHERE {{ {} }}",
+        vec!["{ ?s ?p ?o }"; 64].join(" UNION ")
+    );
+    assert_eq!(
+        solutions(
+            &store,
+            &query,
+            QueryOptions::default().with_parallel_evaluation(4)
+        )?,
+        solutions(&store, &query, QueryOptions::default())?
+    );
+    for _ in 0..16 {
+        if let QueryResults::Solutions(mut solutions) = store.query_opt(
+            query.as_str(),
+            QueryOptions::default().with_parallel_evaluation(4),
+        )? {
+            assert!(solutions.next().is_some());
+        }
+    }
+
+    // The number of threads evaluating the query at the same time is bounded by the limit
+    let query = format!(
+        "SELECT * WHERE {{ {} }}",
+        vec!["{ ?s ?p ?o FILTER(<http://example.com/busy>()) }"; 16].join(" UNION ")
+    );
+    for max_threads in &[1, 3] {
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+        let function_active = active.clone();
+        let function_max_active = max_active.clone();
+        let options = QueryOptions::default()
+            .with_parallel_evaluation(*max_threads)
+            .with_custom_function(
+                NamedNode::new_unchecked("http://example.com/busy"),
+                move |_| {
+                    let current = function_active.fetch_add(1, Ordering::SeqCst) + 1;
+                    function_max_active.fetch_max(current, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(1));
+                    function_active.fetch_sub(1, Ordering::SeqCst);
+                    Some(Literal::from(true).into())
+                },
+            );
+        assert_eq!(
+            solutions(&store, &query, options)?,
+            solutions(
+                &store,
+                &query.replace(" FILTER(<http://example.com/busy>())", ""),
+                QueryOptions::default()
+            )?
+        );
+        assert!(max_active.load(Ordering::SeqCst) <= *max_threads);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_prepared_query() -> Result<(), EvaluationError> {
+    fn names(
+        store: &SledStore,
+        query: &PreparedQuery,
+    ) -> Result<Vec<Option<Term>>, EvaluationError> {
+        if let QueryResults::Solutions(solutions) =
+            store.query_prepared(query, QueryOptions::default())?
+        {
+            solutions
+                .map(|s| Ok(s?.get("name").cloned()))
+                .collect::<Result<_, EvaluationError>>()
+        } else {
+            unreachable!()
+        }
+    }
+
+    let store = SledStore::new()?;
+    store.load_graph(
+        Cursor::new(DATA),
+        GraphFormat::Turtle,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    let paris = NamedNode::new_unchecked("http://www.wikidata.org/entity/Q90");
+    let city = Variable::new_unchecked("city");
+    let lang = Variable::new_unchecked("lang");
+
+    let mut query = PreparedQuery::new(
+        "SELECT ?name WHERE { ?city <http://schema.org/name> ?name FILTER(LANG(?name) = ?lang) } ORDER BY ?name",
+    )?;
+    query.bind(city.clone(), paris.clone());
+    query.bind(lang.clone(), Literal::from("fr"));
+    assert_eq!(
+        names(&store, &query)?,
+        vec![
+            Some(LiteralRef::new_language_tagged_literal_unchecked("Paris", "fr").into()),
+            Some(
+                LiteralRef::new_language_tagged_literal_unchecked("la ville lumière", "fr").into()
+            )
+        ]
+    );
+    query.bind(lang.clone(), Literal::from("en"));
+    assert!(names(&store, &query)?.is_empty());
+    query.bind(
+        city.clone(),
+        NamedNode::new_unchecked("http://www.wikidata.org/entity/Q1"),
+    );
+    query.unbind(&lang);
+    assert!(names(&store, &query)?.is_empty());
+
+    // The bound variables are visible outside of the WHERE clause and in sub-queries
+    let mut query = PreparedQuery::new("SELECT ?city ?name WHERE { { SELECT ?city ?name WHERE { ?city <http://schema.org/name> ?name } } }")?;
+    query.bind(city.clone(), paris.clone());
+    if let QueryResults::Solutions(solutions) =
+        store.query_prepared(&query, QueryOptions::default())?
+    {
+        for solution in solutions {
+            assert_eq!(solution?.get("city"), Some(&paris.clone().into()));
+        }
+    }
+    query.bind(Variable::new_unchecked("name"), Literal::from("Paris"));
+    assert_eq!(names(&store, &query)?, Vec::<Option<Term>>::new());
+
+    let mut query = PreparedQuery::new(
+        "ASK { ?city <http://schema.org/country> <http://www.wikidata.org/entity/Q142> }",
+    )?;
+    query.bind(city.clone(), paris);
+    assert!(matches!(
+        store.query_prepared(&query, QueryOptions::default())?,
+        QueryResults::Boolean(true)
+    ));
+    store.clear()?;
+    assert!(matches!(
+        store.query_prepared(&query, QueryOptions::default())?,
+        QueryResults::Boolean(false)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_describe_strategies() -> Result<(), EvaluationError> {
+    fn describe(store: &SledStore, strategy: DescribeStrategy) -> Result<usize, EvaluationError> {
+        if let QueryResults::Graph(triples) = store.query_opt(
+            "DESCRIBE <http://example.com/a>",
+            QueryOptions::default().with_describe_strategy(strategy),
+        )? {
+            let mut count = 0;
+            for triple in triples {
+                triple?;
+                count += 1;
+            }
+            Ok(count)
+        } else {
+            unreachable!()
+        }
+    }
+
+    let store = SledStore::new()?;
+    store.load_graph(
+        Cursor::new(
+            r#"
+@prefix ex: <http://example.com/> .
+ex:a ex:name "a" ;
+    ex:address [ ex:city "Paris" ; ex:geo [ ex:lat 48.8 ] ] ;
+    ex:knows ex:b .
+ex:b ex:name "b" .
+ex:c ex:knows ex:a .
+[ ex:member ex:a ; ex:name "group" ] .
+"#,
+        ),
+        GraphFormat::Turtle,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    assert_eq!(describe(&store, DescribeStrategy::Subject)?, 3);
+    assert_eq!(
+        describe(&store, DescribeStrategy::ConciseBoundedDescription)?,
+        6
+    );
+    assert_eq!(
+        describe(&store, DescribeStrategy::SymmetricConciseBoundedDescription)?,
+        9
+    );
+    let knows = NamedNode::new_unchecked("http://example.com/knows");
+    assert_eq!(
+        describe(
+            &store,
+            DescribeStrategy::custom(move |resource, graph| {
+                graph
+                    .triples_for_pattern(None, Some(&knows), Some(resource))
+                    .collect()
+            })
+        )?,
+        1
+    );
+    Ok(())
+}
+
+#[test]
+fn test_service_batching() -> Result<(), EvaluationError> {
+    struct CountingServiceHandler {
+        store: SledStore,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ServiceHandler for CountingServiceHandler {
+        type Error = EvaluationError;
+
+        fn handle(&self, _: NamedNode, query: Query) -> Result<QueryResults, EvaluationError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.store.query(query)
+        }
+    }
+
+    let store = SledStore::new()?;
+    store.update(
+        "INSERT DATA {
+            <http://example.com/a> <http://example.com/p> 1, 2, 3, 4, 5 .
+            <http://example.com/a> <http://example.com/q> _:b .
+        }",
+    )?;
+    let service = SledStore::new()?;
+    service.update(
+        "INSERT DATA { <http://example.com/a> <http://example.com/r> 1, 3, 5, 7 ; <http://example.com/q> _:c }",
+    )?;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let count = |query: &str| -> Result<usize, EvaluationError> {
+        if let QueryResults::Solutions(solutions) = store.query_opt(
+            query,
+            QueryOptions::default()
+                .with_service_handler(CountingServiceHandler {
+                    store: service.clone(),
+                    calls: calls.clone(),
+                })
+                .with_service_batch_size(2),
+        )? {
+            let mut count = 0;
+            for solution in solutions {
+                solution?;
+                count += 1;
+            }
+            Ok(count)
+        } else {
+            unreachable!()
+        }
+    };
+
+    assert_eq!(
+        count("SELECT * WHERE { ?s <http://example.com/p> ?o SERVICE <http://example.com/s> { ?s <http://example.com/r> ?o } }")?,
+        3
+    );
+    assert_eq!(calls.swap(0, Ordering::Relaxed), 3);
+    // The variable used to identify the sent solutions must not conflict with the service pattern variables
+    assert_eq!(
+        count("SELECT * WHERE { ?s <http://example.com/p> ?o SERVICE <http://example.com/s> { ?s <http://example.com/r> ?position BIND(?position AS ?o) } }")?,
+        3
+    );
+    assert_eq!(calls.swap(0, Ordering::Relaxed), 3);
+    // Blank nodes are joined locally
+    assert_eq!(
+        count("SELECT * WHERE { ?s <http://example.com/q> ?o SERVICE <http://example.com/s> { ?s <http://example.com/q> ?o } }")?,
+        0
+    );
+    assert_eq!(calls.swap(0, Ordering::Relaxed), 1);
+    assert_eq!(
+        count("SELECT * WHERE { ?s <http://example.com/p> ?o OPTIONAL { SERVICE <http://example.com/s> { ?s <http://example.com/r> ?o } } }")?,
+        5
+    );
+    assert_eq!(
+        count("SELECT * WHERE { ?s <http://example.com/p> ?o SERVICE SILENT ?o { ?s ?p ?o } }")?,
+        5
+    );
+    Ok(())
+}
+
+#[test]
+fn test_local_service_handler() -> Result<(), EvaluationError> {
+    let store = MemoryStore::new();
+    store.update("INSERT DATA { <http://example.com/paris> <http://example.com/country> <http://example.com/france> }")?;
+    let reference = SledStore::new()?;
+    reference.update(
+        "INSERT DATA { <http://example.com/france> <http://example.com/name> \"France\" }",
+    )?;
+    let labels = MemoryStore::new();
+    labels.update(
+        "INSERT DATA { <http://example.com/france> <http://example.com/label> \"France\"@fr }",
+    )?;
+    let service = LocalServiceHandler::new()
+        .with_store(NamedNode::new_unchecked("urn:store:ref"), reference)
+        .with_store(NamedNode::new_unchecked("urn:store:labels"), labels)
+        .without_fallback();
+
+    // The SERVICE calls inside of a local store are also handled
+    if let QueryResults::Solutions(mut solutions) = store.query_opt(
+        "SELECT * WHERE {
+            ?city <http://example.com/country> ?country
+            SERVICE <urn:store:ref> {
+                ?country <http://example.com/name> ?name
+                SERVICE <urn:store:labels> { ?country <http://example.com/label> ?label }
+            }
+        }",
+        QueryOptions::default().with_service_handler(service.clone()),
+    )? {
+        let solution = solutions.next().unwrap()?;
+        assert_eq!(solution.get("name"), Some(&Literal::from("France").into()));
+        assert_eq!(
+            solution.get("label"),
+            Some(&Literal::new_language_tagged_literal_unchecked("France", "fr").into())
+        );
+        assert!(solutions.next().is_none());
+    } else {
+        unreachable!()
+    }
+
+    // The other services are given to the fallback
+    let remote = MemoryStore::new();
+    remote.update(
+        "INSERT DATA { <http://example.com/s> <http://example.com/p> <http://example.com/o> }",
+    )?;
+    let service = service.with_fallback(LocalServiceHandler::new().with_store(
+        NamedNode::new_unchecked("http://example.com/sparql"),
+        remote,
+    ));
+    if let QueryResults::Solutions(mut solutions) = store.query_opt(
+        "SELECT ?s WHERE { SERVICE <http://example.com/sparql> { ?s ?p ?o } }",
+        QueryOptions::default().with_service_handler(service.clone()),
+    )? {
+        assert_eq!(
+            solutions.next().unwrap()?.get("s"),
+            Some(&NamedNode::new_unchecked("http://example.com/s").into())
+        );
+    } else {
+        unreachable!()
+    }
+
+    // The SERVICE patterns are evaluated with the caller options
+    if let QueryResults::Solutions(mut solutions) = store.query_opt(
+        "SELECT ?upper WHERE { SERVICE <urn:store:ref> { ?country <http://example.com/name> ?name BIND(<http://example.com/upper>(?name) AS ?upper) } }",
+        QueryOptions::default()
+            .with_service_handler(service)
+            .with_custom_function(NamedNode::new_unchecked("http://example.com/upper"), |args| {
+                if let [Term::Literal(literal)] = args {
+                    Some(Literal::new_simple_literal(literal.value().to_uppercase()).into())
+                } else {
+                    None
+                }
+            }),
+    )? {
+        assert_eq!(
+            solutions.next().unwrap()?.get("upper"),
+            Some(&Literal::from("FRANCE").into())
+        );
+    } else {
+        unreachable!()
+    }
+    Ok(())
+}
+
+#[test]
+fn test_snapshot() -> Result<(), EvaluationError> {
+    let store = SledStore::new()?;
+    store.load_graph(
+        Cursor::new(DATA),
+        GraphFormat::Turtle,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    let snapshot = store.snapshot();
+    let results = snapshot.query("SELECT ?s WHERE { ?s ?p ?o }")?;
+
+    // Writes done after the snapshot creation are not visible
+    let ex = NamedNodeRef::new_unchecked("http://example.com");
+    store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    for quad in quads(GraphNameRef::DefaultGraph) {
+        store.remove(quad)?;
+    }
+    store
+        .transaction(|transaction| {
+            transaction.insert(QuadRef::new(
+                ex,
+                ex,
+                LiteralRef::new_simple_literal("foo"),
+                GraphNameRef::DefaultGraph,
+            ))?;
+            Ok(()) as Result<(), SledConflictableTransactionError<io::Error>>
+        })
+        .map_err(io::Error::from)?;
+    assert_eq!(store.len(), 2);
+    assert_eq!(snapshot.len(), NUMBER_OF_TRIPLES);
+    for quad in quads(GraphNameRef::DefaultGraph) {
+        assert!(snapshot.contains(quad)?);
+    }
+    assert!(!snapshot.contains(QuadRef::new(ex, ex, ex, ex))?);
+    assert_eq!(snapshot.named_graphs().count(), 0);
+    if let QueryResults::Solutions(solutions) = results {
+        assert_eq!(solutions.count(), NUMBER_OF_TRIPLES);
+    } else {
+        unreachable!()
+    }
+    if let QueryResults::Boolean(result) = snapshot.query("ASK { ?s ?p \"foo\" }")? {
+        assert!(!result);
+    } else {
+        unreachable!()
+    }
+
+    // The snapshot stays the same after other writes
+    store.remove(QuadRef::new(ex, ex, ex, ex))?;
+    let mut buffer = Vec::new();
+    snapshot.dump_graph(
+        &mut buffer,
+        GraphFormat::NTriples,
+        GraphNameRef::DefaultGraph,
+    )?;
+    let dumped = MemoryStore::new();
+    dumped.load_graph(
+        Cursor::new(buffer),
+        GraphFormat::NTriples,
+        GraphNameRef::DefaultGraph,
+        None,
+    )?;
+    assert_eq!(dumped.len(), NUMBER_OF_TRIPLES);
+
+    // The snapshot is not readable anymore after the store is cleared
+    store.clear()?;
+    assert!(store.is_empty());
+    assert_eq!(
+        snapshot
+            .iter()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::NotFound
+    );
+    Ok(())
+}
+
+#[test]
+fn test_remove_unused_strings() -> io::Result<()> {
+    let store = SledStore::new()?;
+    for q in quads(GraphNameRef::DefaultGraph) {
+        store.insert(q)?;
+    }
+    assert_eq!(store.remove_unused_strings()?, 0);
+
+    let ex = NamedNodeRef::new_unchecked("http://example.com/graph");
+    let quad = QuadRef::new(
+        ex,
+        ex,
+        LiteralRef::new_simple_literal("a literal to remove"),
+        ex,
+    );
+    store.insert(quad)?;
+    let snapshot = store.snapshot();
+    store.remove(quad)?;
+    store.remove_named_graph(ex)?;
+    assert_eq!(store.remove_unused_strings()?, 2);
+    assert_eq!(store.remove_unused_strings()?, 0);
+
+    // The used strings are kept
+    for q in quads(GraphNameRef::DefaultGraph) {
+        assert!(store.contains(q)?);
+    }
+    assert_eq!(
+        store.iter().collect::<io::Result<Vec<_>>>()?.len(),
+        NUMBER_OF_TRIPLES
+    );
+
+    // The snapshots still see the removed strings
+    assert!(snapshot.contains(quad)?);
+    assert!(snapshot
+        .iter()
+        .collect::<io::Result<Vec<_>>>()?
+        .contains(&quad.into_owned()));
+
+    // The strings are added back if needed
+    store.insert(quad)?;
+    assert!(store.contains(quad)?);
+    assert_eq!(
+        store.iter().collect::<io::Result<Vec<_>>>()?.len(),
+        NUMBER_OF_TRIPLES + 1
+    );
+    Ok(())
+}
+
+#[test]
+fn test_remove_unused_strings_during_iteration() -> io::Result<()> {
+    let store = SledStore::new()?;
+    let ex = NamedNodeRef::new_unchecked("http://example.com/graph");
+    let quad = QuadRef::new(
+        ex,
+        ex,
+        LiteralRef::new_simple_literal("a literal to remove"),
+        ex,
+    );
+    store.insert(quad)?;
+    let quads = store.iter();
+    let graphs = store.named_graphs();
+    store.remove(quad)?;
+    store.remove_named_graph(ex)?;
+    assert_eq!(store.remove_unused_strings()?, 2);
+    // The iterators read the current content of the store but are still able to decode what they read
+    for q in quads {
+        assert_eq!(q?, quad.into_owned());
+    }
+    for g in graphs {
+        assert_eq!(g?, NamedOrBlankNode::from(ex.into_owned()));
+    }
+
+    // Concurrent reads and collections
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let store = store.clone();
+        let stop = stop.clone();
+        thread::spawn(move || -> io::Result<()> {
+            let mut i = 0;
+            while !stop.load(Ordering::Relaxed) {
+                let literal = Literal::new_simple_literal(format!("a literal to remove {}", i));
+                let quad = QuadRef::new(ex, ex, &literal, ex);
+                store.insert(quad)?;
+                store.remove(quad)?;
+                store.remove_unused_strings()?;
+                i += 1;
+            }
+            Ok(())
+        })
+    };
+    for _ in 0..1000 {
+        store.iter().collect::<io::Result<Vec<_>>>()?;
+        store.named_graphs().collect::<io::Result<Vec<_>>>()?;
+    }
+    stop.store(true, Ordering::Relaxed);
+    writer.join().unwrap()
+}
+
 #[test]
 fn test_backward_compatibility() -> io::Result<()> {
     {
@@ -25,6 +25,7 @@ pub fn add_to_module(module: &PyModule) -> PyResult<()> {
 /// * `N-Quads <https://www.w3.org/TR/n-quads/>`_ (``application/n-quads``)
 /// * `Turtle <https://www.w3.org/TR/turtle/>`_ (``text/turtle``)
 /// * `TriG <https://www.w3.org/TR/trig/>`_ (``application/trig``)
+/// * `JSON-LD <https://www.w3.org/TR/json-ld/>`_ (``application/ld+json``)
 /// * `RDF/XML <https://www.w3.org/TR/rdf-syntax-grammar/>`_ (``application/rdf+xml``)
 ///
 /// It supports also some MIME type aliases.
@@ -54,7 +55,10 @@ pub fn parse(
     py: Python<'_>,
 ) -> PyResult<PyObject> {
     let input = BufReader::new(PyFileLike::new(input));
-    if let Some(graph_format) = GraphFormat::from_media_type(mime_type) {
+    // JSON-LD is both a graph and a dataset format: it is read and written as a dataset
+    if let Some(graph_format) =
+        GraphFormat::from_media_type(mime_type).filter(|f| *f != GraphFormat::JsonLd)
+    {
         let mut parser = GraphParser::from_format(graph_format);
         if let Some(base_iri) = base_iri {
             parser = parser
@@ -92,6 +96,7 @@ pub fn parse(
 /// * `N-Quads <https://www.w3.org/TR/n-quads/>`_ (``application/n-quads``)
 /// * `Turtle <https://www.w3.org/TR/turtle/>`_ (``text/turtle``)
 /// * `TriG <https://www.w3.org/TR/trig/>`_ (``application/trig``)
+/// * `JSON-LD <https://www.w3.org/TR/json-ld/>`_ (``application/ld+json``)
 /// * `RDF/XML <https://www.w3.org/TR/rdf-syntax-grammar/>`_ (``application/rdf+xml``)
 ///
 /// It supports also some MIME type aliases.
@@ -115,7 +120,10 @@ pub fn parse(
 #[text_signature = "(input, output, /, mime_type, *, base_iri = None)"]
 pub fn serialize(input: &PyAny, output: PyObject, mime_type: &str) -> PyResult<()> {
     let output = PyFileLike::new(output);
-    if let Some(graph_format) = GraphFormat::from_media_type(mime_type) {
+    // JSON-LD is both a graph and a dataset format: it is read and written as a dataset
+    if let Some(graph_format) =
+        GraphFormat::from_media_type(mime_type).filter(|f| *f != GraphFormat::JsonLd)
+    {
         let mut writer = GraphSerializer::from_format(graph_format)
             .triple_writer(output)
             .map_err(map_io_err)?;
@@ -145,7 +153,7 @@ pub fn serialize(input: &PyAny, output: PyObject, mime_type: &str) -> PyResult<(
     }
 }
 
-#[pyclass(name = "TripleReader", module = "oxigraph")]
+#[pyclass(unsendable, name = "TripleReader", module = "oxigraph")]
 pub struct PyTripleReader {
     inner: TripleReader<BufReader<PyFileLike>>,
 }
@@ -164,7 +172,7 @@ impl PyIterProtocol for PyTripleReader {
     }
 }
 
-#[pyclass(name = "QuadReader", module = "oxigraph")]
+#[pyclass(unsendable, name = "QuadReader", module = "oxigraph")]
 pub struct PyQuadReader {
     inner: QuadReader<BufReader<PyFileLike>>,
 }
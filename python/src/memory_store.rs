@@ -210,6 +210,7 @@ impl PyMemoryStore {
     /// * `N-Quads <https://www.w3.org/TR/n-quads/>`_ (``application/n-quads``)
     /// * `Turtle <https://www.w3.org/TR/turtle/>`_ (``text/turtle``)
     /// * `TriG <https://www.w3.org/TR/trig/>`_ (``application/trig``)
+    /// * `JSON-LD <https://www.w3.org/TR/json-ld/>`_ (``application/ld+json``)
     /// * `RDF/XML <https://www.w3.org/TR/rdf-syntax-grammar/>`_ (``application/rdf+xml``)
     ///
     /// It supports also some MIME type aliases.
@@ -246,7 +247,10 @@ impl PyMemoryStore {
             None
         };
         let input = BufReader::new(PyFileLike::new(input));
-        if let Some(graph_format) = GraphFormat::from_media_type(mime_type) {
+        // JSON-LD is both a graph and a dataset format: it is used as a graph format only if a graph name is given
+        if let Some(graph_format) = GraphFormat::from_media_type(mime_type)
+            .filter(|f| *f != GraphFormat::JsonLd || to_graph_name.is_some())
+        {
             self.inner
                 .load_graph(
                     input,
@@ -280,6 +284,7 @@ impl PyMemoryStore {
     /// * `N-Quads <https://www.w3.org/TR/n-quads/>`_ (``application/n-quads``)
     /// * `Turtle <https://www.w3.org/TR/turtle/>`_ (``text/turtle``)
     /// * `TriG <https://www.w3.org/TR/trig/>`_ (``application/trig``)
+    /// * `JSON-LD <https://www.w3.org/TR/json-ld/>`_ (``application/ld+json``)
     /// * `RDF/XML <https://www.w3.org/TR/rdf-syntax-grammar/>`_ (``application/rdf+xml``)
     ///
     /// It supports also some MIME type aliases.
@@ -309,7 +314,10 @@ impl PyMemoryStore {
             None
         };
         let output = PyFileLike::new(output);
-        if let Some(graph_format) = GraphFormat::from_media_type(mime_type) {
+        // JSON-LD is both a graph and a dataset format: it is used as a graph format only if a graph name is given
+        if let Some(graph_format) = GraphFormat::from_media_type(mime_type)
+            .filter(|f| *f != GraphFormat::JsonLd || from_graph_name.is_some())
+        {
             self.inner
                 .dump_graph(
                     output,
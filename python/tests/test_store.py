@@ -217,6 +217,25 @@ class TestAbstractStore(unittest.TestCase, ABC):
         )
         self.assertEqual(set(store), {Quad(foo, bar, baz, graph)})
 
+    def test_load_json_ld(self):
+        store = self.store()
+        store.load(
+            BytesIO(
+                b'{"@id": "http://graph", "@graph": {"@id": "http://foo", "http://bar": {"@id": "http://baz"}}}'
+            ),
+            mime_type="application/ld+json",
+        )
+        self.assertEqual(set(store), {Quad(foo, bar, baz, graph)})
+
+    def test_load_json_ld_to_named_graph(self):
+        store = self.store()
+        store.load(
+            BytesIO(b'{"@id": "http://foo", "http://bar": {"@id": "http://baz"}}'),
+            mime_type="application/ld+json",
+            to_graph=graph,
+        )
+        self.assertEqual(set(store), {Quad(foo, bar, baz, graph)})
+
     def test_dump_ntriples(self):
         store = self.store()
         store.add(Quad(foo, bar, baz, graph))
@@ -236,6 +255,15 @@ class TestAbstractStore(unittest.TestCase, ABC):
             b"<http://foo> <http://bar> <http://baz> <http://graph> .\n",
         )
 
+    def test_dump_json_ld_graph(self):
+        store = self.store()
+        store.add(Quad(foo, bar, baz, graph))
+        output = BytesIO()
+        store.dump(output, "application/ld+json", from_graph=graph)
+        self.assertEqual(
+            output.getvalue(), b'[{"@id":"http://foo","http://bar":[{"@id":"http://baz"}]}]',
+        )
+
     def test_write_in_read(self):
         store = self.store()
         store.add(Quad(foo, bar, bar))
@@ -22,14 +22,18 @@ use http_types::{
 use oxigraph::io::{DatasetFormat, GraphFormat};
 use oxigraph::model::{GraphName, GraphNameRef, NamedNode, NamedOrBlankNode};
 use oxigraph::sparql::algebra::GraphUpdateOperation;
-use oxigraph::sparql::{Query, QueryResults, QueryResultsFormat, Update};
+use oxigraph::sparql::{
+    EvaluationError, Query, QueryOptions, QueryResults, QueryResultsFormat, Update, UpdateOptions,
+};
 #[cfg(feature = "rocksdb")]
 use oxigraph::RocksDbStore as Store;
 #[cfg(all(feature = "sled", not(feature = "rocksdb")))]
 use oxigraph::SledStore as Store;
 use rand::random;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use url::{form_urlencoded, Url};
 
 const MAX_SPARQL_BODY_SIZE: u64 = 1_048_576;
@@ -47,21 +51,122 @@ struct Args {
     /// directory in which persist the data
     #[argh(option, short = 'f')]
     file: String,
+
+    /// default timeout in seconds of the SPARQL queries and updates evaluation
+    #[argh(option, short = 't')]
+    timeout: Option<u64>,
+
+    /// open the data directory in read-only mode, allowing other read-only servers to use it at the same time
+    #[cfg(feature = "rocksdb")]
+    #[argh(switch)]
+    read_only: bool,
+
+    /// open the data directory as a secondary instance of the server writing into it, using the given directory to store the secondary instance logs. The data is updated with the writes of the primary server before each request
+    #[cfg(feature = "rocksdb")]
+    #[argh(option)]
+    secondary: Option<String>,
+
+    /// directory in which the "POST /backup" requests add an incremental backup of the data directory. These requests are refused if not set
+    #[cfg(feature = "rocksdb")]
+    #[argh(option)]
+    backup_directory: Option<String>,
+
+    #[cfg(feature = "rocksdb")]
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[cfg(feature = "rocksdb")]
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Backup(BackupArgs),
+    Restore(RestoreArgs),
+}
+
+#[cfg(feature = "rocksdb")]
+#[derive(FromArgs)]
+/// Backup the data directory instead of starting the server. The data directory is opened in read-only mode so a server could use it at the same time
+#[argh(subcommand, name = "backup")]
+struct BackupArgs {
+    /// directory in which write the backup
+    #[argh(option, short = 'd')]
+    destination: String,
+
+    /// add an incremental backup to the destination directory that could be restored using the restore command instead of writing a copy of the data directory
+    #[argh(switch, short = 'i')]
+    incremental: bool,
+}
+
+#[cfg(feature = "rocksdb")]
+#[derive(FromArgs)]
+/// Restore into the data directory the latest incremental backup instead of starting the server
+#[argh(subcommand, name = "restore")]
+struct RestoreArgs {
+    /// directory containing the incremental backups
+    #[argh(option, short = 's')]
+    source: String,
 }
 
 #[async_std::main]
 pub async fn main() -> Result<()> {
     let args: Args = argh::from_env();
+    #[cfg(feature = "rocksdb")]
+    match args.command {
+        Some(Command::Backup(backup_args)) => {
+            let store = Store::open_read_only(args.file)?;
+            if backup_args.incremental {
+                store.incremental_backup(backup_args.destination)?;
+            } else {
+                store.backup(backup_args.destination)?;
+            }
+            return Ok(());
+        }
+        Some(Command::Restore(restore_args)) => {
+            Store::restore_backup(restore_args.source, args.file)?;
+            return Ok(());
+        }
+        None => (),
+    }
+    #[cfg(feature = "rocksdb")]
+    let is_secondary = args.secondary.is_some();
+    #[cfg(feature = "rocksdb")]
+    let store = if let Some(secondary) = args.secondary {
+        Store::open_secondary(args.file, secondary)?
+    } else if args.read_only {
+        Store::open_read_only(args.file)?
+    } else {
+        Store::open(args.file)?
+    };
+    #[cfg(not(feature = "rocksdb"))]
     let store = Store::open(args.file)?;
+    let timeout = args.timeout.map(Duration::from_secs);
+    #[cfg(feature = "rocksdb")]
+    let backup_directory = args.backup_directory.map(PathBuf::from);
+    #[cfg(not(feature = "rocksdb"))]
+    let backup_directory: Option<PathBuf> = None;
 
     println!("Listening for requests at http://{}", &args.bind);
     http_server(&args.bind, move |request| {
-        handle_request(request, store.clone())
+        let store = store.clone();
+        let backup_directory = backup_directory.clone();
+        async move {
+            #[cfg(feature = "rocksdb")]
+            if is_secondary {
+                store.catch_up_with_primary()?;
+            }
+            handle_request(request, store, timeout, backup_directory.as_deref()).await
+        }
     })
     .await
 }
 
-async fn handle_request(request: Request, store: Store) -> Result<Response> {
+async fn handle_request(
+    request: Request,
+    store: Store,
+    timeout: Option<Duration>,
+    backup_directory: Option<&Path>,
+) -> Result<Response> {
     Ok(match (request.url().path(), request.method()) {
         ("/", Method::Get) => {
             let mut response = Response::new(StatusCode::Ok);
@@ -76,7 +181,7 @@ async fn handle_request(request: Request, store: Store) -> Result<Response> {
             response
         }
         ("/query", Method::Get) => {
-            configure_and_evaluate_sparql_query(store, url_query(&request), None, request)?
+            configure_and_evaluate_sparql_query(store, url_query(&request), None, timeout, request)?
         }
         ("/query", Method::Post) => {
             if let Some(content_type) = request.content_type() {
@@ -92,6 +197,7 @@ async fn handle_request(request: Request, store: Store) -> Result<Response> {
                         store,
                         url_query(&request),
                         Some(buffer),
+                        timeout,
                         request,
                     )?
                 } else if content_type.essence() == "application/x-www-form-urlencoded" {
@@ -102,7 +208,7 @@ async fn handle_request(request: Request, store: Store) -> Result<Response> {
                         .take(MAX_SPARQL_BODY_SIZE)
                         .read_to_end(&mut buffer)
                         .await?;
-                    configure_and_evaluate_sparql_query(store, buffer, None, request)?
+                    configure_and_evaluate_sparql_query(store, buffer, None, timeout, request)?
                 } else {
                     bail_status!(415, "Not supported Content-Type given: {}", content_type)
                 }
@@ -124,6 +230,7 @@ async fn handle_request(request: Request, store: Store) -> Result<Response> {
                         store,
                         url_query(&request),
                         Some(buffer),
+                        timeout,
                         request,
                     )?
                 } else if content_type.essence() == "application/x-www-form-urlencoded" {
@@ -134,7 +241,7 @@ async fn handle_request(request: Request, store: Store) -> Result<Response> {
                         .take(MAX_SPARQL_BODY_SIZE)
                         .read_to_end(&mut buffer)
                         .await?;
-                    configure_and_evaluate_sparql_update(store, buffer, None, request)?
+                    configure_and_evaluate_sparql_update(store, buffer, None, timeout, request)?
                 } else {
                     bail_status!(415, "Not supported Content-Type given: {}", content_type)
                 }
@@ -142,6 +249,17 @@ async fn handle_request(request: Request, store: Store) -> Result<Response> {
                 bail_status!(400, "No Content-Type given")
             }
         }
+        ("/backup", Method::Post) => {
+            if let Some(backup_directory) = backup_directory {
+                backup(&store, backup_directory)?;
+                Response::new(StatusCode::NoContent)
+            } else {
+                bail_status!(
+                    403,
+                    "The server has not been started with the --backup-directory option"
+                )
+            }
+        }
         (path, Method::Get) if path.starts_with("/store") => {
             //TODO: stream
             let mut body = Vec::default();
@@ -351,6 +469,17 @@ fn base_url(request: &Request) -> Result<Url> {
     Ok(url)
 }
 
+#[cfg(feature = "rocksdb")]
+fn backup(store: &Store, backup_directory: &Path) -> Result<()> {
+    store.incremental_backup(backup_directory)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn backup(_store: &Store, _backup_directory: &Path) -> Result<()> {
+    bail_status!(501, "Backups are only supported with RocksDB storage")
+}
+
 fn url_query(request: &Request) -> Vec<u8> {
     request.url().query().unwrap_or("").as_bytes().to_vec()
 }
@@ -359,10 +488,12 @@ fn configure_and_evaluate_sparql_query(
     store: Store,
     encoded: Vec<u8>,
     mut query: Option<String>,
+    timeout: Option<Duration>,
     request: Request,
 ) -> Result<Response> {
     let mut default_graph_uris = Vec::new();
     let mut named_graph_uris = Vec::new();
+    let mut explain = None;
     for (k, v) in form_urlencoded::parse(&encoded) {
         match k.as_ref() {
             "query" => {
@@ -373,11 +504,30 @@ fn configure_and_evaluate_sparql_query(
             }
             "default-graph-uri" => default_graph_uris.push(v.into_owned()),
             "named-graph-uri" => named_graph_uris.push(v.into_owned()),
+            "explain" => {
+                explain = Some(match v.as_ref() {
+                    "plan" => false,
+                    "profile" => true,
+                    _ => bail_status!(
+                        400,
+                        "The explain parameter should be 'plan' or 'profile', found {}",
+                        v
+                    ),
+                })
+            }
             _ => bail_status!(400, "Unexpected parameter: {}", k),
         }
     }
     if let Some(query) = query {
-        evaluate_sparql_query(store, query, default_graph_uris, named_graph_uris, request)
+        evaluate_sparql_query(
+            store,
+            query,
+            default_graph_uris,
+            named_graph_uris,
+            explain,
+            timeout,
+            request,
+        )
     } else {
         bail_status!(400, "You should set the 'query' parameter")
     }
@@ -388,6 +538,8 @@ fn evaluate_sparql_query(
     query: String,
     default_graph_uris: Vec<String>,
     named_graph_uris: Vec<String>,
+    explain: Option<bool>,
+    timeout: Option<Duration>,
     request: Request,
 ) -> Result<Response> {
     let mut query =
@@ -409,13 +561,46 @@ fn evaluate_sparql_query(
             .dataset_mut()
             .set_available_named_graphs(named_graph_uris);
     }
+    let mut options = QueryOptions::default();
+    if let Some(timeout) = timeout {
+        options = options.with_timeout(timeout);
+    }
 
-    let results = store.query(query)?;
+    if let Some(with_stats) = explain {
+        let (results, explanation) = store
+            .explain_query_opt(query, options, with_stats)
+            .map_err(evaluation_error)?;
+        if let Some(results) = results {
+            // We evaluate the query completely to get the statistics
+            match results.map_err(evaluation_error)? {
+                QueryResults::Solutions(solutions) => {
+                    for solution in solutions {
+                        solution.map_err(evaluation_error)?;
+                    }
+                }
+                QueryResults::Graph(triples) => {
+                    for triple in triples {
+                        triple.map_err(evaluation_error)?;
+                    }
+                }
+                QueryResults::Boolean(_) => (),
+            }
+        }
+        let mut body = Vec::default();
+        explanation.write_in_json(&mut body)?;
+        let mut response = Response::from(body);
+        ContentType::new("application/json").apply(&mut response);
+        return Ok(response);
+    }
+
+    let results = store.query_opt(query, options).map_err(evaluation_error)?;
     //TODO: stream
     if let QueryResults::Graph(_) = results {
         let format = graph_content_negotiation(request)?;
         let mut body = Vec::default();
-        results.write_graph(&mut body, format)?;
+        results
+            .write_graph(&mut body, format)
+            .map_err(evaluation_error)?;
         let mut response = Response::from(body);
         ContentType::new(format.media_type()).apply(&mut response);
         Ok(response)
@@ -431,7 +616,7 @@ fn evaluate_sparql_query(
             QueryResultsFormat::from_media_type,
         )?;
         let mut body = Vec::default();
-        results.write(&mut body, format)?;
+        results.write(&mut body, format).map_err(evaluation_error)?;
         let mut response = Response::from(body);
         ContentType::new(format.media_type()).apply(&mut response);
         Ok(response)
@@ -442,6 +627,7 @@ fn configure_and_evaluate_sparql_update(
     store: Store,
     encoded: Vec<u8>,
     mut update: Option<String>,
+    timeout: Option<Duration>,
     request: Request,
 ) -> Result<Response> {
     let mut default_graph_uris = Vec::new();
@@ -460,7 +646,14 @@ fn configure_and_evaluate_sparql_update(
         }
     }
     if let Some(update) = update {
-        evaluate_sparql_update(store, update, default_graph_uris, named_graph_uris, request)
+        evaluate_sparql_update(
+            store,
+            update,
+            default_graph_uris,
+            named_graph_uris,
+            timeout,
+            request,
+        )
     } else {
         bail_status!(400, "You should set the 'update' parameter")
     }
@@ -471,6 +664,7 @@ fn evaluate_sparql_update(
     update: String,
     default_graph_uris: Vec<String>,
     named_graph_uris: Vec<String>,
+    timeout: Option<Duration>,
     request: Request,
 ) -> Result<Response> {
     let mut update =
@@ -498,10 +692,23 @@ fn evaluate_sparql_update(
             }
         }
     }
-    store.update(update)?;
+    let mut options = UpdateOptions::default();
+    if let Some(timeout) = timeout {
+        options = options.with_timeout(timeout);
+    }
+    store
+        .update_opt(update, options)
+        .map_err(evaluation_error)?;
     Ok(Response::new(StatusCode::NoContent))
 }
 
+fn evaluation_error(error: EvaluationError) -> Error {
+    match error {
+        EvaluationError::Timeout => Error::new(StatusCode::ServiceUnavailable, error),
+        error => error.into(),
+    }
+}
+
 fn store_target(request: &Request) -> Result<Option<GraphName>> {
     if request.url().path() == "/store" {
         let mut graph = None;
@@ -593,6 +800,7 @@ fn graph_content_negotiation(request: Request) -> Result<GraphFormat> {
             GraphFormat::NTriples.media_type(),
             GraphFormat::Turtle.media_type(),
             GraphFormat::RdfXml.media_type(),
+            GraphFormat::JsonLd.media_type(),
         ],
         GraphFormat::from_media_type,
     )
@@ -604,6 +812,7 @@ fn dataset_content_negotiation(request: Request) -> Result<DatasetFormat> {
         &[
             DatasetFormat::NQuads.media_type(),
             DatasetFormat::TriG.media_type(),
+            DatasetFormat::JsonLd.media_type(),
         ],
         DatasetFormat::from_media_type,
     )
@@ -678,6 +887,37 @@ mod tests {
         ServerTest::new().test_status(request, StatusCode::NoContent)
     }
 
+    #[test]
+    fn post_json_ld_dataset_file() {
+        let mut request = Request::new(Method::Post, Url::parse("http://localhost/store").unwrap());
+        request.insert_header("Content-Type", "application/ld+json");
+        request.set_body(
+            r#"{"@id": "http://example.com", "http://example.com": {"@id": "http://example.com"}}"#,
+        );
+        ServerTest::new().test_status(request, StatusCode::NoContent)
+    }
+
+    #[test]
+    fn put_and_get_json_ld_graph() {
+        let server = ServerTest::new();
+        let mut request = Request::new(
+            Method::Put,
+            Url::parse("http://localhost/store?graph=http://example.com/g").unwrap(),
+        );
+        request.insert_header("Content-Type", "application/ld+json");
+        request.set_body(
+            r#"{"@id": "http://example.com", "http://example.com": {"@id": "http://example.com"}}"#,
+        );
+        server.test_status(request, StatusCode::Created);
+
+        let mut request = Request::new(
+            Method::Get,
+            Url::parse("http://localhost/store?graph=http://example.com/g").unwrap(),
+        );
+        request.insert_header("Accept", "application/ld+json");
+        server.test_status(request, StatusCode::Ok);
+    }
+
     #[test]
     fn post_wrong_file() {
         let mut request = Request::new(Method::Post, Url::parse("http://localhost/store").unwrap());
@@ -707,6 +947,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn get_query_explanation() {
+        let server = ServerTest::new();
+        let mut response = server.exec(Request::new(
+            Method::Get,
+            Url::parse(
+                "http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}&explain=profile",
+            )
+            .unwrap(),
+        ));
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(
+            response.content_type().map(|t| t.essence().to_owned()),
+            Some("application/json".to_owned())
+        );
+        assert!(block_on(response.body_string())
+            .unwrap()
+            .contains("\"rows\":0"));
+
+        server.test_status(
+            Request::new(
+                Method::Get,
+                Url::parse(
+                    "http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}&explain=foo",
+                )
+                .unwrap(),
+            ),
+            StatusCode::BadRequest,
+        );
+    }
+
     #[test]
     fn get_bad_query() {
         ServerTest::new().test_status(
@@ -778,6 +1049,47 @@ mod tests {
         ServerTest::new().test_status(request, StatusCode::BadRequest)
     }
 
+    #[test]
+    fn post_update_timeout() {
+        let mut request =
+            Request::new(Method::Post, Url::parse("http://localhost/update").unwrap());
+        request.insert_header("Content-Type", "application/sparql-update");
+        request.set_body(
+            "INSERT DATA { <http://example.com> <http://example.com> <http://example.com> }",
+        );
+        let mut server = ServerTest::new();
+        server.timeout = Some(Duration::from_secs(0));
+        server.test_status(request, StatusCode::ServiceUnavailable)
+    }
+
+    #[test]
+    fn post_backup_disabled() {
+        let request = Request::new(Method::Post, Url::parse("http://localhost/backup").unwrap());
+        ServerTest::new().test_status(request, StatusCode::Forbidden)
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn post_backup() {
+        let backup_directory = tempdir().unwrap();
+        let mut server = ServerTest::new();
+        server.backup_directory = Some(backup_directory.path().to_owned());
+
+        let mut request =
+            Request::new(Method::Post, Url::parse("http://localhost/update").unwrap());
+        request.insert_header("Content-Type", "application/sparql-update");
+        request.set_body(
+            "INSERT DATA { <http://example.com> <http://example.com> <http://example.com> }",
+        );
+        server.test_status(request, StatusCode::NoContent);
+        let request = Request::new(Method::Post, Url::parse("http://localhost/backup").unwrap());
+        server.test_status(request, StatusCode::NoContent);
+
+        let restored = tempdir().unwrap();
+        Store::restore_backup(backup_directory.path(), restored.path()).unwrap();
+        assert_eq!(Store::open(restored.path()).unwrap().len(), 1);
+    }
+
     #[test]
     fn graph_store_protocol() {
         // Tests from https://www.w3.org/2009/sparql/docs/tests/data-sparql11/http-rdf-update/
@@ -1014,6 +1326,8 @@ mod tests {
 
     struct ServerTest {
         store: Store,
+        timeout: Option<Duration>,
+        backup_directory: Option<PathBuf>,
         _path: TempDir,
     }
 
@@ -1021,11 +1335,21 @@ mod tests {
         fn new() -> ServerTest {
             let path = tempdir().unwrap();
             let store = Store::open(path.path()).unwrap();
-            ServerTest { _path: path, store }
+            ServerTest {
+                _path: path,
+                store,
+                timeout: None,
+                backup_directory: None,
+            }
         }
 
         fn exec(&self, request: Request) -> Response {
-            match block_on(handle_request(request, self.store.clone())) {
+            match block_on(handle_request(
+                request,
+                self.store.clone(),
+                self.timeout,
+                self.backup_directory.as_deref(),
+            )) {
                 Ok(response) => response,
                 Err(e) => {
                     let mut response = Response::new(e.status());